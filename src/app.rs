@@ -15,14 +15,16 @@ impl App {
             config.dry_run,
             config.overwrite,
             config.explain,
-        ));
+        )
+        .with_log(config.steps.clone())
+        .with_label(config.step_label.clone()));
         Self { runner, config }
     }
 
     pub fn execute(&self, intent: Intent) -> Result<()> {
         match intent {
-            Intent::Convert { input, format, quality, codec } => {
-                convert::handle_convert(&self.config, self.runner.as_ref(), input, format, quality, codec)
+            Intent::Convert { input, format, quality, codec, max_size } => {
+                convert::handle_convert(&self.config, self.runner.as_ref(), input, format, quality, codec, max_size)
             }
             Intent::Compress { input, target, two_pass } => {
                 compress::handle_compress(&self.config, self.runner.as_ref(), input, target, two_pass)
@@ -30,8 +32,8 @@ impl App {
             Intent::Trim { input, start, end } => {
                 trim::handle_trim(&self.config, self.runner.as_ref(), input, start, end)
             }
-            Intent::ExtractAudio { input, format } => {
-                audio::handle_extract_audio(&self.config, self.runner.as_ref(), input, format)
+            Intent::ExtractAudio { input, format, quality } => {
+                audio::handle_extract_audio(&self.config, self.runner.as_ref(), input, format, quality)
             }
             Intent::AdjustVolume { input, adjustment } => {
                 audio::handle_adjust_volume(&self.config, self.runner.as_ref(), input, adjustment)
@@ -42,8 +44,8 @@ impl App {
             Intent::MixAudio { audio1, audio2 } => {
                 audio::handle_mix_audio(&self.config, self.runner.as_ref(), audio1, audio2)
             }
-            Intent::ExtractAudioRange { input, start, end, format } => {
-                audio::handle_extract_audio_range(&self.config, self.runner.as_ref(), input, start, end, format)
+            Intent::ExtractAudioRange { input, start, end, format, quality } => {
+                audio::handle_extract_audio_range(&self.config, self.runner.as_ref(), input, start, end, format, quality)
             }
             Intent::Resize { input, target } => {
                 video::handle_resize(&self.config, self.runner.as_ref(), input, target)
@@ -105,8 +107,8 @@ impl App {
             Intent::Blur { input, blur_type } => {
                 video::handle_blur(&self.config, self.runner.as_ref(), input, blur_type)
             }
-            Intent::Normalize { input } => {
-                audio::handle_normalize(&self.config, self.runner.as_ref(), input)
+            Intent::Normalize { input, target } => {
+                audio::handle_normalize(&self.config, self.runner.as_ref(), input, target)
             }
             Intent::Fade { input, fade_in, fade_out } => {
                 audio::handle_fade(&self.config, self.runner.as_ref(), input, fade_in, fade_out)
@@ -117,11 +119,35 @@ impl App {
             Intent::ExtractFrames { input, interval } => {
                 video::handle_extract_frames(&self.config, self.runner.as_ref(), input, interval)
             }
-            Intent::BurnSubtitle { input, subtitle } => {
-                video::handle_burn_subtitle(&self.config, self.runner.as_ref(), input, subtitle)
+            Intent::BurnSubtitle { input, subtitle, track, style } => {
+                video::handle_burn_subtitle(&self.config, self.runner.as_ref(), input, subtitle.as_deref(), track.as_ref(), &style)
             }
-            Intent::Batch { pattern, operation } => {
-                batch::handle_batch(&self.config, self.runner.as_ref(), &pattern, operation)
+            Intent::AddSubtitles { input, subtitles, languages, to } => {
+                subtitle::handle_add_subtitles(&self.config, self.runner.as_ref(), input, &subtitles, &languages, to)
+            }
+            Intent::ExtractSubtitles { input, format, track } => {
+                subtitle::handle_extract_subtitles(&self.config, self.runner.as_ref(), input, format, track)
+            }
+            Intent::ConvertSubtitles { input, format } => {
+                subtitle::handle_convert_subtitles(&self.config, self.runner.as_ref(), input, format)
+            }
+            Intent::ShiftSubtitles { input, offset } => {
+                subtitle::handle_shift_subtitles(&self.config, self.runner.as_ref(), input, offset)
+            }
+            Intent::StretchSubtitles { input, stretch } => {
+                subtitle::handle_stretch_subtitles(&self.config, self.runner.as_ref(), input, stretch)
+            }
+            Intent::CutSubtitles { input, keep } => {
+                subtitle::handle_cut_subtitles(&self.config, self.runner.as_ref(), input, keep)
+            }
+            Intent::MergeSubtitles { inputs, at } => {
+                subtitle::handle_merge_subtitles(&self.config, self.runner.as_ref(), &inputs, &at)
+            }
+            Intent::SplitSubtitles { input, at } => {
+                subtitle::handle_split_subtitles(&self.config, self.runner.as_ref(), input, &at)
+            }
+            Intent::Batch { pattern, operation, options } => {
+                batch::handle_batch(&self.config, self.runner.as_ref(), &pattern, operation, options)
             }
             Intent::Compare { video1, video2, show_psnr } => {
                 combine::handle_compare(&self.config, self.runner.as_ref(), video1, video2, show_psnr)
@@ -150,6 +176,33 @@ impl App {
             Intent::Concat { videos } => {
                 combine::handle_concat(&self.config, self.runner.as_ref(), &videos)
             }
+            Intent::AddChapters { input, chapters } => {
+                chapters::handle_add_chapters(&self.config, self.runner.as_ref(), input, &chapters)
+            }
+            Intent::ListChapters { input, json } => {
+                chapters::handle_list_chapters(&self.config, self.runner.as_ref(), input, json)
+            }
+            Intent::ExtractChapters { input } => {
+                chapters::handle_extract_chapters(&self.config, self.runner.as_ref(), input)
+            }
+            Intent::ChaptersFromScenes { input, scenes, min_length } => {
+                chapters::handle_chapters_from_scenes(&self.config, self.runner.as_ref(), input, scenes.as_deref(), min_length)
+            }
+            Intent::Downmix { input } => {
+                channels::handle_downmix(&self.config, self.runner.as_ref(), input)
+            }
+            Intent::ToMono { input, pick } => {
+                channels::handle_to_mono(&self.config, self.runner.as_ref(), input, pick)
+            }
+            Intent::SwapChannels { input } => {
+                channels::handle_swap_channels(&self.config, self.runner.as_ref(), input)
+            }
+            Intent::SplitChannels { input } => {
+                channels::handle_split_channels(&self.config, self.runner.as_ref(), input)
+            }
+            Intent::CombineChannels { inputs, layout } => {
+                channels::handle_combine_channels(&self.config, self.runner.as_ref(), &inputs, layout)
+            }
             Intent::DetectScenes { input } => {
                 video::handle_detect_scenes(&self.config, self.runner.as_ref(), input)
             }
@@ -168,8 +221,8 @@ impl App {
             Intent::SuggestFormat { input } => {
                 video::handle_suggest_format(&self.config, self.runner.as_ref(), input)
             }
-            Intent::Workflow { config_file } => {
-                workflow::handle_workflow(&self.config, self.runner.as_ref(), config_file)
+            Intent::Workflow { config_file, vars } => {
+                workflow::handle_workflow(&self.config, self.runner.as_ref(), config_file, &vars)
             }
             Intent::MotionBlur { input, radius } => {
                 effects::handle_motion_blur(&self.config, self.runner.as_ref(), input, radius)
@@ -267,6 +320,9 @@ impl App {
             Intent::AnalyzeLoudness { input } => {
                 analysis::handle_analyze_loudness(&self.config, self.runner.as_ref(), input)
             }
+            Intent::QcAudio { input, spec, max_silence } => {
+                analysis::handle_qc_audio(&self.config, self.runner.as_ref(), input, spec, max_silence)
+            }
             Intent::DetectDuplicates { input } => {
                 analysis::handle_detect_duplicates(&self.config, self.runner.as_ref(), input)
             }
@@ -276,6 +332,12 @@ impl App {
             Intent::Slideshow { images, duration } => {
                 combine::handle_slideshow(&self.config, self.runner.as_ref(), &images, duration)
             }
+            Intent::SequenceToVideo { images, fps, codec, quality } => {
+                video::handle_sequence_to_video(&self.config, self.runner.as_ref(), &images, fps, codec, quality)
+            }
+            Intent::VideoToSequence { input, format, pattern, start } => {
+                video::handle_video_to_sequence(&self.config, self.runner.as_ref(), input, format, pattern.as_deref(), start)
+            }
             Intent::Visualize { audio, style } => {
                 video::handle_visualize(&self.config, self.runner.as_ref(), audio, style)
             }
@@ -307,17 +369,32 @@ impl App {
             Intent::FixFramerate { input } => {
                 video::handle_fix_framerate(&self.config, self.runner.as_ref(), input)
             }
-            Intent::WatchFolder { folder, operation } => {
-                watch::handle_watch_folder(&self.config, self.runner.as_ref(), folder, operation)
+            Intent::WatchFolder { folder, operation, options } => {
+                watch::handle_watch_folder(&self.config, self.runner.as_ref(), folder, operation, options)
+            }
+            Intent::ApplyTemplate { input, template_file, vars } => {
+                template::handle_apply_template(&self.config, self.runner.as_ref(), input, template_file, &vars)
+            }
+            Intent::Pipeline { input, steps_file, vars } => {
+                pipeline::handle_pipeline(&self.config, self.runner.as_ref(), input, steps_file, &vars)
+            }
+            Intent::Shell { file } => {
+                shell::handle_shell(&self.config, self.runner.as_ref(), file)
+            }
+            Intent::History { id, limit } => {
+                history::handle_history(&self.config, self.runner.as_ref(), id, limit)
+            }
+            Intent::Redo { id, input } => {
+                history::handle_redo(&self.config, self.runner.as_ref(), id, input)
             }
-            Intent::ApplyTemplate { input, template_file } => {
-                template::handle_apply_template(&self.config, self.runner.as_ref(), input, template_file)
+            Intent::Undo { id } => {
+                history::handle_undo(&self.config, self.runner.as_ref(), id)
             }
-            Intent::Pipeline { input, steps_file } => {
-                pipeline::handle_pipeline(&self.config, self.runner.as_ref(), input, steps_file)
+            Intent::Sentence { text } => {
+                sentence::handle_sentence(&self.config, self.runner.as_ref(), &text)
             }
-            Intent::ConditionalBatch { pattern, operation, condition } => {
-                batch::handle_conditional_batch(&self.config, self.runner.as_ref(), &pattern, operation, condition)
+            Intent::ConditionalBatch { pattern, operation, condition, options } => {
+                batch::handle_conditional_batch(&self.config, self.runner.as_ref(), &pattern, operation, condition, options)
             }
         }
     }
@@ -1,10 +1,25 @@
 use crate::model::*;
+use crate::config::AppConfig;
+use crate::model::channels::{ChannelLayout, ChannelPick};
+use crate::model::sequence::FrameFormat;
+use crate::model::subtitle::{
+    CaptionPosition, CaptionPreset, CutList, Language, Offset, Stretch, SubtitleContainer, SubtitleFormat,
+    SubtitleSelector, SubtitleStyle, Timestamp,
+};
+use crate::model::sentence;
+use crate::model::vars::parse_assignments;
+use crate::util::naming::validate_template;
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::error::{ContextKind, ErrorKind};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
-#[command(name = "ffhuman", about = "FFmpeg for humans")]
+#[command(
+    name = "ffhuman",
+    about = "FFmpeg for humans",
+    after_help = "You can also describe what you want in one sentence:\n  ffhuman \"make video.mp4 a 480p gif without sound, 10 seconds from 1:00\""
+)]
 pub struct Cli {
     /// Print generated ffmpeg commands, do not execute
     #[arg(long, global = true)]
@@ -26,6 +41,19 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub output_dir: Option<PathBuf>,
 
+    /// Output filename template, e.g. "{stem}-{op}-{width}x{height}.{ext}"
+    /// (variables: stem, name, input_ext, parent, op, ext, date, time, width, height, fps, duration, vcodec, acodec)
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    pub name: Option<String>,
+
+    /// What to do when an output file already exists: skip, overwrite, auto-number, or fail (default: fail)
+    #[arg(long, global = true, value_name = "POLICY")]
+    pub on_collision: Option<String>,
+
+    /// Skip the checks of inputs and outputs made before running
+    #[arg(long, global = true)]
+    pub no_checks: bool,
+
     #[command(subcommand)]
     pub cmd: Commands,
 }
@@ -38,10 +66,17 @@ pub enum Commands {
     ///
     /// Examples:
     ///   convert video.mp4 to gif
+    ///   convert video.mp4 to webp --max-size 2mb
+    ///   convert video.mp4 to apng --quality low
     ///   convert video.mp4 to mp4
     ///   convert video.mp4 to webm quality high
+    ///   convert video.mp4 to webm --codec av1
+    ///   convert video.mp4 to mov --codec prores-hq
+    ///   convert video.mp4 to mkv --codec ffv1
     ///   convert video.mp4 to mp3
     ///   convert video.mp4 to wav
+    ///   convert song.wav to flac --quality high
+    ///   convert song.flac to opus
     ///   convert video.mp4 to iphone
     ///   convert video.mp4 to android
     ///   convert video.mp4 to hls
@@ -51,12 +86,14 @@ pub enum Commands {
         input: PathBuf,
         #[arg(value_name = "to")]
         _to: KeywordTo,
-        #[arg(help = "Target format: gif, mp4, webm, mp3, wav, iphone, android, hls, dash, or 360")]
+        #[arg(help = "Target format: gif, webp, apng, avif, mp4, webm, mkv, mov, mp3, wav, flac, m4a, opus, ogg, aiff, iphone, android, hls, dash, or 360")]
         format: ConvertFormatCli,
         #[arg(long, help = "Quality preset: low, medium, high, or ultra")]
         quality: Option<String>,
-        #[arg(long, help = "Video codec: h264, h265, vp9, or copy")]
+        #[arg(long, help = "Video codec: h264, h265, vp9, av1, av1-aom, prores[-proxy|-lt|-standard|-hq|-4444|-4444xq], dnxhr[-lb|-sq|-hq|-hqx|-444], ffv1, or copy")]
         codec: Option<String>,
+        #[arg(long, help = "For gif, webp, apng and avif: lower fps, width and colors until the file fits (e.g. 2mb, 500k)")]
+        max_size: Option<String>,
     },
 
     /// compress video.mp4 to 10mb  OR compress video.mp4 to 2000kbps  OR compress video.mp4 to high-quality
@@ -103,15 +140,23 @@ pub enum Commands {
         end: String,
     },
 
-    /// extract-audio video.mp4
+    /// extract-audio video.mp4  OR extract-audio video.mp4 --format flac
     ///
+    /// Extract the audio track. `--format auto` copies the audio as-is into
+    /// the container made for its codec, without re-encoding.
     ///
     /// Examples:
     ///   extract-audio video.mp4
+    ///   extract-audio video.mp4 --format flac --quality high
+    ///   extract-audio video.mp4 --format auto
     #[command(name = "extract-audio")]
     Extract {
         #[arg(help = "Input video file")]
         input: PathBuf,
+        #[arg(long, default_value = "mp3", help = "Audio format: mp3, wav, flac, aac, opus, ogg, aiff, or auto")]
+        format: String,
+        #[arg(long, help = "Quality preset: low, medium, high, or ultra (bit depth for wav, flac and aiff)")]
+        quality: Option<String>,
     },
 
     /// resize video.mp4 to 1280x720  OR resize video.mp4 to 720p
@@ -128,7 +173,7 @@ pub enum Commands {
         input: PathBuf,
         #[arg(value_name = "to")]
         _to: KeywordTo,
-        #[arg(help = "Target size: WxH (e.g., 1280x720) or preset (720p, 1080p, 4k)")]
+        #[arg(help = "Target size: WxH (e.g., 1280x720) or preset (720p, 1080p, 4k) or height (e.g., 480p)")]
         target: String,
     },
 
@@ -421,15 +466,26 @@ pub enum Commands {
         region: String,
     },
 
-    /// normalize video.mp4
+    /// normalize video.mp4 [--target streaming | --lufs -18]
+    ///
+    /// Normalize audio loudness in two passes (EBU R128): measure the file,
+    /// then apply one linear gain so it lands on the target.
     ///
-    /// Normalize audio levels in video.
+    /// Targets: podcast (-16 LUFS, the default), streaming (-14),
+    /// ebu-r128 (-23), atsc-a85 (-24).
     ///
     /// Examples:
     ///   normalize video.mp4
+    ///   normalize episode.wav --target podcast
+    ///   normalize film.mov --target ebu-r128
+    ///   normalize video.mp4 --lufs -18
     Normalize {
-        #[arg(help = "Input video file")]
+        #[arg(help = "Input video or audio file")]
         input: PathBuf,
+        #[arg(long, conflicts_with = "lufs", help = "Loudness target: podcast, streaming, ebu-r128 or atsc-a85 (default: podcast)")]
+        target: Option<String>,
+        #[arg(long, allow_hyphen_values = true, help = "Custom integrated loudness in LUFS (e.g., -18)")]
+        lufs: Option<f64>,
     },
 
     /// adjust-volume video.mp4 to 50%  OR adjust-volume video.mp4 by +10db
@@ -492,6 +548,7 @@ pub enum Commands {
     ///
     /// Examples:
     ///   extract-audio-range video.mp4 from 0:30 to 2:00
+    ///   extract-audio-range video.mp4 from 0:30 to 2:00 --format wav
     #[command(name = "extract-audio-range")]
     ExtractAudioRange {
         #[arg(help = "Input video file")]
@@ -504,6 +561,10 @@ pub enum Commands {
         _to: KeywordTo,
         #[arg(help = "End time: SS, M:SS, or H:MM:SS format")]
         end: String,
+        #[arg(long, default_value = "mp3", help = "Audio format: mp3, wav, flac, aac, opus, ogg, aiff, or auto")]
+        format: String,
+        #[arg(long, help = "Quality preset: low, medium, high, or ultra (bit depth for wav, flac and aiff)")]
+        quality: Option<String>,
     },
 
     /// fade video.mp4 in 2s out 2s
@@ -525,16 +586,29 @@ pub enum Commands {
 
     /// split video.mp4 every 30s  OR split video.mp4 into 3 parts
     ///
-    /// Split video into segments by time interval or into equal parts.
+    /// Split video into segments by time interval, into equal parts, into its
+    /// chapters (named after them), at scene changes, on silences, or into
+    /// chunks under a file size. Copied parts start on keyframes; parts.json
+    /// lists every part with its time range.
     ///
     /// Examples:
     ///   split video.mp4 every 30s
     ///   split video.mp4 into 3 parts
+    ///   split video.mp4 by chapters
+    ///   split video.mp4 by scenes --threshold 0.4
+    ///   split podcast.mp3 on silence --min-silence 1.5s --noise -35dB
+    ///   split video.mp4 into chunks under 25mb
     Split {
         #[arg(help = "Input video file")]
         input: PathBuf,
-        #[arg(help = "Split mode: 'every <duration>' or 'into <N> parts'")]
-        mode: String,
+        #[arg(required = true, num_args = 1.., help = "Split mode: 'every <duration>', 'into <N> parts', 'by chapters', 'by scenes', 'on silence' or 'into chunks under <size>'")]
+        mode: Vec<String>,
+        #[arg(long, help = "Scene change threshold for 'by scenes', 0 to 1 (default: 0.3)")]
+        threshold: Option<f64>,
+        #[arg(long, help = "Shortest silence to split on for 'on silence' (default: 0.5s)")]
+        min_silence: Option<String>,
+        #[arg(long, allow_hyphen_values = true, help = "Noise floor for 'on silence' (default: -30dB)")]
+        noise: Option<String>,
     },
 
     /// extract-frames video.mp4 every 1s
@@ -553,37 +627,180 @@ pub enum Commands {
         interval: String,
     },
 
-    /// burn-subtitle video.mp4 subtitle.srt
+    /// burn-subtitle video.mp4 subtitle.srt  OR burn-subtitle movie.mkv --track eng
     ///
-    /// Burn subtitles into video.
+    /// Burn subtitles into video. ASS files keep their own styles unless a
+    /// preset or style option overrides them; sizes are in video pixels.
     ///
     /// Examples:
     ///   burn-subtitle video.mp4 subtitle.srt
+    ///   burn-subtitle video.mp4 subtitle.srt --preset social
+    ///   burn-subtitle video.mp4 subtitle.srt --font "Open Sans" --size 48 --color yellow --background black
+    ///   burn-subtitle movie.mkv --track 2
+    ///   burn-subtitle movie.mkv --track fr --preset broadcast
     BurnSubtitle {
         #[arg(help = "Input video file")]
         input: PathBuf,
-        #[arg(help = "Subtitle file (SRT, ASS, etc.)")]
-        subtitle: PathBuf,
+        #[arg(help = "Subtitle file (SRT, WebVTT, ASS); leave out to burn a subtitle track of the video")]
+        subtitle: Option<PathBuf>,
+        #[arg(long, help = "Subtitle track of the video: number from 1, or language (e.g., 2, eng, fr)")]
+        track: Option<String>,
+        #[arg(long, help = "Caption preset: social (big, centered) or broadcast (boxed, inside the safe area)")]
+        preset: Option<String>,
+        #[arg(long, help = "Font name (e.g., Arial)")]
+        font: Option<String>,
+        #[arg(long, help = "Font size in pixels")]
+        size: Option<u32>,
+        #[arg(long, help = "Text color: named color (white, yellow, etc.) or hex (#FFFFFF)")]
+        color: Option<String>,
+        #[arg(long, help = "Outline width in pixels (0 for none)")]
+        outline: Option<u32>,
+        #[arg(long, help = "Draw a semi-transparent box of this color behind the text (e.g., black)")]
+        background: Option<String>,
+        #[arg(long, help = "Position: top, middle, or bottom")]
+        position: Option<String>,
+        #[arg(long, help = "Distance from the top or bottom edge in pixels")]
+        margin: Option<u32>,
+    },
+
+    /// add-subtitles video.mp4 movie.en.srt movie.fr.srt
+    ///
+    /// Add subtitle files as tracks viewers can turn on and off: mov_text in
+    /// MP4/MOV, SRT or ASS in MKV, WebVTT in WebM and HLS. Languages come from
+    /// --lang or from file names like movie.en.srt.
+    ///
+    /// Examples:
+    ///   add-subtitles video.mp4 movie.en.srt movie.fr.srt
+    ///   add-subtitles video.mp4 captions.srt --lang en
+    ///   add-subtitles video.mp4 styled.ass --to mkv
+    ///   add-subtitles video.mp4 en.vtt es.vtt --to hls
+    AddSubtitles {
+        #[arg(help = "Input video file")]
+        input: PathBuf,
+        #[arg(required = true, help = "Subtitle files (SRT, WebVTT or ASS)")]
+        subtitles: Vec<PathBuf>,
+        #[arg(long, value_delimiter = ',', help = "Language of each subtitle file, in order (e.g., en,fr or eng,fra)")]
+        lang: Vec<String>,
+        #[arg(long, help = "Container: mp4, mov, mkv, webm, or hls (default: the input's)")]
+        to: Option<String>,
+    },
+
+    /// extract-subtitles movie.mkv  OR extract-subtitles movie.mkv --track 2 --format vtt
+    ///
+    /// Save embedded subtitle tracks as files, named by language
+    /// (movie_eng.srt). Image-based tracks (DVD, Blu-ray) are skipped.
+    ///
+    /// Examples:
+    ///   extract-subtitles movie.mkv
+    ///   extract-subtitles movie.mkv --track 2
+    ///   extract-subtitles movie.mp4 --format vtt
+    ExtractSubtitles {
+        #[arg(help = "Input video file")]
+        input: PathBuf,
+        #[arg(long, help = "Subtitle format: srt, vtt, or ass (default: srt, or ass for ASS tracks)")]
+        format: Option<String>,
+        #[arg(long, help = "Subtitle track to extract, from 1 (default: all)")]
+        track: Option<usize>,
+    },
+
+    /// convert-subtitles movie.en.srt to vtt
+    ///
+    /// Convert subtitles between SRT, WebVTT and ASS. Italics, bold and
+    /// underline are kept; ASS styles and positions are not.
+    ///
+    /// Examples:
+    ///   convert-subtitles movie.en.srt to vtt
+    ///   convert-subtitles movie.ass to srt
+    ConvertSubtitles {
+        #[arg(help = "Subtitle file")]
+        input: PathBuf,
+        #[arg(value_name = "to")]
+        _to: KeywordTo,
+        #[arg(help = "Target format: srt, vtt, or ass")]
+        format: String,
+    },
+
+    /// subtitle shift movie.srt by -2.5s
+    ///
+    /// Retime subtitle files (SRT, WebVTT or ASS): shift, stretch, cut, merge
+    /// or split them. `trim` brings the subtitles next to a video along by itself.
+    ///
+    /// Examples:
+    ///   subtitle shift movie.srt by -2.5s
+    ///   subtitle stretch movie.srt --fps 25=23.976
+    ///   subtitle cut movie.en.srt 0:30-1:00
+    ///   subtitle merge part1.srt part2.srt --at 0,42:10
+    ///   subtitle split movie.srt at 45:00
+    Subtitle {
+        #[command(subcommand)]
+        action: SubtitleCommands,
+    },
+
+    /// chapters add podcast.mp4 chapters.txt
+    ///
+    /// Add, list and extract chapter markers, or make them from scene changes.
+    /// `split video.mp4 by chapters` cuts a video into its chapters.
+    ///
+    /// Examples:
+    ///   chapters add podcast.mp4 chapters.txt
+    ///   chapters list podcast.mp4
+    ///   chapters extract podcast.mp4
+    ///   chapters from-scenes podcast.mp4
+    Chapters {
+        #[command(subcommand)]
+        action: ChaptersCommands,
+    },
+
+    /// channels downmix movie.mkv
+    ///
+    /// Downmix, make mono, swap, split and combine audio channels. Each
+    /// command works from the file's channel layout (stereo, 5.1, 7.1, ...).
+    ///
+    /// Examples:
+    ///   channels downmix movie.mkv
+    ///   channels mono interview.wav --pick left
+    ///   channels swap recording.wav
+    ///   channels split movie.mkv
+    ///   channels combine L.wav R.wav C.wav LFE.wav Ls.wav Rs.wav --layout 5.1
+    Channels {
+        #[command(subcommand)]
+        action: ChannelsCommands,
     },
 
     /// batch convert *.mp4 to gif
     ///
-    /// Process multiple files with the same operation.
+    /// Process multiple files with the same operation. Outputs are recorded in a
+    /// .ffhuman-manifest.json next to them, so re-runs skip unchanged files.
     ///
     /// Examples:
     ///   batch convert *.mp4 to gif
     ///   batch convert *.mp4 to gif --if duration < 30s
+    ///   batch convert *.mov to mp4 --since 24h
+    ///   batch convert *.mov to mp4 --force
+    ///   batch --resume clips/.ffhuman-batch-1a2b3c4d.json
+    ///   batch --resume clips
+    ///   batch --retry-failed --state clips/.ffhuman-batch-1a2b3c4d.json
     Batch {
-        #[arg(help = "Operation: convert")]
-        operation: BatchOperationCli,
-        #[arg(help = "File pattern (e.g., *.mp4)")]
-        pattern: String,
+        #[arg(help = "Operation: convert", required_unless_present_any = ["resume", "retry_failed"])]
+        operation: Option<BatchOperationCli>,
+        #[arg(help = "File pattern (e.g., *.mp4)", required_unless_present_any = ["resume", "retry_failed"])]
+        pattern: Option<String>,
         #[arg(value_name = "to")]
         _to: Option<KeywordTo>,
         #[arg(help = "Target format (for convert operation): gif, mp4, webm, mp3, or wav")]
         format: Option<ConvertFormatCli>,
         #[arg(long, help = "Conditional processing: --if duration < 30s")]
         r#if: Option<String>,
+        #[arg(long, help = "Re-run every file, even if its output is up to date")]
+        force: bool,
+        #[arg(long, help = "Only process files modified since: 24h, 7d, or 2024-05-01")]
+        since: Option<String>,
+        #[arg(long, value_name = "FILE", help = "Job-state file to write (default: .ffhuman-batch-<id>.json in the output or input folder)")]
+        state: Option<PathBuf>,
+        #[arg(long, value_name = "FILE|FOLDER", help = "Continue an interrupted batch from its job-state file (default: the one in the output or current folder)")]
+        resume: Option<Option<PathBuf>>,
+        #[arg(long, help = "Re-run the files that failed in the last batch")]
+        retry_failed: bool,
     },
 
     /// compare video1.mp4 and video2.mp4
@@ -822,9 +1039,12 @@ pub enum Commands {
     ///
     /// Examples:
     ///   workflow process.yaml
+    ///   workflow process.yaml --set logo=acme.png --set start=0:05
     Workflow {
         #[arg(help = "Workflow configuration file (YAML)")]
         config_file: PathBuf,
+        #[arg(long = "set", value_name = "NAME=VALUE", help = "Set a workflow parameter or variable (repeatable)")]
+        set: Vec<String>,
     },
 
     /// motion-blur video.mp4 [--radius N]
@@ -1305,6 +1525,29 @@ pub enum Commands {
         input: PathBuf,
     },
 
+    /// qc-audio video.mp4 [--spec ebu-r128] [--max-silence 2s]
+    ///
+    /// Audio QC report: loudness, loudness range, true peak, momentary and
+    /// short-term maxima over time, clipping, DC offset, stereo phase and
+    /// silence at the head and tail. Writes <name>_qc.json and exits with an
+    /// error when any check fails against the spec, so CI can gate on it.
+    ///
+    /// Specs: podcast (the default), streaming, ebu-r128, atsc-a85, or -18lufs.
+    ///
+    /// Examples:
+    ///   qc-audio episode.wav
+    ///   qc-audio master.mov --spec ebu-r128
+    ///   qc-audio spot.mxf --spec atsc-a85 --max-silence 2s
+    #[command(name = "qc-audio")]
+    QcAudio {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+        #[arg(long, allow_hyphen_values = true, help = "Spec to check against: podcast, streaming, ebu-r128, atsc-a85 or a LUFS value (default: podcast)")]
+        spec: Option<String>,
+        #[arg(long, help = "Longest silence allowed at the head and tail (e.g., 2s)")]
+        max_silence: Option<String>,
+    },
+
     /// detect-duplicates video.mp4
     ///
     /// Find duplicate/repeated frames.
@@ -1349,6 +1592,49 @@ pub enum Commands {
         images: Vec<PathBuf>,
     },
 
+    /// sequence-to-video shots/ --fps 24  OR sequence-to-video "render/frame_%04d.exr" --fps 30
+    ///
+    /// Turn an image sequence into video. Frames are sorted naturally
+    /// (IMG_9 before IMG_10) and gaps in their numbering are reported.
+    ///
+    /// Examples:
+    ///   sequence-to-video shots/ --fps 24
+    ///   sequence-to-video "shots/*.JPG" --fps 30 --quality high
+    ///   sequence-to-video render/frame_%04d.exr --fps 24 --codec prores-hq
+    ///   sequence-to-video IMG_0001.jpg IMG_0002.jpg IMG_0003.jpg --fps 12
+    #[command(name = "sequence-to-video")]
+    SequenceToVideo {
+        #[arg(required = true, help = "A folder of images, a glob (\"shots/*.jpg\"), a numbered pattern (frame_%04d.png), or the images themselves")]
+        images: Vec<PathBuf>,
+        #[arg(long, default_value_t = 24, help = "Frames per second")]
+        fps: u32,
+        #[arg(long, help = "Video codec: h264 (default), h265, vp9, av1, prores[-...], dnxhr[-...], or ffv1")]
+        codec: Option<String>,
+        #[arg(long, help = "Quality preset: low, medium, high, or ultra")]
+        quality: Option<String>,
+    },
+
+    /// video-to-sequence video.mp4  OR video-to-sequence video.mp4 --format exr --pattern "shot_%04d" --start 1001
+    ///
+    /// Write every frame of a video as an image, losslessly (png, tiff, exr)
+    /// or as high-quality jpg.
+    ///
+    /// Examples:
+    ///   video-to-sequence video.mp4
+    ///   video-to-sequence video.mp4 --format tiff
+    ///   video-to-sequence video.mp4 --format exr --pattern "shot_%04d" --start 1001
+    #[command(name = "video-to-sequence")]
+    VideoToSequence {
+        #[arg(help = "Input video file")]
+        input: PathBuf,
+        #[arg(long, default_value = "png", help = "Image format: png, tiff, exr, or jpg")]
+        format: String,
+        #[arg(long, help = "File name pattern with a number placeholder like %04d (default: <input name>_%06d)")]
+        pattern: Option<String>,
+        #[arg(long, help = "Number of the first frame (default 1)")]
+        start: Option<u32>,
+    },
+
     /// visualize audio.mp3 --style waveform
     ///
     /// Generate video from audio with waveform or spectrum visualization.
@@ -1473,10 +1759,14 @@ pub enum Commands {
 
     /// watch folder ./input --convert to mp4
     ///
-    /// Auto-process files added to folder.
+    /// Auto-process files added to folder. Files are processed once they stop
+    /// growing, then moved into done/ or failed/. Files already in the folder
+    /// are picked up on startup.
     ///
     /// Examples:
     ///   watch folder ./input --convert to mp4
+    ///   watch folder ./input --operation convert --format mp4 --recursive --ignore '*.part'
+    ///   watch folder ./input --rules rules.yaml
     Watch {
         #[arg(help = "What to watch: folder")]
         _folder: KeywordFolder,
@@ -1488,6 +1778,16 @@ pub enum Commands {
         _to: Option<KeywordTo>,
         #[arg(long, help = "Target format (for convert operation): gif, mp4, webm, mp3, or wav")]
         format: Option<ConvertFormatCli>,
+        #[arg(long, help = "Also watch subfolders")]
+        recursive: bool,
+        #[arg(long, value_name = "SECONDS", default_value_t = 2.0, help = "Wait until a file's size and mtime are unchanged this long")]
+        settle: f64,
+        #[arg(long, value_name = "GLOB", help = "Ignore matching files (repeatable), e.g. --ignore '*.part'")]
+        ignore: Vec<String>,
+        #[arg(long, help = "Leave sources in place instead of moving them into done/ or failed/")]
+        keep_sources: bool,
+        #[arg(long, value_name = "FILE", help = "YAML rules routing files to operations or pipelines (first match wins)")]
+        rules: Option<PathBuf>,
     },
 
     /// apply-template video.mp4 template.yaml
@@ -1496,12 +1796,15 @@ pub enum Commands {
     ///
     /// Examples:
     ///   apply-template video.mp4 template.yaml
+    ///   apply-template video.mp4 template.yaml --set client=acme
     #[command(name = "apply-template")]
     Apply {
         #[arg(help = "Input video file")]
         input: PathBuf,
         #[arg(help = "Template file (YAML)")]
         template_file: PathBuf,
+        #[arg(long = "set", value_name = "NAME=VALUE", help = "Set a template parameter or variable (repeatable)")]
+        set: Vec<String>,
     },
 
     /// pipeline video.mp4 steps.yaml
@@ -1510,11 +1813,291 @@ pub enum Commands {
     ///
     /// Examples:
     ///   pipeline video.mp4 steps.yaml
+    ///   pipeline video.mp4 steps.yaml --set crf=20
     Pipeline {
         #[arg(help = "Input video file")]
         input: PathBuf,
         #[arg(help = "Pipeline steps file (YAML)")]
         steps_file: PathBuf,
+        #[arg(long = "set", value_name = "NAME=VALUE", help = "Set a pipeline parameter or variable (repeatable)")]
+        set: Vec<String>,
+    },
+
+    /// shell [video.mp4]
+    ///
+    /// Edit a file interactively: each command works on the previous result.
+    ///
+    /// Examples:
+    ///   shell interview.mov
+    ///   > trim 1:00 to 3:00
+    ///   > normalize
+    ///   > watermark logo.png bottom-right
+    ///   > undo
+    ///   > save as final.mp4
+    ///   > save pipeline steps.yaml
+    Shell {
+        #[arg(help = "File to open")]
+        file: Option<PathBuf>,
+    },
+
+    /// history [id] [--limit N]
+    ///
+    /// List recent runs, or show everything recorded about one run:
+    /// inputs with hashes, outputs, the exact ffmpeg commands and timing.
+    ///
+    /// Examples:
+    ///   history
+    ///   history 12
+    History {
+        #[arg(help = "Run to show in full")]
+        id: Option<u64>,
+        #[arg(long, default_value_t = 20, help = "Number of recent runs to list")]
+        limit: usize,
+    },
+
+    /// redo <id> [other-input.mp4]
+    ///
+    /// Run a recorded command again, optionally on a different input.
+    ///
+    /// Examples:
+    ///   redo 12
+    ///   redo 12 other_take.mp4
+    Redo {
+        #[arg(help = "Run to repeat (see ffhuman history)")]
+        id: u64,
+        #[arg(help = "Input to use instead of the original")]
+        input: Option<PathBuf>,
+    },
+
+    /// undo <id>
+    ///
+    /// Remove the files a run created, if they haven't changed since.
+    ///
+    /// Examples:
+    ///   undo 12
+    Undo {
+        #[arg(help = "Run whose outputs to remove (see ffhuman history)")]
+        id: u64,
+    },
+
+    /// Anything that isn't a command (or a typo of one) is read as a sentence, e.g.
+    /// ffhuman "make video.mp4 a 480p gif without sound, 10 seconds from 1:00"
+    #[command(external_subcommand)]
+    Sentence(Vec<String>),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SubtitleCommands {
+    /// subtitle shift movie.srt by -2.5s
+    ///
+    /// Move every subtitle earlier (-) or later (+).
+    ///
+    /// Examples:
+    ///   subtitle shift movie.srt by 1.5s
+    ///   subtitle shift movie.srt by -500ms
+    Shift {
+        #[arg(help = "Subtitle file")]
+        input: PathBuf,
+        #[arg(value_name = "by")]
+        _by: KeywordBy,
+        #[arg(allow_hyphen_values = true, help = "Offset (e.g., +2.5s, -1.2s, -500ms, -0:01.5)")]
+        offset: String,
+    },
+
+    /// subtitle stretch movie.srt --fps 25=23.976
+    ///
+    /// Retime subtitles linearly: between two cues whose right times you know,
+    /// or from the frame rate they were timed for to the video's.
+    ///
+    /// Examples:
+    ///   subtitle stretch movie.srt --fps 25=23.976
+    ///   subtitle stretch movie.srt --anchor 1:00=1:02.5 --anchor 1:30:00=1:33:45
+    Stretch {
+        #[arg(help = "Subtitle file")]
+        input: PathBuf,
+        #[arg(long, conflicts_with = "anchor", help = "Frame rates: the subtitles', then the video's (e.g., 25=23.976)")]
+        fps: Option<String>,
+        #[arg(long, help = "A cue's current time and the time it should have (e.g., 1:00=1:02.5); give two")]
+        anchor: Vec<String>,
+    },
+
+    /// subtitle cut movie.srt 0:30-1:00
+    ///
+    /// Keep the subtitles of one or more ranges, played back to back like a
+    /// cut video.
+    ///
+    /// Examples:
+    ///   subtitle cut movie.srt 0:30-1:00
+    ///   subtitle cut movie.srt 0:10-0:30,1:00-1:20
+    Cut {
+        #[arg(help = "Subtitle file")]
+        input: PathBuf,
+        #[arg(help = "Ranges to keep (e.g., 0:30-1:00 or 0:10-0:30,1:00-1:20)")]
+        keep: String,
+    },
+
+    /// subtitle merge a.srt b.srt  OR subtitle merge part1.srt part2.srt --at 0,42:10
+    ///
+    /// Combine subtitle files into one: all from the start (e.g. two
+    /// languages at once), or each from its own time for joined videos.
+    ///
+    /// Examples:
+    ///   subtitle merge dialogue.srt signs.srt
+    ///   subtitle merge part1.srt part2.srt --at 0,42:10
+    Merge {
+        #[arg(required = true, num_args = 2.., help = "Subtitle files")]
+        inputs: Vec<PathBuf>,
+        #[arg(long, value_delimiter = ',', help = "When each file starts (e.g., 0,42:10)")]
+        at: Vec<String>,
+    },
+
+    /// subtitle split movie.srt at 45:00
+    ///
+    /// Split a subtitle file into parts, each starting from 0:00.
+    ///
+    /// Examples:
+    ///   subtitle split movie.srt at 45:00
+    ///   subtitle split movie.srt at 30:00,1:00:00
+    Split {
+        #[arg(help = "Subtitle file")]
+        input: PathBuf,
+        #[arg(value_name = "at")]
+        _at: KeywordAt,
+        #[arg(help = "Times to split at (e.g., 45:00 or 30:00,1:00:00)")]
+        points: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChannelsCommands {
+    /// channels downmix movie.mkv
+    ///
+    /// Mix 5.1 or 7.1 down to stereo (ITU-R BS.775: centre and surrounds at
+    /// -3 dB, LFE left out), scaled so the mix can't clip. Video is copied.
+    ///
+    /// Examples:
+    ///   channels downmix movie.mkv
+    Downmix {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+    },
+
+    /// channels mono interview.wav [--pick left]
+    ///
+    /// Mix all channels down to mono, or keep only the left or right one
+    /// (e.g., when one side of a recording is a dead microphone).
+    ///
+    /// Examples:
+    ///   channels mono podcast.wav
+    ///   channels mono interview.wav --pick left
+    Mono {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+        #[arg(long, help = "Keep one side instead of mixing: left or right")]
+        pick: Option<String>,
+    },
+
+    /// channels swap recording.wav
+    ///
+    /// Swap left and right (for surround, every left/right pair).
+    ///
+    /// Examples:
+    ///   channels swap recording.wav
+    Swap {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+    },
+
+    /// channels split movie.mkv
+    ///
+    /// Write each channel to a mono WAV named after it (movie_FL.wav,
+    /// movie_FR.wav, movie_FC.wav, ...).
+    ///
+    /// Examples:
+    ///   channels split movie.mkv
+    ///   channels split stereo.wav --output-dir stems
+    Split {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+    },
+
+    /// channels combine L.wav R.wav [--layout stereo]
+    ///
+    /// Join mono files into one multichannel WAV, in the layout's channel
+    /// order (5.1: FL FR FC LFE BL BR). The layout defaults to the usual one
+    /// for the number of files.
+    ///
+    /// Examples:
+    ///   channels combine left.wav right.wav
+    ///   channels combine L.wav R.wav C.wav LFE.wav Ls.wav Rs.wav --layout 5.1(side)
+    Combine {
+        #[arg(required = true, num_args = 2.., help = "Mono files, one per channel, in order")]
+        inputs: Vec<PathBuf>,
+        #[arg(long, help = "Channel layout: stereo, 2.1, quad, 5.0, 5.1, 5.1(side), 6.1, 7.1, ...")]
+        layout: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChaptersCommands {
+    /// chapters add podcast.mp4 chapters.txt
+    ///
+    /// Replace the video's chapters with a list of "00:00 Intro" lines (or a
+    /// YAML list of them). Streams are copied, not re-encoded.
+    ///
+    /// Examples:
+    ///   chapters add podcast.mp4 chapters.txt
+    ///   chapters add podcast.mp4 chapters.yaml
+    Add {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+        #[arg(help = "Chapter list: text lines like \"02:15 First topic\", or YAML")]
+        chapters: PathBuf,
+    },
+
+    /// chapters list podcast.mp4
+    ///
+    /// Print the chapters as "00:00 Intro" lines, ready for a YouTube
+    /// description, or as JSON.
+    ///
+    /// Examples:
+    ///   chapters list podcast.mp4
+    ///   chapters list podcast.mp4 --json
+    List {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+        #[arg(long, help = "Print JSON instead of a chapter list")]
+        json: bool,
+    },
+
+    /// chapters extract podcast.mp4
+    ///
+    /// Save the chapters as JSON (podcast_chapters.json).
+    ///
+    /// Examples:
+    ///   chapters extract podcast.mp4
+    Extract {
+        #[arg(help = "Input video or audio file")]
+        input: PathBuf,
+    },
+
+    /// chapters from-scenes podcast.mp4
+    ///
+    /// Write a chapter list (podcast_chapters.txt) with a chapter at each scene
+    /// change, to edit the titles of and add with `chapters add`.
+    ///
+    /// Examples:
+    ///   chapters from-scenes podcast.mp4
+    ///   chapters from-scenes podcast.mp4 --min-length 120s
+    ///   chapters from-scenes podcast.mp4 --scenes podcast_scenes.txt
+    #[command(name = "from-scenes")]
+    FromScenes {
+        #[arg(help = "Input video file")]
+        input: PathBuf,
+        #[arg(long, help = "Scene times written by detect-scenes (default: detect them now)")]
+        scenes: Option<PathBuf>,
+        #[arg(long, help = "Shortest chapter (default: 10s, YouTube's minimum)")]
+        min_length: Option<String>,
     },
 }
 
@@ -1528,14 +2111,34 @@ pub enum BlurTypeCli {
 pub enum ConvertFormatCli {
     #[value(help = "Convert to animated GIF format")]
     Gif,
+    #[value(help = "Convert to animated WebP, a fraction of a GIF's size")]
+    Webp,
+    #[value(help = "Convert to animated PNG (full color, lossless frames)")]
+    Apng,
+    #[value(help = "Convert to animated AVIF, the smallest for photos and video")]
+    Avif,
     #[value(help = "Convert to MP4 video format")]
     Mp4,
     #[value(help = "Convert to WebM video format")]
     Webm,
+    #[value(help = "Convert to Matroska (MKV), which holds any codec")]
+    Mkv,
+    #[value(help = "Convert to QuickTime (MOV), e.g. for ProRes hand-offs")]
+    Mov,
     #[value(help = "Extract audio as MP3 format")]
     Mp3,
     #[value(help = "Extract audio as WAV format")]
     Wav,
+    #[value(help = "Extract audio as lossless FLAC")]
+    Flac,
+    #[value(help = "Extract audio as AAC in M4A", alias = "aac")]
+    M4a,
+    #[value(help = "Extract audio as Opus")]
+    Opus,
+    #[value(help = "Extract audio as Ogg Vorbis", alias = "vorbis")]
+    Ogg,
+    #[value(help = "Extract audio as AIFF")]
+    Aiff,
     #[value(help = "Convert to iPhone-optimized MP4 format")]
     Iphone,
     #[value(help = "Convert to Android-optimized MP4 format")]
@@ -2070,14 +2673,55 @@ impl std::fmt::Display for KeywordTemplate {
 
 impl Cli {
     pub fn parse() -> Self {
-        <Self as Parser>::parse()
+        Self::try_parse_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse arguments. A first word that isn't a command is read as a
+    /// sentence, unless it looks like a typo of one: then clap's "similar
+    /// subcommand" error is returned instead.
+    pub fn try_parse_from<I, T>(args: I) -> std::result::Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let cli = <Self as Parser>::try_parse_from(args)?;
+        if let Commands::Sentence(words) = &cli.cmd {
+            let first = words.first().map(String::as_str).unwrap_or_default();
+            if !first.contains(char::is_whitespace) && !sentence::starts_sentence(first) {
+                // The same commands without the sentence fallback
+                let strict = clap::Command::new("ffhuman")
+                    .subcommands(<Self as CommandFactory>::command().get_subcommands().cloned());
+                if let Err(e) = strict.try_get_matches_from(["ffhuman", first]) {
+                    if e.kind() == ErrorKind::InvalidSubcommand && e.get(ContextKind::SuggestedSubcommand).is_some() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(cli)
+    }
+
+    /// The configuration the global flags describe
+    pub fn config(&self) -> Result<AppConfig> {
+        if let Some(template) = &self.name {
+            validate_template(template)?;
+        }
+        let collision = self.on_collision.as_deref().map(CollisionPolicy::parse).transpose()?;
+        Ok(AppConfig::new(
+            self.out.clone(),
+            self.output_dir.clone(),
+            self.explain,
+            self.dry_run,
+            self.overwrite,
+        )
+        .with_naming(self.name.clone(), collision))
     }
 
     pub fn into_intent(self) -> Result<Intent> {
         use regex::Regex;
         
         match self.cmd {
-            Commands::Convert { input, format, quality, codec, .. } => {
+            Commands::Convert { input, format, quality, codec, max_size, .. } => {
                 // Special case: "convert video.mp4 to 360" maps to Convert360 intent
                 if matches!(format, ConvertFormatCli::Video360) {
                     return Ok(Intent::Convert360 { input });
@@ -2085,10 +2729,20 @@ impl Cli {
                 
                 let format_enum = match format {
                     ConvertFormatCli::Gif => ConvertFormat::Gif,
+                    ConvertFormatCli::Webp => ConvertFormat::Webp,
+                    ConvertFormatCli::Apng => ConvertFormat::Apng,
+                    ConvertFormatCli::Avif => ConvertFormat::Avif,
                     ConvertFormatCli::Mp4 => ConvertFormat::Mp4,
                     ConvertFormatCli::Webm => ConvertFormat::Webm,
+                    ConvertFormatCli::Mkv => ConvertFormat::Mkv,
+                    ConvertFormatCli::Mov => ConvertFormat::Mov,
                     ConvertFormatCli::Mp3 => ConvertFormat::Mp3,
                     ConvertFormatCli::Wav => ConvertFormat::Wav,
+                    ConvertFormatCli::Flac => ConvertFormat::Flac,
+                    ConvertFormatCli::M4a => ConvertFormat::M4a,
+                    ConvertFormatCli::Opus => ConvertFormat::Opus,
+                    ConvertFormatCli::Ogg => ConvertFormat::Ogg,
+                    ConvertFormatCli::Aiff => ConvertFormat::Aiff,
                     ConvertFormatCli::Iphone => ConvertFormat::Iphone,
                     ConvertFormatCli::Android => ConvertFormat::Android,
                     ConvertFormatCli::Hls => ConvertFormat::Hls,
@@ -2098,59 +2752,25 @@ impl Cli {
                 
                 let quality_parsed = quality.map(|s| QualityPreset::parse(&s)).transpose()?;
                 
-                let codec_parsed = if let Some(codec_str) = codec {
-                    let codec_lower = codec_str.trim().to_lowercase();
-                    Some(match codec_lower.as_str() {
-                        "h264" | "x264" => VideoCodec::H264,
-                        "h265" | "x265" | "hevc" => VideoCodec::H265,
-                        "vp9" => VideoCodec::Vp9,
-                        "copy" => VideoCodec::Copy,
-                        _ => anyhow::bail!("Invalid codec: {codec_str} (try h264, h265, vp9, or copy)"),
-                    })
-                } else {
-                    None
-                };
+                let codec_parsed = codec.map(|s| VideoCodec::parse(&s)).transpose()?;
                 
-                Ok(Intent::Convert { input, format: format_enum, quality: quality_parsed, codec: codec_parsed })
+                let max_size = max_size.map(|s| TargetSize::parse(&s)).transpose()?;
+
+                Ok(Intent::Convert { input, format: format_enum, quality: quality_parsed, codec: codec_parsed, max_size })
             }
             Commands::Compress { input, target, two_pass, .. } => {
-                // Try to parse as quality preset first (e.g., "high-quality")
-                let target_lower = target.trim().to_lowercase();
-                if target_lower.ends_with("-quality") {
-                    let quality_str = target_lower.strip_suffix("-quality").unwrap().trim();
-                    let quality = QualityPreset::parse(quality_str)?;
-                    Ok(Intent::Compress { input, target: CompressTarget::Quality(quality), two_pass })
-                } else {
-                    // Try to parse as bitrate first (e.g., "2000kbps", "2mbps", "500k")
-                    // Bitrate patterns: ends with bps/kbps/mbps/gbps, or just k/m/g (but not mb/gb which are sizes)
-                    let target_trimmed = target.trim();
-                    let target_lower_trimmed = target_trimmed.to_lowercase();
-                    let looks_like_bitrate = target_lower_trimmed.ends_with("bps")
-                        || target_lower_trimmed.ends_with("kbps")
-                        || target_lower_trimmed.ends_with("mbps")
-                        || target_lower_trimmed.ends_with("gbps")
-                        || (target_lower_trimmed.ends_with('k') && !target_lower_trimmed.ends_with("mk"))
-                        || (target_lower_trimmed.ends_with('m') && !target_lower_trimmed.ends_with("mb") && !target_lower_trimmed.ends_with("gb"))
-                        || (target_lower_trimmed.ends_with('g') && !target_lower_trimmed.ends_with("gb"));
-                    
-                    if looks_like_bitrate {
-                        if let Ok(bitrate) = TargetBitrate::parse(&target) {
-                            return Ok(Intent::Compress { input, target: CompressTarget::Bitrate(bitrate), two_pass });
-                        }
-                    }
-                    
-                    // Fall back to size parsing
-                    let target_size = TargetSize::parse(&target)?;
-                    Ok(Intent::Compress { input, target: CompressTarget::Size(target_size), two_pass })
-                }
+                let target = CompressTarget::parse(&target)?;
+                Ok(Intent::Compress { input, target, two_pass })
             }
             Commands::Trim { input, start, end, .. } => {
                 let start = Time::parse(&start)?;
                 let end = Time::parse(&end)?;
                 Ok(Intent::Trim { input, start, end })
             }
-            Commands::Extract { input, .. } => {
-                Ok(Intent::ExtractAudio { input, format: AudioFormat::Mp3 })
+            Commands::Extract { input, format, quality } => {
+                let format = AudioFormat::parse(&format)?;
+                let quality = quality.map(|s| QualityPreset::parse(&s)).transpose()?;
+                Ok(Intent::ExtractAudio { input, format, quality })
             }
             Commands::AdjustVolume { input, adjustment, .. } => {
                 let adjustment_parsed = VolumeAdjustment::parse(&adjustment)?;
@@ -2164,10 +2784,12 @@ impl Cli {
             Commands::MixAudio { audio1, audio2, .. } => {
                 Ok(Intent::MixAudio { audio1, audio2 })
             }
-            Commands::ExtractAudioRange { input, start, end, .. } => {
+            Commands::ExtractAudioRange { input, start, end, format, quality, .. } => {
                 let start_parsed = Time::parse(&start)?;
                 let end_parsed = Time::parse(&end)?;
-                Ok(Intent::ExtractAudioRange { input, start: start_parsed, end: end_parsed, format: AudioFormat::Mp3 })
+                let format = AudioFormat::parse(&format)?;
+                let quality = quality.map(|s| QualityPreset::parse(&s)).transpose()?;
+                Ok(Intent::ExtractAudioRange { input, start: start_parsed, end: end_parsed, format, quality })
             }
             Commands::Resize { input, target, .. } => {
                 let target = ResizeTarget::parse(&target)?;
@@ -2274,8 +2896,13 @@ impl Cli {
                 };
                 Ok(Intent::Blur { input, blur_type: blur_type_parsed })
             }
-            Commands::Normalize { input } => {
-                Ok(Intent::Normalize { input })
+            Commands::Normalize { input, target, lufs } => {
+                let target = match (target, lufs) {
+                    (_, Some(lufs)) => LoudnessTarget::lufs(lufs)?,
+                    (Some(target), None) => LoudnessTarget::parse(&target)?,
+                    (None, None) => LoudnessTarget::default(),
+                };
+                Ok(Intent::Normalize { input, target })
             }
             Commands::Fade { input, fade_in, fade_out } => {
                 // Validate that at least one fade is specified
@@ -2288,45 +2915,139 @@ impl Cli {
                 
                 Ok(Intent::Fade { input, fade_in: fade_in_parsed, fade_out: fade_out_parsed })
             }
-            Commands::Split { input, mode } => {
+            Commands::Split { input, mode, threshold, min_silence, noise } => {
                 // Parse split mode: "every 30s" or "into 3 parts"
-                let mode_lower = mode.trim().to_lowercase();
-                
-                if mode_lower.starts_with("every ") {
-                    let duration_str = mode_lower.strip_prefix("every ").unwrap().trim();
-                    let duration = Duration::parse(duration_str)?;
-                    Ok(Intent::Split { input, mode: SplitMode::Every(duration) })
-                } else if mode_lower.starts_with("into ") {
-                    let re = Regex::new(r"^into\s+(\d+)\s+parts?$")
-                        .map_err(|e| anyhow!("Invalid regex: {}", e))?;
-                    let caps = re.captures(&mode_lower)
-                        .ok_or_else(|| anyhow!("Invalid split mode: {mode} (try 'every 30s' or 'into 3 parts')"))?;
-                    let parts = caps.get(1).unwrap().as_str().parse::<u32>()?;
-                    if parts < 2 {
-                        anyhow::bail!("Split must be into at least 2 parts");
-                    }
-                    Ok(Intent::Split { input, mode: SplitMode::IntoParts(parts) })
-                } else {
-                    anyhow::bail!("Invalid split mode: {mode} (try 'every 30s' or 'into 3 parts')");
-                }
+                let min_silence = min_silence.map(|d| Duration::parse(&d)).transpose()?;
+                let noise = noise.map(|n| parse_noise(&n)).transpose()?;
+                let mode = SplitMode::parse(&mode.join(" "))?.tune(threshold, min_silence, noise)?;
+                Ok(Intent::Split { input, mode })
             }
             Commands::ExtractFrames { input, interval, .. } => {
                 let interval_parsed = Duration::parse(&interval)?;
                 Ok(Intent::ExtractFrames { input, interval: interval_parsed })
             }
-            Commands::BurnSubtitle { input, subtitle } => {
-                Ok(Intent::BurnSubtitle { input, subtitle })
+            Commands::BurnSubtitle { input, subtitle, track, preset, font, size, color, outline, background, position, margin } => {
+                let track = track.map(|t| SubtitleSelector::parse(&t)).transpose()?;
+                let style = SubtitleStyle {
+                    preset: preset.map(|p| CaptionPreset::parse(&p)).transpose()?,
+                    font,
+                    size,
+                    color: color.map(|c| TextColor::parse(&c)).transpose()?,
+                    outline,
+                    background: background.map(|c| TextColor::parse(&c)).transpose()?,
+                    position: position.map(|p| CaptionPosition::parse(&p)).transpose()?,
+                    margin,
+                };
+                Ok(Intent::BurnSubtitle { input, subtitle, track, style })
+            }
+            Commands::AddSubtitles { input, subtitles, lang, to } => {
+                let languages = lang.iter().map(|l| Language::parse(l)).collect::<Result<Vec<_>>>()?;
+                let to = to.map(|s| SubtitleContainer::parse(&s)).transpose()?;
+                Ok(Intent::AddSubtitles { input, subtitles, languages, to })
+            }
+            Commands::ExtractSubtitles { input, format, track } => {
+                let format = format.map(|s| SubtitleFormat::parse(&s)).transpose()?;
+                Ok(Intent::ExtractSubtitles { input, format, track })
             }
-            Commands::Batch { operation, pattern, format, r#if, .. } => {
+            Commands::ConvertSubtitles { input, format, .. } => {
+                let format = SubtitleFormat::parse(&format)?;
+                Ok(Intent::ConvertSubtitles { input, format })
+            }
+            Commands::Subtitle { action } => match action {
+                SubtitleCommands::Shift { input, offset, .. } => {
+                    let offset = Offset::parse(&offset)?;
+                    Ok(Intent::ShiftSubtitles { input, offset })
+                }
+                SubtitleCommands::Stretch { input, fps, anchor } => {
+                    let stretch = match (fps, anchor.as_slice()) {
+                        (Some(fps), _) => Stretch::parse(&format!("{}fps", fps.trim_end_matches("fps")))?,
+                        (None, [_, _]) => Stretch::parse(&anchor.join(","))?,
+                        (None, _) => anyhow::bail!("Give --fps, or --anchor twice"),
+                    };
+                    Ok(Intent::StretchSubtitles { input, stretch })
+                }
+                SubtitleCommands::Cut { input, keep } => {
+                    let keep = CutList::parse(&keep)?;
+                    Ok(Intent::CutSubtitles { input, keep })
+                }
+                SubtitleCommands::Merge { inputs, at } => {
+                    let at = at.iter().map(|t| Timestamp::parse(t)).collect::<Result<Vec<_>>>()?;
+                    Ok(Intent::MergeSubtitles { inputs, at })
+                }
+                SubtitleCommands::Split { input, points, .. } => {
+                    let at = points.split(',').map(Timestamp::parse).collect::<Result<Vec<_>>>()?;
+                    Ok(Intent::SplitSubtitles { input, at })
+                }
+            },
+            Commands::Chapters { action } => match action {
+                ChaptersCommands::Add { input, chapters } => Ok(Intent::AddChapters { input, chapters }),
+                ChaptersCommands::List { input, json } => Ok(Intent::ListChapters { input, json }),
+                ChaptersCommands::Extract { input } => Ok(Intent::ExtractChapters { input }),
+                ChaptersCommands::FromScenes { input, scenes, min_length } => {
+                    let min_length = min_length.map(|d| Duration::parse(&d)).transpose()?;
+                    Ok(Intent::ChaptersFromScenes { input, scenes, min_length })
+                }
+            },
+            Commands::Channels { action } => match action {
+                ChannelsCommands::Downmix { input } => Ok(Intent::Downmix { input }),
+                ChannelsCommands::Mono { input, pick } => {
+                    let pick = pick.map(|p| ChannelPick::parse(&p)).transpose()?;
+                    Ok(Intent::ToMono { input, pick })
+                }
+                ChannelsCommands::Swap { input } => Ok(Intent::SwapChannels { input }),
+                ChannelsCommands::Split { input } => Ok(Intent::SplitChannels { input }),
+                ChannelsCommands::Combine { inputs, layout } => {
+                    let layout = layout.map(|l| ChannelLayout::parse(&l)).transpose()?;
+                    Ok(Intent::CombineChannels { inputs, layout })
+                }
+            },
+            Commands::Batch { operation, pattern, format, r#if, force, since, state, resume, retry_failed, .. } => {
+                let mut options = BatchOptions {
+                    force,
+                    since: since.map(|s| SinceCutoff::parse(&s)).transpose()?,
+                    state_file: state,
+                    resume: resume.is_some(),
+                    retry_failed,
+                };
+
+                // Resumed and retried batches take their operation from the job-state file
+                if options.resume || options.retry_failed {
+                    if operation.is_some() || pattern.is_some() {
+                        anyhow::bail!("--resume and --retry-failed continue the batch recorded in the job-state file; drop the operation and pattern");
+                    }
+                    let given = resume.flatten().or(options.state_file.clone());
+                    let state_path = crate::util::job_state::locate_state_file(given.as_deref(), self.output_dir.as_deref())?;
+                    let job_state = crate::util::job_state::JobState::load(&state_path)?;
+                    let batch_op = BatchOperation::parse(&job_state.operation)?;
+                    let condition = job_state.condition.as_deref().map(ProcessingCondition::parse).transpose()?;
+                    options.state_file = Some(state_path);
+                    return Ok(match condition {
+                        Some(condition) => Intent::ConditionalBatch { pattern: job_state.pattern, operation: batch_op, condition, options },
+                        None => Intent::Batch { pattern: job_state.pattern, operation: batch_op, options },
+                    });
+                }
+
+                let operation = operation.ok_or_else(|| anyhow!("Batch requires an operation"))?;
+                let pattern = pattern.ok_or_else(|| anyhow!("Batch requires a file pattern"))?;
                 match operation {
                     BatchOperationCli::Convert => {
                         let format_parsed = format.ok_or_else(|| anyhow!("Batch convert requires a target format"))?;
                         let format_enum = match format_parsed {
                             ConvertFormatCli::Gif => ConvertFormat::Gif,
+                            ConvertFormatCli::Webp => ConvertFormat::Webp,
+                            ConvertFormatCli::Apng => ConvertFormat::Apng,
+                            ConvertFormatCli::Avif => ConvertFormat::Avif,
                             ConvertFormatCli::Mp4 => ConvertFormat::Mp4,
                             ConvertFormatCli::Webm => ConvertFormat::Webm,
+                            ConvertFormatCli::Mkv => ConvertFormat::Mkv,
+                            ConvertFormatCli::Mov => ConvertFormat::Mov,
                             ConvertFormatCli::Mp3 => ConvertFormat::Mp3,
                             ConvertFormatCli::Wav => ConvertFormat::Wav,
+                            ConvertFormatCli::Flac => ConvertFormat::Flac,
+                            ConvertFormatCli::M4a => ConvertFormat::M4a,
+                            ConvertFormatCli::Opus => ConvertFormat::Opus,
+                            ConvertFormatCli::Ogg => ConvertFormat::Ogg,
+                            ConvertFormatCli::Aiff => ConvertFormat::Aiff,
                             ConvertFormatCli::Iphone => ConvertFormat::Iphone,
                             ConvertFormatCli::Android => ConvertFormat::Android,
                             ConvertFormatCli::Hls => ConvertFormat::Hls,
@@ -2336,10 +3057,10 @@ impl Cli {
                         let batch_op = BatchOperation::Convert(format_enum);
                         
                         if let Some(condition_str) = r#if {
-                            let condition = parse_processing_condition(&condition_str)?;
-                            Ok(Intent::ConditionalBatch { pattern, operation: batch_op, condition })
+                            let condition = ProcessingCondition::parse(&condition_str)?;
+                            Ok(Intent::ConditionalBatch { pattern, operation: batch_op, condition, options })
                         } else {
-                            Ok(Intent::Batch { pattern, operation: batch_op })
+                            Ok(Intent::Batch { pattern, operation: batch_op, options })
                         }
                     }
                 }
@@ -2408,8 +3129,8 @@ impl Cli {
             Commands::Suggest { input, .. } => {
                 Ok(Intent::SuggestFormat { input })
             }
-            Commands::Workflow { config_file } => {
-                Ok(Intent::Workflow { config_file })
+            Commands::Workflow { config_file, set } => {
+                Ok(Intent::Workflow { config_file, vars: parse_assignments(&set)? })
             }
             Commands::MotionBlur { input, radius } => {
                 Ok(Intent::MotionBlur { input, radius })
@@ -2424,14 +3145,9 @@ impl Cli {
                 Ok(Intent::Interpolate { input, fps })
             }
             Commands::ExtractMetadata { input, format, .. } => {
-                let format_enum = if let Some(fmt_str) = format {
-                    match fmt_str.trim().to_lowercase().as_str() {
-                        "json" => crate::model::intent::MetadataFormat::Json,
-                        "xml" => crate::model::intent::MetadataFormat::Xml,
-                        _ => anyhow::bail!("Invalid metadata format: {fmt_str} (try json or xml)"),
-                    }
-                } else {
-                    crate::model::intent::MetadataFormat::Json // default
+                let format_enum = match format {
+                    Some(fmt_str) => crate::model::intent::MetadataFormat::parse(&fmt_str)?,
+                    None => crate::model::intent::MetadataFormat::Json, // default
                 };
                 Ok(Intent::ExtractMetadata { input, format: format_enum })
             }
@@ -2555,6 +3271,11 @@ impl Cli {
             Commands::AnalyzeLoudness { input, .. } => {
                 Ok(Intent::AnalyzeLoudness { input })
             }
+            Commands::QcAudio { input, spec, max_silence } => {
+                let spec = spec.as_deref().map(LoudnessTarget::parse).transpose()?.unwrap_or_default();
+                let max_silence = max_silence.as_deref().map(Duration::parse).transpose()?;
+                Ok(Intent::QcAudio { input, spec, max_silence })
+            }
             Commands::DetectDuplicates { input, .. } => {
                 Ok(Intent::DetectDuplicates { input })
             }
@@ -2576,6 +3297,15 @@ impl Cli {
                 let duration_parsed = Duration::parse(&duration)?;
                 Ok(Intent::Slideshow { images, duration: duration_parsed })
             }
+            Commands::SequenceToVideo { images, fps, codec, quality } => {
+                let codec = codec.map(|s| VideoCodec::parse(&s)).transpose()?;
+                let quality = quality.map(|s| QualityPreset::parse(&s)).transpose()?;
+                Ok(Intent::SequenceToVideo { images, fps, codec, quality })
+            }
+            Commands::VideoToSequence { input, format, pattern, start } => {
+                let format = FrameFormat::parse(&format)?;
+                Ok(Intent::VideoToSequence { input, format, pattern, start })
+            }
             Commands::Visualize { audio, style } => {
                 let style_parsed = if let Some(style_str) = style {
                     crate::model::types::VisualizationStyle::parse(&style_str)?
@@ -2610,60 +3340,58 @@ impl Cli {
             Commands::FixFramerate { input, .. } => {
                 Ok(Intent::FixFramerate { input })
             }
-            Commands::Watch { folder, operation, format, .. } => {
-                let batch_op = if let Some(BatchOperationCli::Convert) = operation {
+            Commands::Watch { folder, operation, format, recursive, settle, ignore, keep_sources, rules, .. } => {
+                let batch_op = if rules.is_some() {
+                    if operation.is_some() || format.is_some() {
+                        anyhow::bail!("Use either --rules or --operation/--format, not both");
+                    }
+                    None
+                } else if let Some(BatchOperationCli::Convert) = operation {
                     let format_parsed = format.ok_or_else(|| anyhow!("Watch requires a target format"))?;
                     let format_enum = match format_parsed {
                         ConvertFormatCli::Gif => ConvertFormat::Gif,
+                        ConvertFormatCli::Webp => ConvertFormat::Webp,
+                        ConvertFormatCli::Apng => ConvertFormat::Apng,
+                        ConvertFormatCli::Avif => ConvertFormat::Avif,
                         ConvertFormatCli::Mp4 => ConvertFormat::Mp4,
                         ConvertFormatCli::Webm => ConvertFormat::Webm,
+                        ConvertFormatCli::Mkv => ConvertFormat::Mkv,
+                        ConvertFormatCli::Mov => ConvertFormat::Mov,
                         ConvertFormatCli::Mp3 => ConvertFormat::Mp3,
                         ConvertFormatCli::Wav => ConvertFormat::Wav,
+                        ConvertFormatCli::Flac => ConvertFormat::Flac,
+                        ConvertFormatCli::M4a => ConvertFormat::M4a,
+                        ConvertFormatCli::Opus => ConvertFormat::Opus,
+                        ConvertFormatCli::Ogg => ConvertFormat::Ogg,
+                        ConvertFormatCli::Aiff => ConvertFormat::Aiff,
                         ConvertFormatCli::Iphone => ConvertFormat::Iphone,
                         ConvertFormatCli::Android => ConvertFormat::Android,
                         ConvertFormatCli::Hls => ConvertFormat::Hls,
                         ConvertFormatCli::Dash => ConvertFormat::Dash,
                         ConvertFormatCli::Video360 => ConvertFormat::Video360,
                     };
-                    BatchOperation::Convert(format_enum)
+                    Some(BatchOperation::Convert(format_enum))
                 } else {
-                    anyhow::bail!("Watch currently only supports --convert operation");
+                    anyhow::bail!("Watch needs --operation convert --format <FORMAT>, or --rules <FILE>");
                 };
-                Ok(Intent::WatchFolder { folder, operation: batch_op })
+                if settle < 0.0 {
+                    anyhow::bail!("--settle must be zero or more seconds");
+                }
+                let options = WatchOptions { recursive, settle_seconds: settle, ignore, move_sources: !keep_sources, rules };
+                Ok(Intent::WatchFolder { folder, operation: batch_op, options })
             }
-            Commands::Apply { input, template_file, .. } => {
-                Ok(Intent::ApplyTemplate { input, template_file })
+            Commands::Apply { input, template_file, set } => {
+                Ok(Intent::ApplyTemplate { input, template_file, vars: parse_assignments(&set)? })
             }
-            Commands::Pipeline { input, steps_file } => {
-                Ok(Intent::Pipeline { input, steps_file })
+            Commands::Pipeline { input, steps_file, set } => {
+                Ok(Intent::Pipeline { input, steps_file, vars: parse_assignments(&set)? })
             }
+            Commands::Shell { file } => Ok(Intent::Shell { file }),
+            Commands::History { id, limit } => Ok(Intent::History { id, limit }),
+            Commands::Redo { id, input } => Ok(Intent::Redo { id, input }),
+            Commands::Undo { id } => Ok(Intent::Undo { id }),
+            Commands::Sentence(words) => Ok(Intent::Sentence { text: words.join(" ") }),
         }
     }
 }
 
-/// Parse a processing condition string like "duration < 30s"
-fn parse_processing_condition(s: &str) -> Result<crate::model::types::ProcessingCondition> {
-    use crate::model::types::*;
-    use regex::Regex;
-    
-    let s = s.trim();
-    
-    // Parse patterns like "duration < 30s", "duration > 1:00", "duration = 30s"
-    let re = Regex::new(r"(?i)^\s*duration\s*(<|>|=)\s*(.+)$")
-        .map_err(|e| anyhow!("Invalid regex: {}", e))?;
-    
-    let caps = re.captures(s)
-        .ok_or_else(|| anyhow!("Invalid condition: {s} (try 'duration < 30s')"))?;
-    
-    let operator = caps.get(1).unwrap().as_str();
-    let value_str = caps.get(2).unwrap().as_str();
-    let duration = Duration::parse(value_str)?;
-    
-    match operator {
-        "<" => Ok(ProcessingCondition::DurationLessThan(duration)),
-        ">" => Ok(ProcessingCondition::DurationGreaterThan(duration)),
-        "=" => Ok(ProcessingCondition::DurationEquals(duration)),
-        _ => anyhow::bail!("Invalid operator: {operator} (use <, >, or =)"),
-    }
-}
-
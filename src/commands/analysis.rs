@@ -1,8 +1,11 @@
 use crate::config::AppConfig;
+use crate::ffmpeg::probe;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
+use crate::model::qc::{AudioQc, QcReport};
+use crate::model::{Duration, LoudnessTarget};
 use crate::util::{default_out, system::ensure_ffmpeg_exists};
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use std::path::Path;
 
 pub fn handle_detect_silence(
@@ -32,15 +35,79 @@ pub fn handle_analyze_loudness(
     let input = input.as_ref();
 
     let out = default_out(config, input, "loudness", "json")?;
-    let steps = recipes::analyze_loudness_steps(input, &out, config.overwrite);
-    for step in steps {
-        runner.run(&step)?;
+    if config.dry_run {
+        let steps = recipes::analyze_loudness_steps(input, &out, &LoudnessTarget::default(), config.overwrite);
+        for step in steps {
+            runner.run(&step)?;
+        }
+    } else {
+        let loudness = probe::measure_loudness(input)?;
+        let json = serde_json::json!({
+            "integrated_lufs": loudness.integrated,
+            "true_peak_dbtp": loudness.true_peak,
+            "range_lu": loudness.range,
+            "threshold_lufs": loudness.threshold,
+        });
+        let json = serde_json::to_string_pretty(&json).context("failed to serialize loudness")? + "\n";
+        std::fs::write(&out, json).with_context(|| format!("failed to write {}", out.display()))?;
+        eprintln!("Loudness: {loudness}");
     }
 
     eprintln!("Output: {}", out.display());
     Ok(())
 }
 
+pub fn handle_qc_audio(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    spec: LoudnessTarget,
+    max_silence: Option<Duration>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let out = default_out(config, input, "qc", "json")?;
+    let stereo = probe::channels(input) == Some(2);
+    if config.explain {
+        eprintln!("[explain] One pass of ebur128 (loudness, true peak), astats (clipping, DC offset), silencedetect{}",
+            if stereo { " and aphasemeter (phase correlation)" } else { "" });
+    }
+    if config.dry_run {
+        for step in recipes::qc_audio_steps(input, &out, stereo, config.overwrite) {
+            runner.run(&step)?;
+        }
+        eprintln!("Output: {}", out.display());
+        return Ok(());
+    }
+
+    eprintln!("Analyzing audio...");
+    let duration = probe::duration_seconds(input)?;
+    let log = probe::audio_filter_log(input, &recipes::qc_audio_filter(stereo))?;
+    let measurements = AudioQc::parse(&log, duration)?;
+    let report = QcReport::new(input, &spec, measurements, max_silence.map(|d| d.to_seconds()));
+    report.write(&out)?;
+
+    let m = &report.measurements;
+    println!("{}  (spec: {spec})", input.display());
+    println!("  Loudness {:.1} LUFS, range {:.1} LU, true peak {:.1} dBTP", m.integrated, m.range, m.true_peak);
+    if let (Some(momentary), Some(short_term)) = (m.momentary_max, m.short_term_max) {
+        println!("  Loudest moment {:.1} LUFS at {:.1}s, loudest 3s {:.1} LUFS at {:.1}s",
+            momentary.lufs, momentary.time, short_term.lufs, short_term.time);
+    }
+    println!("  Silence {:.2}s at the head, {:.2}s at the tail", m.head_silence, m.tail_silence);
+    for check in &report.checks {
+        println!("  {check}");
+    }
+    eprintln!("Output: {}", out.display());
+
+    let failed = report.checks.iter().filter(|c| !c.pass).count();
+    if failed > 0 {
+        bail!("{} failed {failed} of {} checks against {spec}", input.display(), report.checks.len());
+    }
+    Ok(())
+}
+
 pub fn handle_detect_duplicates(
     config: &AppConfig,
     runner: &dyn Runner,
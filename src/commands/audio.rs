@@ -1,9 +1,10 @@
 use crate::config::AppConfig;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
-use crate::model::{AudioFormat, AudioSyncDirection, Duration, Time, VolumeAdjustment, SpeedFactor};
+use crate::ffmpeg::probe::{self, stream_codec};
+use crate::model::{AudioFormat, AudioSyncDirection, Duration, LoudnessTarget, QualityPreset, Time, VolumeAdjustment, SpeedFactor};
 use crate::util::{base_stem, default_out, system::ensure_ffmpeg_exists};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::path::Path;
 
 pub fn handle_extract_audio(
@@ -11,16 +12,14 @@ pub fn handle_extract_audio(
     runner: &dyn Runner,
     input: impl AsRef<Path>,
     format: AudioFormat,
+    quality: Option<QualityPreset>,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
 
-    let ext = match format {
-        AudioFormat::Mp3 => "mp3",
-        AudioFormat::Wav => "wav",
-    };
+    let ext = container_for(config, input, format);
     let out = default_out(config, input, "audio", ext)?;
-    let steps = recipes::extract_audio_steps(input, &out, ext, config.overwrite);
+    let steps = recipes::extract_audio_steps(input, &out, format, quality, config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
@@ -29,6 +28,23 @@ pub fn handle_extract_audio(
     Ok(())
 }
 
+/// Container extension for extracted audio. Auto copies the input's audio
+/// as-is, into the container made for its codec or else into Matroska audio.
+pub fn container_for(config: &AppConfig, input: &Path, format: AudioFormat) -> &'static str {
+    if format != AudioFormat::Auto {
+        return format.ext();
+    }
+    let codec = stream_codec(input, "a:0");
+    let ext = codec.as_deref().and_then(AudioFormat::for_codec).unwrap_or(AudioFormat::Auto).ext();
+    if config.explain {
+        match codec {
+            Some(codec) => eprintln!("[explain] Audio is {codec}: copying it into .{ext} without re-encoding"),
+            None => eprintln!("[explain] Audio codec unknown: copying it into .{ext} without re-encoding"),
+        }
+    }
+    ext
+}
+
 pub fn handle_mute(
     config: &AppConfig,
     runner: &dyn Runner,
@@ -85,16 +101,56 @@ pub fn handle_normalize(
     config: &AppConfig,
     runner: &dyn Runner,
     input: impl AsRef<Path>,
+    target: LoudnessTarget,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
 
-    let out = default_out(config, input, "normalized", "mp4")?;
-    let steps = recipes::normalize_steps(input, &out, config.overwrite);
+    // Audio files keep their container and, where lossless, their bit depth
+    let ext = input.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    let (ext, audio_codec) = match ext.filter(|ext| AudioFormat::parse(ext).is_ok_and(|f| f != AudioFormat::Auto)) {
+        Some(ext) => {
+            let args = recipes::keep_audio_args(&ext, probe::audio_encoding(input).as_ref());
+            (ext, args)
+        }
+        None => ("mp4".to_string(), vec!["-c:a".to_string(), "aac".to_string()]),
+    };
+    let out = default_out(config, input, "normalized", &ext)?;
+    if config.dry_run {
+        // The second pass is built from what the first one measures; show the measuring pass
+        for step in recipes::analyze_loudness_steps(input, &out, &target, config.overwrite) {
+            runner.run(&step)?;
+        }
+        eprintln!("Then: loudnorm with the measured values to {} LUFS, {} dBTP (linear)", target.integrated(), target.true_peak());
+        eprintln!("Output: {}", out.display());
+        return Ok(());
+    }
+
+    eprintln!("Measuring loudness...");
+    let before = probe::measure_loudness_for(input, &target)?;
+    if !before.integrated.is_finite() {
+        bail!("{} is silent; there is no loudness to normalize", input.display());
+    }
+    let gain = target.integrated() - before.integrated;
+    if config.explain {
+        eprintln!("[explain] Target {target}: {} LUFS, {} dBTP; the second pass applies one {gain:+.1} dB gain to the whole file",
+            target.integrated(), target.true_peak());
+    }
+    if before.true_peak + gain > target.true_peak() {
+        eprintln!("Warning: {gain:+.1} dB would push peaks to {:.1} dBTP, over {} dBTP; loudnorm will limit them instead of applying a plain gain",
+            before.true_peak + gain, target.true_peak());
+    }
+
+    let steps = recipes::normalize_steps(input, &out, &target, Some(&before), probe::sample_rate(input), &audio_codec, config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
 
+    eprintln!("Before: {before}");
+    match probe::measure_loudness(&out) {
+        Ok(after) => eprintln!("After:  {after}"),
+        Err(e) => eprintln!("Warning: couldn't measure the result: {e}"),
+    }
     eprintln!("Output: {}", out.display());
     Ok(())
 }
@@ -202,16 +258,14 @@ pub fn handle_extract_audio_range(
     start: Time,
     end: Time,
     format: AudioFormat,
+    quality: Option<QualityPreset>,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
 
-    let ext = match format {
-        AudioFormat::Mp3 => "mp3",
-        AudioFormat::Wav => "wav",
-    };
+    let ext = container_for(config, input, format);
     let out = default_out(config, input, "audio_range", ext)?;
-    let steps = recipes::extract_audio_range_steps(input, &out, &start, &end, ext, config.overwrite);
+    let steps = recipes::extract_audio_range_steps(input, &out, &start, &end, format, quality, config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
@@ -1,11 +1,18 @@
 use crate::app::App;
 use crate::config::AppConfig;
 use crate::ffmpeg::runner::Runner;
-use crate::model::{BatchOperation, Intent};
-use crate::util::system::ensure_ffmpeg_exists;
+use crate::ffmpeg::probe::duration_seconds;
+use crate::model::types::{Duration, ProcessingCondition};
+use crate::model::{BatchOperation, BatchOptions, Intent};
+use crate::util::job_state::{default_state_path, JobState, JobStatus, OutputSettings};
+use crate::util::manifest::{size_and_mtime, Manifest};
+use crate::util::{ensure_per_file_output, planned_out, system::{ensure_ffmpeg_exists, ffmpeg_version}, OutputSkipped};
 use anyhow::Result;
 use glob::glob;
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 /// Draw a simple progress bar
 fn draw_progress_bar(current: usize, total: usize, width: usize) {
@@ -31,70 +38,178 @@ fn draw_progress_bar(current: usize, total: usize, width: usize) {
     io::stderr().flush().ok();
 }
 
-pub fn handle_batch(
-    config: &AppConfig,
-    _runner: &dyn Runner, // Not used directly, we create our own App
-    pattern: &str,
-    operation: BatchOperation,
-) -> Result<()> {
-    ensure_ffmpeg_exists()?;
+/// Result of processing a single file in a batch
+enum FileOutcome {
+    Done,
+    UpToDate,
+    Skipped,
+    Failed(String),
+}
 
-    // Expand glob pattern to get matching files
-    let files: Vec<_> = glob(pattern)?
-        .filter_map(|entry| entry.ok())
-        .filter(|path| path.is_file())
-        .collect();
+/// Manifests and options for make-style incremental batch runs
+struct Incremental {
+    options: BatchOptions,
+    ffmpeg_version: String,
+    manifests: HashMap<PathBuf, Manifest>,
+}
 
-    if files.is_empty() {
-        anyhow::bail!("No files found matching pattern: {}", pattern);
+impl Incremental {
+    fn new(options: BatchOptions) -> Self {
+        Self {
+            options,
+            ffmpeg_version: ffmpeg_version().unwrap_or_else(|| "unknown".to_string()),
+            manifests: HashMap::new(),
+        }
     }
 
-    eprintln!("Processing {} files...\n", files.len());
+    fn manifest_for(&mut self, output: &Path) -> Result<&mut Manifest> {
+        let dir = output.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+        if !self.manifests.contains_key(&dir) {
+            let manifest = Manifest::load(&dir)?;
+            self.manifests.insert(dir.clone(), manifest);
+        }
+        Ok(self.manifests.get_mut(&dir).unwrap())
+    }
+}
 
-    // Create a temporary app to execute intents
-    let app = App::new(config.clone());
-    
-    let total = files.len();
-    let mut successful = 0;
-    let mut failed = 0;
-
-    for (idx, file) in files.iter().enumerate() {
-        // Draw progress bar
-        draw_progress_bar(idx, total, 50);
-        eprintln!("\n[{}/{}] Processing: {}", idx + 1, total, file.display());
-
-        let intent = match &operation {
-            BatchOperation::Convert(format) => {
-                Intent::Convert {
-                    input: file.clone(),
-                    format: *format,
-                    quality: None,
-                    codec: None,
-                }
+/// Build the intent that applies a batch operation to one file
+pub fn build_intent(file: &Path, operation: &BatchOperation) -> Intent {
+    match operation {
+        BatchOperation::Convert(format) => {
+            Intent::Convert {
+                input: file.to_path_buf(),
+                format: *format,
+                quality: None,
+                codec: None,
+                max_size: None,
             }
-        };
+        }
+    }
+}
 
-        match app.execute(intent) {
-            Ok(_) => {
-                successful += 1;
-                eprintln!("Completed: {}", file.display());
+/// Output path the intent will write to, if it is a single predictable file
+pub fn expected_output(config: &AppConfig, intent: &Intent) -> Option<PathBuf> {
+    match intent {
+        Intent::Convert { input, format, .. } => {
+            let (suffix, ext) = format.output_naming()?;
+            planned_out(config, input, suffix, ext).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Stable fingerprint of a batch operation's parameters for the manifest.
+///
+/// Values are written the way they are on the command line, so renaming a
+/// field or changing `Debug` output in a later release doesn't make every
+/// recorded output stale.
+pub fn operation_fingerprint(operation: &BatchOperation) -> String {
+    let value = match operation {
+        BatchOperation::Convert(format) => serde_json::json!({
+            "operation": "convert",
+            "format": format.to_string(),
+        }),
+    };
+    value.to_string()
+}
+
+/// Process one file, skipping it when the manifest shows its output is up to date
+fn process_file(
+    config: &AppConfig,
+    app: &App,
+    rebuild_app: &App,
+    state: &mut Incremental,
+    file: &Path,
+    operation: &BatchOperation,
+) -> FileOutcome {
+    if let Some(since) = state.options.since {
+        match size_and_mtime(file) {
+            Ok((_, mtime)) if mtime < since.unix_seconds => {
+                eprintln!("Skipped: not modified since cutoff: {}", file.display());
+                return FileOutcome::Skipped;
             }
-            Err(e) => {
-                failed += 1;
-                eprintln!("Error processing {}: {}", file.display(), e);
-                // Continue with next file instead of failing completely
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    let intent = build_intent(file, operation);
+    let intent_key = operation_fingerprint(operation);
+    let output = expected_output(config, &intent);
+
+    // Outputs recorded in the manifest belong to us: rebuild them in place when stale
+    let mut owned = false;
+    if let Some(out) = &output {
+        let ffmpeg_version = state.ffmpeg_version.clone();
+        let force = state.options.force;
+        match state.manifest_for(out) {
+            Ok(manifest) => {
+                if !force {
+                    match manifest.is_up_to_date(out, file, &intent_key, &ffmpeg_version) {
+                        Ok(true) => {
+                            eprintln!("Up to date: {}", out.display());
+                            return FileOutcome::UpToDate;
+                        }
+                        Ok(false) => {}
+                        Err(e) => eprintln!("Warning: {}", e),
+                    }
+                }
+                owned = manifest.contains(out) && out.exists();
             }
+            Err(e) => eprintln!("Warning: {}", e),
         }
+    } else if config.explain {
+        eprintln!("[explain] Output of {} is not tracked in the manifest; it is always re-run.", file.display());
     }
 
-    // Final progress bar
-    draw_progress_bar(total, total, 50);
-    eprintln!("\n\nBatch processing complete!");
-    eprintln!(" Successful: {}", successful);
-    if failed > 0 {
-        eprintln!(" Failed: {}", failed);
+    let runner_app = if owned { rebuild_app } else { app };
+    let recorded_before = config.outputs.len();
+    match runner_app.execute(intent) {
+        Ok(_) => {
+            eprintln!("Completed: {}", file.display());
+            // The file actually written, which auto-numbering may have renamed
+            let written = output.map(|planned| config.outputs.since(recorded_before).pop().unwrap_or(planned));
+            if let (Some(out), false) = (&written, config.dry_run) {
+                let ffmpeg_version = state.ffmpeg_version.clone();
+                let recorded = state.manifest_for(out).and_then(|manifest| {
+                    manifest.record(out, file, &intent_key, &ffmpeg_version)?;
+                    manifest.save()
+                });
+                if let Err(e) = recorded {
+                    eprintln!("Warning: could not update manifest: {}", e);
+                }
+            }
+            FileOutcome::Done
+        }
+        Err(e) if e.downcast_ref::<OutputSkipped>().is_some() => {
+            eprintln!("{}", e);
+            FileOutcome::Skipped
+        }
+        Err(e) => {
+            eprintln!("Error processing {}: {}", file.display(), e);
+            FileOutcome::Failed(format!("{:#}", e))
+        }
     }
-    Ok(())
+}
+
+/// Config used to rebuild stale outputs that an earlier run produced
+fn rebuild_config(config: &AppConfig) -> AppConfig {
+    let mut rebuild = config.clone();
+    rebuild.overwrite = true;
+    rebuild.collision = crate::model::CollisionPolicy::Overwrite;
+    rebuild
+}
+
+pub fn handle_batch(
+    config: &AppConfig,
+    _runner: &dyn Runner, // Not used directly, we create our own App
+    pattern: &str,
+    operation: BatchOperation,
+    options: BatchOptions,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    ensure_per_file_output(config, "Batch")?;
+    run_batch(config, pattern, &operation, None, options)
 }
 
 pub fn handle_conditional_batch(
@@ -102,11 +217,30 @@ pub fn handle_conditional_batch(
     _runner: &dyn Runner,
     pattern: &str,
     operation: BatchOperation,
-    condition: crate::model::types::ProcessingCondition,
+    condition: ProcessingCondition,
+    options: BatchOptions,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
-    use crate::ffmpeg::probe::duration_seconds;
-    use crate::model::types::Duration;
+    ensure_per_file_output(config, "Conditional batch")?;
+    run_batch(config, pattern, &operation, Some(&condition), options)
+}
+
+/// Load the job state to continue, or start a fresh one from the glob pattern
+fn open_job_state(
+    config: &AppConfig,
+    pattern: &str,
+    operation: &BatchOperation,
+    condition: Option<&ProcessingCondition>,
+    options: &BatchOptions,
+) -> Result<JobState> {
+    let operation_text = operation.to_string();
+    let condition_text = condition.map(|c| c.to_string());
+    let path = options.state_file.clone().unwrap_or_else(|| {
+        default_state_path(config.output_dir.as_deref(), pattern, &operation_text, condition_text.as_deref())
+    });
+    if options.resume || options.retry_failed {
+        return JobState::load(&path);
+    }
 
     // Expand glob pattern to get matching files
     let files: Vec<_> = glob(pattern)?
@@ -118,115 +252,186 @@ pub fn handle_conditional_batch(
         anyhow::bail!("No files found matching pattern: {}", pattern);
     }
 
-    eprintln!("Processing {} files with condition...\n", files.len());
+    let outputs = OutputSettings::new(config.out.as_deref(), config.output_dir.as_deref(), config.name_template.as_deref());
+    Ok(JobState::new(&path, pattern, operation_text, condition_text, outputs, &files))
+}
+
+/// The config a resumed batch runs with: the original run's output settings
+fn resumed_config(config: &AppConfig, outputs: &OutputSettings) -> AppConfig {
+    let mut resumed = config.clone();
+    resumed.out = outputs.out.clone();
+    resumed.output_dir = outputs.output_dir.clone();
+    resumed.name_template = outputs.name.clone();
+    resumed
+}
+
+fn run_batch(
+    config: &AppConfig,
+    pattern: &str,
+    operation: &BatchOperation,
+    condition: Option<&ProcessingCondition>,
+    options: BatchOptions,
+) -> Result<()> {
+    let mut job_state = open_job_state(config, pattern, operation, condition, &options)?;
+    let resumed;
+    let (config, to_run) = if options.resume || options.retry_failed {
+        resumed = resumed_config(config, &job_state.outputs);
+        (&resumed, job_state.jobs_to_run(options.resume, options.retry_failed))
+    } else {
+        (config, (0..job_state.jobs.len()).collect())
+    };
+
+    if to_run.is_empty() {
+        eprintln!("Nothing to do: every job in {} is finished", job_state.path().display());
+        return Ok(());
+    }
+
+    // Dry runs never touch the state file, so a real run can follow
+    let persist = !config.dry_run;
+    let save = |job_state: &JobState| {
+        if persist {
+            if let Err(e) = job_state.save() {
+                eprintln!("Warning: could not save job state: {}", e);
+            }
+        }
+    };
+
+    if condition.is_some() {
+        eprintln!("Processing {} files with condition...", to_run.len());
+    } else {
+        eprintln!("Processing {} files...", to_run.len());
+    }
+    if persist {
+        eprintln!("Job state: {} (continue with: ffhuman batch --resume {})\n",
+            job_state.path().display(), job_state.path().display());
+    }
+    save(&job_state);
 
     // Create a temporary app to execute intents
     let app = App::new(config.clone());
-    
-    let total = files.len();
+    let rebuild_app = App::new(rebuild_config(config));
+    let mut state = Incremental::new(options);
+
+    let total = to_run.len();
+    let started = Instant::now();
     let mut successful = 0;
-    let mut failed = 0;
     let mut skipped = 0;
+    let mut up_to_date = 0;
+    let mut failures: Vec<(PathBuf, String)> = Vec::new();
 
-    for (idx, file) in files.iter().enumerate() {
-        eprintln!("\n[{}/{}] Checking: {}", idx + 1, total, file.display());
-
-        // Check condition
-        let should_process = match &condition {
-            crate::model::types::ProcessingCondition::DurationLessThan(threshold) => {
-                match duration_seconds(file) {
-                    Ok(duration) => {
-                        let file_duration = Duration { seconds: duration };
-                        let result = file_duration.to_seconds() < threshold.to_seconds();
-                        if !result {
-                            eprintln!("Skipped: duration {}s >= threshold {}s", 
-                                file_duration.to_seconds(), threshold.to_seconds());
-                        }
-                        result
-                    }
-                    Err(e) => {
-                        eprintln!(" Warning: Could not get duration: {}", e);
-                        false
-                    }
-                }
+    for (idx, &job_idx) in to_run.iter().enumerate() {
+        let file = job_state.jobs[job_idx].input.clone();
+        if persist {
+            match job_state.remove_partial_output(job_idx) {
+                Ok(Some(partial)) => eprintln!("Removed partial output of the earlier run: {}", partial.display()),
+                Ok(None) => {}
+                Err(e) => eprintln!("Warning: {}", e),
             }
-            crate::model::types::ProcessingCondition::DurationGreaterThan(threshold) => {
-                match duration_seconds(file) {
-                    Ok(duration) => {
-                        let file_duration = Duration { seconds: duration };
-                        let result = file_duration.to_seconds() > threshold.to_seconds();
-                        if !result {
-                            eprintln!("Skipped: duration {}s <= threshold {}s", 
-                                file_duration.to_seconds(), threshold.to_seconds());
-                        }
-                        result
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Could not get duration: {}", e);
-                        false
-                    }
-                }
-            }
-            crate::model::types::ProcessingCondition::DurationEquals(threshold) => {
-                match duration_seconds(file) {
-                    Ok(duration) => {
-                        let file_duration = Duration { seconds: duration };
-                        // Use a small epsilon for floating point comparison
-                        let result = (file_duration.to_seconds() - threshold.to_seconds()).abs() < 0.1;
-                        if !result {
-                            eprintln!("Skipped: duration {}s != threshold {}s", 
-                                file_duration.to_seconds(), threshold.to_seconds());
-                        }
-                        result
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Could not get duration: {}", e);
-                        false
-                    }
-                }
-            }
-        };
-
-        if !should_process {
-            skipped += 1;
-            continue;
         }
+        let job = &mut job_state.jobs[job_idx];
+        job.status = JobStatus::Running;
+        job.error = None;
+        job.output = expected_output(config, &build_intent(&file, operation)).filter(|out| !out.exists());
+        save(&job_state);
+        let job_started = Instant::now();
 
-        // Draw progress bar
-        draw_progress_bar(idx, total, 50);
-        eprintln!("\n[{}/{}] Processing: {}", idx + 1, total, file.display());
-
-        let intent = match &operation {
-            BatchOperation::Convert(format) => {
-                Intent::Convert {
-                    input: file.clone(),
-                    format: *format,
-                    quality: None,
-                    codec: None,
+        let outcome = match condition {
+            Some(condition) => {
+                eprintln!("\n[{}/{}] Checking: {}", idx + 1, total, file.display());
+                if condition_matches(&file, condition) {
+                    None
+                } else {
+                    Some(FileOutcome::Skipped)
                 }
             }
+            None => None,
         };
 
-        match app.execute(intent) {
-            Ok(_) => {
+        // Continue with next file instead of failing completely
+        let outcome = outcome.unwrap_or_else(|| {
+            draw_progress_bar(idx, total, 50);
+            eprintln!("\n[{}/{}] Processing: {}", idx + 1, total, file.display());
+            process_file(config, &app, &rebuild_app, &mut state, &file, operation)
+        });
+
+        let job = &mut job_state.jobs[job_idx];
+        job.duration_secs = Some(job_started.elapsed().as_secs_f64());
+        match outcome {
+            FileOutcome::Done => {
                 successful += 1;
-                eprintln!("Completed: {}", file.display());
+                job.status = JobStatus::Done;
+            }
+            FileOutcome::UpToDate => {
+                up_to_date += 1;
+                job.status = JobStatus::Done;
             }
-            Err(e) => {
-                failed += 1;
-                eprintln!("Error processing {}: {}", file.display(), e);
+            FileOutcome::Skipped => {
+                skipped += 1;
+                job.status = JobStatus::Skipped;
             }
+            FileOutcome::Failed(error) => {
+                failures.push((file.clone(), error.clone()));
+                job.status = JobStatus::Failed;
+                job.error = Some(error);
+            }
+        }
+        if job.status != JobStatus::Failed {
+            job.output = None;
         }
+        save(&job_state);
     }
 
     // Final progress bar
     draw_progress_bar(total, total, 50);
-    eprintln!("\n\nConditional batch processing complete!");
+    if condition.is_some() {
+        eprintln!("\n\nConditional batch processing complete! ({:.1}s)", started.elapsed().as_secs_f64());
+    } else {
+        eprintln!("\n\nBatch processing complete! ({:.1}s)", started.elapsed().as_secs_f64());
+    }
     eprintln!(" Successful: {}", successful);
-    eprintln!(" Skipped: {}", skipped);
-    if failed > 0 {
-        eprintln!(" Failed: {}", failed);
+    if up_to_date > 0 {
+        eprintln!(" Up to date: {}", up_to_date);
+    }
+    if skipped > 0 || condition.is_some() {
+        eprintln!(" Skipped: {}", skipped);
+    }
+    if !failures.is_empty() {
+        eprintln!(" Failed: {}", failures.len());
+        for (file, error) in &failures {
+            eprintln!("   {}: {}", file.display(), error.lines().next().unwrap_or(""));
+        }
+        if persist {
+            eprintln!("\nRe-run the failed files with: ffhuman batch --retry-failed --state {}", job_state.path().display());
+        }
     }
     Ok(())
 }
 
+/// Check a file against a batch condition, explaining why it is skipped
+fn condition_matches(file: &Path, condition: &ProcessingCondition) -> bool {
+    let duration = match duration_seconds(file) {
+        Ok(duration) => Duration { seconds: duration },
+        Err(e) => {
+            eprintln!("Warning: Could not get duration: {}", e);
+            return false;
+        }
+    };
+
+    let (result, relation, threshold) = match condition {
+        ProcessingCondition::DurationLessThan(threshold) => {
+            (duration.to_seconds() < threshold.to_seconds(), ">=", threshold)
+        }
+        ProcessingCondition::DurationGreaterThan(threshold) => {
+            (duration.to_seconds() > threshold.to_seconds(), "<=", threshold)
+        }
+        ProcessingCondition::DurationEquals(threshold) => {
+            // Use a small epsilon for floating point comparison
+            ((duration.to_seconds() - threshold.to_seconds()).abs() < 0.1, "!=", threshold)
+        }
+    };
+    if !result {
+        eprintln!("Skipped: duration {}s {} threshold {}s",
+            duration.to_seconds(), relation, threshold.to_seconds());
+    }
+    result
+}
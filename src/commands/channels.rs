@@ -0,0 +1,155 @@
+use crate::config::AppConfig;
+use crate::ffmpeg::probe;
+use crate::ffmpeg::recipes;
+use crate::ffmpeg::runner::Runner;
+use crate::model::channels::{ChannelLayout, ChannelPick};
+use crate::util::{default_out, system::ensure_ffmpeg_exists};
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+pub fn handle_downmix(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let layout = probe::channel_layout(input)?;
+    if layout.count() <= 2 {
+        bail!("{} is already {layout}; there is nothing to downmix", input.display());
+    }
+    if config.explain {
+        eprintln!("[explain] {layout} to stereo: fronts as they are, centre and surrounds at -3 dB, LFE left out; \
+            each side is scaled so the mix can't clip");
+    }
+    remix(config, runner, input, "stereo", &recipes::downmix_filter(&layout))
+}
+
+pub fn handle_to_mono(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    pick: Option<ChannelPick>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let layout = probe::channel_layout(input)?;
+    if layout.count() == 1 {
+        bail!("{} is already mono", input.display());
+    }
+    let filter = recipes::mono_filter(&layout, pick)?;
+    if config.explain {
+        match pick {
+            Some(pick) => eprintln!("[explain] Keeping the {pick} channel of {layout}"),
+            None => eprintln!("[explain] Mixing the {layout} channels down to one, scaled so it can't clip"),
+        }
+    }
+    let suffix = pick.map(|p| p.to_string()).unwrap_or_else(|| "mono".to_string());
+    remix(config, runner, input, &suffix, &filter)
+}
+
+pub fn handle_swap_channels(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let layout = probe::channel_layout(input)?;
+    if layout.index_of("FL").is_none() {
+        bail!("{} is {layout}; there are no left and right channels to swap", input.display());
+    }
+    if config.explain {
+        eprintln!("[explain] Swapping left and right in {layout} ({})", layout.channels.join(" "));
+    }
+    remix(config, runner, input, "swapped", &recipes::swap_filter(&layout))
+}
+
+pub fn handle_split_channels(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let layout = probe::channel_layout(input)?;
+    if layout.count() == 1 {
+        bail!("{} is already mono", input.display());
+    }
+    if config.out.is_some() {
+        bail!("--out names one file, but splitting writes {}; use --output-dir or --name instead", layout.count());
+    }
+    if config.explain {
+        eprintln!("[explain] {layout}: one mono WAV per channel ({})", layout.channels.join(" "));
+    }
+
+    let outputs = layout.channels.iter()
+        .map(|channel| default_out(config, input, channel, "wav"))
+        .collect::<Result<Vec<_>>>()?;
+    let steps = recipes::split_channels_steps(input, &layout, &outputs, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    for out in &outputs {
+        eprintln!("Output: {}", out.display());
+    }
+    Ok(())
+}
+
+pub fn handle_combine_channels(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    inputs: &[PathBuf],
+    layout: Option<ChannelLayout>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    if inputs.len() < 2 {
+        bail!("Give at least two mono files to combine");
+    }
+    let layout = match layout {
+        Some(layout) => layout,
+        None => ChannelLayout::for_count(inputs.len())?,
+    };
+    if layout.count() != inputs.len() {
+        bail!("{layout} has {} channels, but {} files were given", layout.count(), inputs.len());
+    }
+    for input in inputs {
+        if let Some(count) = probe::channels(input).filter(|&count| count != 1) {
+            bail!("{} has {count} channels; combine takes mono files (split them first with `channels split`)",
+                input.display());
+        }
+    }
+    if config.explain {
+        for (input, channel) in inputs.iter().zip(layout.channels) {
+            eprintln!("[explain] {} -> {channel}", input.display());
+        }
+    }
+
+    let out = default_out(config, &inputs[0], "combined", "wav")?;
+    let steps = recipes::combine_channels_steps(inputs, &layout, &out, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+/// Write the input with its audio through `filter`, in the input's container
+fn remix(config: &AppConfig, runner: &dyn Runner, input: &Path, suffix: &str, filter: &str) -> Result<()> {
+    let ext = input.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase();
+    let out = default_out(config, input, suffix, &ext)?;
+    let encoding = probe::audio_encoding(input);
+    let steps = recipes::remix_channels_steps(input, &out, filter, encoding.as_ref(), config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
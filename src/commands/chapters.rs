@@ -0,0 +1,160 @@
+use crate::config::AppConfig;
+use crate::ffmpeg::probe::{self, duration_seconds, scene_changes};
+use crate::ffmpeg::recipes::{self, SCENE_THRESHOLD};
+use crate::ffmpeg::runner::Runner;
+use crate::model::chapter::Chapters;
+use crate::model::subtitle::Timestamp;
+use crate::model::Duration;
+use crate::util::{default_out, system::ensure_ffmpeg_exists};
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::path::Path;
+
+/// YouTube ignores chapters shorter than this
+const MIN_CHAPTER_MS: u64 = 10_000;
+
+pub fn handle_add_chapters(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    list: &Path,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let mut chapters = Chapters::read(list)?;
+    let duration = duration_seconds(input).ok().map(|d| (d * 1000.0).round() as u64);
+    if let (Some(last), Some(duration)) = (chapters.chapters.last(), duration) {
+        if last.start >= duration {
+            bail!("Chapter \"{}\" starts at {}, after the end of {} ({})",
+                last.title, Timestamp(last.start), input.display(), Timestamp(duration));
+        }
+    }
+    if chapters.chapters[0].start != 0 {
+        eprintln!("Warning: the first chapter starts at {}; YouTube only shows chapters when one starts at 00:00",
+            Timestamp(chapters.chapters[0].start));
+    }
+    chapters.close(duration);
+    if config.explain {
+        eprintln!("[explain] {} chapters, written as ffmetadata and copied in with -map_chapters; streams are copied",
+            chapters.chapters.len());
+        if duration.is_none() {
+            eprintln!("[explain] Couldn't read the length of {}; the last chapter runs to the end", input.display());
+        }
+    }
+
+    // ffmpeg reads the chapters from an ffmetadata file, kept until it's done
+    let mut metadata = tempfile::Builder::new()
+        .prefix("ffhuman-chapters")
+        .suffix(".txt")
+        .tempfile()
+        .context("failed to create chapter metadata file")?;
+    metadata.write_all(chapters.to_ffmetadata().as_bytes()).context("write chapter metadata")?;
+    metadata.flush().context("write chapter metadata")?;
+
+    let ext = input.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase();
+    let out = default_out(config, input, "chapters", &ext)?;
+    let steps = recipes::add_chapters_steps(input, metadata.path(), &out, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+pub fn handle_list_chapters(
+    _config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    json: bool,
+) -> Result<()> {
+    let input = input.as_ref();
+    let chapters = probe::chapters(input)?;
+    if chapters.chapters.is_empty() && !json {
+        eprintln!("{} has no chapters", input.display());
+        return Ok(());
+    }
+
+    // On stdout, to paste into a description or pipe into another tool
+    if json {
+        print!("{}", chapters.to_json());
+    } else {
+        print!("{}", chapters.to_list());
+    }
+    Ok(())
+}
+
+pub fn handle_extract_chapters(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+) -> Result<()> {
+    let input = input.as_ref();
+    let chapters = probe::chapters(input)?;
+    if chapters.chapters.is_empty() {
+        bail!("{} has no chapters", input.display());
+    }
+
+    let out = default_out(config, input, "chapters", "json")?;
+    if !config.dry_run {
+        std::fs::write(&out, chapters.to_json()).with_context(|| format!("failed to write {}", out.display()))?;
+    }
+    eprintln!("Found {} chapters", chapters.chapters.len());
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+pub fn handle_chapters_from_scenes(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    scenes: Option<&Path>,
+    min_length: Option<Duration>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+    let out = default_out(config, input, "chapters", "txt")?;
+
+    let times = match scenes {
+        Some(scenes) => read_scene_times(scenes)?,
+        None if config.dry_run => {
+            // Nothing to read the scene changes from; show the detection that would run
+            for step in recipes::detect_scenes_steps(input, &out, config.overwrite) {
+                runner.run(&step)?;
+            }
+            eprintln!("Output: {}", out.display());
+            return Ok(());
+        }
+        None => {
+            eprintln!("Detecting scene changes...");
+            scene_changes(input, SCENE_THRESHOLD)?
+        }
+    };
+
+    let min_length = min_length.map(|d| (d.to_seconds() * 1000.0).round() as u64).unwrap_or(MIN_CHAPTER_MS);
+    let duration = duration_seconds(input).ok().map(|d| (d * 1000.0).round() as u64);
+    let chapters = Chapters::from_scenes(&times, min_length, duration);
+    if config.explain {
+        eprintln!("[explain] {} scene changes; chapters at least {} long", times.len(), Timestamp(min_length));
+    }
+
+    if !config.dry_run {
+        std::fs::write(&out, chapters.to_list()).with_context(|| format!("failed to write {}", out.display()))?;
+    }
+    eprintln!("{} chapters; edit the titles, then add them with: ffhuman chapters add {} {}",
+        chapters.chapters.len(), input.display(), out.display());
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+/// Scene times in seconds, one per line, as `detect-scenes` writes them
+fn read_scene_times(path: &Path) -> Result<Vec<f64>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Timestamp::parse(line).map(|t| t.0 as f64 / 1000.0))
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("in {}", path.display()))
+}
@@ -7,6 +7,24 @@ use anyhow::{Context, Result};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// Write a concat list of `files` to a temporary file of its own, so steps
+/// running at the same time don't overwrite each other's; removed on drop
+fn write_concat_list<'a>(files: impl IntoIterator<Item = &'a Path>) -> Result<tempfile::NamedTempFile> {
+    let mut list = tempfile::Builder::new()
+        .prefix("ffhuman-concat")
+        .suffix(".txt")
+        .tempfile()
+        .context("failed to create concat list")?;
+    for file in files {
+        // Use absolute paths in concat list file
+        let file = file.canonicalize()
+            .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(file)))?;
+        writeln!(list, "file '{}'", file.to_string_lossy().replace('\'', "\\'")).context("write concat list")?;
+    }
+    list.as_file().sync_all().context("sync concat list")?;
+    Ok(list)
+}
+
 pub fn handle_loop(
     config: &AppConfig,
     runner: &dyn Runner,
@@ -18,36 +36,12 @@ pub fn handle_loop(
 
     let out = default_out(config, input, "loop", "mp4")?;
 
-    // Create concat list file in output directory to ensure it persists
-    let list_path = if let Some(output_dir) = &config.output_dir {
-        std::fs::create_dir_all(output_dir)?;
-        output_dir.join("concat_list.txt")
-    } else {
-        let parent = out.parent().unwrap_or_else(|| Path::new("."));
-        std::fs::create_dir_all(parent)?;
-        parent.join("concat_list.txt")
-    };
-    
-    // Convert to absolute path for FFmpeg
-    let list_path = list_path.canonicalize()
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&list_path)))?;
-    
-    let mut list = std::fs::File::create(&list_path).context("failed to create concat list")?;
-    for _ in 0..times {
-        // Use absolute paths in concat list file
-        let input_path = input.canonicalize()
-            .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(input)))?;
-        writeln!(list, "file '{}'", input_path.to_string_lossy().replace('\'', "\\'"))
-            .context("write concat list")?;
-    }
-    list.sync_all().context("sync concat list")?;
-
-    let steps = recipes::loop_steps(input, &out, &list_path, config.overwrite);
+    let list = write_concat_list(std::iter::repeat_n(input, times as usize))?;
+    let steps = recipes::loop_steps(input, &out, list.path(), config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
 
-    // _list_file is dropped here, but that's OK - step has completed
     eprintln!("Output: {}", out.display());
     Ok(())
 }
@@ -78,36 +72,12 @@ pub fn handle_merge(
         dir.join(format!("{stem}_merged.mp4"))
     };
 
-    // Create concat list file in output directory to ensure it persists
-    let list_path = if let Some(output_dir) = &config.output_dir {
-        std::fs::create_dir_all(output_dir)?;
-        output_dir.join("concat_list.txt")
-    } else {
-        let parent = out.parent().unwrap_or_else(|| Path::new("."));
-        std::fs::create_dir_all(parent)?;
-        parent.join("concat_list.txt")
-    };
-    
-    // Convert to absolute path for FFmpeg
-    let list_path = list_path.canonicalize()
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&list_path)))?;
-    
-    let mut list = std::fs::File::create(&list_path).context("failed to create concat list")?;
-    // Use absolute paths in concat list file
-    let a_path = a.canonicalize()
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(a)))?;
-    let b_path = b.canonicalize()
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(b)))?;
-    writeln!(list, "file '{}'", a_path.to_string_lossy().replace('\'', "\\'"))?;
-    writeln!(list, "file '{}'", b_path.to_string_lossy().replace('\'', "\\'"))?;
-    list.sync_all().context("sync concat list")?;
-
-    let steps = recipes::merge_steps(a, &out, &list_path, config.overwrite);
+    let list = write_concat_list([a, b])?;
+    let steps = recipes::merge_steps(a, &out, list.path(), config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
 
-    // _list_file is dropped here, but that's OK - step has completed
     eprintln!("Output: {}", out.display());
     Ok(())
 }
@@ -246,36 +216,12 @@ pub fn handle_concat(
         dir.join(format!("{stem}_concat.mp4"))
     };
 
-    // Create concat list file in output directory to ensure it persists
-    let list_path = if let Some(output_dir) = &config.output_dir {
-        std::fs::create_dir_all(output_dir)?;
-        output_dir.join("concat_list.txt")
-    } else {
-        let parent = out.parent().unwrap_or_else(|| Path::new("."));
-        std::fs::create_dir_all(parent)?;
-        parent.join("concat_list.txt")
-    };
-    
-    // Convert to absolute path for FFmpeg
-    let list_path = list_path.canonicalize()
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&list_path)))?;
-    
-    let mut list = std::fs::File::create(&list_path).context("failed to create concat list")?;
-    for video in videos {
-        // Use absolute paths in concat list file
-        let video_path = video.canonicalize()
-            .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(video)))?;
-        writeln!(list, "file '{}'", video_path.to_string_lossy().replace('\'', "\\'"))
-            .context("write concat list")?;
-    }
-    list.sync_all().context("sync concat list")?;
-
-    let steps = recipes::concat_steps(&out, &list_path, config.overwrite, &videos[0]);
+    let list = write_concat_list(videos.iter().map(PathBuf::as_path))?;
+    let steps = recipes::concat_steps(&out, list.path(), config.overwrite, &videos[0]);
     for step in steps {
         runner.run(&step)?;
     }
 
-    // _list_file is dropped here, but that's OK - step has completed
     eprintln!("Output: {}", out.display());
     Ok(())
 }
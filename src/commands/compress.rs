@@ -4,7 +4,7 @@ use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
 use crate::model::types::CompressTarget;
 use crate::util::{default_out, system::{ensure_ffmpeg_exists, ensure_ffprobe_exists}};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::Path;
 
 pub fn handle_compress(
@@ -18,6 +18,9 @@ pub fn handle_compress(
     let input = input.as_ref();
 
     let out = default_out(config, input, "compressed", "mp4")?;
+    let log_dir = if two_pass { Some(pass_log_dir()?) } else { None };
+    let two_pass_log = log_dir.as_ref().map(|dir| dir.path().join("pass"));
+    let two_pass_log = two_pass_log.as_deref();
 
     match target {
         CompressTarget::Size(target_size) => {
@@ -45,7 +48,7 @@ pub fn handle_compress(
                 }
             }
 
-            let steps = recipes::compress_steps(input, &out, target_size.bytes, duration, config.overwrite, two_pass);
+            let steps = recipes::compress_steps(input, &out, target_size.bytes, duration, config.overwrite, two_pass_log);
             for step in steps {
                 runner.run(&step)?;
             }
@@ -68,7 +71,7 @@ pub fn handle_compress(
                 }
             }
 
-            let steps = recipes::compress_bitrate_steps(input, &out, target_bitrate.bps, config.overwrite, two_pass);
+            let steps = recipes::compress_bitrate_steps(input, &out, target_bitrate.bps, config.overwrite, two_pass_log);
             for step in steps {
                 runner.run(&step)?;
             }
@@ -83,7 +86,7 @@ pub fn handle_compress(
                 }
             }
 
-            let steps = recipes::compress_quality_steps(input, &out, quality, config.overwrite, two_pass);
+            let steps = recipes::compress_quality_steps(input, &out, quality, config.overwrite, two_pass_log);
             for step in steps {
                 runner.run(&step)?;
             }
@@ -94,3 +97,12 @@ pub fn handle_compress(
     Ok(())
 }
 
+
+/// A folder of its own for each two-pass encode's pass logs, so steps
+/// running at the same time don't read each other's; removed on drop
+fn pass_log_dir() -> Result<tempfile::TempDir> {
+    tempfile::Builder::new()
+        .prefix("ffhuman-passlog")
+        .tempdir()
+        .context("failed to create pass log folder")
+}
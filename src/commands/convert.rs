@@ -1,10 +1,12 @@
 use crate::config::AppConfig;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
-use crate::model::{ConvertFormat, QualityPreset, VideoCodec};
+use crate::ffmpeg::probe::duration_seconds;
+use crate::ffmpeg::recipes::AnimationSettings;
+use crate::model::{ConvertFormat, QualityPreset, TargetSize, VideoCodec};
 use crate::util::{base_stem, default_out, system::ensure_ffmpeg_exists};
 use crate::commands::video;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use std::path::Path;
 
 pub fn handle_convert(
@@ -14,46 +16,34 @@ pub fn handle_convert(
     format: ConvertFormat,
     quality: Option<QualityPreset>,
     codec: Option<VideoCodec>,
+    max_size: Option<TargetSize>,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
+    if max_size.is_some() && !format.is_animation() {
+        bail!("--max-size works for gif, webp, apng and avif (use `compress ... to <size>` for video)");
+    }
 
     match format {
-        ConvertFormat::Gif => {
-            let out = default_out(config, input, "gif", "gif")?;
-
-            // Defaults
-            let fps = 15;
-            let width = 480;
-
-            // Create palette file in output directory to ensure it persists
-            let palette_path = if let Some(output_dir) = &config.output_dir {
-                std::fs::create_dir_all(output_dir)?;
-                output_dir.join("palette.png")
-            } else {
-                let parent = out.parent().unwrap_or_else(|| Path::new("."));
-                std::fs::create_dir_all(parent)?;
-                parent.join("palette.png")
+        ConvertFormat::Gif | ConvertFormat::Webp | ConvertFormat::Apng | ConvertFormat::Avif => {
+            convert_animation(config, runner, input, format, quality, max_size)
+        }
+        ConvertFormat::Mp4 | ConvertFormat::Webm | ConvertFormat::Mkv | ConvertFormat::Mov => {
+            // For WebM, default to VP9 codec if not specified
+            let codec = match format {
+                ConvertFormat::Webm => codec.or(Some(VideoCodec::Vp9)),
+                _ => codec,
             };
-
-            if config.explain {
-                eprintln!("[explain] GIF uses palettegen + paletteuse for quality & smaller size.");
-                if let Some(q) = quality {
-                    eprintln!("[explain] Quality preset: {} (affects fps and resolution)", q);
+            if let Some(codec) = codec {
+                format.check_codec(codec)?;
+                if config.explain {
+                    eprintln!("[explain] Video codec: {} ({})", codec, codec.ffmpeg_name());
+                    if codec.is_intermediate() {
+                        eprintln!("[explain] Intermediate codec: large files for editing, with PCM/FLAC audio.");
+                    }
                 }
             }
-
-            let steps = recipes::gif_steps(input, &out, &palette_path, fps, width, quality);
-            for step in steps {
-                runner.run(&step)?;
-            }
-
-            // _palette_file is dropped here, but that's OK - both steps have completed
-            eprintln!("Output: {}", out.display());
-            Ok(())
-        }
-        ConvertFormat::Mp4 => {
-            let out = default_out(config, input, "convert", "mp4")?;
+            let out = default_out(config, input, "convert", &format.to_string())?;
             let steps = recipes::convert_generic_steps(input, &out, config.overwrite, quality, codec);
             for step in steps {
                 runner.run(&step)?;
@@ -61,25 +51,19 @@ pub fn handle_convert(
             eprintln!("Output: {}", out.display());
             Ok(())
         }
-        ConvertFormat::Webm => {
-            let out = default_out(config, input, "convert", "webm")?;
-            // For WebM, default to VP9 codec if not specified
-            let webm_codec = codec.or(Some(VideoCodec::Vp9));
-            let steps = recipes::convert_generic_steps(input, &out, config.overwrite, quality, webm_codec);
-            for step in steps {
-                runner.run(&step)?;
+        ConvertFormat::Mp3
+        | ConvertFormat::Wav
+        | ConvertFormat::Flac
+        | ConvertFormat::M4a
+        | ConvertFormat::Opus
+        | ConvertFormat::Ogg
+        | ConvertFormat::Aiff => {
+            let audio = format.audio_format().expect("audio target");
+            if config.explain {
+                eprintln!("[explain] {}: {}", audio, audio.codec_args(quality).join(" "));
             }
-            eprintln!("Output: {}", out.display());
-            Ok(())
-        }
-        ConvertFormat::Mp3 | ConvertFormat::Wav => {
-            let ext = match format {
-                ConvertFormat::Mp3 => "mp3",
-                ConvertFormat::Wav => "wav",
-                _ => unreachable!(),
-            };
-            let out = default_out(config, input, "audio", ext)?;
-            let steps = recipes::extract_audio_steps(input, &out, ext, config.overwrite);
+            let out = default_out(config, input, "audio", audio.ext())?;
+            let steps = recipes::extract_audio_steps(input, &out, audio, quality, config.overwrite);
             for step in steps {
                 runner.run(&step)?;
             }
@@ -148,6 +132,108 @@ pub fn handle_convert(
     }
 }
 
+/// A palette file of its own for each two-pass GIF/APNG encode, so steps
+/// running at the same time don't overwrite each other's; removed on drop
+fn palette_file() -> Result<tempfile::NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix("ffhuman-palette")
+        .suffix(".png")
+        .tempfile()
+        .context("failed to create palette file")
+}
+
+/// Re-encodes before giving up on --max-size
+const MAX_SIZE_ATTEMPTS: u32 = 8;
+
+/// Convert to an animated image. With a size limit the output is encoded
+/// again at lower settings until it fits.
+fn convert_animation(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: &Path,
+    format: ConvertFormat,
+    quality: Option<QualityPreset>,
+    max_size: Option<TargetSize>,
+) -> Result<()> {
+    let (suffix, ext) = format.output_naming().expect("animated images have an output name");
+    let out = default_out(config, input, suffix, ext)?;
+
+    // Defaults
+    let mut settings = AnimationSettings::for_quality(quality, 15, 480);
+
+    // Palette for two-pass encoding, kept until both passes are done
+    let palette = palette_file()?;
+    let palette_path = palette.path();
+
+    if config.explain {
+        match format {
+            ConvertFormat::Gif | ConvertFormat::Apng => {
+                eprintln!("[explain] {} uses palettegen + paletteuse for quality & smaller size.", format.to_string().to_uppercase());
+            }
+            _ => eprintln!("[explain] {} is encoded directly; quality trades size for detail.", format.to_string().to_uppercase()),
+        }
+        if let Some(q) = quality {
+            eprintln!("[explain] Quality preset: {} (affects fps and resolution)", q);
+        }
+        eprintln!("[explain] Starting at {}", settings.describe(format));
+    }
+
+    let Some(max_size) = max_size else {
+        for step in recipes::animated_image_steps(input, &out, palette_path, format, &settings) {
+            runner.run(&step)?;
+        }
+        eprintln!("Output: {}", out.display());
+        return Ok(());
+    };
+
+    // The duration turns the limit into a per-second budget, and says how
+    // much of the input would fit if nothing gets small enough
+    let duration = duration_seconds(input).ok().filter(|d| *d > 0.0);
+    if config.explain {
+        if let Some(duration) = duration {
+            eprintln!("[explain] {max_size} over {duration:.1}s is about {:.0} KB per second", max_size.bytes as f64 / 1024.0 / duration);
+        }
+    }
+    if config.dry_run {
+        for step in recipes::animated_image_steps(input, &out, palette_path, format, &settings) {
+            runner.run(&step)?;
+        }
+        eprintln!("(re-encoded at lower fps, width and colors while over {max_size})");
+        eprintln!("Output: {}", out.display());
+        return Ok(());
+    }
+
+    let mut size = 0;
+    for attempt in 1..=MAX_SIZE_ATTEMPTS {
+        for step in recipes::animated_image_steps(input, &out, palette_path, format, &settings) {
+            runner.run(&step)?;
+        }
+        size = std::fs::metadata(&out)?.len();
+        if size <= max_size.bytes {
+            eprintln!("{} fits in {max_size} ({})", TargetSize { bytes: size }, settings.describe(format));
+            eprintln!("Output: {}", out.display());
+            return Ok(());
+        }
+        let ratio = size as f64 / max_size.bytes as f64;
+        match settings.shrink(format, ratio) {
+            Some(next) if attempt < MAX_SIZE_ATTEMPTS => {
+                eprintln!("Attempt {attempt}: {} is over {max_size}; trying {}", TargetSize { bytes: size }, next.describe(format));
+                settings = next;
+            }
+            _ => break,
+        }
+    }
+    let hint = duration
+        .map(|d| format!("; about {:.0}s of the input would fit, so trim it first", d * max_size.bytes as f64 / size as f64))
+        .unwrap_or_default();
+    bail!(
+        "Couldn't get {} under {max_size}: the smallest try was {} at {}{hint}",
+        out.display(),
+        TargetSize { bytes: size },
+        settings.describe(format)
+    )
+}
+
 pub fn handle_animated_gif(
     config: &AppConfig,
     runner: &dyn Runner,
@@ -164,15 +250,9 @@ pub fn handle_animated_gif(
     let fps = 15;
     let width = 480;
 
-    // Create palette file in output directory to ensure it persists
-    let palette_path = if let Some(output_dir) = &config.output_dir {
-        std::fs::create_dir_all(output_dir)?;
-        output_dir.join("palette.png")
-    } else {
-        let parent = out.parent().unwrap_or_else(|| Path::new("."));
-        std::fs::create_dir_all(parent)?;
-        parent.join("palette.png")
-    };
+    // Palette for two-pass encoding, kept until both passes are done
+    let palette = palette_file()?;
+    let palette_path = palette.path();
 
     if config.explain {
         eprintln!("[explain] Animated GIF uses palettegen + paletteuse for quality & smaller size.");
@@ -184,12 +264,11 @@ pub fn handle_animated_gif(
         }
     }
 
-    let steps = recipes::animated_gif_steps(input, &out, &palette_path, fps, width, loop_video, optimize);
+    let steps = recipes::animated_gif_steps(input, &out, palette_path, fps, width, loop_video, optimize);
     for step in steps {
         runner.run(&step)?;
     }
 
-    // _palette_file is dropped here, but that's OK - both steps have completed
     eprintln!("Output: {}", out.display());
     Ok(())
 }
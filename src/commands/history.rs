@@ -0,0 +1,194 @@
+use crate::app::App;
+use crate::cli::Cli;
+use crate::config::AppConfig;
+use crate::ffmpeg::runner::Runner;
+use crate::model::{CollisionPolicy, Intent};
+use crate::util::history::{absolute, remove_if_unchanged, step_inputs, FileRecord, History, HistoryEntry, RunStatus};
+use crate::util::naming::utc_date_time;
+use crate::util::OutputSkipped;
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// What to journal about a run, taken from its intent before it executes
+#[derive(Debug, Clone)]
+pub struct Journal {
+    operation: String,
+    input: Option<PathBuf>,
+}
+
+/// The journal record to keep for an intent, or None for dry runs and for
+/// commands that only read or change the journal, or run an interactive session
+pub fn journal_for(config: &AppConfig, intent: &Intent) -> Option<Journal> {
+    if config.dry_run {
+        return None;
+    }
+    if matches!(intent, Intent::History { .. } | Intent::Undo { .. } | Intent::Shell { .. }) {
+        return None;
+    }
+    Some(Journal { operation: intent.kind().to_string(), input: intent.clone().input_mut().cloned() })
+}
+
+/// Append a finished run to the journal and return its id
+pub fn record_run(
+    config: &AppConfig,
+    journal: Journal,
+    args: &[String],
+    (started_at, clock): (SystemTime, Instant),
+    result: &Result<()>,
+) -> Result<u64> {
+    let history = History::open_default()?;
+    let cwd = std::env::current_dir().context("Failed to read the current directory")?;
+    let steps = config.steps.all();
+
+    let mut outputs: Vec<PathBuf> = Vec::new();
+    for output in config.outputs.since(0) {
+        if !outputs.contains(&output) {
+            outputs.push(output);
+        }
+    }
+    // Inputs are the intent's own plus whatever the commands read, except
+    // intermediate files the run wrote itself
+    let mut inputs: Vec<PathBuf> = journal.input.into_iter().collect();
+    for path in steps.iter().flat_map(step_inputs) {
+        if !inputs.contains(&path) {
+            inputs.push(path);
+        }
+    }
+    inputs.retain(|p| !outputs.contains(p) && p.exists());
+
+    let (status, error) = match result {
+        Ok(()) => (RunStatus::Ok, None),
+        Err(e) if e.downcast_ref::<OutputSkipped>().is_some() => (RunStatus::Skipped, None),
+        Err(e) => (RunStatus::Failed, Some(format!("{:#}", e))),
+    };
+    let entry = HistoryEntry {
+        id: history.next_id()?,
+        started_at: started_at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        args: args.to_vec(),
+        operation: journal.operation,
+        inputs: inputs.iter().map(|p| FileRecord::of(&absolute(p, &cwd), false)).collect(),
+        outputs: outputs.iter()
+            .map(|p| FileRecord::of(&absolute(p, &cwd), config.outputs.was_replaced(p)))
+            .collect(),
+        steps,
+        duration_secs: clock.elapsed().as_secs_f64(),
+        status,
+        error,
+        cwd,
+    };
+    history.append(&entry)?;
+    Ok(entry.id)
+}
+
+fn when(entry: &HistoryEntry) -> (String, String) {
+    let (date, time) = utc_date_time(UNIX_EPOCH + Duration::from_secs(entry.started_at));
+    (date, format!("{}:{}:{}", &time[0..2], &time[2..4], &time[4..6]))
+}
+
+/// List recent runs, or show one run in full
+pub fn handle_history(_config: &AppConfig, _runner: &dyn Runner, id: Option<u64>, limit: usize) -> Result<()> {
+    let history = History::open_default()?;
+    if let Some(id) = id {
+        print_entry(&history.get(id)?);
+        return Ok(());
+    }
+
+    let entries = history.entries()?;
+    if entries.is_empty() {
+        println!("No runs recorded yet ({})", history.path().display());
+        return Ok(());
+    }
+    println!("{:>5}  {:<19}  {:<7}  COMMAND", "ID", "WHEN (UTC)", "STATUS");
+    let skip = entries.len().saturating_sub(limit);
+    for entry in &entries[skip..] {
+        let (date, time) = when(entry);
+        println!("{:>5}  {} {}  {:<7}  ffhuman {}", entry.id, date, time, entry.status, entry.args.join(" "));
+    }
+    if skip > 0 {
+        println!("({} earlier runs not shown; use --limit)", skip);
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &HistoryEntry) {
+    let (date, time) = when(entry);
+    println!("Run {} ({}, {:.1}s)", entry.id, entry.status, entry.duration_secs);
+    println!("  When:      {} {} UTC", date, time);
+    println!("  Directory: {}", entry.cwd.display());
+    println!("  Command:   ffhuman {}", entry.args.join(" "));
+    let files = |label: &str, records: &[FileRecord]| {
+        for (idx, record) in records.iter().enumerate() {
+            let label = if idx == 0 { label } else { "" };
+            let hash = record.sha256.as_deref().map(|h| format!("  sha256 {}", h)).unwrap_or_default();
+            let replaced = if record.replaced { "  (replaced an existing file)" } else { "" };
+            println!("  {:<10} {}{}{}", label, record.path.display(), hash, replaced);
+        }
+    };
+    files("Inputs:", &entry.inputs);
+    files("Outputs:", &entry.outputs);
+    if !entry.steps.is_empty() {
+        println!("  Steps:");
+        for step in &entry.steps {
+            println!("    {} {}", step.program, step.args.join(" "));
+        }
+    }
+    if let Some(error) = &entry.error {
+        println!("  Error:     {}", error);
+    }
+}
+
+/// Run a recorded command again, optionally on a different input.
+/// Relative paths are read from the directory the run was made in.
+pub fn handle_redo(config: &AppConfig, _runner: &dyn Runner, id: u64, input: Option<PathBuf>) -> Result<()> {
+    let entry = History::open_default()?.get(id)?;
+    let cli = Cli::try_parse_from(std::iter::once("ffhuman".to_string()).chain(entry.args.iter().cloned()))
+        .map_err(|e| anyhow!("Run {id} can't be parsed any more: {}", e.to_string().trim_end()))?;
+
+    let mut run_config = cli.config()?;
+    run_config.dry_run |= config.dry_run;
+    run_config.explain |= config.explain;
+    if config.overwrite {
+        run_config.overwrite = true;
+        run_config.collision = CollisionPolicy::Overwrite;
+    } else if config.collision != CollisionPolicy::Fail {
+        run_config.collision = config.collision;
+    }
+    run_config.outputs = config.outputs.clone();
+    run_config.steps = config.steps.clone();
+
+    let mut intent = cli.into_intent()?;
+    let new_input = match input {
+        Some(input) => Some(std::fs::canonicalize(&input)
+            .with_context(|| format!("Input file not found: {}", input.display()))?),
+        None => None,
+    };
+    if let Some(new_input) = new_input {
+        let slot = intent.input_mut()
+            .ok_or_else(|| anyhow!("Run {id} ({}) doesn't take a single input to replace", entry.operation))?;
+        *slot = new_input;
+        if run_config.out.take().is_some() {
+            eprintln!("Note: run {id} wrote to --out; the new result gets a name next to its input instead");
+        }
+    }
+    if !entry.cwd.is_dir() {
+        bail!("Run {id} was made in {}, which no longer exists", entry.cwd.display());
+    }
+    std::env::set_current_dir(&entry.cwd)
+        .with_context(|| format!("Failed to change to {}", entry.cwd.display()))?;
+
+    eprintln!("Redoing run {id}: ffhuman {}", entry.args.join(" "));
+    App::new(run_config).execute(intent)
+}
+
+/// Remove the outputs a run created, if they haven't changed since
+pub fn handle_undo(config: &AppConfig, _runner: &dyn Runner, id: u64) -> Result<()> {
+    let entry = History::open_default()?.get(id)?;
+    if entry.outputs.is_empty() {
+        bail!("Run {id} ({}) recorded no outputs", entry.operation);
+    }
+    for record in entry.outputs.iter().rev() {
+        eprintln!("{}", remove_if_unchanged(record, config.dry_run)?);
+    }
+    Ok(())
+}
@@ -1,13 +1,19 @@
 pub mod analysis;
 pub mod audio;
 pub mod batch;
+pub mod channels;
+pub mod chapters;
 pub mod combine;
 pub mod compress;
 pub mod convert;
 pub mod doctor;
 pub mod effects;
+pub mod history;
 pub mod metadata;
 pub mod pipeline;
+pub mod sentence;
+pub mod shell;
+pub mod subtitle;
 pub mod template;
 pub mod trim;
 pub mod video;
@@ -0,0 +1,41 @@
+use crate::commands::workflow::run_workflow;
+use crate::config::AppConfig;
+use crate::ffmpeg::runner::Runner;
+use crate::model::sentence::Sentence;
+use crate::model::workflow::{WorkflowMode, WorkflowSpec, WorkflowStep};
+use crate::util::system::ensure_ffmpeg_exists;
+use anyhow::{bail, Result};
+use std::io::{self, IsTerminal, Write};
+
+/// Run a command written as a sentence. The interpretation is always shown;
+/// when the grammar had to guess, it asks before running.
+pub fn handle_sentence(config: &AppConfig, _runner: &dyn Runner, text: &str) -> Result<()> {
+    let sentence = Sentence::parse(text)?;
+    eprintln!("{}", sentence.describe());
+
+    if sentence.is_ambiguous() && !config.dry_run {
+        if !io::stdin().is_terminal() {
+            bail!("Not running a guessed interpretation without confirmation; rephrase the sentence or run it in a terminal");
+        }
+        eprint!("Run this? (y/n): ");
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            eprintln!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    ensure_ffmpeg_exists()?;
+    if !sentence.input.exists() && !config.dry_run {
+        bail!("Input file not found: {}", sentence.input.display());
+    }
+    let mut steps: Vec<WorkflowStep> = sentence.steps.into_iter().map(WorkflowStep::new).collect();
+    if let Some(last) = steps.last_mut() {
+        last.output = sentence.output;
+    }
+    let spec = WorkflowSpec { input: Some(sentence.input.clone()), mode: None, steps };
+    run_workflow(config, &spec, Some(&sentence.input), WorkflowMode::Chain)?;
+    Ok(())
+}
@@ -0,0 +1,543 @@
+use crate::app::App;
+use crate::cli::Cli;
+use crate::config::{AppConfig, OutputLog};
+use crate::ffmpeg::runner::Runner;
+use crate::model::workflow::WorkflowSpec;
+use crate::model::{CollisionPolicy, Intent};
+use crate::util::system::ensure_ffmpeg_exists;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory};
+use serde_yaml::{Mapping, Value};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const HELP: &str = "\
+Session commands:
+  open FILE               start working on FILE
+  undo                    go back to the previous result
+  preview                 play the current result (or write a 10 second preview)
+  history                 list the commands applied so far
+  save as FILE            write the current result to FILE (converting if the extension differs)
+  save pipeline FILE      write the history as a pipeline file for `ffhuman pipeline`
+  help                    show this help
+  quit                    leave the shell
+
+Anything else is an ffhuman command without the input file, e.g.
+  trim 1:00 to 3:00
+  normalize
+  watermark logo.png bottom-right --opacity 0.8";
+
+/// What the shell should do after a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+/// One command applied in the session
+#[derive(Debug, Clone)]
+struct Entry {
+    line: String,
+    /// The command as a pipeline step, if it can be written as one
+    step: Result<Mapping, String>,
+    before: PathBuf,
+}
+
+/// An interactive editing session: each command works on the previous
+/// command's result. Intermediate results live in a temporary folder until
+/// they are saved.
+pub struct ShellSession {
+    config: AppConfig,
+    work_dir: tempfile::TempDir,
+    source: Option<PathBuf>,
+    current: Option<PathBuf>,
+    history: Vec<Entry>,
+    saved: bool,
+    warned_unsaved: bool,
+}
+
+pub fn handle_shell(config: &AppConfig, _runner: &dyn Runner, file: Option<PathBuf>) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let mut session = ShellSession::new(config)?;
+    if let Some(file) = file {
+        session.open(&file)?;
+    }
+    eprintln!("ffhuman shell. Type 'help' for commands, 'quit' to leave.");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        eprint!("{}> ", session.prompt());
+        io::stderr().flush().ok();
+        let Some(line) = lines.next() else {
+            eprintln!();
+            break;
+        };
+        match session.run_line(&line?) {
+            Ok(Flow::Quit) => break,
+            Ok(Flow::Continue) => {}
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+    }
+    Ok(())
+}
+
+impl ShellSession {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let work_dir = tempfile::Builder::new()
+            .prefix("ffhuman-shell-")
+            .tempdir()
+            .context("Failed to create a folder for intermediate results")?;
+        Ok(Self {
+            config: config.clone(),
+            work_dir,
+            source: None,
+            current: None,
+            history: Vec::new(),
+            saved: true,
+            warned_unsaved: false,
+        })
+    }
+
+    /// The file the next command works on
+    pub fn current(&self) -> Option<&Path> {
+        self.current.as_deref()
+    }
+
+    fn prompt(&self) -> String {
+        match &self.current {
+            Some(current) => current.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            None => "ffhuman".to_string(),
+        }
+    }
+
+    /// Run one line typed at the prompt
+    pub fn run_line(&mut self, line: &str) -> Result<Flow> {
+        let words = split_words(line)?;
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            [] => {}
+            ["quit" | "exit" | "q"] => {
+                if !self.saved && !self.warned_unsaved {
+                    self.warned_unsaved = true;
+                    eprintln!("The current result isn't saved (use 'save as FILE'). Type 'quit' again to leave anyway.");
+                } else {
+                    return Ok(Flow::Quit);
+                }
+            }
+            ["help" | "?"] => eprintln!("{HELP}"),
+            ["open", file] => self.open(Path::new(file))?,
+            ["undo"] => self.undo()?,
+            ["history"] => self.print_history(),
+            ["preview"] => self.preview()?,
+            ["save", "as", file] => self.save_as(Path::new(file))?,
+            ["save", "pipeline", file] => self.save_pipeline(Path::new(file))?,
+            ["open" | "save", ..] => bail!("Usage: open FILE, save as FILE, or save pipeline FILE"),
+            _ => self.apply(line)?,
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Start working on a file. The history starts over.
+    pub fn open(&mut self, file: &Path) -> Result<()> {
+        if !file.exists() && !self.config.dry_run {
+            bail!("File not found: {}", file.display());
+        }
+        self.source = Some(file.to_path_buf());
+        self.current = Some(file.to_path_buf());
+        self.history.clear();
+        self.saved = true;
+        eprintln!("Opened {}", file.display());
+        Ok(())
+    }
+
+    fn require_current(&self) -> Result<PathBuf> {
+        self.current.clone().ok_or_else(|| anyhow!("No file open (use 'open FILE')"))
+    }
+
+    /// Run an ffhuman command on the current result
+    pub fn apply(&mut self, line: &str) -> Result<()> {
+        let before = self.require_current()?;
+        let args = command_args(line, Some(&before))?;
+        let cli = Cli::try_parse_from(std::iter::once("ffhuman".to_string()).chain(args.iter().cloned()))
+            .map_err(clap_error)?;
+        let intent = cli.into_intent()?;
+        if intent.clone().input_mut().is_none() {
+            bail!("'{}' doesn't work on a single file and can't be used in the shell", args[0]);
+        }
+        let step = pipeline_step(&args).map_err(|e| format!("{:#}", e));
+
+        let outputs = self.run_intent(intent, None)?;
+        let after = outputs.last().cloned()
+            .ok_or_else(|| anyhow!("'{}' wrote no file to continue from", args[0]))?;
+        self.history.push(Entry { line: line.trim().to_string(), step, before });
+        self.current = Some(after);
+        self.saved = false;
+        self.warned_unsaved = false;
+        Ok(())
+    }
+
+    /// Execute an intent with intermediate results in the session folder,
+    /// or at `out` when given
+    fn run_intent(&self, intent: Intent, out: Option<&Path>) -> Result<Vec<PathBuf>> {
+        let mut config = self.config.clone();
+        config.outputs = OutputLog::default();
+        config.out = out.map(Path::to_path_buf);
+        if out.is_none() {
+            config.output_dir = Some(self.work_dir.path().to_path_buf());
+            config.name_template = None;
+            config.collision = CollisionPolicy::AutoNumber;
+        }
+        App::new(config.clone()).execute(intent)?;
+        Ok(config.outputs.since(0))
+    }
+
+    /// Go back to the result before the last command
+    pub fn undo(&mut self) -> Result<()> {
+        let entry = self.history.pop().ok_or_else(|| anyhow!("Nothing to undo"))?;
+        eprintln!("Undid '{}'", entry.line);
+        self.current = Some(entry.before);
+        self.saved = self.history.is_empty();
+        Ok(())
+    }
+
+    fn print_history(&self) {
+        match &self.source {
+            Some(source) => eprintln!("open {}", source.display()),
+            None => eprintln!("No file open"),
+        }
+        for (idx, entry) in self.history.iter().enumerate() {
+            eprintln!("{:>3}. {}", idx + 1, entry.line);
+        }
+    }
+
+    /// Play the current result with ffplay, or write a short preview clip
+    /// when ffplay isn't installed
+    fn preview(&self) -> Result<()> {
+        let current = self.require_current()?;
+        let has_ffplay = Command::new("ffplay")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        if has_ffplay && !self.config.dry_run {
+            Command::new("ffplay")
+                .args(["-autoexit", "-loglevel", "error"])
+                .arg(&current)
+                .status()
+                .context("Failed to run ffplay")?;
+            return Ok(());
+        }
+        self.run_intent(Intent::Preview { input: current }, None)?;
+        Ok(())
+    }
+
+    /// Write the current result to `file`. A different extension converts.
+    pub fn save_as(&mut self, file: &Path) -> Result<()> {
+        let current = self.require_current()?;
+        let ext = |p: &Path| p.extension().map(|e| e.to_string_lossy().to_lowercase());
+        if file.exists() && !self.config.overwrite {
+            bail!("{} already exists (start the shell with -y to overwrite)", file.display());
+        }
+        if ext(file) == ext(&current) {
+            if self.config.dry_run {
+                eprintln!("Would copy {} to {}", current.display(), file.display());
+            } else {
+                std::fs::copy(&current, file)
+                    .with_context(|| format!("Failed to save {}", file.display()))?;
+            }
+        } else {
+            let format = ext(file).ok_or_else(|| anyhow!("{} has no extension to pick a format from", file.display()))?;
+            let args = command_args(&format!("convert to {format}"), Some(&current))?;
+            let cli = Cli::try_parse_from(std::iter::once("ffhuman".to_string()).chain(args))
+                .map_err(|_| anyhow!("Can't save as .{format}; save with the current extension or convert first"))?;
+            self.run_intent(cli.into_intent()?, Some(file))?;
+        }
+        eprintln!("Saved {}", file.display());
+        self.saved = true;
+        Ok(())
+    }
+
+    /// Write the history as a pipeline file
+    pub fn save_pipeline(&self, file: &Path) -> Result<()> {
+        if file.exists() && !self.config.overwrite {
+            bail!("{} already exists (start the shell with -y to overwrite)", file.display());
+        }
+        let content = self.pipeline_yaml()?;
+        std::fs::write(file, content).with_context(|| format!("Failed to write {}", file.display()))?;
+        eprintln!("Saved pipeline {} ({} steps)", file.display(), self.history.len());
+        Ok(())
+    }
+
+    /// The history as pipeline YAML
+    pub fn pipeline_yaml(&self) -> Result<String> {
+        if self.history.is_empty() {
+            bail!("No commands to save yet");
+        }
+        let mut steps = Vec::new();
+        for entry in &self.history {
+            let step = entry.step.as_ref()
+                .map_err(|e| anyhow!("'{}' can't be written as a pipeline step: {}", entry.line, e))?;
+            steps.push(Value::Mapping(step.clone()));
+        }
+        let mut doc = Mapping::new();
+        doc.insert("steps".into(), Value::Sequence(steps));
+
+        let source = self.source.as_deref().map(|s| s.display().to_string()).unwrap_or_else(|| "INPUT".into());
+        let mut out = format!("# Recorded with ffhuman shell\n# Run with: ffhuman pipeline {source} <this file>\n");
+        out.push_str(&serde_yaml::to_string(&doc)?);
+        Ok(out)
+    }
+}
+
+/// Split a line into words. Single and double quotes group words; a
+/// backslash escapes the next character.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                word.push(chars.next().ok_or_else(|| anyhow!("Line ends with a lone backslash"))?);
+                in_word = true;
+            }
+            (q, None) if q == '"' || q == '\'' => {
+                quote = Some(q);
+                in_word = true;
+            }
+            (q, Some(open)) if q == open => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (c, _) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        bail!("Unclosed quote in: {line}");
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Turn a shell line into ffhuman arguments: the current file goes in as
+/// the command's input, and keywords the command expects ("from", "at", ...)
+/// are filled in when left out, so `trim 1:00 to 3:00` reads as
+/// `trim FILE from 1:00 to 3:00`.
+pub fn command_args(line: &str, current: Option<&Path>) -> Result<Vec<String>> {
+    let words = split_words(line)?;
+    let (name, rest) = words.split_first().ok_or_else(|| anyhow!("Empty command"))?;
+    let root = Cli::command();
+    let Some(command) = root.find_subcommand(name) else {
+        bail!("Unknown command: {name} (type 'help' for session commands, or see ffhuman --help)");
+    };
+
+    let mut args = vec![command.get_name().to_string()];
+    let mut rest = rest.iter().peekable();
+    for (idx, positional) in command.get_positionals().enumerate() {
+        let id = positional.get_id().as_str();
+        if idx == 0 && id == "input" {
+            let current = current.ok_or_else(|| anyhow!("No file open (use 'open FILE')"))?;
+            args.push(current.to_string_lossy().into_owned());
+            continue;
+        }
+        if id.starts_with('_') {
+            let keywords: Vec<String> = positional.get_possible_values().iter().map(|v| v.get_name().to_string()).collect();
+            match rest.peek() {
+                Some(word) if keywords.iter().any(|k| k.eq_ignore_ascii_case(word)) => {
+                    args.push(rest.next().cloned().unwrap_or_default());
+                }
+                _ => args.extend(keywords.first().cloned()),
+            }
+            continue;
+        }
+        match rest.peek() {
+            Some(word) if !word.starts_with("--") => args.push(rest.next().cloned().unwrap_or_default()),
+            _ => break,
+        }
+    }
+    args.extend(rest.cloned());
+    Ok(args)
+}
+
+/// How a command-line argument is written in a pipeline step, for the
+/// arguments that differ from the step's fields
+#[derive(Debug, Clone, Copy)]
+enum StepField {
+    /// Under another key, or nested keys (`style: { color: red }`)
+    At(&'static [&'static str]),
+    /// A -100..100 percentage, as the -1..1 the step takes
+    Percent(&'static [&'static str]),
+    /// `WxH`, as separate `width` and `height`
+    Size,
+    /// Several words, as one value
+    Words,
+    /// Not part of the step: a keyword or flag the operation implies
+    Skip,
+}
+
+/// Arguments whose step field isn't the argument's own name, by command
+/// (subcommands as e.g. "subtitle shift")
+const STEP_FIELDS: &[(&str, &str, StepField)] = &[
+    ("crop", "size", StepField::Size),
+    ("filter", "brightness", StepField::Percent(&["adjustments", "brightness"])),
+    ("filter", "contrast", StepField::Percent(&["adjustments", "contrast"])),
+    ("filter", "saturation", StepField::Percent(&["adjustments", "saturation"])),
+    ("add-text", "font_size", StepField::At(&["style", "font_size"])),
+    ("add-text", "color", StepField::At(&["style", "color"])),
+    ("animated-text", "animate", StepField::At(&["animation"])),
+    ("animated-text", "font_size", StepField::At(&["style", "font_size"])),
+    ("animated-text", "color", StepField::At(&["style", "color"])),
+    ("blur", "blur_type", StepField::Skip),
+    ("blur", "region", StepField::At(&["blur_type"])),
+    ("normalize", "lufs", StepField::At(&["target"])),
+    ("split", "mode", StepField::Words),
+    ("burn-subtitle", "preset", StepField::At(&["style", "preset"])),
+    ("burn-subtitle", "font", StepField::At(&["style", "font"])),
+    ("burn-subtitle", "size", StepField::At(&["style", "size"])),
+    ("burn-subtitle", "color", StepField::At(&["style", "color"])),
+    ("burn-subtitle", "outline", StepField::At(&["style", "outline"])),
+    ("burn-subtitle", "background", StepField::At(&["style", "background"])),
+    ("burn-subtitle", "position", StepField::At(&["style", "position"])),
+    ("burn-subtitle", "margin", StepField::At(&["style", "margin"])),
+    ("add-subtitles", "lang", StepField::At(&["languages"])),
+    ("speed-audio", "keep_pitch", StepField::Skip),
+];
+
+/// The pipeline step for a command given as ffhuman arguments (see
+/// [`command_args`]). Options given on the line are written, and values the
+/// command fills in by default, since a step may require them; see
+/// [`STEP_FIELDS`] for arguments written differently. The step is checked to
+/// read back as the same operation.
+pub fn pipeline_step(args: &[String]) -> Result<Mapping> {
+    let full: Vec<String> = std::iter::once("ffhuman".to_string()).chain(args.iter().cloned()).collect();
+    let mut expected = Cli::try_parse_from(&full)?.into_intent()?;
+    if let Some(input) = expected.input_mut() {
+        *input = PathBuf::new();
+    }
+
+    // Walk down to the command that was run, e.g. `subtitle shift`
+    let matches = Cli::command().try_get_matches_from(&full).map_err(clap_error)?;
+    let mut command = Cli::command();
+    let mut names = Vec::new();
+    let mut sub = &matches;
+    while let Some((name, next)) = sub.subcommand() {
+        command = command.find_subcommand(name).cloned().ok_or_else(|| anyhow!("Unknown command: {name}"))?;
+        names.push(name.to_string());
+        sub = next;
+    }
+    let name = names.join(" ");
+
+    // (argument, its values, whether it takes a list)
+    let given: Vec<(String, Vec<String>, bool)> = command.get_arguments()
+        .filter(|arg| !arg.is_global_set())
+        .filter(|arg| {
+            let id = arg.get_id().as_str();
+            id != "input" && !id.starts_with('_')
+        })
+        .filter(|arg| match sub.value_source(arg.get_id().as_str()) {
+            Some(ValueSource::CommandLine) => true,
+            Some(ValueSource::DefaultValue) => arg.get_action().takes_values(),
+            _ => false,
+        })
+        .map(|arg| {
+            let id = arg.get_id().as_str();
+            let values = sub.get_raw(id).into_iter().flatten().map(|v| v.to_string_lossy().into_owned()).collect();
+            let list = matches!(arg.get_action(), ArgAction::Append) || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+            (id.to_string(), values, list)
+        })
+        .collect();
+
+    // Prefer YAML numbers and booleans; fall back to plain strings for
+    // fields that take text which happens to look like a number
+    let mut last_error = None;
+    for typed in [true, false] {
+        let mut step = Mapping::new();
+        step.insert("type".into(), expected.kind().into());
+        for (id, values, list) in &given {
+            let field = STEP_FIELDS.iter()
+                .find(|(command, arg, _)| *command == name && arg == id)
+                .map(|(_, _, field)| *field);
+            let value = match values.as_slice() {
+                [single] if !list => scalar_value(single, typed),
+                many => Value::Sequence(many.iter().map(|v| scalar_value(v, typed)).collect()),
+            };
+            match field {
+                None => insert_at(&mut step, &[id.as_str()], value),
+                Some(StepField::At(path)) => insert_at(&mut step, path, value),
+                Some(StepField::Percent(path)) => {
+                    let percent: f64 = values.join("").trim().parse()
+                        .map_err(|_| anyhow!("--{id} takes a number, got {}", values.join(" ")))?;
+                    insert_at(&mut step, path, (percent.clamp(-100.0, 100.0) / 100.0).into());
+                }
+                Some(StepField::Size) => {
+                    let size = values.join("");
+                    let (width, height) = size.split_once(['x', 'X'])
+                        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+                        .ok_or_else(|| anyhow!("Invalid size: {size} (try 640x480)"))?;
+                    insert_at(&mut step, &["width"], width.into());
+                    insert_at(&mut step, &["height"], height.into());
+                }
+                Some(StepField::Words) => insert_at(&mut step, &[id.as_str()], Value::String(values.join(" "))),
+                Some(StepField::Skip) => {}
+            }
+        }
+        let mut doc = Mapping::new();
+        doc.insert("steps".into(), Value::Sequence(vec![Value::Mapping(step.clone())]));
+        match WorkflowSpec::parse(&serde_yaml::to_string(&doc)?, "shell") {
+            Ok(spec) if format!("{:?}", spec.steps[0].intent) == format!("{:?}", expected) => return Ok(step),
+            Ok(_) => last_error = Some(anyhow!("the step doesn't read back as the same operation")),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("no pipeline step type for '{name}'")))
+}
+
+/// Set `value` at a path of keys, creating the mappings on the way
+fn insert_at(map: &mut Mapping, path: &[&str], value: Value) {
+    let Some((last, parents)) = path.split_last() else { return };
+    let mut map = map;
+    for key in parents {
+        let entry = map.entry((*key).into()).or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !entry.is_mapping() {
+            *entry = Value::Mapping(Mapping::new());
+        }
+        let Value::Mapping(inner) = entry else { return };
+        map = inner;
+    }
+    map.insert((*last).into(), value);
+}
+
+/// A clap parse error without its own "error: " prefix
+fn clap_error(e: clap::Error) -> anyhow::Error {
+    let text = e.to_string();
+    anyhow!("{}", text.trim_start_matches("error: ").trim_end())
+}
+
+fn scalar_value(text: &str, typed: bool) -> Value {
+    if typed {
+        if let Ok(b) = text.parse::<bool>() {
+            return Value::Bool(b);
+        }
+        if let Ok(n) = text.parse::<i64>() {
+            return n.into();
+        }
+        if let Ok(n) = text.parse::<f64>() {
+            return n.into();
+        }
+    }
+    Value::String(text.to_string())
+}
@@ -0,0 +1,480 @@
+use crate::config::AppConfig;
+use crate::ffmpeg::probe::{duration_seconds, subtitle_streams, SubtitleStream};
+use crate::ffmpeg::recipes::{self, SubtitleTrack};
+use crate::ffmpeg::runner::Runner;
+use crate::model::subtitle::{CutList, Language, Offset, Stretch, SubtitleContainer, SubtitleFormat, Subtitles, Timestamp};
+use crate::util::output::resolve_collision;
+use crate::util::{base_stem, default_out, system::ensure_ffmpeg_exists};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+pub fn handle_add_subtitles(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    subtitles: &[PathBuf],
+    languages: &[Language],
+    to: Option<SubtitleContainer>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+    if subtitles.is_empty() {
+        bail!("Give at least one subtitle file");
+    }
+    if languages.len() > subtitles.len() {
+        bail!("{} languages for {} subtitle files", languages.len(), subtitles.len());
+    }
+
+    // Read every file up front, so a broken one fails before anything is written
+    let mut tracks = Vec::new();
+    for (i, path) in subtitles.iter().enumerate() {
+        let format = SubtitleFormat::from_path(path)?;
+        let cues = Subtitles::read(path)?;
+        let language = languages.get(i).cloned().or_else(|| Language::from_file_name(path));
+        if language.is_none() {
+            eprintln!("Warning: no language for {}; name it like movie.en.srt or use --lang", path.display());
+        }
+        tracks.push((path, format, cues, language));
+    }
+
+    let container = to
+        .or_else(|| SubtitleContainer::from_path(input))
+        .unwrap_or(SubtitleContainer::Mp4);
+    if container == SubtitleContainer::Hls {
+        let tracks: Vec<_> = tracks.into_iter().map(|(path, _, cues, language)| (path.as_path(), cues, language)).collect();
+        return add_hls_subtitles(config, runner, input, &tracks);
+    }
+
+    // Keep the input's own tracks; they only need converting when the container changes
+    let existing = subtitle_streams(input).unwrap_or_default();
+    let same_container = SubtitleContainer::from_path(input) == Some(container);
+    let existing_codec = if same_container { "copy" } else { container.codec_for(SubtitleFormat::Srt) };
+    let keep = if same_container || existing.iter().all(|s| SubtitleFormat::for_codec(&s.codec).is_some()) {
+        existing.len()
+    } else {
+        eprintln!("Warning: {} has image-based subtitles that can't go in {container}; leaving its own tracks out", input.display());
+        0
+    };
+
+    if config.explain {
+        for (path, format, cues, language) in &tracks {
+            eprintln!("[explain] {} ({format}, {} cues{}) as a {} track",
+                path.display(),
+                cues.cues.len(),
+                language.as_ref().map(|l| format!(", {}", l.name)).unwrap_or_default(),
+                container.codec_for(*format));
+            if *format == SubtitleFormat::Ass && container.codec_for(*format) != "ass" {
+                eprintln!("[explain] ASS styling and positions are dropped in {container}");
+            }
+        }
+        if keep > 0 {
+            eprintln!("[explain] Keeping the input's {keep} subtitle track(s)");
+        }
+    }
+
+    let ext = container.ext().unwrap_or("mp4");
+    let out = default_out(config, input, "subs", ext)?;
+    let tracks: Vec<SubtitleTrack> = tracks.iter().map(|(path, format, _, language)| SubtitleTrack {
+        path,
+        codec: container.codec_for(*format),
+        language: language.as_ref().map(|l| l.code_for(container)),
+        title: language.as_ref().map(|l| l.name.as_str()),
+    }).collect();
+    let steps = recipes::add_subtitles_steps(input, &tracks, keep, existing_codec, &out, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+/// An HLS folder: ffmpeg segments the video, then each subtitle file becomes
+/// a WebVTT rendition listed in a master playlist
+fn add_hls_subtitles(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: &Path,
+    tracks: &[(&Path, Subtitles, Option<Language>)],
+) -> Result<()> {
+    let out_dir = if let Some(output_dir) = &config.output_dir {
+        output_dir.clone()
+    } else {
+        let dir = input.parent().unwrap_or_else(|| Path::new("."));
+        dir.join(format!("{}_hls", base_stem(input)?))
+    };
+    std::fs::create_dir_all(&out_dir)?;
+
+    let steps = recipes::subtitled_hls_steps(input, &out_dir, None, config.overwrite)?;
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    let duration = duration_seconds(input).ok()
+        .unwrap_or_else(|| tracks.iter().map(|(_, cues, _)| cues.end()).max().unwrap_or(0) as f64 / 1000.0);
+    let mut media = Vec::new();
+    let mut used = Vec::<String>::new();
+    for (i, (path, cues, language)) in tracks.iter().enumerate() {
+        let tag = language.as_ref().map(|l| l.tag.clone()).unwrap_or_else(|| format!("track{}", i + 1));
+        let name = language.as_ref().map(|l| l.name.clone()).unwrap_or_else(|| format!("Subtitles {}", i + 1));
+        // Names and files have to be unique within the group
+        let count = used.iter().filter(|t| **t == tag).count();
+        used.push(tag.clone());
+        let (file, name) = if count == 0 { (tag.clone(), name) } else { (format!("{tag}_{}", count + 1), format!("{name} {}", count + 1)) };
+
+        let vtt = format!("subtitles_{file}.vtt");
+        let playlist = format!("subtitles_{file}.m3u8");
+        if config.explain {
+            eprintln!("[explain] {} as WebVTT rendition {playlist} ({name})", path.display());
+        }
+        if !config.dry_run {
+            let text = cues.render(SubtitleFormat::Vtt)
+                .replacen("WEBVTT\n", "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:0,LOCAL:00:00:00.000\n", 1);
+            std::fs::write(out_dir.join(&vtt), text).with_context(|| format!("Failed to write {vtt}"))?;
+            std::fs::write(out_dir.join(&playlist), subtitle_playlist(&vtt, duration))
+                .with_context(|| format!("Failed to write {playlist}"))?;
+        }
+        let language = language.as_ref().map(|l| format!(",LANGUAGE=\"{}\"", l.tag)).unwrap_or_default();
+        media.push(format!(
+            "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"{name}\"{language},DEFAULT=NO,AUTOSELECT=YES,URI=\"{playlist}\""
+        ));
+    }
+
+    let master = out_dir.join("master.m3u8");
+    if !config.dry_run {
+        let bandwidth = peak_bandwidth(&out_dir.join("playlist.m3u8")).unwrap_or(5_000_000);
+        let text = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n{}\n#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},SUBTITLES=\"subs\"\nplaylist.m3u8\n",
+            media.join("\n")
+        );
+        std::fs::write(&master, text).context("Failed to write master.m3u8")?;
+    }
+
+    eprintln!("Output directory: {} (play {})", out_dir.display(), master.display());
+    Ok(())
+}
+
+/// A media playlist with the whole WebVTT file as its one segment
+fn subtitle_playlist(vtt: &str, duration: f64) -> String {
+    format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:{duration:.3},\n{vtt}\n#EXT-X-ENDLIST\n",
+        duration.ceil().max(1.0) as u64
+    )
+}
+
+/// Highest bit rate of any segment in a media playlist, for BANDWIDTH
+fn peak_bandwidth(playlist: &Path) -> Option<u64> {
+    let text = std::fs::read_to_string(playlist).ok()?;
+    let dir = playlist.parent()?;
+    let mut peak = None;
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some(seconds) = line.strip_prefix("#EXTINF:") else {
+            continue;
+        };
+        let seconds: f64 = seconds.trim_end_matches(',').split(',').next()?.parse().ok()?;
+        let segment = lines.next()?;
+        let bytes = std::fs::metadata(dir.join(segment)).ok()?.len();
+        if seconds > 0.0 {
+            let bits = (bytes as f64 * 8.0 / seconds) as u64;
+            peak = Some(peak.map_or(bits, |p: u64| p.max(bits)));
+        }
+    }
+    peak
+}
+
+pub fn handle_extract_subtitles(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    format: Option<SubtitleFormat>,
+    track: Option<usize>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let streams = match subtitle_streams(input) {
+        Some(streams) if streams.is_empty() => bail!("{} has no subtitle tracks", input.display()),
+        Some(streams) => streams,
+        None => {
+            // Without ffprobe, assume the track asked for (or the first) is there
+            if config.explain {
+                eprintln!("[explain] Couldn't list the subtitle tracks; extracting track {}", track.unwrap_or(1));
+            }
+            let count = track.unwrap_or(1);
+            vec![SubtitleStream { codec: String::new(), language: None }; count]
+        }
+    };
+
+    let selected: Vec<(usize, &SubtitleStream)> = match track {
+        Some(0) => bail!("Subtitle tracks are numbered from 1"),
+        Some(n) if n > streams.len() => bail!("{} has {} subtitle track(s); there is no track {n}", input.display(), streams.len()),
+        Some(n) => {
+            let stream = &streams[n - 1];
+            if is_bitmap(stream) {
+                bail!("Track {n} is image-based ({}) and can't be saved as text", stream.codec);
+            }
+            vec![(n - 1, stream)]
+        }
+        None => {
+            let text: Vec<_> = streams.iter().enumerate().filter(|(_, s)| !is_bitmap(s)).collect();
+            if text.len() < streams.len() {
+                eprintln!("Skipping {} image-based subtitle track(s)", streams.len() - text.len());
+            }
+            if text.is_empty() {
+                bail!("{} only has image-based subtitles, which can't be saved as text", input.display());
+            }
+            text
+        }
+    };
+    if config.out.is_some() && selected.len() > 1 {
+        bail!("--out names one file, but {} has {} subtitle tracks; pick one with --track", input.display(), selected.len());
+    }
+
+    for &(index, stream) in &selected {
+        let format = format
+            .or_else(|| SubtitleFormat::for_codec(&stream.codec).filter(|f| *f == SubtitleFormat::Ass))
+            .unwrap_or(SubtitleFormat::Srt);
+        // movie_eng.srt, or movie_track2.srt when the language is unknown or shared
+        let shared = stream.language.is_some()
+            && selected.iter().filter(|(_, s)| s.language == stream.language).count() > 1;
+        let suffix = match &stream.language {
+            Some(language) if !shared => language.clone(),
+            Some(language) => format!("{language}_track{}", index + 1),
+            None if selected.len() == 1 => "subs".to_string(),
+            None => format!("track{}", index + 1),
+        };
+        let out = default_out(config, input, &suffix, format.ext())?;
+        if config.explain {
+            eprintln!("[explain] Subtitle track {}{} to {format}", index + 1,
+                if stream.codec.is_empty() { String::new() } else { format!(" ({})", stream.codec) });
+        }
+        let steps = recipes::extract_subtitle_steps(input, index, &out, format, config.overwrite);
+        for step in steps {
+            runner.run(&step)?;
+        }
+        eprintln!("Output: {}", out.display());
+    }
+    Ok(())
+}
+
+/// DVD, Blu-ray and DVB subtitles are pictures, not text
+pub fn is_bitmap(stream: &SubtitleStream) -> bool {
+    !stream.codec.is_empty() && SubtitleFormat::for_codec(&stream.codec).is_none()
+}
+
+pub fn handle_convert_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    format: SubtitleFormat,
+) -> Result<()> {
+    let input = input.as_ref();
+    let from = SubtitleFormat::from_path(input)?;
+    let subtitles = Subtitles::read(input)?;
+
+    let out = subtitle_out(config, input, None, format)?;
+    eprintln!("Converting {} subtitles from {from} to {format}", subtitles.cues.len());
+    if config.explain && from == SubtitleFormat::Ass {
+        eprintln!("[explain] ASS styles and positions are dropped; italics, bold and underline are kept");
+    }
+    save(config, &subtitles, &out, format)
+}
+
+pub fn handle_shift_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    offset: Offset,
+) -> Result<()> {
+    let input = input.as_ref();
+    let format = SubtitleFormat::from_path(input)?;
+    let mut subtitles = Subtitles::read(input)?;
+    let count = subtitles.cues.len();
+    subtitles.shift(offset.0);
+
+    eprintln!("Moving {count} subtitles by {offset}");
+    if subtitles.cues.len() < count {
+        eprintln!("Warning: {} subtitle(s) would start before 0:00 and were dropped", count - subtitles.cues.len());
+    }
+    let out = subtitle_out(config, input, Some("shifted"), format)?;
+    save(config, &subtitles, &out, format)
+}
+
+pub fn handle_stretch_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    stretch: Stretch,
+) -> Result<()> {
+    let input = input.as_ref();
+    let format = SubtitleFormat::from_path(input)?;
+    let mut subtitles = Subtitles::read(input)?;
+    let (first, last) = (subtitles.cues[0].start, subtitles.end());
+    subtitles.stretch(&stretch);
+
+    eprintln!("Retiming {} subtitles: {} now starts at {}, {} now ends at {}",
+        subtitles.cues.len(), Timestamp(first), Timestamp(subtitles.cues[0].start), Timestamp(last), Timestamp(subtitles.end()));
+    if config.explain {
+        match stretch {
+            Stretch::Fps(from, to) => eprintln!("[explain] Timed for {from} fps, played at {to} fps: every time scales by {:.5}", from / to),
+            Stretch::Anchors(..) => eprintln!("[explain] Times move linearly through the two anchors ({stretch})"),
+        }
+    }
+    let out = subtitle_out(config, input, Some("synced"), format)?;
+    save(config, &subtitles, &out, format)
+}
+
+pub fn handle_cut_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    keep: CutList,
+) -> Result<()> {
+    let input = input.as_ref();
+    let format = SubtitleFormat::from_path(input)?;
+    let subtitles = Subtitles::read(input)?.cut(&keep.ranges());
+
+    eprintln!("Keeping {} subtitles from {keep}", subtitles.cues.len());
+    if subtitles.cues.is_empty() {
+        eprintln!("Warning: no subtitles are shown in {keep}");
+    }
+    let out = subtitle_out(config, input, Some("cut"), format)?;
+    save(config, &subtitles, &out, format)
+}
+
+pub fn handle_merge_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    inputs: &[PathBuf],
+    at: &[Timestamp],
+) -> Result<()> {
+    if inputs.len() < 2 {
+        bail!("Give at least two subtitle files to merge");
+    }
+    if !at.is_empty() && at.len() != inputs.len() {
+        bail!("{} start times for {} subtitle files; give one per file, or none to overlay them", at.len(), inputs.len());
+    }
+
+    let mut parts = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        let offset = at.get(i).map(|t| t.0).unwrap_or(0);
+        if config.explain {
+            eprintln!("[explain] {} from {}", input.display(), Timestamp(offset));
+        }
+        parts.push((Subtitles::read(input)?, offset));
+    }
+    let subtitles = Subtitles::merge(parts);
+
+    eprintln!("Merging {} files ({} subtitles)", inputs.len(), subtitles.cues.len());
+    let format = SubtitleFormat::from_path(&inputs[0])?;
+    let out = subtitle_out(config, &inputs[0], Some("merged"), format)?;
+    save(config, &subtitles, &out, format)
+}
+
+pub fn handle_split_subtitles(
+    config: &AppConfig,
+    _runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    at: &[Timestamp],
+) -> Result<()> {
+    let input = input.as_ref();
+    if at.is_empty() {
+        bail!("Give at least one time to split at");
+    }
+    if at.windows(2).any(|pair| pair[1] <= pair[0]) {
+        bail!("Split times have to be in order");
+    }
+    if config.out.is_some() {
+        bail!("--out names one file, but splitting writes {}; use --output-dir or --name instead", at.len() + 1);
+    }
+    let format = SubtitleFormat::from_path(input)?;
+    let subtitles = Subtitles::read(input)?;
+    let points: Vec<u64> = at.iter().map(|t| t.0).collect();
+
+    for (i, part) in subtitles.split(&points).iter().enumerate() {
+        if part.cues.is_empty() {
+            eprintln!("Warning: part {} has no subtitles", i + 1);
+        }
+        let out = subtitle_out(config, input, Some(&format!("part{}", i + 1)), format)?;
+        save(config, part, &out, format)?;
+    }
+    Ok(())
+}
+
+/// Trim the subtitle files that sit next to a trimmed video (movie.srt,
+/// movie.en.vtt, ...) to the same range, named after its output
+pub fn trim_sidecars(config: &AppConfig, input: &Path, out: &Path, start: u64, end: u64) -> Result<()> {
+    let stem = base_stem(input)?;
+    let out_stem = base_stem(out)?;
+    let dir = input.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(());
+    };
+    let mut sidecars: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| input.with_file_name(e.file_name())))
+        .filter(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            name.starts_with(&format!("{stem}.")) && SubtitleFormat::from_path(path).is_ok()
+        })
+        .collect();
+    sidecars.sort();
+
+    for sidecar in sidecars {
+        let name = sidecar.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let target = out.with_file_name(format!("{out_stem}{}", &name[stem.len()..]));
+        let subtitles = match Subtitles::read(&sidecar) {
+            Ok(subtitles) => subtitles.cut(&[(start, end)]),
+            Err(e) => {
+                eprintln!("Warning: leaving {} as it is: {e:#}", sidecar.display());
+                continue;
+            }
+        };
+        if config.explain {
+            eprintln!("[explain] Trimming {} to match ({} subtitles)", sidecar.display(), subtitles.cues.len());
+        }
+        let target = resolve_collision(config, target)?;
+        config.outputs.record(target.clone());
+        save(config, &subtitles, &target, SubtitleFormat::from_path(&sidecar)?)?;
+    }
+    Ok(())
+}
+
+/// Where an edited subtitle file goes: movie_shifted.en.srt for
+/// movie.en.srt, so players still pick up the language. Without a suffix the
+/// name stays and only the extension changes (movie.en.vtt).
+fn subtitle_out(config: &AppConfig, input: &Path, suffix: Option<&str>, format: SubtitleFormat) -> Result<PathBuf> {
+    if config.out.is_some() || config.name_template.is_some() {
+        return default_out(config, input, suffix.unwrap_or("converted"), format.ext());
+    }
+    let dir = match &config.output_dir {
+        Some(output_dir) => {
+            std::fs::create_dir_all(output_dir)?;
+            output_dir.as_path()
+        }
+        None => input.parent().unwrap_or_else(|| Path::new(".")),
+    };
+    let stem = base_stem(input)?;
+    let (base, language) = match (Language::from_file_name(input), stem.rsplit_once('.')) {
+        (Some(_), Some((base, language))) => (base, format!(".{language}")),
+        _ => (stem.as_str(), String::new()),
+    };
+    let mut out = match suffix {
+        Some(suffix) => dir.join(format!("{base}_{suffix}{language}.{}", format.ext())),
+        None => dir.join(format!("{stem}.{}", format.ext())),
+    };
+    if out == input {
+        out = dir.join(format!("{base}_converted{language}.{}", format.ext()));
+    }
+    let out = resolve_collision(config, out)?;
+    config.outputs.record(out.clone());
+    Ok(out)
+}
+
+fn save(config: &AppConfig, subtitles: &Subtitles, out: &Path, format: SubtitleFormat) -> Result<()> {
+    if !config.dry_run {
+        subtitles.write(out, format)?;
+    }
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::commands::subtitle;
 use crate::config::AppConfig;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
@@ -23,6 +24,8 @@ pub fn handle_trim(
     }
 
     eprintln!("Output: {}", out.display());
-    Ok(())
+    // Subtitles next to the video follow the trim
+    let (start, end) = (start.to_seconds() as u64 * 1000, end.to_seconds() as u64 * 1000);
+    subtitle::trim_sidecars(config, input, &out, start, end)
 }
 
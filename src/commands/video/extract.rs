@@ -1,9 +1,10 @@
 use crate::config::AppConfig;
+use crate::ffmpeg::probe::scene_changes;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
 use crate::model::{Duration, Time};
 use crate::util::{base_stem, default_out, system::ensure_ffmpeg_exists};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::Path;
 
 pub fn handle_thumbnail(
@@ -89,25 +90,23 @@ pub fn handle_detect_scenes(
     let input = input.as_ref();
 
     // Output scene detection file (text file with timestamps)
-    let out = if let Some(out) = &config.out {
-        if let Some(parent) = out.parent() {
-            std::fs::create_dir_all(parent)?;
+    let out = default_out(config, input, "scenes", "txt")?;
+    let steps = recipes::detect_scenes_steps(input, &out, config.overwrite);
+    if config.dry_run {
+        for step in steps {
+            runner.run(&step)?;
         }
-        out.clone()
     } else {
-        let dir = if let Some(output_dir) = &config.output_dir {
-            std::fs::create_dir_all(output_dir)?;
-            output_dir
-        } else {
-            input.parent().unwrap_or_else(|| Path::new("."))
-        };
-        let stem = base_stem(input)?;
-        dir.join(format!("{stem}_scenes.txt"))
-    };
-
-    let steps = recipes::detect_scenes_steps(input, &out, config.overwrite);
-    for step in steps {
-        runner.run(&step)?;
+        // ffmpeg only logs the scene changes, so the detection runs through
+        // the probe to collect them; the step is still shown and logged
+        for step in &steps {
+            eprintln!("Running: {} {}", step.program, step.args.join(" "));
+            config.steps.record(step);
+        }
+        let times = scene_changes(input, recipes::SCENE_THRESHOLD)?;
+        let text: String = times.iter().map(|t| format!("{t:.3}\n")).collect();
+        std::fs::write(&out, text).with_context(|| format!("failed to write {}", out.display()))?;
+        eprintln!("Found {} scene changes", times.len());
     }
 
     eprintln!("Output: {}", out.display());
@@ -255,15 +254,10 @@ pub fn handle_social_media_convert(
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
 
-    let platform_str = match platform {
-        crate::model::intent::SocialPlatform::Instagram => "instagram",
-        crate::model::intent::SocialPlatform::TikTok => "tiktok",
-        crate::model::intent::SocialPlatform::YoutubeShorts => "youtube-shorts",
-        crate::model::intent::SocialPlatform::Twitter => "twitter",
-    };
+    let platform_str = platform.to_string();
 
-    let out = default_out(config, input, platform_str, "mp4")?;
-    let steps = recipes::social_media_convert_steps(input, &out, platform_str, config.overwrite)?;
+    let out = default_out(config, input, &platform_str, "mp4")?;
+    let steps = recipes::social_media_convert_steps(input, &out, &platform_str, config.overwrite)?;
     for step in steps {
         runner.run(&step)?;
     }
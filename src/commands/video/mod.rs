@@ -4,6 +4,7 @@ pub mod extract;
 pub mod filter;
 pub mod settings;
 pub mod split;
+pub mod sequence;
 
 // Re-export all handlers for backward compatibility
 pub use transform::*;
@@ -12,4 +13,5 @@ pub use extract::*;
 pub use filter::*;
 pub use settings::*;
 pub use split::*;
+pub use sequence::*;
 
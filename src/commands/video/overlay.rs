@@ -1,10 +1,13 @@
 use crate::config::AppConfig;
-use crate::ffmpeg::recipes;
+use crate::commands::subtitle::is_bitmap;
+use crate::ffmpeg::probe::{subtitle_streams, video_height};
+use crate::ffmpeg::recipes::{self, BurnSource};
 use crate::ffmpeg::runner::Runner;
 use crate::model::{Opacity, TextPosition, TextStyle, WatermarkPosition, WatermarkSize};
 use crate::model::types::{MirrorDirection, SplitScreenOrientation, ColorGradePreset, TextAnimation, TransitionType};
+use crate::model::subtitle::{ass_play_res, SubtitleSelector, SubtitleStyle};
 use crate::util::{default_out, system::ensure_ffmpeg_exists};
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use std::io::Write;
 use std::path::Path;
 
@@ -62,19 +65,62 @@ pub fn handle_burn_subtitle(
     config: &AppConfig,
     runner: &dyn Runner,
     input: impl AsRef<Path>,
-    subtitle: impl AsRef<Path>,
+    subtitle: Option<&Path>,
+    track: Option<&SubtitleSelector>,
+    style: &SubtitleStyle,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let input = input.as_ref();
-    let subtitle = subtitle.as_ref();
 
-    // Validate that subtitle file exists
-    if !subtitle.exists() {
-        anyhow::bail!("Subtitle file not found: {}", subtitle.display());
-    }
+    let (source, play_res) = match subtitle {
+        Some(_) if track.is_some() => {
+            bail!("--track picks a subtitle track of the video; leave out either the subtitle file or --track")
+        }
+        Some(subtitle) => {
+            // Validate that subtitle file exists
+            if !subtitle.exists() {
+                bail!("Subtitle file not found: {}", subtitle.display());
+            }
+            let is_ass = subtitle.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ass"));
+            let play_res = if is_ass {
+                let text = std::fs::read_to_string(subtitle)
+                    .with_context(|| format!("failed to read {}", subtitle.display()))?;
+                if config.explain {
+                    eprintln!("[explain] {} is ASS: {}", subtitle.display(),
+                        if style.is_empty() { "keeping its own styles" } else { "the style options override its styles" });
+                }
+                ass_play_res(&text)
+            } else {
+                288
+            };
+            (BurnSource::File(subtitle), play_res)
+        }
+        None => (embedded_subtitle(config, input, track)?, 288),
+    };
+
+    let force_style = match source {
+        _ if style.is_empty() => None,
+        BurnSource::Bitmap(_) => {
+            eprintln!("Warning: image-based subtitles are burned as they are; the style options don't apply");
+            None
+        }
+        _ => {
+            let height = video_height(input).unwrap_or_else(|| {
+                if config.explain {
+                    eprintln!("[explain] Couldn't read the video height; sizing the captions for 1080 lines");
+                }
+                1080
+            });
+            let force_style = style.force_style(height, play_res);
+            if config.explain {
+                eprintln!("[explain] Subtitle style: {force_style}");
+            }
+            Some(force_style)
+        }
+    };
 
     let out = default_out(config, input, "subtitled", "mp4")?;
-    let steps = recipes::burn_subtitle_steps(input, subtitle, &out, config.overwrite);
+    let steps = recipes::burn_subtitle_steps(input, source, force_style.as_deref(), &out, config.overwrite);
     for step in steps {
         runner.run(&step)?;
     }
@@ -83,6 +129,46 @@ pub fn handle_burn_subtitle(
     Ok(())
 }
 
+/// The subtitle stream of the input to burn: the one asked for, or the first
+fn embedded_subtitle(config: &AppConfig, input: &Path, track: Option<&SubtitleSelector>) -> Result<BurnSource<'static>> {
+    let Some(streams) = subtitle_streams(input) else {
+        // Without ffprobe, trust the track number (or take the first) and hope it's text
+        return match track {
+            Some(SubtitleSelector::Index(n)) => Ok(BurnSource::Text(n - 1)),
+            None => Ok(BurnSource::Text(0)),
+            Some(selector) => bail!(
+                "Couldn't list the subtitle tracks of {} to find {selector}; pick one by number with --track",
+                input.display()
+            ),
+        };
+    };
+    if streams.is_empty() {
+        bail!("{} has no subtitle tracks; give a subtitle file to burn", input.display());
+    }
+
+    let index = match track {
+        None => 0,
+        Some(SubtitleSelector::Index(n)) if *n > streams.len() => {
+            bail!("{} has {} subtitle track(s); there is no track {n}", input.display(), streams.len())
+        }
+        Some(SubtitleSelector::Index(n)) => n - 1,
+        Some(selector) => streams.iter()
+            .position(|s| s.language.as_deref().is_some_and(|l| selector.matches_language(l)))
+            .ok_or_else(|| {
+                let languages: Vec<_> = streams.iter().filter_map(|s| s.language.as_deref()).collect();
+                anyhow::anyhow!("{} has no {selector} subtitle track (it has: {})", input.display(),
+                    if languages.is_empty() { "untagged tracks only".to_string() } else { languages.join(", ") })
+            })?,
+    };
+
+    let stream = &streams[index];
+    if config.explain {
+        eprintln!("[explain] Burning subtitle track {} ({}{})", index + 1, stream.codec,
+            stream.language.as_deref().map(|l| format!(", {l}")).unwrap_or_default());
+    }
+    Ok(if is_bitmap(stream) { BurnSource::Bitmap(index) } else { BurnSource::Text(index) })
+}
+
 pub fn handle_pip(
     config: &AppConfig,
     runner: &dyn Runner,
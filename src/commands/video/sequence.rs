@@ -0,0 +1,155 @@
+use crate::config::AppConfig;
+use crate::ffmpeg::probe::pix_fmt;
+use crate::ffmpeg::recipes::{self, SequenceInput};
+use crate::ffmpeg::runner::Runner;
+use crate::model::sequence::{frame_pattern, is_high_bit_depth, video_pix_fmt, FrameFormat, ImageSequence};
+use crate::model::{QualityPreset, VideoCodec};
+use crate::util::{base_stem, default_out, system::ensure_ffmpeg_exists};
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub fn handle_sequence_to_video(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    images: &[PathBuf],
+    fps: u32,
+    codec: Option<VideoCodec>,
+    quality: Option<QualityPreset>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    if fps == 0 {
+        bail!("The frame rate has to be at least 1 fps");
+    }
+    let codec = codec.unwrap_or(VideoCodec::H264);
+    if codec == VideoCodec::Copy {
+        bail!("Frames have to be encoded, so --codec copy doesn't apply");
+    }
+
+    let sequence = ImageSequence::find(images)?;
+    let count = sequence.frames.len();
+    eprintln!(
+        "Found {count} frames, {} to {} ({:.1}s at {fps} fps)",
+        sequence.frames[0].display(),
+        sequence.frames[count - 1].display(),
+        count as f64 / fps as f64
+    );
+    if !sequence.gaps().is_empty() {
+        eprintln!(
+            "Warning: {} frames are missing from the numbering ({}); the video goes straight past them",
+            sequence.missing(),
+            sequence.describe_gaps()
+        );
+    }
+
+    // Intermediate codecs go in .mov, FFV1 in .mkv
+    let ext = ["mp4", "mov", "mkv"].into_iter().find(|ext| codec.fits_container(ext)).unwrap_or("mkv");
+    let first = &sequence.frames[0];
+    let out = default_out(config, &first.with_file_name(sequence.name()), "video", ext)?;
+
+    let probed = pix_fmt(first);
+    let pix_fmt = video_pix_fmt(codec, sequence.is_high_bit_depth(probed.as_deref()));
+    if config.explain {
+        eprintln!("[explain] Frames are {} ({}); encoding {} with pixel format {}",
+            sequence.extension(),
+            probed.as_deref().unwrap_or("pixel format unknown"),
+            codec,
+            pix_fmt.unwrap_or("from the codec profile"));
+    }
+
+    let image2 = sequence.image2_input();
+    let list;
+    let input = match &image2 {
+        Some((path, start)) => {
+            if config.explain {
+                eprintln!("[explain] Numbered without gaps: reading {} from frame {start}", path.display());
+            }
+            SequenceInput::Pattern { path, start: *start }
+        }
+        None => {
+            // Gaps or unnumbered names: list every frame, each lasting one frame
+            list = write_frame_list(&sequence, fps)?;
+            if config.explain {
+                eprintln!("[explain] Listing frames in {}", list.path().display());
+            }
+            SequenceInput::List(list.path())
+        }
+    };
+
+    let steps = recipes::sequence_to_video_steps(input, &out, fps, codec, quality, pix_fmt, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output: {}", out.display());
+    Ok(())
+}
+
+/// Write an ffconcat list of the frames to a temporary file, removed when
+/// it is dropped
+fn write_frame_list(sequence: &ImageSequence, fps: u32) -> Result<tempfile::NamedTempFile> {
+    let mut list = tempfile::Builder::new()
+        .prefix("ffhuman-sequence")
+        .suffix(".txt")
+        .tempfile()
+        .context("failed to create frame list")?;
+    writeln!(list, "ffconcat version 1.0").context("write frame list")?;
+    let frame_duration = 1.0 / fps as f64;
+    for frame in &sequence.frames {
+        let frame = frame.canonicalize()
+            .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(frame)))?;
+        writeln!(list, "file '{}'", frame.to_string_lossy().replace('\'', "'\\''")).context("write frame list")?;
+        writeln!(list, "duration {frame_duration:.6}").context("write frame list")?;
+    }
+    // The concat demuxer drops the last duration unless the file is repeated
+    if let Some(last) = sequence.frames.last() {
+        let last = last.canonicalize().unwrap_or_else(|_| last.clone());
+        writeln!(list, "file '{}'", last.to_string_lossy().replace('\'', "'\\''")).context("write frame list")?;
+    }
+    list.as_file().sync_all().context("sync frame list")?;
+    Ok(list)
+}
+
+pub fn handle_video_to_sequence(
+    config: &AppConfig,
+    runner: &dyn Runner,
+    input: impl AsRef<Path>,
+    format: FrameFormat,
+    pattern: Option<&str>,
+    start: Option<u32>,
+) -> Result<()> {
+    ensure_ffmpeg_exists()?;
+    let input = input.as_ref();
+
+    let stem = base_stem(input)?;
+    let pattern = frame_pattern(pattern, &stem, format)?;
+
+    // Frames go in their own folder, like extract-frames
+    let output_dir = if let Some(output_dir) = &config.output_dir {
+        output_dir.clone()
+    } else {
+        let dir = input.parent().unwrap_or_else(|| Path::new("."));
+        dir.join(format!("{stem}_sequence"))
+    };
+    std::fs::create_dir_all(&output_dir)?;
+
+    let probed = pix_fmt(input);
+    let high_bit_depth = probed.as_deref().is_some_and(is_high_bit_depth);
+    if config.explain {
+        eprintln!("[explain] Every frame as {format}{}; source pixel format {}",
+            if format == FrameFormat::Jpg { " (lossy)" } else { " (lossless)" },
+            probed.as_deref().unwrap_or("unknown"));
+        if high_bit_depth && matches!(format, FrameFormat::Png | FrameFormat::Tiff) {
+            eprintln!("[explain] Keeping 16 bits per channel");
+        }
+    }
+
+    let start = start.unwrap_or(1);
+    let steps = recipes::video_to_sequence_steps(input, &output_dir.join(&pattern), format, high_bit_depth, start, config.overwrite);
+    for step in steps {
+        runner.run(&step)?;
+    }
+
+    eprintln!("Output directory: {} ({}, from {start})", output_dir.display(), pattern);
+    Ok(())
+}
@@ -1,9 +1,12 @@
 use crate::config::AppConfig;
+use crate::ffmpeg::probe;
 use crate::ffmpeg::recipes;
 use crate::ffmpeg::runner::Runner;
+use crate::model::chapter::file_name_part;
+use crate::model::split::{self, SplitManifest, SplitPart, Start};
 use crate::model::types::SplitMode;
 use crate::util::{base_stem, system::ensure_ffmpeg_exists};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::path::Path;
 
 pub fn handle_split(
@@ -27,15 +30,115 @@ pub fn handle_split(
         split_dir
     };
 
-    let steps = recipes::split_steps(input, &output_dir, &mode, config.overwrite)?;
-    
+    // Size-limited chunks keep the input's container, so they can be copied and weighed in advance
+    let ext = match mode {
+        SplitMode::Size(_) => input.extension().and_then(|e| e.to_str()).unwrap_or("mp4").to_lowercase(),
+        _ => "mp4".to_string(),
+    };
+    let duration = probe::duration_seconds(input)?;
+
+    let starts: Vec<Start> = match &mode {
+        SplitMode::Every(interval) => split::every_starts(duration, interval.to_seconds()),
+        SplitMode::IntoParts(parts) => split::equal_starts(duration, *parts),
+        SplitMode::ByChapters => {
+            let chapters = probe::chapters(input)?;
+            if chapters.chapters.is_empty() {
+                bail!("{} has no chapters to split by", input.display());
+            }
+            chapters.chapters.into_iter().map(|c| (c.start as f64 / 1000.0, Some(c.title))).collect()
+        }
+        SplitMode::Scenes { .. } | SplitMode::Silence { .. } if config.dry_run => {
+            // Finding the split points means decoding the whole file; show the detection instead
+            let out = output_dir.join("parts.json");
+            let steps = match &mode {
+                SplitMode::Scenes { .. } => recipes::detect_scenes_steps(input, &out, config.overwrite),
+                _ => recipes::detect_silence_steps(input, &out, config.overwrite),
+            };
+            for step in steps {
+                runner.run(&step)?;
+            }
+            eprintln!("Output directory: {}", output_dir.display());
+            return Ok(());
+        }
+        SplitMode::Scenes { threshold } => {
+            eprintln!("Detecting scene changes...");
+            split::starts_at(&probe::scene_changes(input, *threshold)?)
+        }
+        SplitMode::Silence { min_silence, noise } => {
+            eprintln!("Detecting silence...");
+            split::silence_starts(&probe::silences(input, *noise, min_silence.to_seconds())?)
+        }
+        SplitMode::Size(limit) => {
+            let size = std::fs::metadata(input)?.len();
+            if size <= limit.bytes {
+                bail!("{} is already under {limit} ({})", input.display(), crate::model::TargetSize { bytes: size });
+            }
+            let (starts, oversized) = split::size_starts(&probe::packets(input)?, limit.bytes);
+            if oversized {
+                eprintln!("Warning: keyframes are too far apart for every chunk to stay under {limit}");
+            }
+            starts
+        }
+    };
+
+    let copies = recipes::split_copies_video(input, &output_dir.join(format!("part.{ext}")));
+    let starts = if copies {
+        match probe::keyframes(input) {
+            Ok(keyframes) => {
+                if config.explain {
+                    eprintln!("[explain] Streams are copied, so each part starts on the nearest of {} keyframes", keyframes.len());
+                }
+                split::snap_to_keyframes(starts, &keyframes)
+            }
+            Err(_) => {
+                eprintln!("Warning: couldn't read the keyframes; copied parts may start with a few frozen frames");
+                starts
+            }
+        }
+    } else {
+        starts
+    };
+
+    let spans = split::spans(starts, duration);
+    if spans.len() < 2 {
+        eprintln!("Warning: found nowhere to split {}; writing it as a single part", input.display());
+    }
+    let count = spans.len();
+    let parts: Vec<SplitPart> = spans.into_iter()
+        .enumerate()
+        .map(|(i, (start, end, title))| {
+            let name = match &mode {
+                SplitMode::Every(_) | SplitMode::Silence { .. } => format!("segment_{:03}", i + 1),
+                SplitMode::IntoParts(_) => format!("part_{:03}_of_{:03}", i + 1, count),
+                SplitMode::ByChapters => file_name_part(i + 1, title.as_deref().unwrap_or_default()),
+                SplitMode::Scenes { .. } => format!("scene_{:03}", i + 1),
+                SplitMode::Size(_) => format!("chunk_{:03}", i + 1),
+            };
+            SplitPart { output: output_dir.join(format!("{name}.{ext}")), start, end, title }
+        })
+        .collect();
+
+    let steps = recipes::split_steps(input, &parts, config.overwrite);
+
     eprintln!("Splitting into {} segments...", steps.len());
     for (idx, step) in steps.iter().enumerate() {
         eprintln!(" Processing segment {}/{}...", idx + 1, steps.len());
         runner.run(step)?;
     }
 
+    // parts.json lists every part with its time range, and its size once written
+    let manifest_path = output_dir.join("parts.json");
+    if !config.dry_run {
+        SplitManifest::new(input, &mode.to_string(), &parts).write(&manifest_path)?;
+        if let SplitMode::Size(limit) = mode {
+            let over = parts.iter().filter(|p| std::fs::metadata(&p.output).is_ok_and(|m| m.len() > limit.bytes)).count();
+            if over > 0 {
+                eprintln!("Warning: {over} chunk(s) came out over {limit}");
+            }
+        }
+    }
+
+    eprintln!("Manifest: {}", manifest_path.display());
     eprintln!("Output directory: {}", output_dir.display());
     Ok(())
 }
-
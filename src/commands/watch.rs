@@ -1,21 +1,65 @@
 use crate::app::App;
-use crate::config::AppConfig;
+use crate::commands::batch::build_intent;
+use crate::commands::workflow::run_workflow;
+use crate::config::{AppConfig, OutputLog};
 use crate::ffmpeg::runner::Runner;
-use crate::model::{BatchOperation, Intent};
-use crate::util::system::ensure_ffmpeg_exists;
-use anyhow::Result;
+use crate::model::workflow::WorkflowMode;
+use crate::model::{BatchOperation, CollisionPolicy, WatchOptions};
+use crate::util::job_state::JobStatus;
+use crate::util::manifest::size_and_mtime;
+use crate::util::watch_rules::{WatchRules, DEFAULT_MEDIA_EXTENSIONS};
+use crate::util::watch_state::{WatchRecord, WatchState, WATCH_STATE_FILE};
+use crate::util::{ensure_per_file_output, resolve_collision, system::ensure_ffmpeg_exists, OutputSkipped};
+use anyhow::{Context, Result};
+use glob::Pattern;
+use notify::event::{AccessKind, AccessMode};
 use notify::{Watcher, RecommendedWatcher, RecursiveMode, Event, EventKind};
+use std::collections::HashMap;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::time::Duration as StdDuration;
+use std::time::{Duration as StdDuration, Instant};
+
+/// Subfolders that processed sources are moved into
+const DONE_DIR: &str = "done";
+const FAILED_DIR: &str = "failed";
+
+/// What the watcher does with each settled file
+enum Action {
+    /// The same operation for every file
+    Operation(BatchOperation),
+    /// The first matching rule's steps
+    Rules(WatchRules),
+}
+
+impl Action {
+    fn is_media_file(&self, path: &Path) -> bool {
+        match self {
+            Action::Operation(_) => is_media_file(path),
+            Action::Rules(rules) => rules.is_media_file(path),
+        }
+    }
+}
+
+/// A file that has appeared but may still be being written
+struct PendingFile {
+    size: u64,
+    mtime: u64,
+    /// When size or mtime last changed
+    changed_at: Instant,
+    /// The writer closed the file (inotify close-write)
+    closed: bool,
+}
 
 pub fn handle_watch_folder(
     config: &AppConfig,
     _runner: &dyn Runner,
     folder: impl AsRef<Path>,
-    operation: BatchOperation,
+    operation: Option<BatchOperation>,
+    options: WatchOptions,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
+    ensure_per_file_output(config, "Watch folder")?;
     let folder = folder.as_ref();
 
     if !folder.exists() {
@@ -26,29 +70,42 @@ pub fn handle_watch_folder(
         anyhow::bail!("Path is not a directory: {}", folder.display());
     }
 
-    eprintln!("Watching folder: {}", folder.display());
-    eprintln!("Operation: {:?}", operation);
+    let ignore = options.ignore.iter()
+        .map(|p| Pattern::new(p).with_context(|| format!("Invalid ignore pattern: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let settle = StdDuration::from_secs_f64(options.settle_seconds.max(0.0));
+    let action = match (&options.rules, operation) {
+        (Some(rules_file), _) => Action::Rules(WatchRules::load(rules_file)?),
+        (None, Some(operation)) => Action::Operation(operation),
+        (None, None) => anyhow::bail!("Watch folder needs an operation or a rules file"),
+    };
+
+    eprintln!("Watching folder: {}{}", folder.display(), if options.recursive { " (recursive)" } else { "" });
+    match &action {
+        Action::Operation(operation) => eprintln!("Operation: {}", operation),
+        Action::Rules(rules) => eprintln!("Rules: {} ({} rules, first match wins)",
+            options.rules.as_ref().map(|p| p.display().to_string()).unwrap_or_default(), rules.rules.len()),
+    }
+    eprintln!("Files are processed once unchanged for {:.1}s", settle.as_secs_f64());
+    if options.move_sources {
+        eprintln!("Sources are moved into {}/ or {}/ afterwards", DONE_DIR, FAILED_DIR);
+    }
     eprintln!("Press Ctrl+C to stop...\n");
 
-    // Create app to execute intents
-    let app = App::new(config.clone());
+    let mut state = WatchState::load(folder)?;
 
-    // Create channel for file events
-    let (tx, rx) = mpsc::channel::<PathBuf>();
+    // Create channel for file events: (path, writer closed the file)
+    let (tx, rx) = mpsc::channel::<(PathBuf, bool)>();
 
     // Create watcher
     let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
         match res {
             Ok(event) => {
-                if let EventKind::Create(_) = event.kind {
+                let closed = matches!(event.kind, EventKind::Access(AccessKind::Close(AccessMode::Write)));
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) || closed {
                     for path in event.paths {
-                        if path.is_file() {
-                            // Check if it's a video/audio file
-                            if is_media_file(&path) {
-                                if let Err(e) = tx.send(path) {
-                                    eprintln!("Error sending file path: {}", e);
-                                }
-                            }
+                        if let Err(e) = tx.send((path, closed)) {
+                            eprintln!("Error sending file path: {}", e);
                         }
                     }
                 }
@@ -57,77 +114,261 @@ pub fn handle_watch_folder(
         }
     })?;
 
-    // Watch the folder
-    watcher.watch(folder, RecursiveMode::NonRecursive)?;
+    let mode = if options.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+    watcher.watch(folder, mode)?;
+
+    // Files that showed up but haven't settled yet
+    let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
 
-    // Track processed files to avoid duplicates
-    let mut processed_files = std::collections::HashSet::new();
+    // Pick up files that arrived while we weren't running
+    for path in scan_folder(folder, options.recursive)? {
+        if is_candidate(folder, &path, &action, &ignore, &state) {
+            track(&mut pending, path, false);
+        }
+    }
+    if !pending.is_empty() {
+        eprintln!("Found {} existing file(s) to process", pending.len());
+    }
 
-    // Process files as they arrive
+    // Process files as they settle
     loop {
-        match rx.recv_timeout(StdDuration::from_secs(1)) {
-            Ok(file_path) => {
-                // Skip if already processed
-                if processed_files.contains(&file_path) {
-                    continue;
+        match rx.recv_timeout(StdDuration::from_millis(250)) {
+            Ok((path, closed)) => {
+                if is_candidate(folder, &path, &action, &ignore, &state) {
+                    track(&mut pending, path, closed);
                 }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                eprintln!("Watcher disconnected");
+                break;
+            }
+        }
+
+        for path in settled_files(&mut pending, settle) {
+            if !is_candidate(folder, &path, &action, &ignore, &state) {
+                continue;
+            }
+            eprintln!("New file detected: {}", path.display());
+            process_file(config, folder, &path, &action, &options, &mut state);
+        }
+    }
 
-                // Wait a bit for file to be fully written
-                std::thread::sleep(StdDuration::from_millis(500));
+    Ok(())
+}
 
-                // Check if file is still accessible
-                if !file_path.exists() {
-                    continue;
-                }
+/// Start or refresh tracking of a file that may still be being written
+fn track(pending: &mut HashMap<PathBuf, PendingFile>, path: PathBuf, closed: bool) {
+    let Ok((size, mtime)) = size_and_mtime(&path) else {
+        return;
+    };
+    let entry = pending.entry(path).or_insert(PendingFile {
+        size,
+        mtime,
+        changed_at: Instant::now(),
+        closed: false,
+    });
+    if entry.size != size || entry.mtime != mtime {
+        entry.size = size;
+        entry.mtime = mtime;
+        entry.changed_at = Instant::now();
+    }
+    entry.closed |= closed;
+}
 
-                eprintln!("New file detected: {}", file_path.display());
+/// Remove and return files whose size and mtime have been stable long enough,
+/// or whose writer has closed them
+fn settled_files(pending: &mut HashMap<PathBuf, PendingFile>, settle: StdDuration) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    pending.retain(|path, file| {
+        let Ok((size, mtime)) = size_and_mtime(path) else {
+            // Deleted or renamed before it settled
+            return false;
+        };
+        if size != file.size || mtime != file.mtime {
+            file.size = size;
+            file.mtime = mtime;
+            file.changed_at = Instant::now();
+            return true;
+        }
+        if file.closed || file.changed_at.elapsed() >= settle {
+            ready.push(path.clone());
+            return false;
+        }
+        true
+    });
+    ready.sort();
+    ready
+}
 
-                // Build intent based on operation
-                let intent = match &operation {
-                    BatchOperation::Convert(format) => {
-                        Intent::Convert {
-                            input: file_path.clone(),
-                            format: *format,
-                            quality: None,
-                            codec: None,
-                        }
-                    }
-                };
+/// True if the path is a media file the watcher should process
+fn is_candidate(folder: &Path, path: &Path, action: &Action, ignore: &[Pattern], state: &WatchState) -> bool {
+    if !path.is_file() || !action.is_media_file(path) {
+        return false;
+    }
 
-                match app.execute(intent) {
-                    Ok(_) => {
-                        eprintln!("Processed: {}\n", file_path.display());
-                        processed_files.insert(file_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Error processing {}: {}\n", file_path.display(), e);
-                    }
+    let relative = path.strip_prefix(folder).unwrap_or(path);
+    // Never re-process filed-away sources
+    if let Some(first) = relative.components().next() {
+        let first = first.as_os_str();
+        if relative.components().count() > 1 && (first == DONE_DIR || first == FAILED_DIR) {
+            return false;
+        }
+    }
+    if relative.as_os_str() == WATCH_STATE_FILE {
+        return false;
+    }
+
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if ignore.iter().any(|p| p.matches(&name) || p.matches_path(relative)) {
+        return false;
+    }
+
+    // Outputs we wrote ourselves, and files already handled
+    if state.is_output(path) {
+        return false;
+    }
+    match size_and_mtime(path) {
+        Ok((size, mtime)) => !state.is_processed(path, size, mtime),
+        Err(_) => false,
+    }
+}
+
+/// List the files currently in the folder
+fn scan_folder(folder: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![folder.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read folder: {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
                 }
+            } else {
+                files.push(path);
             }
-            Err(mpsc::RecvTimeoutError::Timeout) => {
-                // Continue watching
-                continue;
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn process_file(
+    config: &AppConfig,
+    folder: &Path,
+    path: &Path,
+    action: &Action,
+    options: &WatchOptions,
+    state: &mut WatchState,
+) {
+    let Ok((size, mtime)) = size_and_mtime(path) else {
+        return;
+    };
+
+    // Each file is a run of its own, with its own outputs and auto-numbered names
+    let mut config = config.clone();
+    config.outputs = OutputLog::default();
+    let config = &config;
+
+    let mut rule_name = None;
+    let result = match action {
+        Action::Operation(operation) => {
+            let intent = build_intent(path, operation);
+            // Record the file actually written, which auto-numbering may have renamed
+            App::new(config.clone()).execute(intent).map(|_| config.outputs.since(0).pop().into_iter().collect::<Vec<_>>())
+        }
+        Action::Rules(rules) => match rules.first_match(path) {
+            Some((idx, rule)) => {
+                let label = rule.label(idx);
+                eprintln!("Matched rule {}", label);
+                rule_name = Some(rule.name.clone().unwrap_or(label));
+                rules.workflow_for(rule)
+                    .and_then(|spec| run_workflow(config, &spec, Some(path), WorkflowMode::Chain))
             }
-            Err(mpsc::RecvTimeoutError::Disconnected) => {
-                eprintln!("Watcher disconnected");
-                break;
+            None => {
+                // Leave unmatched files alone, but remember them so they aren't re-checked
+                eprintln!("No rule matched: {}\n", path.display());
+                if !config.dry_run {
+                    record(state, path, WatchRecord {
+                        size, mtime, status: JobStatus::Skipped, error: None, output: None,
+                        intermediates: Vec::new(), rule: None, moved_to: None,
+                    });
+                }
+                return;
             }
+        },
+    };
+
+    let (status, error, mut outputs) = match result {
+        Ok(outputs) => {
+            eprintln!("Processed: {}\n", path.display());
+            (JobStatus::Done, None, outputs)
+        }
+        Err(e) if e.downcast_ref::<OutputSkipped>().is_some() => {
+            eprintln!("{}\n", e);
+            (JobStatus::Skipped, None, Vec::new())
         }
+        Err(e) => {
+            eprintln!("Error processing {}: {:#}\n", path.display(), e);
+            (JobStatus::Failed, Some(format!("{:#}", e)), Vec::new())
+        }
+    };
+
+    // Dry runs leave the folder and its state untouched
+    if config.dry_run {
+        return;
     }
 
-    Ok(())
+    let moved_to = match (options.move_sources, status) {
+        (true, JobStatus::Done) => move_source(folder, path, DONE_DIR),
+        (true, JobStatus::Failed) => move_source(folder, path, FAILED_DIR),
+        _ => None,
+    };
+
+    let output = outputs.pop();
+    record(state, path, WatchRecord {
+        size, mtime, status, error, output,
+        intermediates: outputs, rule: rule_name, moved_to,
+    });
 }
 
-fn is_media_file(path: &Path) -> bool {
-    if let Some(ext) = path.extension() {
-        let ext_lower = ext.to_string_lossy().to_lowercase();
-        matches!(
-            ext_lower.as_str(),
-            "mp4" | "avi" | "mov" | "mkv" | "webm" | "flv" | "wmv" | "mpg" | "mpeg" |
-            "mp3" | "wav" | "aac" | "flac" | "ogg" | "m4a" | "wma"
-        )
-    } else {
-        false
+fn record(state: &mut WatchState, path: &Path, record: WatchRecord) {
+    state.record(path, record);
+    if let Err(e) = state.save() {
+        eprintln!("Warning: could not save watch state: {}", e);
     }
 }
 
+/// Move a source into done/ or failed/, keeping its path relative to the folder
+fn move_source(folder: &Path, path: &Path, subdir: &str) -> Option<PathBuf> {
+    let relative = path.strip_prefix(folder).unwrap_or(path);
+    let dest = folder.join(subdir).join(relative);
+    let numbered = AppConfig::new(None, None, false, false, false)
+        .with_naming(None, Some(CollisionPolicy::AutoNumber));
+
+    let moved = dest.parent()
+        .map(fs::create_dir_all)
+        .transpose()
+        .map_err(anyhow::Error::from)
+        .and_then(|_| resolve_collision(&numbered, dest))
+        .and_then(|dest| fs::rename(path, &dest).map(|_| dest).map_err(anyhow::Error::from));
+
+    match moved {
+        Ok(dest) => {
+            eprintln!("Moved source to: {}", dest.display());
+            Some(dest)
+        }
+        Err(e) => {
+            eprintln!("Warning: could not move {} into {}/: {}", path.display(), subdir, e);
+            None
+        }
+    }
+}
+
+fn is_media_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .map(|ext| DEFAULT_MEDIA_EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
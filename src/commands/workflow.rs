@@ -1,32 +1,21 @@
 use crate::app::App;
 use crate::config::AppConfig;
 use crate::ffmpeg::runner::Runner;
-use crate::model::Intent;
+use crate::model::vars::Vars;
+use crate::model::condition::MediaFacts;
+use crate::model::workflow::{StepInput, WorkflowGraph, WorkflowMode, WorkflowSpec, WorkflowStep};
 use crate::util::system::ensure_ffmpeg_exists;
-use anyhow::{anyhow, Result};
-use std::fs;
-use std::path::Path;
-
-/// Workflow step definition
-#[derive(Debug, Clone)]
-struct WorkflowStep {
-    operation: String,
-    input: String,
-    #[allow(dead_code)]
-    output: Option<String>, // Reserved for future use
-    params: serde_json::Value,
-}
-
-/// Workflow configuration
-#[derive(Debug)]
-struct WorkflowConfig {
-    steps: Vec<WorkflowStep>,
-}
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
 
 pub fn handle_workflow(
     config: &AppConfig,
     _runner: &dyn Runner,
     config_file: impl AsRef<Path>,
+    vars: &Vars,
 ) -> Result<()> {
     ensure_ffmpeg_exists()?;
     let config_file = config_file.as_ref();
@@ -35,216 +24,211 @@ pub fn handle_workflow(
         anyhow::bail!("Workflow file not found: {}", config_file.display());
     }
 
-    // Read and parse workflow file
-    let content = fs::read_to_string(config_file)?;
-    let workflow = parse_workflow(&content)?;
+    let spec = WorkflowSpec::load_with_vars(config_file, vars)?;
 
-    eprintln!("Processing workflow: {} steps", workflow.steps.len());
+    eprintln!("Processing workflow: {} steps", spec.steps.len());
+    run_workflow(config, &spec, None, WorkflowMode::Chain)?;
 
-    // Create app to execute intents
-    let app = App::new(config.clone());
+    eprintln!("\n Workflow completed successfully!");
+    Ok(())
+}
 
-    for (idx, step) in workflow.steps.iter().enumerate() {
-        eprintln!("\n[{}/{}] Executing: {}", idx + 1, workflow.steps.len(), step.operation);
+/// Run a workflow's steps and return every output they wrote, in step order.
+///
+/// `input` (from the command line) takes precedence over the file's `input:`.
+/// In chain mode a step without an input gets the previous step's output; in
+/// fan-out mode it gets the original input. Steps can also use earlier
+/// outputs by id (`${steps.trim.output}`). Steps whose inputs are ready run
+/// at the same time. `default_mode` applies when the file doesn't set `mode:`.
+pub fn run_workflow(
+    config: &AppConfig,
+    spec: &WorkflowSpec,
+    input: Option<&Path>,
+    default_mode: WorkflowMode,
+) -> Result<Vec<PathBuf>> {
+    let mode = spec.mode.unwrap_or(default_mode);
+    let original: Option<PathBuf> = input.map(Path::to_path_buf).or_else(|| spec.input.clone());
+    if let Some(original) = &original {
+        if !original.exists() && !config.dry_run {
+            anyhow::bail!("Input file not found: {}", original.display());
+        }
+    }
 
-        let intent = build_intent_from_step(step)?;
-        
-        match app.execute(intent) {
-            Ok(_) => {
-                eprintln!("Step {} completed", idx + 1);
-            }
-            Err(e) => {
-                anyhow::bail!("Step {} failed: {}", idx + 1, e);
-            }
+    let graph = WorkflowGraph::build(spec, mode)?;
+    for (idx, step_input) in graph.inputs.iter().enumerate() {
+        if *step_input == StepInput::Original && original.is_none() {
+            anyhow::bail!(
+                "Step {} ({}) needs an input: set 'input:' on the step or the workflow",
+                idx + 1, spec.steps[idx].name()
+            );
         }
     }
 
-    eprintln!("\n Workflow completed successfully!");
-    Ok(())
-}
+    // --out names the final result; every step nothing depends on is a final result
+    let sinks = graph.sinks();
+    if config.out.is_some() && sinks.len() > 1 {
+        anyhow::bail!("--out would receive the output of {} steps; set 'output:' on each final step instead", sinks.len());
+    }
 
-fn parse_workflow(content: &str) -> Result<WorkflowConfig> {
-    // Simple YAML-like parser for workflow files
-    // Expected format:
-    // steps:
-    //   - operation: convert
-    //     input: video.mp4
-    //     output: video.gif
-    //     params:
-    //       format: gif
-    
-    let mut steps = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-    
-    // Find steps section
-    while i < lines.len() {
-        let line = lines[i].trim();
-        if line.starts_with("steps:") {
-            i += 1;
-            break;
-        }
-        i += 1;
+    if config.dry_run {
+        eprintln!("{}", graph.describe(spec));
     }
-    
-    // Parse steps
-    while i < lines.len() {
-        let line = lines[i].trim();
-        
-        if line.starts_with("- operation:") {
-            let operation = line.strip_prefix("- operation:").unwrap_or("").trim().to_string();
-            i += 1;
-            
-            let mut input = String::new();
-            let mut output = None;
-            let mut params = serde_json::json!({});
-            
-            while i < lines.len() {
-                let step_line = lines[i].trim();
-                if step_line.is_empty() || (!step_line.starts_with(" ") && !step_line.starts_with("-")) {
+
+    // Dry runs go one step at a time so the printed commands stay readable
+    let parallel = if config.dry_run {
+        1
+    } else {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    };
+
+    let total = spec.steps.len();
+    let mut results: Vec<Option<StepResult>> = vec![None; total];
+    let (tx, rx) = mpsc::channel::<(usize, Result<StepResult>)>();
+
+    thread::scope(|scope| -> Result<()> {
+        let mut started = vec![false; total];
+        let mut running = 0;
+        let mut failure: Option<anyhow::Error> = None;
+
+        loop {
+            // Start every step whose dependencies are done, unless something failed
+            let ready: Vec<usize> = (0..total)
+                .filter(|&idx| !started[idx] && graph.deps[idx].iter().all(|&d| results[d].is_some()))
+                .collect();
+            for idx in ready {
+                if failure.is_some() || running >= parallel {
                     break;
                 }
-                
-                if step_line.starts_with("input:") {
-                    input = step_line.strip_prefix("input:").unwrap_or("").trim().to_string();
-                } else if step_line.starts_with("output:") {
-                    output = Some(step_line.strip_prefix("output:").unwrap_or("").trim().to_string());
-                } else if step_line.starts_with("params:") {
-                    i += 1;
-                    while i < lines.len() {
-                        let param_line = lines[i].trim();
-                        if param_line.is_empty() || !param_line.starts_with(" ") {
-                            i -= 1;
-                            break;
-                        }
-                        if param_line.contains(":") {
-                            let parts: Vec<&str> = param_line.splitn(2, ':').collect();
-                            if parts.len() == 2 {
-                                let key = parts[0].trim();
-                                let value = parts[1].trim().trim_matches('"');
-                                params[key] = serde_json::Value::String(value.to_string());
-                            }
-                        }
-                        i += 1;
+                started[idx] = true;
+                let prepared = prepare_step(config, spec, &graph, idx, original.as_deref(), &results, &sinks);
+                let (step, step_config) = match prepared {
+                    Ok(prepared) => prepared,
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                };
+
+                let parent_log = config.outputs.clone();
+                let tx = tx.clone();
+                running += 1;
+                eprintln!("\n[{}/{}] Executing: {}", idx + 1, total, step.name());
+                scope.spawn(move || {
+                    let result = run_step(step, step_config, idx);
+                    if let Ok(result) = &result {
+                        result.outputs.iter().for_each(|o| parent_log.append(o.clone()));
                     }
+                    let _ = tx.send((idx, result));
+                });
+            }
+
+            if running == 0 {
+                break;
+            }
+            let (idx, result) = rx.recv().map_err(|e| anyhow!("Workflow step thread stopped: {e}"))?;
+            running -= 1;
+            match result {
+                Ok(result) => results[idx] = Some(result),
+                Err(e) => {
+                    failure.get_or_insert(e);
                 }
-                i += 1;
             }
-            
-            steps.push(WorkflowStep {
-                operation,
-                input,
-                output,
-                params,
-            });
-        } else {
-            i += 1;
         }
+        failure.map_or(Ok(()), Err)
+    })?;
+
+    Ok(results.into_iter().flatten().flat_map(|r| r.outputs).collect())
+}
+
+/// What a finished step left for the steps after it
+#[derive(Debug, Clone)]
+struct StepResult {
+    outputs: Vec<PathBuf>,
+    /// The input, passed on unchanged because `when` was false
+    skipped: Option<PathBuf>,
+}
+
+impl StepResult {
+    /// The file later steps read: the last output, or the input of a skipped step
+    fn file(&self) -> Option<&PathBuf> {
+        self.outputs.last().or(self.skipped.as_ref())
     }
-    
-    if steps.is_empty() {
-        anyhow::bail!("No steps found in workflow file");
-    }
-    
-    Ok(WorkflowConfig { steps })
 }
 
-fn build_intent_from_step(step: &WorkflowStep) -> Result<Intent> {
-    use crate::model::*;
-    use std::path::PathBuf;
-    
-    let input = PathBuf::from(&step.input);
-    
-    match step.operation.as_str() {
-        "convert" => {
-            let format_str = step.params.get("format")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("convert operation requires 'format' parameter"))?;
-            
-            let format = match format_str {
-                "gif" => ConvertFormat::Gif,
-                "mp4" => ConvertFormat::Mp4,
-                "webm" => ConvertFormat::Webm,
-                "mp3" => ConvertFormat::Mp3,
-                "wav" => ConvertFormat::Wav,
-                _ => anyhow::bail!("Unsupported format: {}", format_str),
-            };
-            
-            Ok(Intent::Convert {
-                input,
-                format,
-                quality: None,
-                codec: None,
-            })
-        }
-        "trim" => {
-            let start_str = step.params.get("start")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("trim operation requires 'start' parameter"))?;
-            let end_str = step.params.get("end")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("trim operation requires 'end' parameter"))?;
-            
-            Ok(Intent::Trim {
-                input,
-                start: Time::parse(start_str)?,
-                end: Time::parse(end_str)?,
-            })
+/// Check the step's `when` condition, then run it
+fn run_step(step: WorkflowStep, step_config: AppConfig, idx: usize) -> Result<StepResult> {
+    let name = step.name();
+    let mut intent = step.intent;
+    if let (Some(condition), Some(input)) = (&step.when, intent.input_mut()) {
+        if input.exists() {
+            if !condition.evaluate(&mut MediaFacts::new(input)) {
+                eprintln!("Step {} ({}) skipped: '{}' is false for {}", idx + 1, name, condition, input.display());
+                return Ok(StepResult { outputs: Vec::new(), skipped: Some(input.clone()) });
+            }
+        } else {
+            // Dry runs plan inputs that don't exist yet
+            eprintln!("Step {} ({}) runs only if '{}' holds for {}", idx + 1, name, condition, input.display());
         }
-        "resize" => {
-            let target_str = step.params.get("target")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("resize operation requires 'target' parameter"))?;
-            
-            Ok(Intent::Resize {
-                input,
-                target: ResizeTarget::parse(target_str)?,
-            })
+    }
+
+    App::new(step_config.clone()).execute(intent)
+        .map_err(|e| e.context(format!("Step {} ({}) failed", idx + 1, name)))?;
+    eprintln!("Step {} ({}) completed", idx + 1, name);
+    Ok(StepResult { outputs: step_config.outputs.since(0), skipped: None })
+}
+
+/// Step `idx` and the config to run it with, its inputs filled in from
+/// finished steps
+fn prepare_step(
+    config: &AppConfig,
+    spec: &WorkflowSpec,
+    graph: &WorkflowGraph,
+    idx: usize,
+    original: Option<&Path>,
+    results: &[Option<StepResult>],
+    sinks: &[usize],
+) -> Result<(WorkflowStep, AppConfig)> {
+    let step = &spec.steps[idx];
+    let label = format!("Step {} ({})", idx + 1, step.name());
+
+    // Results of earlier steps by id. Copies of a foreach step share one id;
+    // copies skipped by `when` drop out rather than passing their item on.
+    let mut named: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (earlier, result) in spec.steps.iter().zip(results) {
+        let Some(result) = result else { continue };
+        let file = if earlier.item.is_some() { result.outputs.last() } else { result.file() };
+        if let (Some(id), Some(file)) = (&earlier.id, file) {
+            named.entry(id.clone()).or_default().push(file.clone());
         }
-        "compress" => {
-            let target_str = step.params.get("target")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow!("compress operation requires 'target' parameter"))?;
-            
-            let two_pass = step.params.get("two_pass")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            
-            // Try to parse as quality preset first
-            let target_lower = target_str.trim().to_lowercase();
-            let target = if target_lower.ends_with("-quality") {
-                let quality_str = target_lower.strip_suffix("-quality").unwrap().trim();
-                CompressTarget::Quality(QualityPreset::parse(quality_str)?)
-            } else {
-                // Try to parse as bitrate first
-                let target_trimmed = target_str.trim();
-                let target_lower_trimmed = target_trimmed.to_lowercase();
-                let looks_like_bitrate = target_lower_trimmed.ends_with("bps")
-                    || target_lower_trimmed.ends_with("kbps")
-                    || target_lower_trimmed.ends_with("mbps")
-                    || target_lower_trimmed.ends_with("gbps")
-                    || (target_lower_trimmed.ends_with('k') && !target_lower_trimmed.ends_with("mk"))
-                    || (target_lower_trimmed.ends_with('m') && !target_lower_trimmed.ends_with("mb") && !target_lower_trimmed.ends_with("gb"))
-                    || (target_lower_trimmed.ends_with('g') && !target_lower_trimmed.ends_with("gb"));
-                
-                if looks_like_bitrate {
-                    if let Ok(bitrate) = TargetBitrate::parse(target_str) {
-                        CompressTarget::Bitrate(bitrate)
-                    } else {
-                        CompressTarget::Size(TargetSize::parse(target_str)?)
-                    }
-                } else {
-                    CompressTarget::Size(TargetSize::parse(target_str)?)
-                }
-            };
-            
-            Ok(Intent::Compress {
-                input,
-                target,
-                two_pass,
-            })
+    }
+    for id in step.references() {
+        if !named.contains_key(&id) {
+            anyhow::bail!("{label} uses the output of step '{id}', but it wrote no file");
         }
-        op => anyhow::bail!("Unsupported operation: {}", op),
     }
-}
+    let mut step = step.resolve(&named).with_context(|| label.clone())?;
+
+    let step_input = match graph.inputs[idx] {
+        StepInput::Own => None,
+        StepInput::Original => original.map(Path::to_path_buf),
+        StepInput::Previous(prev) => Some(
+            results[prev].as_ref().and_then(StepResult::file).cloned().ok_or_else(|| {
+                anyhow!("Step {} ({}) wrote no file for the next step to use", prev + 1, spec.steps[prev].name())
+            })?,
+        ),
+    };
+    if let (Some(slot), Some(step_input)) = (step.intent.input_mut(), step_input) {
+        *slot = step_input;
+    }
 
+    let mut step_config = config.clone();
+    step_config.outputs = config.outputs.child();
+    step_config.step_label = Some(step.name());
+    if step.output.is_some() {
+        step_config.out = step.output.take();
+    } else if !sinks.contains(&idx) {
+        // Intermediate results keep their default names
+        step_config.out = None;
+    }
+    Ok((step, step_config))
+}
@@ -1,4 +1,85 @@
-use std::path::PathBuf;
+use crate::model::CollisionPolicy;
+use crate::ffmpeg::step::Step;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Output paths resolved by handlers, in order. Clones share the same log,
+/// so workflows can see what each step wrote.
+#[derive(Debug, Clone, Default)]
+pub struct OutputLog {
+    paths: Arc<Mutex<Vec<PathBuf>>>,
+    /// Outputs that already existed when they were recorded; shared with child logs
+    replaced: Arc<Mutex<Vec<PathBuf>>>,
+    /// Names `auto-number` handed out in this run; shared with child logs.
+    /// Workflow steps run at the same time, before either has written its
+    /// file, so the file system alone would give both the same name.
+    claimed: Arc<Mutex<BTreeSet<PathBuf>>>,
+}
+
+impl OutputLog {
+    /// Record an output a handler is about to write
+    pub fn record(&self, path: PathBuf) {
+        if path.exists() {
+            if let Ok(mut replaced) = self.replaced.lock() {
+                replaced.push(path.clone());
+            }
+        }
+        self.append(path);
+    }
+
+    /// Add an output already recorded in a child log
+    pub fn append(&self, path: PathBuf) {
+        if let Ok(mut log) = self.paths.lock() {
+            log.push(path);
+        }
+    }
+
+    /// An empty log for a nested run that still notes replaced files here
+    pub fn child(&self) -> Self {
+        Self { paths: Arc::default(), replaced: self.replaced.clone(), claimed: self.claimed.clone() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.lock().map(|log| log.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Outputs recorded after the log had `start` entries
+    pub fn since(&self, start: usize) -> Vec<PathBuf> {
+        self.paths.lock().map(|log| log.iter().skip(start).cloned().collect()).unwrap_or_default()
+    }
+
+    /// Claim a name for an output of this run; false if it was claimed already
+    pub fn claim(&self, path: &Path) -> bool {
+        self.claimed.lock().map(|mut claimed| claimed.insert(path.to_path_buf())).unwrap_or(false)
+    }
+
+    /// Whether `path` existed before it was recorded as an output
+    pub fn was_replaced(&self, path: &Path) -> bool {
+        self.replaced.lock().map(|r| r.iter().any(|p| p == path)).unwrap_or(false)
+    }
+}
+
+/// Commands the runner executed (or would have, in a dry run), in order.
+/// Clones share the same log.
+#[derive(Debug, Clone, Default)]
+pub struct StepLog(Arc<Mutex<Vec<Step>>>);
+
+impl StepLog {
+    pub fn record(&self, step: &Step) {
+        if let Ok(mut log) = self.0.lock() {
+            log.push(step.clone());
+        }
+    }
+
+    pub fn all(&self) -> Vec<Step> {
+        self.0.lock().map(|log| log.clone()).unwrap_or_default()
+    }
+}
 
 /// Application configuration holding global flags
 #[derive(Debug, Clone)]
@@ -8,6 +89,16 @@ pub struct AppConfig {
     pub explain: bool,
     pub dry_run: bool,
     pub overwrite: bool,
+    /// Output filename template (e.g. "{stem}-{op}-{width}x{height}.{ext}")
+    pub name_template: Option<String>,
+    /// What to do when an output file already exists
+    pub collision: CollisionPolicy,
+    /// Outputs written so far
+    pub outputs: OutputLog,
+    /// Commands run so far
+    pub steps: StepLog,
+    /// The workflow step being run, to tell the output of parallel steps apart
+    pub step_label: Option<String>,
 }
 
 impl AppConfig {
@@ -24,7 +115,22 @@ impl AppConfig {
             explain,
             dry_run,
             overwrite,
+            name_template: None,
+            collision: if overwrite { CollisionPolicy::Overwrite } else { CollisionPolicy::Fail },
+            outputs: OutputLog::default(),
+            steps: StepLog::default(),
+            step_label: None,
         }
     }
-}
 
+    /// Set the output name template and collision policy.
+    /// An explicit `overwrite` policy also enables ffmpeg's `-y`.
+    pub fn with_naming(mut self, name_template: Option<String>, collision: Option<CollisionPolicy>) -> Self {
+        self.name_template = name_template;
+        if let Some(policy) = collision {
+            self.collision = policy;
+            self.overwrite = policy == CollisionPolicy::Overwrite;
+        }
+        self
+    }
+}
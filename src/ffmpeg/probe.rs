@@ -1,4 +1,8 @@
-use crate::util::system::ensure_ffprobe_exists;
+use crate::ffmpeg::recipes::{loudnorm_filter, silencedetect_filter};
+use crate::model::LoudnessTarget;
+use crate::model::channels::ChannelLayout;
+use crate::model::chapter::Chapters;
+use crate::util::system::{ensure_ffmpeg_exists, ensure_ffprobe_exists};
 use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
@@ -220,3 +224,299 @@ pub fn get_video_info(input: &Path) -> Result<VideoInfo> {
     })
 }
 
+
+/// Codec of the first stream matching an ffprobe selector (e.g. "a:0"), if any
+pub fn stream_codec(input: &Path, selector: &str) -> Option<String> {
+    stream_entry(input, selector, "codec_name")
+}
+
+/// Height of the first video stream in pixels, if known
+pub fn video_height(input: &Path) -> Option<u32> {
+    stream_entry(input, "v:0", "height")?.parse().ok()
+}
+
+/// Sample rate of the first audio stream in Hz, if known
+pub fn sample_rate(input: &Path) -> Option<u32> {
+    stream_entry(input, "a:0", "sample_rate")?.parse().ok()
+}
+
+/// Channel count of the first audio stream, if known
+pub fn channels(input: &Path) -> Option<u32> {
+    stream_entry(input, "a:0", "channels")?.parse().ok()
+}
+
+/// Channel layout of the first audio stream, guessed from the channel count
+/// when the file doesn't name one
+pub fn channel_layout(input: &Path) -> Result<ChannelLayout> {
+    ensure_ffprobe_exists()?;
+    let name = stream_entry(input, "a:0", "channel_layout");
+    let count = channels(input);
+    if name.is_none() && count.is_none() {
+        anyhow::bail!("Couldn't read the audio channels of {}", input.display());
+    }
+    ChannelLayout::reported(name.as_deref(), count.map(|c| c as usize))
+        .map_err(|e| anyhow::anyhow!("{}: {e}", input.display()))
+}
+
+/// How an audio stream is encoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioEncoding {
+    /// ffmpeg codec name, e.g. "pcm_s24le" or "flac"
+    pub codec: String,
+    /// Sample format, e.g. "s16" or "s32"
+    pub sample_fmt: Option<String>,
+}
+
+/// Codec and sample format of the first audio stream, if any
+pub fn audio_encoding(input: &Path) -> Option<AudioEncoding> {
+    Some(AudioEncoding {
+        codec: stream_codec(input, "a:0")?,
+        sample_fmt: stream_entry(input, "a:0", "sample_fmt"),
+    })
+}
+
+/// Pixel format of the first video stream (e.g. "rgb48be" for a 16-bit PNG), if any
+pub fn pix_fmt(input: &Path) -> Option<String> {
+    stream_entry(input, "v:0", "pix_fmt")
+}
+
+/// A subtitle stream of a media file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleStream {
+    /// ffmpeg codec name, e.g. "subrip", "mov_text" or "hdmv_pgs_subtitle"
+    pub codec: String,
+    /// Language tag, e.g. "eng"
+    pub language: Option<String>,
+}
+
+/// Subtitle streams in order (`0:s:0`, `0:s:1`, ...), or None when ffprobe can't read the file
+pub fn subtitle_streams(input: &Path) -> Option<Vec<SubtitleStream>> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "s", "-show_entries", "stream=codec_name:stream_tags=language", "-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let streams = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.trim().splitn(2, ',');
+            SubtitleStream {
+                codec: fields.next().unwrap_or_default().to_string(),
+                language: fields.next().map(|l| l.to_string()).filter(|l| !l.is_empty() && l != "und"),
+            }
+        })
+        .collect();
+    Some(streams)
+}
+
+fn stream_entry(input: &Path, selector: &str, entry: &str) -> Option<String> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", selector, "-show_entries", &format!("stream={entry}"), "-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .ok()?;
+    let value = String::from_utf8_lossy(&out.stdout).lines().next()?.trim().to_string();
+    (out.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// Loudness measurements from ffmpeg's loudnorm filter (EBU R128)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+    /// Loudness range in LU
+    pub range: f64,
+    /// Gating threshold in LUFS
+    pub threshold: f64,
+    /// Gain left over after the measured target, fed back to loudnorm's second pass
+    pub offset: f64,
+}
+
+impl std::fmt::Display for Loudness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} LUFS, true peak {:.1} dBTP, range {:.1} LU", self.integrated, self.true_peak, self.range)
+    }
+}
+
+/// Chapters of a media file (empty if it has none)
+pub fn chapters(input: &Path) -> Result<Chapters> {
+    ensure_ffprobe_exists()?;
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-show_chapters", "-of", "json"])
+        .arg(input)
+        .output()
+        .context("ffprobe failed")?;
+
+    if !out.status.success() {
+        anyhow::bail!("ffprobe could not read chapters of {}", input.display());
+    }
+    Chapters::from_ffprobe(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Times in seconds where the picture changes by more than `threshold`
+/// (0 to 1), the way `detect-scenes` finds them (decodes the whole file)
+pub fn scene_changes(input: &Path, threshold: f64) -> Result<Vec<f64>> {
+    ensure_ffmpeg_exists()?;
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input)
+        .args(["-an", "-vf", &format!("select='gt(scene,{threshold})',showinfo"), "-f", "null", "-"])
+        .output()
+        .context("ffmpeg scene detection failed")?;
+
+    if !out.status.success() {
+        anyhow::bail!("ffmpeg could not detect scenes in {}", input.display());
+    }
+    Ok(parse_showinfo_times(&String::from_utf8_lossy(&out.stderr)))
+}
+
+/// Silences quieter than `noise` dB and at least `min_silence` seconds long,
+/// as (start, end) in seconds; a silence running to the end has no end
+pub fn silences(input: &Path, noise: f64, min_silence: f64) -> Result<Vec<(f64, Option<f64>)>> {
+    ensure_ffmpeg_exists()?;
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input)
+        .args(["-vn", "-af", &silencedetect_filter(noise, min_silence), "-f", "null", "-"])
+        .output()
+        .context("ffmpeg silence detection failed")?;
+
+    if !out.status.success() {
+        anyhow::bail!("ffmpeg could not detect silence in {}", input.display());
+    }
+    Ok(parse_silencedetect(&String::from_utf8_lossy(&out.stderr)))
+}
+
+/// Pair up the silence_start and silence_end lines silencedetect logs
+pub fn parse_silencedetect(log: &str) -> Vec<(f64, Option<f64>)> {
+    let value = |line: &str, key: &str| -> Option<f64> {
+        line.split(key).nth(1)?.split_whitespace().next()?.parse().ok()
+    };
+    let mut silences = Vec::new();
+    let mut start = None;
+    for line in log.lines().filter(|line| line.contains("silencedetect")) {
+        if let Some(t) = value(line, "silence_start:") {
+            start = Some(t);
+        } else if let (Some(t), Some(s)) = (value(line, "silence_end:"), start.take()) {
+            silences.push((s, Some(t)));
+        }
+    }
+    if let Some(s) = start {
+        silences.push((s, None));
+    }
+    silences
+}
+
+/// A packet of a media file, as `split` weighs them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    /// Presentation time in seconds
+    pub time: f64,
+    pub size: u64,
+    pub video: bool,
+    /// Whether decoding can start here (a keyframe, for video)
+    pub key: bool,
+}
+
+/// Every packet of every stream, in time order (reads the file, but doesn't decode it)
+pub fn packets(input: &Path) -> Result<Vec<Packet>> {
+    ensure_ffprobe_exists()?;
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "packet=codec_type,pts_time,size,flags", "-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .context("ffprobe failed")?;
+
+    if !out.status.success() {
+        anyhow::bail!("ffprobe could not read the packets of {}", input.display());
+    }
+    let mut packets: Vec<Packet> = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().split(',').collect();
+            let [codec_type, time, size, flags, ..] = fields.as_slice() else { return None };
+            Some(Packet {
+                time: time.parse().ok()?,
+                size: size.parse().ok()?,
+                video: *codec_type == "video",
+                key: flags.contains('K'),
+            })
+        })
+        .collect();
+    packets.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(packets)
+}
+
+/// Keyframe times of the first video stream, in seconds
+pub fn keyframes(input: &Path) -> Result<Vec<f64>> {
+    Ok(packets(input)?.iter().filter(|p| p.video && p.key).map(|p| p.time).collect())
+}
+
+/// The pts_time of every frame showinfo logged
+pub fn parse_showinfo_times(log: &str) -> Vec<f64> {
+    log.lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| line.split("pts_time:").nth(1))
+        .filter_map(|rest| rest.split_whitespace().next()?.parse().ok())
+        .collect()
+}
+
+/// Measure the loudness of a file's audio (decodes the whole file)
+pub fn measure_loudness(input: &Path) -> Result<Loudness> {
+    run_loudnorm(input, "loudnorm=print_format=json")
+}
+
+/// Measure the loudness of a file's audio against a target, as the first of
+/// loudnorm's two passes
+pub fn measure_loudness_for(input: &Path, target: &LoudnessTarget) -> Result<Loudness> {
+    run_loudnorm(input, &loudnorm_filter(target, None))
+}
+
+fn run_loudnorm(input: &Path, filter: &str) -> Result<Loudness> {
+    parse_loudnorm_output(&audio_filter_log(input, filter)?)
+}
+
+/// Run an audio analysis filter over the whole file and return what it logs
+pub fn audio_filter_log(input: &Path, filter: &str) -> Result<String> {
+    ensure_ffmpeg_exists()?;
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input)
+        .args(["-vn", "-af", filter, "-f", "null", "-"])
+        .output()
+        .context("ffmpeg audio analysis failed")?;
+
+    if !out.status.success() {
+        anyhow::bail!("ffmpeg could not analyze the audio of {}", input.display());
+    }
+    Ok(String::from_utf8_lossy(&out.stderr).to_string())
+}
+
+/// Parse the JSON block loudnorm prints at the end of its log
+pub fn parse_loudnorm_output(log: &str) -> Result<Loudness> {
+    let start = log.rfind('{').ok_or_else(|| anyhow::anyhow!("No loudnorm measurements in ffmpeg output"))?;
+    let end = log[start..].find('}').map(|i| start + i + 1)
+        .ok_or_else(|| anyhow::anyhow!("Incomplete loudnorm measurements in ffmpeg output"))?;
+    let json: serde_json::Value = serde_json::from_str(&log[start..end])
+        .context("failed to parse loudnorm JSON")?;
+
+    let field = |name: &str| -> Result<f64> {
+        json.get(name)
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .ok_or_else(|| anyhow::anyhow!("loudnorm output has no {name}"))
+    };
+    Ok(Loudness {
+        integrated: field("input_i")?,
+        true_peak: field("input_tp")?,
+        range: field("input_lra")?,
+        threshold: field("input_thresh")?,
+        offset: field("target_offset").unwrap_or(0.0),
+    })
+}
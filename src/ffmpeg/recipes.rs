@@ -1,4 +1,6 @@
 use crate::ffmpeg::step::Step;
+use crate::ffmpeg::probe::{AudioEncoding, Loudness};
+use crate::model::channels::{ChannelLayout, ChannelPick};
 use crate::model::types::*;
 use anyhow::{anyhow, bail, Context, Result};
 use std::path::{Path, PathBuf};
@@ -48,6 +50,9 @@ fn get_audio_codec(input: &Path, output: &Path) -> &'static str {
     } else if (input_ext == "mp4" || input_ext == "avi" || input_ext == "mov") && output_ext == "webm" {
         // MP4/AVI/MOV (AAC) audio cannot be copied into WebM - must re-encode to Opus (preferred) or Vorbis
         "libopus"
+    } else if (input_ext == "webm" || input_ext == "mkv" || input_ext == "ogg") && output_ext == "mov" {
+        // Opus/Vorbis don't belong in QuickTime
+        "aac"
     } else {
         "copy"
     }
@@ -63,47 +68,230 @@ pub fn gif_steps(
     width: u32,
     quality: Option<crate::model::types::QualityPreset>,
 ) -> Vec<Step> {
-    
-    // Adjust fps and width based on quality if specified
-    let (final_fps, final_width) = if let Some(q) = quality {
-        match q {
-            QualityPreset::Low => (10, 320),
-            QualityPreset::Medium => (fps, width),
-            QualityPreset::High => (20, 640),
-            QualityPreset::Ultra => (30, 800),
+    let settings = AnimationSettings::for_quality(quality, fps, width);
+    animated_image_steps(input, output, palette_path, crate::model::ConvertFormat::Gif, &settings)
+}
+
+/// Frame rate, size and colors of an animated GIF, WebP, APNG or AVIF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationSettings {
+    pub fps: u32,
+    pub width: u32,
+    /// Palette size for GIF and APNG (16-256)
+    pub colors: u32,
+    /// Encoder quality for WebP and AVIF (0-100)
+    pub quality: u32,
+}
+
+impl AnimationSettings {
+    /// Settings for a quality preset; medium (or none) keeps the given fps and width
+    pub fn for_quality(quality: Option<crate::model::types::QualityPreset>, fps: u32, width: u32) -> Self {
+        let (fps, width, colors, quality) = match quality {
+            Some(QualityPreset::Low) => (10, 320, 128, 50),
+            None | Some(QualityPreset::Medium) => (fps, width, 256, 75),
+            Some(QualityPreset::High) => (20, 640, 256, 85),
+            Some(QualityPreset::Ultra) => (30, 800, 256, 95),
+        };
+        AnimationSettings { fps, width, colors, quality }
+    }
+
+    /// The next attempt for a file that came out `ratio` times too big, or
+    /// None when everything is already at its floor. Size goes roughly with
+    /// fps × width², so frame rate is lowered first, then width, then colors
+    /// (encoder quality for WebP and AVIF).
+    pub fn shrink(&self, format: crate::model::ConvertFormat, ratio: f64) -> Option<Self> {
+        const MIN_FPS: u32 = 8;
+        const MIN_WIDTH: u32 = 160;
+        let mut next = *self;
+        // Aim a little under the limit so the next attempt is likely the last
+        let mut factor = 0.9 / ratio.max(1.0);
+
+        if factor < 1.0 && next.fps > MIN_FPS {
+            next.fps = ((next.fps as f64 * factor).floor() as u32).clamp(MIN_FPS, next.fps - 1);
+            factor *= self.fps as f64 / next.fps as f64;
         }
-    } else {
-        (fps, width)
-    };
-    
-    vec![
-        // Pass 1: Generate palette
-        Step::new(
-            "ffmpeg",
-            vec![
-                "-y".to_string(),
-                "-i".to_string(),
-                input.to_string_lossy().to_string(),
-                "-vf".to_string(),
-                format!("fps={final_fps},scale={final_width}:-1:flags=lanczos,palettegen"),
-                palette_path.to_string_lossy().to_string(),
-            ],
-        ),
-        // Pass 2: Render with palette
-        Step::new(
-            "ffmpeg",
-            vec![
+        if factor < 1.0 && next.width > MIN_WIDTH {
+            let width = (next.width as f64 * factor.sqrt()).floor() as u32 / 2 * 2;
+            next.width = width.clamp(MIN_WIDTH, next.width - 2);
+            factor *= (self.width as f64 / next.width as f64).powi(2);
+        }
+        if factor < 1.0 {
+            match format {
+                crate::model::ConvertFormat::Webp | crate::model::ConvertFormat::Avif => {
+                    next.quality = next.quality.saturating_sub(15).max(10);
+                }
+                _ => next.colors = (next.colors / 2).max(16),
+            }
+        }
+        (next != *self).then_some(next)
+    }
+
+    /// Human-readable summary, e.g. "12 fps, 400px wide, 128 colors"
+    pub fn describe(&self, format: crate::model::ConvertFormat) -> String {
+        match format {
+            crate::model::ConvertFormat::Webp | crate::model::ConvertFormat::Avif => {
+                format!("{} fps, {}px wide, quality {}", self.fps, self.width, self.quality)
+            }
+            _ => format!("{} fps, {}px wide, {} colors", self.fps, self.width, self.colors),
+        }
+    }
+}
+
+/// Build steps for an animated image. GIF and APNG go through a palette
+/// (2-pass like `gif_steps`); WebP and AVIF are encoded directly at the
+/// settings' quality. Any other format is treated as GIF.
+pub fn animated_image_steps(
+    input: &Path,
+    output: &Path,
+    palette_path: &Path,
+    format: crate::model::ConvertFormat,
+    settings: &AnimationSettings,
+) -> Vec<Step> {
+    use crate::model::ConvertFormat;
+    let AnimationSettings { fps, width, colors, quality } = *settings;
+    let input = input.to_string_lossy().to_string();
+    let output = output.to_string_lossy().to_string();
+
+    match format {
+        ConvertFormat::Webp => vec![Step::new("ffmpeg", vec![
+            "-y".to_string(), "-i".to_string(), input,
+            "-vf".to_string(), format!("fps={fps},scale={width}:-1:flags=lanczos"),
+            "-an".to_string(),
+            "-c:v".to_string(), "libwebp".to_string(),
+            "-lossless".to_string(), "0".to_string(),
+            "-quality".to_string(), quality.to_string(),
+            "-compression_level".to_string(), "6".to_string(),
+            "-loop".to_string(), "0".to_string(),
+            output,
+        ])],
+        ConvertFormat::Avif => {
+            // AV1 CRF 0-63: quality 95 -> 22, 75 -> 30, 50 -> 41
+            let crf = 20 + (100 - quality.min(100)) * 43 / 100;
+            vec![Step::new("ffmpeg", vec![
+                "-y".to_string(), "-i".to_string(), input,
+                // 4:2:0 needs an even height
+                "-vf".to_string(), format!("fps={fps},scale={width}:-2:flags=lanczos"),
+                "-an".to_string(),
+                "-c:v".to_string(), "libaom-av1".to_string(),
+                "-crf".to_string(), crf.to_string(),
+                "-b:v".to_string(), "0".to_string(),
+                "-cpu-used".to_string(), "6".to_string(),
+                "-row-mt".to_string(), "1".to_string(),
+                "-pix_fmt".to_string(), "yuv420p".to_string(),
+                "-loop".to_string(), "0".to_string(),
+                "-f".to_string(), "avif".to_string(),
+                output,
+            ])]
+        }
+        _ => {
+            let palettegen = if colors < 256 { format!("palettegen=max_colors={colors}") } else { "palettegen".to_string() };
+            let mut render = vec![
                 "-y".to_string(),
                 "-i".to_string(),
-                input.to_string_lossy().to_string(),
+                input.clone(),
                 "-i".to_string(),
                 palette_path.to_string_lossy().to_string(),
                 "-lavfi".to_string(),
-                format!("fps={final_fps},scale={final_width}:-1:flags=lanczos[x];[x][1:v]paletteuse=dither=bayer"),
-                output.to_string_lossy().to_string(),
-            ],
-        ),
-    ]
+                format!("fps={fps},scale={width}:-1:flags=lanczos[x];[x][1:v]paletteuse=dither=bayer"),
+            ];
+            if format == ConvertFormat::Apng {
+                // Loop forever, as GIFs do
+                render.extend(["-plays", "0", "-f", "apng"].map(String::from));
+            }
+            render.push(output);
+            vec![
+                // Pass 1: Generate palette
+                Step::new(
+                    "ffmpeg",
+                    vec![
+                        "-y".to_string(),
+                        "-i".to_string(),
+                        input,
+                        "-vf".to_string(),
+                        format!("fps={fps},scale={width}:-1:flags=lanczos,{palettegen}"),
+                        palette_path.to_string_lossy().to_string(),
+                    ],
+                ),
+                // Pass 2: Render with palette
+                Step::new("ffmpeg", render),
+            ]
+        }
+    }
+}
+
+/// Encoder arguments for a video codec: `-c:v` plus the codec's own quality
+/// settings (CRF, profile or lossless options)
+pub fn video_codec_args(
+    vcodec: crate::model::types::VideoCodec,
+    quality: Option<crate::model::types::QualityPreset>,
+) -> Vec<String> {
+    use crate::model::types::{DnxhrProfile, ProResProfile, QualityPreset, VideoCodec};
+
+    let mut args = vec!["-c:v".to_string(), vcodec.ffmpeg_name().to_string()];
+
+    // Add quality settings based on codec
+    match vcodec {
+        VideoCodec::H264 | VideoCodec::H265 => {
+            // Use CRF for H.264/H.265
+            if let Some(quality_preset) = quality {
+                args.push("-crf".to_string());
+                args.push(quality_preset.crf_value().to_string());
+                args.push("-preset".to_string());
+                args.push("medium".to_string());
+            }
+        }
+        VideoCodec::Vp9 => {
+            // Use CRF for VP9 (range 0-63, lower is better)
+            if let Some(quality_preset) = quality {
+                let crf = match quality_preset {
+                    QualityPreset::Low => 50,
+                    QualityPreset::Medium => 40,
+                    QualityPreset::High => 30,
+                    QualityPreset::Ultra => 20,
+                };
+                args.push("-crf".to_string());
+                args.push(crf.to_string());
+            }
+        }
+        VideoCodec::Av1 | VideoCodec::Av1Aom => {
+            // AV1 CRF (0-63) runs higher than x264's for the same quality;
+            // SVT-AV1 presets (0-13) and libaom cpu-used (0-8) trade speed for efficiency
+            let (crf, preset, cpu_used) = match quality.unwrap_or(QualityPreset::Medium) {
+                QualityPreset::Low => (40, 10, 8),
+                QualityPreset::Medium => (32, 8, 6),
+                QualityPreset::High => (26, 6, 4),
+                QualityPreset::Ultra => (20, 4, 3),
+            };
+            args.push("-crf".to_string());
+            args.push(crf.to_string());
+            if vcodec == VideoCodec::Av1 {
+                args.push("-preset".to_string());
+                args.push(preset.to_string());
+            } else {
+                // Constant-quality mode needs the bitrate cap turned off
+                args.extend(["-b:v", "0", "-cpu-used", &cpu_used.to_string(), "-row-mt", "1"].map(String::from));
+            }
+            args.push("-pix_fmt".to_string());
+            args.push("yuv420p".to_string());
+        }
+        VideoCodec::ProRes(profile) => {
+            // Quality is the profile: no CRF
+            let profile = profile.unwrap_or_else(|| ProResProfile::for_quality(quality.unwrap_or(QualityPreset::Medium)));
+            args.extend(["-profile:v", &profile.number().to_string(), "-vendor", "apl0", "-pix_fmt", profile.pix_fmt()].map(String::from));
+        }
+        VideoCodec::Dnxhr(profile) => {
+            let profile = profile.unwrap_or_else(|| DnxhrProfile::for_quality(quality.unwrap_or(QualityPreset::Medium)));
+            args.extend(["-profile:v", profile.ffmpeg_name(), "-pix_fmt", profile.pix_fmt()].map(String::from));
+        }
+        VideoCodec::Ffv1 => {
+            // Lossless: quality presets don't apply. Version 3, intra-only, with slice CRCs for archival
+            args.extend(["-level", "3", "-g", "1", "-slices", "16", "-slicecrc", "1"].map(String::from));
+        }
+        VideoCodec::Copy => {
+            // No quality settings for copy
+        }
+    }
+    args
 }
 
 /// Build steps for generic format conversion with quality and codec options
@@ -114,7 +302,7 @@ pub fn convert_generic_steps(
     quality: Option<crate::model::types::QualityPreset>,
     codec: Option<crate::model::types::VideoCodec>,
 ) -> Vec<Step> {
-    use crate::model::types::{QualityPreset, VideoCodec};
+    use crate::model::types::VideoCodec;
     
     let mut args = vec![
         if overwrite { "-y" } else { "-n" }.to_string(),
@@ -124,35 +312,7 @@ pub fn convert_generic_steps(
     
     // Add video codec if specified
     if let Some(vcodec) = codec {
-        args.push("-c:v".to_string());
-        args.push(vcodec.ffmpeg_name().to_string());
-        
-        // Add quality settings based on codec
-        if let Some(quality_preset) = quality {
-            match vcodec {
-                VideoCodec::H264 | VideoCodec::H265 => {
-                    // Use CRF for H.264/H.265
-                    args.push("-crf".to_string());
-                    args.push(quality_preset.crf_value().to_string());
-                    args.push("-preset".to_string());
-                    args.push("medium".to_string());
-                }
-                VideoCodec::Vp9 => {
-                    // Use CRF for VP9 (range 0-63, lower is better)
-                    let crf = match quality_preset {
-                        QualityPreset::Low => 50,
-                        QualityPreset::Medium => 40,
-                        QualityPreset::High => 30,
-                        QualityPreset::Ultra => 20,
-                    };
-                    args.push("-crf".to_string());
-                    args.push(crf.to_string());
-                }
-                VideoCodec::Copy => {
-                    // No quality settings for copy
-                }
-            }
-        }
+        args.extend(video_codec_args(vcodec, quality));
     } else if let Some(quality_preset) = quality {
         // If no codec specified but quality is, use H.264 with CRF
         args.push("-c:v".to_string());
@@ -163,8 +323,13 @@ pub fn convert_generic_steps(
         args.push("medium".to_string());
     }
     
-    // Use appropriate audio codec (may need re-encoding for WebM/WMV to MP4)
-    let audio_codec = get_audio_codec(input, output);
+    // Intermediates carry uncompressed (ProRes/DNxHR) or lossless (FFV1) audio;
+    // otherwise use the container's codec (may need re-encoding for WebM/WMV to MP4)
+    let audio_codec = match codec {
+        Some(VideoCodec::ProRes(_) | VideoCodec::Dnxhr(_)) => "pcm_s16le",
+        Some(VideoCodec::Ffv1) => "flac",
+        _ => get_audio_codec(input, output),
+    };
     args.push("-c:a".to_string());
     args.push(audio_codec.to_string());
     
@@ -173,14 +338,15 @@ pub fn convert_generic_steps(
     vec![Step::new("ffmpeg", args)]
 }
 
-/// Build steps for compressing video to target size
+/// Build steps for compressing video to target size; with `two_pass_log`,
+/// in two passes sharing the pass log at that path prefix
 pub fn compress_steps(
     input: &Path,
     output: &Path,
     target_bytes: u64,
     duration_sec: f64,
     overwrite: bool,
-    two_pass: bool,
+    two_pass_log: Option<&Path>,
 ) -> Vec<Step> {
     // Calculate bitrates
     let total_bps = (target_bytes as f64 * 8.0 / duration_sec).max(50_000.0);
@@ -189,7 +355,7 @@ pub fn compress_steps(
     let v_kbps = (video_bps / 1000.0).floor() as u64;
     let a_kbps = (audio_bps / 1000.0).floor() as u64;
 
-    if two_pass {
+    if let Some(passlog) = two_pass_log {
         let null_sink = if cfg!(windows) { "NUL" } else { "/dev/null" };
 
         vec![
@@ -206,6 +372,8 @@ pub fn compress_steps(
                     format!("{v_kbps}k"),
                     "-pass".to_string(),
                     "1".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-an".to_string(),
                     "-f".to_string(),
                     "mp4".to_string(),
@@ -225,6 +393,8 @@ pub fn compress_steps(
                     format!("{v_kbps}k"),
                     "-pass".to_string(),
                     "2".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-c:a".to_string(),
                     "aac".to_string(),
                     "-b:a".to_string(),
@@ -259,13 +429,14 @@ pub fn compress_steps(
     }
 }
 
-/// Build steps for compressing video to target bitrate
+/// Build steps for compressing video to target bitrate, in two passes with
+/// `two_pass_log` as for [`compress_steps`]
 pub fn compress_bitrate_steps(
     input: &Path,
     output: &Path,
     target_bitrate_bps: u64,
     overwrite: bool,
-    two_pass: bool,
+    two_pass_log: Option<&Path>,
 ) -> Vec<Step> {
     // Convert to kbps for FFmpeg (which uses kbps for -b:v)
     let v_kbps = (target_bitrate_bps / 1000).max(50); // Minimum 50 kbps
@@ -273,7 +444,7 @@ pub fn compress_bitrate_steps(
     let audio_bps = (target_bitrate_bps as f64 * 0.08).clamp(96_000.0, 160_000.0);
     let a_kbps = (audio_bps / 1000.0).floor() as u64;
 
-    if two_pass {
+    if let Some(passlog) = two_pass_log {
         let null_sink = if cfg!(windows) { "NUL" } else { "/dev/null" };
 
         vec![
@@ -290,6 +461,8 @@ pub fn compress_bitrate_steps(
                     format!("{v_kbps}k"),
                     "-pass".to_string(),
                     "1".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-an".to_string(),
                     "-f".to_string(),
                     "mp4".to_string(),
@@ -309,6 +482,8 @@ pub fn compress_bitrate_steps(
                     format!("{v_kbps}k"),
                     "-pass".to_string(),
                     "2".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-c:a".to_string(),
                     "aac".to_string(),
                     "-b:a".to_string(),
@@ -343,17 +518,18 @@ pub fn compress_bitrate_steps(
     }
 }
 
-/// Build steps for compressing video with quality preset (CRF encoding)
+/// Build steps for compressing video with quality preset (CRF encoding), or
+/// in two passes with `two_pass_log` as for [`compress_steps`]
 pub fn compress_quality_steps(
     input: &Path,
     output: &Path,
     quality: crate::model::types::QualityPreset,
     overwrite: bool,
-    two_pass: bool,
+    two_pass_log: Option<&Path>,
 ) -> Vec<Step> {
     let crf = quality.crf_value();
     
-    if two_pass {
+    if let Some(passlog) = two_pass_log {
         // For two-pass with quality preset, we need to estimate bitrate from CRF
         // This is approximate - two-pass with CRF is less common
         // We'll use a reasonable bitrate estimate based on quality
@@ -381,6 +557,8 @@ pub fn compress_quality_steps(
                     estimated_bitrate.to_string(),
                     "-pass".to_string(),
                     "1".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-an".to_string(),
                     "-f".to_string(),
                     "mp4".to_string(),
@@ -402,6 +580,8 @@ pub fn compress_quality_steps(
                     "medium".to_string(),
                     "-pass".to_string(),
                     "2".to_string(),
+                    "-passlogfile".to_string(),
+                    passlog.to_string_lossy().to_string(),
                     "-c:a".to_string(),
                     audio_codec.to_string(),
                     "-b:a".to_string(),
@@ -462,25 +642,21 @@ pub fn trim_steps(input: &Path, output: &Path, start: &Time, end: &Time, overwri
 pub fn extract_audio_steps(
     input: &Path,
     output: &Path,
-    format: &str,
+    format: crate::model::AudioFormat,
+    quality: Option<crate::model::types::QualityPreset>,
     overwrite: bool,
 ) -> Vec<Step> {
-    let (codec_args, container_ext) = match format {
-        "mp3" => (vec!["-c:a", "libmp3lame", "-q:a", "2"], "mp3"),
-        "wav" => (vec!["-c:a", "pcm_s16le"], "wav"),
-        _ => (vec!["-c:a", "aac", "-b:a", "192k"], "m4a"),
-    };
-
     let mut args = vec![
         if overwrite { "-y" } else { "-n" }.to_string(),
         "-i".to_string(),
         input.to_string_lossy().to_string(),
         "-vn".to_string(),
+        // Carry tags over; the muxer writes them as ID3 or Vorbis comments
+        "-map_metadata".to_string(),
+        "0".to_string(),
     ];
-    args.extend(codec_args.iter().map(|s| s.to_string()));
-    
-    let output_path = output.with_extension(container_ext);
-    args.push(output_path.to_string_lossy().to_string());
+    args.extend(format.codec_args(quality));
+    args.push(output.to_string_lossy().to_string());
 
     vec![Step::new("ffmpeg", args)]
 }
@@ -980,30 +1156,79 @@ pub fn filter_steps(
     )])
 }
 
-/// Build steps for audio normalization
+/// The loudnorm filter for a target: the measuring pass without
+/// measurements, or the linear second pass with them
+pub fn loudnorm_filter(target: &LoudnessTarget, measured: Option<&Loudness>) -> String {
+    let Some(measured) = measured else {
+        return format!(
+            "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+            target.integrated(), target.true_peak(), target.range()
+        );
+    };
+    // Linear mode only holds when the target range covers the measured one;
+    // otherwise loudnorm quietly falls back to compressing the dynamics
+    let range = target.range().max(measured.range).clamp(1.0, 50.0);
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=summary",
+        target.integrated(), target.true_peak(), range,
+        measured.integrated, measured.true_peak, measured.range, measured.threshold, measured.offset
+    )
+}
+
+/// Build steps for audio normalization: the second loudnorm pass when the
+/// first pass' measurements are known, a single dynamic pass otherwise
 pub fn normalize_steps(
     input: &Path,
     output: &Path,
+    target: &LoudnessTarget,
+    measured: Option<&Loudness>,
+    sample_rate: Option<u32>,
+    audio_codec: &[String],
     overwrite: bool,
 ) -> Vec<Step> {
-    // Use loudnorm filter for audio normalization
-    // This normalizes audio to a target loudness level
     let video_codec = get_video_codec(input, output);
-    vec![Step::new(
-        "ffmpeg",
-        vec![
-            if overwrite { "-y" } else { "-n" }.to_string(),
-            "-i".to_string(),
-            input.to_string_lossy().to_string(),
-            "-af".to_string(),
-            "loudnorm=I=-16:TP=-1.5:LRA=11".to_string(), // Standard broadcast loudness
-            "-c:v".to_string(),
-            video_codec.to_string(),
-            "-c:a".to_string(),
-            "aac".to_string(),
-            output.to_string_lossy().to_string(),
-        ],
-    )]
+    let filter = match measured {
+        Some(_) => loudnorm_filter(target, measured),
+        None => format!("loudnorm=I={}:TP={}:LRA={}", target.integrated(), target.true_peak(), target.range()),
+    };
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-af".to_string(),
+        filter,
+        // loudnorm works at 192kHz; go back to the input's rate
+        "-ar".to_string(),
+        sample_rate.unwrap_or(48000).to_string(),
+        "-c:v".to_string(),
+        video_codec.to_string(),
+    ];
+    args.extend(audio_codec.iter().cloned());
+    args.push(output.to_string_lossy().to_string());
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Encoder arguments for writing audio into the container `ext` without
+/// losing the input's bit depth: PCM keeps its codec (a 24-bit wav stays
+/// 24-bit) and FLAC its sample format. Other audio containers get their
+/// usual encoder; video containers no arguments.
+pub fn keep_audio_args(ext: &str, input: Option<&AudioEncoding>) -> Vec<String> {
+    use crate::model::AudioFormat;
+
+    let Ok(format) = AudioFormat::parse(ext) else { return Vec::new() };
+    let args: Vec<&str> = match (format, input) {
+        (AudioFormat::Auto, _) => return Vec::new(),
+        (AudioFormat::Wav | AudioFormat::Aiff, Some(input)) if AudioFormat::for_codec(&input.codec) == Some(format) => {
+            vec!["-c:a", &input.codec]
+        }
+        (AudioFormat::Flac, Some(input)) => match input.sample_fmt.as_deref() {
+            Some("s32" | "s32p") => vec!["-c:a", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24"],
+            Some("s16" | "s16p") => vec!["-c:a", "flac", "-sample_fmt", "s16"],
+            _ => vec!["-c:a", "flac"],
+        },
+        (format, _) => return format.codec_args(None),
+    };
+    args.into_iter().map(String::from).collect()
 }
 
 /// Build steps for adjusting audio volume
@@ -1087,6 +1312,128 @@ pub fn sync_audio_steps(
 }
 
 
+/// `c2` at full gain, `0.707*c2` otherwise
+fn pan_terms(gains: impl Iterator<Item = (usize, f64)>) -> String {
+    gains.filter(|(_, gain)| *gain > 0.0)
+        .map(|(i, gain)| if gain == 1.0 { format!("c{i}") } else { format!("{gain}*c{i}") })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// A stereo downmix of `layout`. `<` has pan scale each side's gains to add
+/// up to 1, so the mix can't clip.
+pub fn downmix_filter(layout: &ChannelLayout) -> String {
+    let gains = layout.stereo_gains();
+    format!(
+        "pan=stereo|c0<{}|c1<{}",
+        pan_terms(gains.iter().map(|g| g.0).enumerate()),
+        pan_terms(gains.iter().map(|g| g.1).enumerate())
+    )
+}
+
+/// Mono from `layout`: one side as it is, or both sides of the downmix
+pub fn mono_filter(layout: &ChannelLayout, pick: Option<ChannelPick>) -> Result<String> {
+    match pick {
+        Some(pick) => {
+            let index = layout.index_of(pick.channel())
+                .ok_or_else(|| anyhow!("A {layout} file has no {pick} channel"))?;
+            Ok(format!("pan=mono|c0=c{index}"))
+        }
+        None => {
+            let gains = layout.stereo_gains();
+            Ok(format!("pan=mono|c0<{}", pan_terms(gains.iter().map(|g| g.0 + g.1).enumerate())))
+        }
+    }
+}
+
+/// Every left channel of `layout` swapped with its right
+pub fn swap_filter(layout: &ChannelLayout) -> String {
+    let mapping: Vec<String> = layout.channels.iter()
+        .enumerate()
+        .map(|(i, channel)| {
+            let from = layout.index_of(ChannelLayout::mirrored(channel)).unwrap_or(i);
+            format!("c{i}=c{from}")
+        })
+        .collect();
+    format!("pan={}|{}", layout.name, mapping.join("|"))
+}
+
+/// Build steps for remixing a file's channels through a pan filter; video is
+/// copied where the container allows, and audio containers keep the input's
+/// encoding (see [`keep_audio_args`])
+pub fn remix_channels_steps(
+    input: &Path,
+    output: &Path,
+    filter: &str,
+    encoding: Option<&AudioEncoding>,
+    overwrite: bool,
+) -> Vec<Step> {
+    let video_codec = get_video_codec(input, output);
+    let ext = output.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-af".to_string(),
+        filter.to_string(),
+        "-c:v".to_string(),
+        video_codec.to_string(),
+    ];
+    args.extend(keep_audio_args(&ext, encoding));
+    args.push(output.to_string_lossy().to_string());
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Build steps for writing each channel of `layout` to its own mono file,
+/// `outputs` in channel order
+pub fn split_channels_steps(
+    input: &Path,
+    layout: &ChannelLayout,
+    outputs: &[PathBuf],
+    overwrite: bool,
+) -> Vec<Step> {
+    let labels: String = layout.channels.iter().map(|c| format!("[{c}]")).collect();
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-filter_complex".to_string(),
+        format!("[0:a:0]channelsplit=channel_layout={}{labels}", layout.name),
+    ];
+    for (channel, output) in layout.channels.iter().zip(outputs) {
+        args.extend([
+            "-map".to_string(),
+            format!("[{channel}]"),
+            output.to_string_lossy().to_string(),
+        ]);
+    }
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Build steps for joining mono files into one file of `layout`, the first
+/// file becoming its first channel
+pub fn combine_channels_steps(
+    inputs: &[PathBuf],
+    layout: &ChannelLayout,
+    output: &Path,
+    overwrite: bool,
+) -> Vec<Step> {
+    let mut args = vec![if overwrite { "-y" } else { "-n" }.to_string()];
+    for input in inputs {
+        args.extend(["-i".to_string(), input.to_string_lossy().to_string()]);
+    }
+    let sources: String = (0..inputs.len()).map(|i| format!("[{i}:a]")).collect();
+    let map: Vec<String> = layout.channels.iter().enumerate().map(|(i, c)| format!("{i}.0-{c}")).collect();
+    args.extend([
+        "-filter_complex".to_string(),
+        format!("{sources}join=inputs={}:channel_layout={}:map={}[a]", inputs.len(), layout.name, map.join("|")),
+        "-map".to_string(),
+        "[a]".to_string(),
+        output.to_string_lossy().to_string(),
+    ]);
+    vec![Step::new("ffmpeg", args)]
+}
+
 /// Build steps for mixing two audio files
 pub fn mix_audio_steps(
     audio1: &Path,
@@ -1146,15 +1493,10 @@ pub fn extract_audio_range_steps(
     output: &Path,
     start: &crate::model::types::Time,
     end: &crate::model::types::Time,
-    format: &str,
+    format: crate::model::AudioFormat,
+    quality: Option<crate::model::types::QualityPreset>,
     overwrite: bool,
 ) -> Vec<Step> {
-    let (codec_args, container_ext) = match format {
-        "mp3" => (vec!["-c:a", "libmp3lame", "-q:a", "2"], "mp3"),
-        "wav" => (vec!["-c:a", "pcm_s16le"], "wav"),
-        _ => (vec!["-c:a", "aac", "-b:a", "192k"], "m4a"),
-    };
-
     let start_time = start.to_ffmpeg();
     let duration_secs = end.to_seconds() as f64 - start.to_seconds() as f64;
 
@@ -1167,11 +1509,11 @@ pub fn extract_audio_range_steps(
         "-t".to_string(),
         format!("{}", duration_secs),
         "-vn".to_string(),
+        "-map_metadata".to_string(),
+        "0".to_string(),
     ];
-    args.extend(codec_args.iter().map(|s| s.to_string()));
-    
-    let output_path = output.with_extension(container_ext);
-    args.push(output_path.to_string_lossy().to_string());
+    args.extend(format.codec_args(quality));
+    args.push(output.to_string_lossy().to_string());
 
     vec![Step::new("ffmpeg", args)]
 }
@@ -1514,96 +1856,48 @@ pub fn crossfade_steps(
 }
 
 /// Build steps for splitting video into segments
-/// Returns multiple steps, one for each segment
+/// Returns multiple steps, one for each planned part
 pub fn split_steps(
     input: &Path,
-    output_dir: &Path,
-    mode: &crate::model::types::SplitMode,
+    parts: &[crate::model::split::SplitPart],
     overwrite: bool,
-) -> Result<Vec<Step>> {
-    use crate::ffmpeg::probe;
-    use crate::model::types::SplitMode;
-    
-    let duration = probe::duration_seconds(input)?;
-    let mut steps = Vec::new();
+) -> Vec<Step> {
+    parts.iter()
+        .map(|part| segment_step(input, &part.output, part.start, part.end - part.start, overwrite))
+        .collect()
+}
+
+/// Whether `split` stream-copies the video into this output, so parts have
+/// to start on keyframes
+pub fn split_copies_video(input: &Path, output: &Path) -> bool {
+    get_video_codec(input, output) == "copy"
+}
+
+/// One segment of `split_steps`, copied when the codecs allow it
+fn segment_step(input: &Path, output: &Path, start: f64, length: f64, overwrite: bool) -> Step {
+    let video_codec = get_video_codec(input, output);
+    let mut step_args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-ss".to_string(),
+        format!("{:.3}", start),
+        "-t".to_string(),
+        format!("{:.3}", length),
+    ];
     
-    match mode {
-        SplitMode::Every(interval) => {
-            let interval_secs = interval.to_seconds();
-            let mut start = 0.0;
-            let mut segment_num = 1;
-            
-            while start < duration {
-                let end = (start + interval_secs).min(duration);
-                let output = output_dir.join(format!("segment_{:03}.mp4", segment_num));
-                
-                let video_codec = get_video_codec(input, &output);
-                let mut step_args = vec![
-                    if overwrite { "-y" } else { "-n" }.to_string(),
-                    "-i".to_string(),
-                    input.to_string_lossy().to_string(),
-                    "-ss".to_string(),
-                    format!("{:.3}", start),
-                    "-t".to_string(),
-                    format!("{:.3}", end - start),
-                ];
-                
-                if video_codec == "copy" {
-                    step_args.push("-c".to_string());
-                    step_args.push("copy".to_string());
-                } else {
-                    step_args.push("-c:v".to_string());
-                    step_args.push(video_codec.to_string());
-                    step_args.push("-c:a".to_string());
-                    step_args.push("copy".to_string());
-                }
-                
-                step_args.push(output.to_string_lossy().to_string());
-                steps.push(Step::new("ffmpeg", step_args));
-                
-                start = end;
-                segment_num += 1;
-            }
-        }
-        SplitMode::IntoParts(parts) => {
-            let segment_duration = duration / (*parts as f64);
-            for i in 0..*parts {
-                let start = i as f64 * segment_duration;
-                let end = if i == parts - 1 {
-                    duration
-                } else {
-                    (i + 1) as f64 * segment_duration
-                };
-                
-                let output = output_dir.join(format!("part_{:03}_of_{:03}.mp4", i + 1, parts));
-                let video_codec = get_video_codec(input, &output);
-                let mut step_args = vec![
-                    if overwrite { "-y" } else { "-n" }.to_string(),
-                    "-i".to_string(),
-                    input.to_string_lossy().to_string(),
-                    "-ss".to_string(),
-                    format!("{:.3}", start),
-                    "-t".to_string(),
-                    format!("{:.3}", end - start),
-                ];
-                
-                if video_codec == "copy" {
-                    step_args.push("-c".to_string());
-                    step_args.push("copy".to_string());
-                } else {
-                    step_args.push("-c:v".to_string());
-                    step_args.push(video_codec.to_string());
-                    step_args.push("-c:a".to_string());
-                    step_args.push("copy".to_string());
-                }
-                
-                step_args.push(output.to_string_lossy().to_string());
-                steps.push(Step::new("ffmpeg", step_args));
-            }
-        }
+    if video_codec == "copy" {
+        step_args.push("-c".to_string());
+        step_args.push("copy".to_string());
+    } else {
+        step_args.push("-c:v".to_string());
+        step_args.push(video_codec.to_string());
+        step_args.push("-c:a".to_string());
+        step_args.push("copy".to_string());
     }
     
-    Ok(steps)
+    step_args.push(output.to_string_lossy().to_string());
+    Step::new("ffmpeg", step_args)
 }
 
 /// Build steps for extracting frames at intervals
@@ -1647,36 +1941,270 @@ pub fn extract_frames_steps(
     Ok(steps)
 }
 
-/// Build steps for burning subtitles into video
+/// Where `sequence_to_video_steps` reads frames from
+#[derive(Debug, Clone, Copy)]
+pub enum SequenceInput<'a> {
+    /// Numbered frames without gaps, read by the image2 demuxer
+    Pattern { path: &'a Path, start: u64 },
+    /// A concat list naming every frame, each shown for one frame's duration
+    List(&'a Path),
+}
+
+/// Build steps for turning an image sequence into a video at `fps`
+pub fn sequence_to_video_steps(
+    input: SequenceInput,
+    output: &Path,
+    fps: u32,
+    codec: crate::model::types::VideoCodec,
+    quality: Option<crate::model::types::QualityPreset>,
+    pix_fmt: Option<&str>,
+    overwrite: bool,
+) -> Vec<Step> {
+    let mut args = vec![if overwrite { "-y" } else { "-n" }.to_string()];
+    match input {
+        SequenceInput::Pattern { path, start } => args.extend([
+            "-framerate".to_string(),
+            fps.to_string(),
+            "-start_number".to_string(),
+            start.to_string(),
+            "-i".to_string(),
+            path.to_string_lossy().to_string(),
+        ]),
+        SequenceInput::List(list) => args.extend([
+            "-f".to_string(),
+            "concat".to_string(),
+            "-safe".to_string(),
+            "0".to_string(),
+            "-i".to_string(),
+            list.to_string_lossy().to_string(),
+        ]),
+    }
+    if codec != crate::model::types::VideoCodec::Ffv1 {
+        // Chroma subsampling needs even dimensions; camera stills often aren't
+        args.push("-vf".to_string());
+        args.push("scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+    }
+    args.extend(video_codec_args(codec, quality));
+    if let Some(pix_fmt) = pix_fmt {
+        args.push("-pix_fmt".to_string());
+        args.push(pix_fmt.to_string());
+    }
+    args.push("-r".to_string());
+    args.push(fps.to_string());
+    args.push(output.to_string_lossy().to_string());
+
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Build steps for writing every frame of a video as an image, named by
+/// `pattern` (e.g. "dir/shot_%06d.png")
+pub fn video_to_sequence_steps(
+    input: &Path,
+    pattern: &Path,
+    format: crate::model::sequence::FrameFormat,
+    high_bit_depth: bool,
+    start: u32,
+    overwrite: bool,
+) -> Vec<Step> {
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-map".to_string(),
+        "0:v:0".to_string(),
+        // One image per decoded frame: no duplicates or drops
+        "-vsync".to_string(),
+        "passthrough".to_string(),
+        "-start_number".to_string(),
+        start.to_string(),
+    ];
+    args.extend(format.codec_args(high_bit_depth));
+    args.push(pattern.to_string_lossy().to_string());
+
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Where `burn_subtitle_steps` takes the subtitles from
+#[derive(Debug, Clone, Copy)]
+pub enum BurnSource<'a> {
+    /// A subtitle file
+    File(&'a Path),
+    /// A text subtitle stream of the input, counted from 0
+    Text(usize),
+    /// An image subtitle stream of the input (DVD, Blu-ray), counted from 0
+    Bitmap(usize),
+}
+
+/// Build steps for burning subtitles into video. ASS files keep their own
+/// styles unless `force_style` overrides them; image subtitles are overlaid
+/// as they are.
 pub fn burn_subtitle_steps(
     input: &Path,
-    subtitle: &Path,
+    source: BurnSource,
+    force_style: Option<&str>,
     output: &Path,
     overwrite: bool,
 ) -> Vec<Step> {
-    // Use subtitles filter for SRT files, or ass filter for ASS files
-    // For simplicity, we'll use subtitles filter which works with SRT
-    // and can be extended to support ASS later
-    
-    let subtitle_path = subtitle.to_string_lossy();
-    let filter = if subtitle_path.ends_with(".ass") || subtitle_path.ends_with(".ASS") {
-        format!("ass={}", subtitle_path)
-    } else {
-        format!("subtitles={}", subtitle_path)
+    // Two levels: the option value escapes \ ' and :, then the filtergraph
+    // quotes it, closing and reopening the quote around each '
+    let quote = |path: &Path| {
+        let value = path.to_string_lossy().replace('\\', "\\\\").replace('\'', "\\'").replace(':', "\\:");
+        format!("'{}'", value.replace('\'', "'\\''"))
     };
-    
+    let style = force_style.map(|style| format!(":force_style='{style}'")).unwrap_or_default();
+
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+    ];
+    match source {
+        BurnSource::Bitmap(index) => {
+            args.extend([
+                "-filter_complex".to_string(),
+                format!("[0:v][0:s:{index}]overlay[v]"),
+                "-map".to_string(),
+                "[v]".to_string(),
+                "-map".to_string(),
+                "0:a?".to_string(),
+            ]);
+        }
+        BurnSource::File(subtitle) => {
+            let is_ass = subtitle.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ass"));
+            let filter = if is_ass && force_style.is_none() {
+                format!("ass={}", quote(subtitle))
+            } else {
+                format!("subtitles={}{style}", quote(subtitle))
+            };
+            args.extend(["-vf".to_string(), filter, "-sn".to_string()]);
+        }
+        BurnSource::Text(index) => {
+            let filter = format!("subtitles={}:si={index}{style}", quote(input));
+            args.extend(["-vf".to_string(), filter, "-sn".to_string()]);
+        }
+    }
+    args.extend([
+        "-c:a".to_string(),
+        "copy".to_string(),
+        "-c:v".to_string(),
+        "libx264".to_string(),
+        output.to_string_lossy().to_string(),
+    ]);
+
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// A subtitle file muxed as a track by `add_subtitles_steps`
+#[derive(Debug, Clone)]
+pub struct SubtitleTrack<'a> {
+    pub path: &'a Path,
+    /// Subtitle codec in the output, e.g. "mov_text"
+    pub codec: &'static str,
+    /// ISO 639-2 code in the container's form, e.g. "eng"
+    pub language: Option<&'a str>,
+    /// Track name players show, e.g. "English"
+    pub title: Option<&'a str>,
+}
+
+/// Build steps for muxing subtitle files into a video as selectable tracks.
+/// Video and audio are copied; the input's own `existing` subtitle tracks are
+/// kept with `existing_codec` ("copy" unless the container changes).
+pub fn add_subtitles_steps(
+    input: &Path,
+    tracks: &[SubtitleTrack],
+    existing: usize,
+    existing_codec: &str,
+    output: &Path,
+    overwrite: bool,
+) -> Vec<Step> {
+    let mut args = vec![
+        if overwrite { "-y" } else { "-n" }.to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+    ];
+    for track in tracks {
+        args.push("-i".to_string());
+        args.push(track.path.to_string_lossy().to_string());
+    }
+    args.extend(["-map", "0:v?", "-map", "0:a?"].map(String::from));
+    if existing > 0 {
+        args.extend(["-map".to_string(), "0:s".to_string()]);
+    }
+    for i in 0..tracks.len() {
+        args.push("-map".to_string());
+        args.push(format!("{}:0", i + 1));
+    }
+    args.extend(["-c:v", "copy", "-c:a", "copy"].map(String::from));
+    if existing > 0 {
+        args.push("-c:s".to_string());
+        args.push(existing_codec.to_string());
+    }
+    for (i, track) in tracks.iter().enumerate() {
+        let n = existing + i;
+        args.push(format!("-c:s:{n}"));
+        args.push(track.codec.to_string());
+        if let Some(language) = track.language {
+            args.push(format!("-metadata:s:s:{n}"));
+            args.push(format!("language={language}"));
+        }
+        if let Some(title) = track.title {
+            args.push(format!("-metadata:s:s:{n}"));
+            args.push(format!("title={title}"));
+        }
+    }
+    args.push(output.to_string_lossy().to_string());
+
+    vec![Step::new("ffmpeg", args)]
+}
+
+/// Build steps for writing one embedded subtitle stream (`0:s:stream`) to a file
+pub fn extract_subtitle_steps(
+    input: &Path,
+    stream: usize,
+    output: &Path,
+    format: crate::model::subtitle::SubtitleFormat,
+    overwrite: bool,
+) -> Vec<Step> {
     vec![Step::new(
         "ffmpeg",
         vec![
             if overwrite { "-y" } else { "-n" }.to_string(),
             "-i".to_string(),
             input.to_string_lossy().to_string(),
-            "-vf".to_string(),
-            filter,
-            "-c:a".to_string(),
+            "-map".to_string(),
+            format!("0:s:{stream}"),
+            "-c:s".to_string(),
+            format.encoder().to_string(),
+            output.to_string_lossy().to_string(),
+        ],
+    )]
+}
+
+/// Build steps for replacing a file's chapters with those of an ffmetadata file
+pub fn add_chapters_steps(
+    input: &Path,
+    metadata: &Path,
+    output: &Path,
+    overwrite: bool,
+) -> Vec<Step> {
+    vec![Step::new(
+        "ffmpeg",
+        vec![
+            if overwrite { "-y" } else { "-n" }.to_string(),
+            "-i".to_string(),
+            input.to_string_lossy().to_string(),
+            "-f".to_string(),
+            "ffmetadata".to_string(),
+            "-i".to_string(),
+            metadata.to_string_lossy().to_string(),
+            "-map".to_string(),
+            "0".to_string(),
+            "-map_metadata".to_string(),
+            "0".to_string(),
+            "-map_chapters".to_string(),
+            "1".to_string(),
+            "-c".to_string(),
             "copy".to_string(),
-            "-c:v".to_string(),
-            "libx264".to_string(),
             output.to_string_lossy().to_string(),
         ],
     )]
@@ -2030,6 +2558,9 @@ pub fn concat_steps(
     vec![Step::new("ffmpeg", args)]
 }
 
+/// How much the picture has to change (0 to 1) to count as a new scene
+pub const SCENE_THRESHOLD: f64 = SplitMode::DEFAULT_THRESHOLD;
+
 /// Build steps for detecting scene changes in video
 /// Outputs scene change timestamps to a text file
 pub fn detect_scenes_steps(
@@ -2047,7 +2578,7 @@ pub fn detect_scenes_steps(
             "-i".to_string(),
             input.to_string_lossy().to_string(),
             "-vf".to_string(),
-            "select='gt(scene,0.3)',showinfo".to_string(),
+            format!("select='gt(scene,{SCENE_THRESHOLD})',showinfo"),
             "-f".to_string(),
             "null".to_string(),
             "-".to_string(),
@@ -2238,6 +2769,21 @@ pub fn convert_hls_steps(
     Ok(vec![Step::new("ffmpeg", args)])
 }
 
+/// Build steps for an HLS stream that gets WebVTT subtitle renditions
+/// afterwards. Segment timestamps start at zero, so the renditions' cue times
+/// line up without an offset.
+pub fn subtitled_hls_steps(
+    input: &Path,
+    output_dir: &Path,
+    quality: Option<crate::model::types::QualityPreset>,
+    overwrite: bool,
+) -> Result<Vec<Step>> {
+    let mut steps = convert_hls_steps(input, output_dir, quality, overwrite)?;
+    let at = steps[0].args.iter().position(|arg| arg == "-f").unwrap_or(steps[0].args.len() - 1);
+    steps[0].args.splice(at..at, ["-muxdelay", "0", "-muxpreload", "0"].map(String::from));
+    Ok(steps)
+}
+
 /// Build steps for DASH streaming format conversion
 pub fn convert_dash_steps(
     input: &Path,
@@ -3500,6 +4046,12 @@ pub fn convert_colorspace_steps(
     )]
 }
 
+/// The silencedetect filter for silences quieter than `noise` dB and at
+/// least `min_silence` seconds long
+pub fn silencedetect_filter(noise: f64, min_silence: f64) -> String {
+    format!("silencedetect=noise={noise}dB:duration={min_silence}")
+}
+
 /// Build steps for detecting silence in audio
 pub fn detect_silence_steps(
     input: &Path,
@@ -3515,7 +4067,43 @@ pub fn detect_silence_steps(
             "-i".to_string(),
             input.to_string_lossy().to_string(),
             "-af".to_string(),
-            "silencedetect=noise=-30dB:duration=0.5".to_string(),
+            silencedetect_filter(SplitMode::DEFAULT_NOISE, SplitMode::DEFAULT_MIN_SILENCE),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ],
+    )]
+}
+
+/// The analysis chain `qc-audio` runs in one pass: loudness with true peak,
+/// per-channel stats, silence, and for stereo the phase correlation
+pub fn qc_audio_filter(stereo: bool) -> String {
+    let mut filter = format!(
+        "ebur128=peak=true:framelog=info,astats,{}",
+        silencedetect_filter(crate::model::qc::SILENCE_NOISE, crate::model::qc::SILENCE_MIN)
+    );
+    if stereo {
+        filter.push_str(",aphasemeter=video=0,ametadata=mode=print:key=lavfi.aphasemeter.phase");
+    }
+    filter
+}
+
+/// Build steps for the audio QC analysis
+pub fn qc_audio_steps(
+    input: &Path,
+    _output: &Path,
+    stereo: bool,
+    overwrite: bool,
+) -> Vec<Step> {
+    vec![Step::new(
+        "ffmpeg",
+        vec![
+            if overwrite { "-y" } else { "-n" }.to_string(),
+            "-i".to_string(),
+            input.to_string_lossy().to_string(),
+            "-vn".to_string(),
+            "-af".to_string(),
+            qc_audio_filter(stereo),
             "-f".to_string(),
             "null".to_string(),
             "-".to_string(),
@@ -3527,6 +4115,7 @@ pub fn detect_silence_steps(
 pub fn analyze_loudness_steps(
     input: &Path,
     _output: &Path,
+    target: &LoudnessTarget,
     overwrite: bool,
 ) -> Vec<Step> {
     // Use loudnorm filter in first pass to analyze loudness
@@ -3538,7 +4127,7 @@ pub fn analyze_loudness_steps(
             "-i".to_string(),
             input.to_string_lossy().to_string(),
             "-af".to_string(),
-            "loudnorm=I=-16:TP=-1.5:LRA=11:print_format=json".to_string(),
+            loudnorm_filter(target, None),
             "-f".to_string(),
             "null".to_string(),
             "-".to_string(),
@@ -1,3 +1,4 @@
+use crate::config::StepLog;
 use crate::ffmpeg::step::Step;
 use anyhow::{anyhow, Context, Result};
 use regex::Regex;
@@ -16,6 +17,10 @@ pub struct CliRunner {
     pub overwrite: bool,
     pub explain: bool,
     pub show_progress: bool,
+    /// Where executed steps are recorded
+    pub log: StepLog,
+    /// Names the workflow step the output belongs to, for steps running side by side
+    pub label: Option<String>,
 }
 
 impl CliRunner {
@@ -25,21 +30,40 @@ impl CliRunner {
             overwrite,
             explain,
             show_progress: true,
+            log: StepLog::default(),
+            label: None,
         }
     }
+
+    /// Record executed steps into `log`
+    pub fn with_log(mut self, log: StepLog) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Start each line printed with `[label]`
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    fn prefix(&self) -> String {
+        self.label.as_ref().map(|label| format!("[{label}] ")).unwrap_or_default()
+    }
 }
 
 impl Runner for CliRunner {
     fn run(&self, step: &Step) -> Result<()> {
         if self.explain {
-            eprintln!("\n[explain] Running: {} {}", step.program, step.args.join(" "));
+            eprintln!("\n{}[explain] Running: {} {}", self.prefix(), step.program, step.args.join(" "));
         } else {
-            eprintln!("Running: {} {}", step.program, step.args.join(" "));
+            eprintln!("{}Running: {} {}", self.prefix(), step.program, step.args.join(" "));
         }
 
         if self.dry_run {
             return Ok(());
         }
+        self.log.record(step);
 
         // For FFmpeg commands, show progress if enabled
         if self.show_progress && step.program == "ffmpeg" {
@@ -142,14 +166,14 @@ impl CliRunner {
                     
                     // Clear previous line and print progress
                     eprint!("\r\x1B[K"); // Clear line
-                    eprint!("  {}", progress_parts.join(" "));
+                    eprint!("  {}{}", self.prefix(), progress_parts.join(" "));
                     std::io::stderr().flush().ok(); // Ensure progress is visible immediately
                     
                     last_update = Instant::now();
                 }
             } else if line.contains("error") || line.contains("Error") || line.contains("failed") {
                 // Show errors immediately
-                eprintln!("\n{}", line);
+                eprintln!("\n{}{}", self.prefix(), line);
             }
         }
         
@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// Represents a single FFmpeg execution step
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Step {
     pub program: String,
     pub args: Vec<String>,
@@ -1,16 +1,34 @@
 use anyhow::Result;
-use ffhuman::{App, AppConfig, Cli};
+use ffhuman::commands::history;
+use ffhuman::model::preflight;
+use ffhuman::util::OutputSkipped;
+use ffhuman::{App, Cli};
+use std::time::{Instant, SystemTime};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = AppConfig::new(
-        cli.out.clone(),
-        cli.output_dir.clone(),
-        cli.explain,
-        cli.dry_run,
-        cli.overwrite,
-    );
+    let config = cli.config()?;
+    let checks = !cli.no_checks;
     let intent = cli.into_intent()?;
-    let app = App::new(config);
-    app.execute(intent)
+    if checks {
+        preflight::ensure_ready(&config, &intent)?;
+    }
+    let journal = history::journal_for(&config, &intent);
+    let app = App::new(config.clone());
+
+    let started = (SystemTime::now(), Instant::now());
+    let result = app.execute(intent);
+    if let Some(journal) = journal {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if let Err(e) = history::record_run(&config, journal, &args, started, &result) {
+            eprintln!("Warning: couldn't write the history journal: {:#}", e);
+        }
+    }
+    match result {
+        Err(e) if e.downcast_ref::<OutputSkipped>().is_some() => {
+            eprintln!("{}", e);
+            Ok(())
+        }
+        result => result,
+    }
 }
@@ -0,0 +1,168 @@
+//! Channel layouts for the `channels` commands.
+//!
+//! Layouts use ffmpeg's names and channel order, so a probed
+//! `channel_layout` maps straight onto one:
+//!
+//! ```text
+//! 5.1        FL FR FC LFE BL BR
+//! 5.1(side)  FL FR FC LFE SL SR
+//! 7.1        FL FR FC LFE BL BR SL SR
+//! ```
+//!
+//! Filters address channels by index (`c0`, `c1`, ...), which works even when
+//! a file doesn't name its layout.
+
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// -3 dB, the ITU-R BS.775 weight of centre and surround channels in a downmix
+const MINUS_3DB: f64 = 0.707;
+
+const LAYOUTS: &[(&str, &[&str])] = &[
+    ("mono", &["FC"]),
+    ("stereo", &["FL", "FR"]),
+    ("2.1", &["FL", "FR", "LFE"]),
+    ("3.0", &["FL", "FR", "FC"]),
+    ("quad", &["FL", "FR", "BL", "BR"]),
+    ("4.0", &["FL", "FR", "FC", "BC"]),
+    ("5.0", &["FL", "FR", "FC", "BL", "BR"]),
+    ("5.0(side)", &["FL", "FR", "FC", "SL", "SR"]),
+    ("5.1", &["FL", "FR", "FC", "LFE", "BL", "BR"]),
+    ("5.1(side)", &["FL", "FR", "FC", "LFE", "SL", "SR"]),
+    ("6.1", &["FL", "FR", "FC", "LFE", "BC", "SL", "SR"]),
+    ("7.1", &["FL", "FR", "FC", "LFE", "BL", "BR", "SL", "SR"]),
+    ("7.1(wide)", &["FL", "FR", "FC", "LFE", "BL", "BR", "FLC", "FRC"]),
+];
+
+/// A channel layout: its ffmpeg name and channels in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLayout {
+    pub name: &'static str,
+    pub channels: &'static [&'static str],
+}
+
+impl ChannelLayout {
+    /// Parse an ffmpeg layout name: "stereo", "5.1", "5.1(side)", "7.1", ...
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        let name = match s_lower.as_str() {
+            "1.0" | "1" => "mono",
+            "2.0" | "2" => "stereo",
+            "4.0(quad)" => "quad",
+            other => other,
+        };
+        LAYOUTS.iter()
+            .find(|(layout, _)| *layout == name)
+            .map(|&(name, channels)| ChannelLayout { name, channels })
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown channel layout: {s} (try mono, stereo, 2.1, quad, 5.0, 5.1, 5.1(side), 6.1 or 7.1)"
+            ))
+    }
+
+    /// The layout a file reports. Only when it names none ("unknown", or
+    /// "6 channels" from newer ffprobe) is it guessed from the channel
+    /// count; a name missing from the table is an error.
+    pub fn reported(name: Option<&str>, count: Option<usize>) -> Result<Self> {
+        let name = name.map(str::trim).filter(|n| !n.is_empty() && *n != "unknown" && !n.ends_with(" channels"));
+        match (name, count) {
+            (Some(name), _) => ChannelLayout::parse(name),
+            (None, Some(count)) => ChannelLayout::for_count(count),
+            (None, None) => bail!("No channel layout or count was reported"),
+        }
+    }
+
+    /// The usual layout for a number of channels
+    pub fn for_count(count: usize) -> Result<Self> {
+        let name = match count {
+            1 => "mono",
+            2 => "stereo",
+            3 => "3.0",
+            4 => "quad",
+            5 => "5.0",
+            6 => "5.1",
+            7 => "6.1",
+            8 => "7.1",
+            _ => bail!("No standard layout has {count} channels"),
+        };
+        ChannelLayout::parse(name)
+    }
+
+    pub fn count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Index of a channel ("FL", "LFE", ...), if the layout has it
+    pub fn index_of(&self, channel: &str) -> Option<usize> {
+        self.channels.iter().position(|c| *c == channel)
+    }
+
+    /// How much of each channel goes into the left and right of a stereo
+    /// downmix: fronts as they are, centre and surrounds at -3 dB, no LFE
+    pub fn stereo_gains(&self) -> Vec<(f64, f64)> {
+        self.channels.iter()
+            .map(|channel| match *channel {
+                "FL" | "FLC" => (1.0, 0.0),
+                "FR" | "FRC" => (0.0, 1.0),
+                "FC" | "BC" => (MINUS_3DB, MINUS_3DB),
+                "BL" | "SL" => (MINUS_3DB, 0.0),
+                "BR" | "SR" => (0.0, MINUS_3DB),
+                _ => (0.0, 0.0),
+            })
+            .collect()
+    }
+
+    /// The channel a left/right channel is mirrored to when swapping sides
+    pub fn mirrored(channel: &str) -> &str {
+        match channel {
+            "FL" => "FR",
+            "FR" => "FL",
+            "BL" => "BR",
+            "BR" => "BL",
+            "SL" => "SR",
+            "SR" => "SL",
+            "FLC" => "FRC",
+            "FRC" => "FLC",
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Which side to keep when making mono from stereo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPick {
+    Left,
+    Right,
+}
+
+impl ChannelPick {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "left" | "l" => Ok(ChannelPick::Left),
+            "right" | "r" => Ok(ChannelPick::Right),
+            _ => bail!("Unknown channel: {s} (use left or right)"),
+        }
+    }
+
+    /// The front channel of that side
+    pub fn channel(&self) -> &'static str {
+        match self {
+            ChannelPick::Left => "FL",
+            ChannelPick::Right => "FR",
+        }
+    }
+}
+
+impl fmt::Display for ChannelPick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelPick::Left => write!(f, "left"),
+            ChannelPick::Right => write!(f, "right"),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Chapter markers for the `chapters` commands and `split ... by chapters`.
+//!
+//! Chapter lists are written the way YouTube descriptions have them, one
+//! chapter per line, or as YAML (a list of those lines, or of `start`/`title`
+//! maps):
+//!
+//! ```text
+//! 00:00 Intro
+//! 02:15 First topic
+//! 1:04:30 Q&A
+//! ```
+//!
+//! Times are in milliseconds, like subtitle cues.
+
+use crate::model::subtitle::Timestamp;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// One chapter: from `start` to `end`, in milliseconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub start: u64,
+    /// Unknown for a chapter list's last chapter until the video's length is
+    pub end: Option<u64>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chapters {
+    pub chapters: Vec<Chapter>,
+}
+
+/// A YAML chapter entry: "00:00 Intro" or {start: "00:00", title: Intro}
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum YamlChapter {
+    Line(String),
+    Map {
+        #[serde(deserialize_with = "crate::model::de::scalar_string")]
+        start: String,
+        title: String,
+    },
+}
+
+impl Chapters {
+    /// Read a chapter list, as YAML if the file is .yaml or .yml
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let yaml = path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+        let parsed = if yaml { Self::parse_yaml(&text) } else { Self::parse(&text) };
+        parsed.with_context(|| format!("in {}", path.display()))
+    }
+
+    /// Parse "00:00 Intro" lines; blank lines and lines starting with # are skipped
+    pub fn parse(text: &str) -> Result<Self> {
+        let chapters = text.trim_start_matches('\u{feff}')
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_line)
+            .collect::<Result<Vec<_>>>()?;
+        Self::checked(chapters)
+    }
+
+    pub fn parse_yaml(text: &str) -> Result<Self> {
+        let entries: Vec<YamlChapter> = serde_yaml::from_str(text)
+            .context("expected a list of \"00:00 Title\" lines or start/title entries")?;
+        let chapters = entries.into_iter()
+            .map(|entry| match entry {
+                YamlChapter::Line(line) => parse_line(line.trim()),
+                YamlChapter::Map { start, title } => Ok(Chapter {
+                    start: Timestamp::parse(&start)?.0,
+                    end: None,
+                    title: title.trim().to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::checked(chapters)
+    }
+
+    /// Chapters as ffprobe prints them with `-show_chapters -of json`
+    pub fn from_ffprobe(json: &str) -> Result<Self> {
+        let json: serde_json::Value = serde_json::from_str(json).context("failed to parse ffprobe chapters")?;
+        let seconds = |value: &serde_json::Value| {
+            value.as_str().and_then(|s| s.parse::<f64>().ok()).map(|s| (s * 1000.0).round().max(0.0) as u64)
+        };
+        let chapters = json.get("chapters")
+            .and_then(|c| c.as_array())
+            .map(|chapters| {
+                chapters.iter()
+                    .enumerate()
+                    .filter_map(|(i, chapter)| {
+                        let start = chapter.get("start_time").and_then(seconds)?;
+                        let end = chapter.get("end_time").and_then(seconds);
+                        let title = chapter.pointer("/tags/title")
+                            .and_then(|t| t.as_str())
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("Chapter {}", i + 1));
+                        Some(Chapter { start, end, title })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Chapters { chapters })
+    }
+
+    /// Chapters starting at scene changes (in seconds), each at least
+    /// `min_length` ms long and titled "Chapter 1", "Chapter 2", ...
+    pub fn from_scenes(scenes: &[f64], min_length: u64, duration: Option<u64>) -> Self {
+        let mut starts = vec![0];
+        for &scene in scenes {
+            let t = (scene * 1000.0).round().max(0.0) as u64;
+            let long_enough = t >= starts.last().copied().unwrap_or(0) + min_length;
+            let leaves_enough = duration.is_none_or(|d| t + min_length <= d);
+            if long_enough && leaves_enough {
+                starts.push(t);
+            }
+        }
+        let chapters = starts.iter()
+            .enumerate()
+            .map(|(i, &start)| Chapter { start, end: None, title: format!("Chapter {}", i + 1) })
+            .collect();
+        let mut chapters = Chapters { chapters };
+        chapters.close(duration);
+        chapters
+    }
+
+    /// Starts must rise and titles can't be empty
+    fn checked(chapters: Vec<Chapter>) -> Result<Self> {
+        if chapters.is_empty() {
+            bail!("No chapters in the list");
+        }
+        for pair in chapters.windows(2) {
+            if pair[1].start <= pair[0].start {
+                bail!("Chapter \"{}\" at {} doesn't come after \"{}\" at {}",
+                    pair[1].title, Timestamp(pair[1].start), pair[0].title, Timestamp(pair[0].start));
+            }
+        }
+        Ok(Chapters { chapters })
+    }
+
+    /// End each chapter where the next starts, and the last at `duration`
+    pub fn close(&mut self, duration: Option<u64>) {
+        let next_starts: Vec<Option<u64>> = self.chapters.iter().skip(1).map(|c| Some(c.start)).chain([duration]).collect();
+        for (chapter, next) in self.chapters.iter_mut().zip(next_starts) {
+            if chapter.end.is_none() {
+                chapter.end = next;
+            }
+        }
+    }
+
+    /// An ffmetadata file carrying the chapters, for `-map_chapters`
+    pub fn to_ffmetadata(&self) -> String {
+        let mut out = String::from(";FFMETADATA1\n");
+        for chapter in &self.chapters {
+            out.push_str("\n[CHAPTER]\nTIMEBASE=1/1000\n");
+            out.push_str(&format!("START={}\n", chapter.start));
+            if let Some(end) = chapter.end {
+                out.push_str(&format!("END={end}\n"));
+            }
+            out.push_str(&format!("title={}\n", escape_ffmetadata(&chapter.title)));
+        }
+        out
+    }
+
+    /// "00:00 Intro" lines, ready for a YouTube description
+    pub fn to_list(&self) -> String {
+        self.chapters.iter()
+            .map(|chapter| format!("{} {}\n", list_time(chapter.start), chapter.title))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let chapters: Vec<serde_json::Value> = self.chapters.iter()
+            .enumerate()
+            .map(|(i, chapter)| serde_json::json!({
+                "index": i + 1,
+                "title": chapter.title,
+                "start": chapter.start as f64 / 1000.0,
+                "end": chapter.end.map(|end| end as f64 / 1000.0),
+                "timestamp": list_time(chapter.start),
+            }))
+            .collect();
+        let json = serde_json::json!({ "chapters": chapters });
+        serde_json::to_string_pretty(&json).unwrap_or_default() + "\n"
+    }
+}
+
+/// "02:15 First topic", "1:04:30 - Q&A" or "0:00:00.5 Intro"
+fn parse_line(line: &str) -> Result<Chapter> {
+    let (time, title) = line.split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("Expected a time and a title: {line} (e.g., 02:15 First topic)"))?;
+    let title = title.trim().trim_start_matches(['-', '–', '|']).trim();
+    if title.is_empty() {
+        bail!("Chapter at {time} has no title");
+    }
+    Ok(Chapter { start: Timestamp::parse(time)?.0, end: None, title: title.to_string() })
+}
+
+/// Whole seconds, as "02:15" or "1:04:30"
+fn list_time(ms: u64) -> String {
+    let s = ms / 1000;
+    if s >= 3600 {
+        format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+    } else {
+        format!("{:02}:{:02}", s / 60, s % 60)
+    }
+}
+
+/// ffmetadata values escape '=', ';', '#', '\' and newlines with a backslash
+fn escape_ffmetadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A chapter title as part of a file name: "01_First_topic"
+pub fn file_name_part(index: usize, title: &str) -> String {
+    let words: Vec<String> = title
+        .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect();
+    if words.is_empty() {
+        format!("{index:02}")
+    } else {
+        format!("{index:02}_{}", words.join("_"))
+    }
+}
@@ -0,0 +1,591 @@
+//! `when:` conditions on workflow steps.
+//!
+//! A condition compares properties of a step's input with values:
+//!
+//! ```text
+//! width > 1920
+//! duration >= 1:00 and has_audio
+//! not loudness in -17..-15
+//! video_codec in [h264, hevc] or extension == mov
+//! ```
+//!
+//! Probed properties only run ffprobe (or, for loudness, decode the audio)
+//! when a condition uses them.
+
+use crate::ffmpeg::probe::{duration_seconds, get_video_info, measure_loudness, stream_codec};
+use crate::model::types::{Duration, TargetSize, Time};
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A property of a media file that conditions can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Width,
+    Height,
+    Fps,
+    /// Seconds
+    Duration,
+    /// Bytes
+    Size,
+    VideoCodec,
+    AudioCodec,
+    HasVideo,
+    HasAudio,
+    /// Integrated loudness in LUFS
+    Loudness,
+    /// True peak in dBTP
+    TruePeak,
+    Extension,
+    Name,
+}
+
+impl Property {
+    pub const NAMES: &'static [&'static str] = &[
+        "width", "height", "fps", "duration", "size", "video_codec", "audio_codec",
+        "has_video", "has_audio", "loudness", "true_peak", "extension", "name",
+    ];
+
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "width" => Ok(Property::Width),
+            "height" => Ok(Property::Height),
+            "fps" => Ok(Property::Fps),
+            "duration" => Ok(Property::Duration),
+            "size" => Ok(Property::Size),
+            "video_codec" | "vcodec" => Ok(Property::VideoCodec),
+            "audio_codec" | "acodec" => Ok(Property::AudioCodec),
+            "has_video" => Ok(Property::HasVideo),
+            "has_audio" => Ok(Property::HasAudio),
+            "loudness" | "lufs" => Ok(Property::Loudness),
+            "true_peak" => Ok(Property::TruePeak),
+            "extension" | "ext" => Ok(Property::Extension),
+            "name" => Ok(Property::Name),
+            _ => bail!("Unknown property: {s} (try {})", Property::NAMES.join(", ")),
+        }
+    }
+
+    fn kind(&self) -> PropertyKind {
+        match self {
+            Property::VideoCodec | Property::AudioCodec | Property::Extension | Property::Name => PropertyKind::Text,
+            Property::HasVideo | Property::HasAudio => PropertyKind::Flag,
+            _ => PropertyKind::Number,
+        }
+    }
+
+    /// Parse a value to compare this property with, e.g. "1:30" for duration or "10mb" for size
+    fn value(&self, s: &str) -> Result<Value> {
+        match self.kind() {
+            PropertyKind::Text => Ok(Value::Text(s.to_string())),
+            PropertyKind::Flag => match s.to_lowercase().as_str() {
+                "true" | "yes" => Ok(Value::Number(1.0)),
+                "false" | "no" => Ok(Value::Number(0.0)),
+                _ => bail!("{self} is true or false, not {s}"),
+            },
+            PropertyKind::Number => {
+                let number = match self {
+                    Property::Duration => Duration::parse(s).map(|d| d.to_seconds())
+                        .or_else(|_| Time::parse(s).map(|t| t.to_seconds() as f64)).ok(),
+                    Property::Size => s.parse::<f64>().ok()
+                        .or_else(|| TargetSize::parse(s).ok().map(|t| t.bytes as f64)),
+                    _ => s.parse::<f64>().ok(),
+                };
+                number.map(Value::Number).ok_or_else(|| anyhow!("Invalid value for {self}: {s}"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Property::Width => "width",
+            Property::Height => "height",
+            Property::Fps => "fps",
+            Property::Duration => "duration",
+            Property::Size => "size",
+            Property::VideoCodec => "video_codec",
+            Property::AudioCodec => "audio_codec",
+            Property::HasVideo => "has_video",
+            Property::HasAudio => "has_audio",
+            Property::Loudness => "loudness",
+            Property::TruePeak => "true_peak",
+            Property::Extension => "extension",
+            Property::Name => "name",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PropertyKind {
+    Number,
+    Text,
+    Flag,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare(Property, CompareOp, Value),
+    Flag(Property),
+    InRange(Property, f64, f64),
+    InList(Property, Vec<Value>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// A parsed `when:` condition
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepCondition {
+    expr: Expr,
+    text: String,
+}
+
+impl StepCondition {
+    /// Parse a condition such as "width > 1920 and has_audio"
+    pub fn parse(s: &str) -> Result<Self> {
+        let tokens = tokenize(s).map_err(|e| anyhow!("Invalid condition '{s}': {e}"))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().and_then(|expr| match parser.peek() {
+            None => Ok(expr),
+            Some(token) => bail!("unexpected '{token}'"),
+        }).map_err(|e| anyhow!("Invalid condition '{s}': {e}"))?;
+        Ok(StepCondition { expr, text: s.trim().to_string() })
+    }
+
+    /// Properties the condition reads
+    pub fn properties(&self) -> Vec<Property> {
+        let mut props = Vec::new();
+        self.expr.properties(&mut props);
+        props
+    }
+
+    /// Evaluate against a file. Properties the file doesn't have (e.g. width
+    /// of an audio file) make their comparison false.
+    pub fn evaluate(&self, facts: &mut MediaFacts) -> bool {
+        self.expr.evaluate(facts)
+    }
+}
+
+impl fmt::Display for StepCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Expr {
+    fn properties(&self, props: &mut Vec<Property>) {
+        match self {
+            Expr::Compare(p, ..) | Expr::Flag(p) | Expr::InRange(p, ..) | Expr::InList(p, _) => {
+                if !props.contains(p) {
+                    props.push(*p);
+                }
+            }
+            Expr::Not(e) => e.properties(props),
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.properties(props);
+                b.properties(props);
+            }
+        }
+    }
+
+    fn evaluate(&self, facts: &mut MediaFacts) -> bool {
+        match self {
+            Expr::Compare(p, op, value) => facts.get(*p).is_some_and(|actual| compare(&actual, *op, value)),
+            Expr::Flag(p) => facts.get(*p) == Some(Value::Number(1.0)),
+            Expr::InRange(p, low, high) => {
+                matches!(facts.get(*p), Some(Value::Number(n)) if n >= *low && n <= *high)
+            }
+            Expr::InList(p, values) => facts.get(*p)
+                .is_some_and(|actual| values.iter().any(|v| compare(&actual, CompareOp::Eq, v))),
+            Expr::Not(e) => !e.evaluate(facts),
+            Expr::And(a, b) => a.evaluate(facts) && b.evaluate(facts),
+            Expr::Or(a, b) => a.evaluate(facts) || b.evaluate(facts),
+        }
+    }
+}
+
+fn compare(actual: &Value, op: CompareOp, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => match op {
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Eq => (a - b).abs() < 1e-6,
+            CompareOp::Ne => (a - b).abs() >= 1e-6,
+        },
+        (Value::Text(a), Value::Text(b)) => match op {
+            CompareOp::Eq => a.trim_start_matches('.').eq_ignore_ascii_case(b.trim_start_matches('.')),
+            CompareOp::Ne => !a.trim_start_matches('.').eq_ignore_ascii_case(b.trim_start_matches('.')),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Properties of one file, probed on first use
+pub struct MediaFacts {
+    path: PathBuf,
+    probe: Option<Probed>,
+    loudness: Option<Option<(f64, f64)>>,
+}
+
+/// What ffprobe reported about a file; codecs are "none" for a missing stream
+#[derive(Debug, Clone, Default)]
+pub struct Probed {
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub fps: Option<f64>,
+    pub duration: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+}
+
+impl MediaFacts {
+    pub fn new(path: &Path) -> Self {
+        MediaFacts { path: path.to_path_buf(), probe: None, loudness: None }
+    }
+
+    /// Facts for a file that has already been probed
+    pub fn known(path: &Path, probed: Probed) -> Self {
+        MediaFacts { path: path.to_path_buf(), probe: Some(probed), loudness: None }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A numeric property (flags are 1 or 0), or None when it can't be probed
+    pub fn number(&mut self, property: Property) -> Option<f64> {
+        match self.get(property)? {
+            Value::Number(n) => Some(n),
+            Value::Text(_) => None,
+        }
+    }
+
+    fn probed(&mut self) -> &Probed {
+        let path = &self.path;
+        self.probe.get_or_insert_with(|| match get_video_info(path) {
+            Ok(info) => Probed {
+                width: Some(info.width as f64),
+                height: Some(info.height as f64),
+                fps: Some(info.fps),
+                duration: Some(info.duration),
+                video_codec: Some(info.video_codec),
+                audio_codec: Some(info.audio_codec),
+            },
+            // No video stream: audio-only files still have a duration and codec
+            Err(_) => Probed {
+                duration: duration_seconds(path).ok(),
+                video_codec: Some("none".to_string()),
+                audio_codec: stream_codec(path, "a:0").or_else(|| Some("none".to_string())),
+                ..Probed::default()
+            },
+        })
+    }
+
+    fn get(&mut self, property: Property) -> Option<Value> {
+        let number = |n: Option<f64>| n.map(Value::Number);
+        let flag = |b: bool| Value::Number(if b { 1.0 } else { 0.0 });
+        match property {
+            Property::Width => number(self.probed().width),
+            Property::Height => number(self.probed().height),
+            Property::Fps => number(self.probed().fps),
+            Property::Duration => number(self.probed().duration),
+            Property::Size => number(std::fs::metadata(&self.path).ok().map(|m| m.len() as f64)),
+            Property::VideoCodec => self.probed().video_codec.clone().map(Value::Text),
+            Property::AudioCodec => self.probed().audio_codec.clone().map(Value::Text),
+            Property::HasVideo => self.probed().video_codec.as_ref().map(|c| flag(c != "none")),
+            Property::HasAudio => self.probed().audio_codec.as_ref().map(|c| flag(c != "none")),
+            Property::Loudness | Property::TruePeak => {
+                let path = &self.path;
+                let loudness = *self.loudness.get_or_insert_with(|| {
+                    measure_loudness(path).ok().map(|l| (l.integrated, l.true_peak))
+                });
+                let (integrated, peak) = loudness?;
+                let value = if property == Property::Loudness { integrated } else { peak };
+                value.is_finite().then_some(Value::Number(value))
+            }
+            Property::Extension => self.path.extension().map(|e| Value::Text(e.to_string_lossy().into_owned())),
+            Property::Name => self.path.file_name().map(|n| Value::Text(n.to_string_lossy().into_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CompareOp),
+    Open,
+    Close,
+    OpenList,
+    CloseList,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) | Token::Quoted(w) => write!(f, "{w}"),
+            Token::Op(op) => write!(f, "{}", match op {
+                CompareOp::Lt => "<",
+                CompareOp::Le => "<=",
+                CompareOp::Gt => ">",
+                CompareOp::Ge => ">=",
+                CompareOp::Eq => "==",
+                CompareOp::Ne => "!=",
+            }),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::OpenList => write!(f, "["),
+            Token::CloseList => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => { tokens.push(Token::Open); i += 1; }
+            ')' => { tokens.push(Token::Close); i += 1; }
+            '[' => { tokens.push(Token::OpenList); i += 1; }
+            ']' => { tokens.push(Token::CloseList); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            '<' | '>' | '=' | '!' => {
+                let (op, len) = match (c, next) {
+                    ('<', Some('=')) => (CompareOp::Le, 2),
+                    ('>', Some('=')) => (CompareOp::Ge, 2),
+                    ('=', Some('=')) => (CompareOp::Eq, 2),
+                    ('!', Some('=')) => (CompareOp::Ne, 2),
+                    ('<', _) => (CompareOp::Lt, 1),
+                    ('>', _) => (CompareOp::Gt, 1),
+                    ('=', _) => (CompareOp::Eq, 1),
+                    _ => bail!("'!' must be followed by '=' (use 'not' to negate)"),
+                };
+                tokens.push(Token::Op(op));
+                i += len;
+            }
+            '\'' | '"' => {
+                let end = chars[i + 1..].iter().position(|&ch| ch == c)
+                    .ok_or_else(|| anyhow!("unclosed quote"))?;
+                tokens.push(Token::Quoted(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 2;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"()[],<>=!'\"".contains(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let expr = self.or()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(expr),
+                _ => bail!("missing ')'"),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let property = match self.next() {
+            Some(Token::Word(w)) => Property::parse(&w)?,
+            Some(token) => bail!("expected a property, found '{token}'"),
+            None => bail!("expected a property (try {})", Property::NAMES.join(", ")),
+        };
+
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.pos += 1;
+            let value = self.value(property)?;
+            if property.kind() != PropertyKind::Number && !matches!(op, CompareOp::Eq | CompareOp::Ne) {
+                bail!("{property} can only be compared with == or !=");
+            }
+            return Ok(Expr::Compare(property, op, value));
+        }
+
+        if self.keyword("in") {
+            if self.peek() == Some(&Token::OpenList) {
+                self.pos += 1;
+                let mut values = vec![self.value(property)?];
+                loop {
+                    match self.next() {
+                        Some(Token::Comma) => values.push(self.value(property)?),
+                        Some(Token::CloseList) => return Ok(Expr::InList(property, values)),
+                        _ => bail!("missing ']'"),
+                    }
+                }
+            }
+            if property.kind() != PropertyKind::Number {
+                bail!("{property} in ... needs a list such as [a, b]");
+            }
+            let range = match self.next() {
+                Some(Token::Word(w)) => w,
+                _ => bail!("expected a range such as -17..-15 or a list such as [a, b]"),
+            };
+            let (low, high) = range.split_once("..")
+                .ok_or_else(|| anyhow!("expected a range such as -17..-15, found {range}"))?;
+            let number = |s: &str| match property.value(s)? {
+                Value::Number(n) => Ok(n),
+                Value::Text(_) => bail!("Invalid value for {property}: {s}"),
+            };
+            return Ok(Expr::InRange(property, number(low)?, number(high)?));
+        }
+
+        if property.kind() == PropertyKind::Flag {
+            return Ok(Expr::Flag(property));
+        }
+        bail!("expected a comparison after {property} (e.g. {property} > 10)")
+    }
+
+    fn value(&mut self, property: Property) -> Result<Value> {
+        match self.next() {
+            Some(Token::Word(w)) | Some(Token::Quoted(w)) => property.value(&w),
+            Some(token) => bail!("expected a value, found '{token}'"),
+            None => bail!("expected a value after {property}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Expr {
+        StepCondition::parse(s).unwrap().expr
+    }
+
+    fn flag(p: Property) -> Box<Expr> {
+        Box::new(Expr::Flag(p))
+    }
+
+    #[test]
+    fn test_precedence() {
+        // not binds tightest, then and, then or
+        assert_eq!(
+            expr("not has_video and has_audio or width > 100"),
+            Expr::Or(
+                Box::new(Expr::And(Box::new(Expr::Not(flag(Property::HasVideo))), flag(Property::HasAudio))),
+                Box::new(Expr::Compare(Property::Width, CompareOp::Gt, Value::Number(100.0))),
+            )
+        );
+        assert_eq!(
+            expr("has_video or has_audio and not has_video"),
+            Expr::Or(
+                flag(Property::HasVideo),
+                Box::new(Expr::And(flag(Property::HasAudio), Box::new(Expr::Not(flag(Property::HasVideo))))),
+            )
+        );
+        assert_eq!(
+            expr("not (has_video or has_audio)"),
+            Expr::Not(Box::new(Expr::Or(flag(Property::HasVideo), flag(Property::HasAudio))))
+        );
+        assert_eq!(
+            expr("has_video and has_audio and has_video"),
+            Expr::And(Box::new(Expr::And(flag(Property::HasVideo), flag(Property::HasAudio))), flag(Property::HasVideo))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_precedence() {
+        let mut facts = MediaFacts::known(Path::new("clip.mp4"), Probed {
+            width: Some(1280.0),
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("none".to_string()),
+            ..Probed::default()
+        });
+        let holds = |s: &str, facts: &mut MediaFacts| StepCondition::parse(s).unwrap().evaluate(facts);
+        // "not a and b" reads as "(not a) and b"
+        assert!(holds("not has_audio and has_video", &mut facts));
+        assert!(!holds("not (has_video and has_audio) and width < 100", &mut facts));
+        assert!(holds("has_audio and width < 100 or extension == MP4", &mut facts));
+        assert!(!holds("has_audio and (width < 100 or extension == mp4)", &mut facts));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(StepCondition::parse("width >").is_err());
+        assert!(StepCondition::parse("(has_audio").is_err());
+        assert!(StepCondition::parse("has_audio has_video").is_err());
+        assert!(StepCondition::parse("codec == h264").is_err());
+        assert!(StepCondition::parse("extension > mp4").is_err());
+        assert!(StepCondition::parse("width ! 3").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Deserialization of model types from workflow YAML.
+//!
+//! Every value type is written the same way it is on the command line
+//! ("0:30", "720p", "high-quality", ...), so each one deserializes from a
+//! scalar through its existing `parse` function.
+
+use crate::model::channels::{ChannelLayout, ChannelPick};
+use crate::model::condition::StepCondition;
+use crate::model::intent::*;
+use crate::model::sequence::FrameFormat;
+use crate::model::subtitle::{
+    CaptionPosition, CaptionPreset, CutList, Language, Offset, Stretch, SubtitleContainer, SubtitleFormat,
+    SubtitleSelector, Timestamp,
+};
+use crate::model::types::*;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::fmt;
+
+/// Accept any YAML scalar (string, number or bool) as text, so `start: 5`
+/// and `start: "0:05"` both reach `Time::parse`
+pub fn scalar_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    struct ScalarVisitor;
+
+    impl Visitor<'_> for ScalarVisitor {
+        type Value = String;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a string or number")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<String, E> {
+            Ok(v.to_string())
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<String, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<String, E> {
+            Ok(v.to_string())
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<String, E> {
+            Ok(v.to_string())
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<String, E> {
+            Ok(v.to_string())
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<String, E> {
+            Ok(v.to_string())
+        }
+    }
+
+    deserializer.deserialize_any(ScalarVisitor)
+}
+
+macro_rules! deserialize_via_parse {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s = scalar_string(deserializer)?;
+                    <$ty>::parse(&s).map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+deserialize_via_parse!(
+    // types.rs
+    Time, Duration, TargetSize, TargetBitrate, CompressTarget, ResizeTarget, SpeedFactor,
+    FlipDirection, RotateDegrees, WatermarkPosition, WatermarkSize, Opacity, TextPosition,
+    TextColor, ColorPreset, BlurType, SplitMode, QualityPreset, VideoCodec, MetadataField,
+    MontageLayout, PipPosition, ChromaKeyColor, VolumeAdjustment, AudioSyncDirection,
+    SplitScreenOrientation, MirrorDirection, ColorGradePreset, TextAnimation, TransitionType,
+    Colorspace, VisualizationStyle, LoudnessTarget,
+    // intent.rs
+    ConvertFormat, AudioFormat, MetadataFormat, ThumbnailGridLayout, SocialPlatform, SocialCropShape,
+    // sequence.rs
+    FrameFormat,
+    // subtitle.rs
+    SubtitleFormat, SubtitleContainer, Language, Timestamp, Offset, CutList, Stretch,
+    CaptionPreset, CaptionPosition, SubtitleSelector,
+    // channels.rs
+    ChannelLayout, ChannelPick,
+    // condition.rs
+    StepCondition,
+);
+
+/// One step on the way to a node of a YAML document
+#[derive(Debug, Clone, Copy)]
+pub enum PathPart<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Position of the node at `path` in YAML text. The walk stops at the node
+/// with an error, which serde_yaml marks with the node's line and column.
+pub fn node_location(content: &str, path: &[PathPart]) -> Option<serde_yaml::Location> {
+    FindNode { path }.deserialize(serde_yaml::Deserializer::from_str(content)).err()?.location()
+}
+
+struct FindNode<'a> {
+    path: &'a [PathPart<'a>],
+}
+
+impl<'de> DeserializeSeed<'de> for FindNode<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FindNode<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Reaching any node at the end of the path is the "error" that marks it
+        f.write_str("the end of the path")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let Some((PathPart::Key(wanted), rest)) = self.path.split_first() else {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+        };
+        while let Some(key) = map.next_key::<Value>()? {
+            if key.as_str() == Some(*wanted) {
+                map.next_value_seed(FindNode { path: rest })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let Some((PathPart::Index(wanted), rest)) = self.path.split_first() else {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &self));
+        };
+        let mut idx = 0;
+        loop {
+            let found = if idx == *wanted {
+                seq.next_element_seed(FindNode { path: rest })?.is_some()
+            } else {
+                seq.next_element::<IgnoredAny>()?.is_some()
+            };
+            if !found {
+                return Ok(());
+            }
+            idx += 1;
+        }
+    }
+}
@@ -1,34 +1,59 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use crate::model::channels::{ChannelLayout, ChannelPick};
+use crate::model::sequence::FrameFormat;
+use crate::model::subtitle::{
+    CutList, Language, Offset, Stretch, SubtitleContainer, SubtitleFormat, SubtitleSelector, SubtitleStyle, Timestamp,
+};
 use crate::model::types::*;
+use crate::model::vars::Vars;
 use std::path::PathBuf;
 
 /// Represents the user's intent - what they want to do with their media
-#[derive(Debug, Clone)]
+///
+/// Intents also deserialize from workflow YAML: the `type` key names the
+/// variant in kebab-case (e.g. `type: extract-audio`) and the remaining keys
+/// are its fields, written as on the command line. `input` may be left out
+/// when the workflow engine supplies it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
 pub enum Intent {
     Convert {
+        #[serde(default)]
         input: PathBuf,
         format: ConvertFormat,
         quality: Option<QualityPreset>,
         codec: Option<VideoCodec>,
+        /// For animated images: re-encode smaller until the file fits
+        #[serde(default)]
+        max_size: Option<TargetSize>,
     },
     Compress {
+        #[serde(default)]
         input: PathBuf,
         target: CompressTarget,
+        #[serde(default)]
         two_pass: bool,
     },
     Trim {
+        #[serde(default)]
         input: PathBuf,
         start: Time,
         end: Time,
     },
     ExtractAudio {
+        #[serde(default)]
         input: PathBuf,
         format: AudioFormat,
+        quality: Option<QualityPreset>,
     },
     AdjustVolume {
+        #[serde(default)]
         input: PathBuf,
         adjustment: VolumeAdjustment,
     },
     SyncAudio {
+        #[serde(default)]
         input: PathBuf,
         direction: AudioSyncDirection,
         offset: Duration,
@@ -38,51 +63,64 @@ pub enum Intent {
         audio2: PathBuf,
     },
     ExtractAudioRange {
+        #[serde(default)]
         input: PathBuf,
         start: Time,
         end: Time,
         format: AudioFormat,
+        quality: Option<QualityPreset>,
     },
     Resize {
+        #[serde(default)]
         input: PathBuf,
         target: ResizeTarget,
     },
     SpeedUp {
+        #[serde(default)]
         input: PathBuf,
         factor: SpeedFactor,
     },
     SlowDown {
+        #[serde(default)]
         input: PathBuf,
         factor: SpeedFactor,
     },
     Reverse {
+        #[serde(default)]
         input: PathBuf,
     },
     Mute {
+        #[serde(default)]
         input: PathBuf,
     },
     Rotate {
+        #[serde(default)]
         input: PathBuf,
         degrees: RotateDegrees,
     },
     Flip {
+        #[serde(default)]
         input: PathBuf,
         direction: FlipDirection,
     },
     Thumbnail {
+        #[serde(default)]
         input: PathBuf,
         time: Time,
     },
     Crop {
+        #[serde(default)]
         input: PathBuf,
         width: u32,
         height: u32,
     },
     SetFps {
+        #[serde(default)]
         input: PathBuf,
         fps: u32,
     },
     Loop {
+        #[serde(default)]
         input: PathBuf,
         times: u32,
     },
@@ -95,67 +133,202 @@ pub enum Intent {
         video: PathBuf,
     },
     Grayscale {
+        #[serde(default)]
         input: PathBuf,
     },
     Stabilize {
+        #[serde(default)]
         input: PathBuf,
     },
     Denoise {
+        #[serde(default)]
         input: PathBuf,
     },
     Watermark {
+        #[serde(default)]
         input: PathBuf,
         logo: PathBuf,
         position: WatermarkPosition,
+        #[serde(default)]
         opacity: Opacity,
         size: Option<WatermarkSize>,
     },
     AddText {
+        #[serde(default)]
         input: PathBuf,
         text: String,
         position: TextPosition,
+        #[serde(default)]
         style: TextStyle,
+        #[serde(default)]
         timestamp: bool,
     },
     Filter {
+        #[serde(default)]
         input: PathBuf,
+        #[serde(default)]
         adjustments: FilterAdjustments,
         preset: Option<ColorPreset>,
     },
     Blur {
+        #[serde(default)]
         input: PathBuf,
         blur_type: BlurType,
     },
     Normalize {
+        #[serde(default)]
         input: PathBuf,
+        #[serde(default)]
+        target: LoudnessTarget,
     },
     Fade {
+        #[serde(default)]
         input: PathBuf,
         fade_in: Option<Duration>,
         fade_out: Option<Duration>,
     },
     Split {
+        #[serde(default)]
         input: PathBuf,
         mode: SplitMode,
     },
     ExtractFrames {
+        #[serde(default)]
         input: PathBuf,
         interval: Duration,
     },
     BurnSubtitle {
+        #[serde(default)]
+        input: PathBuf,
+        /// Without a subtitle file, a subtitle track of the input is burned
+        #[serde(default)]
+        subtitle: Option<PathBuf>,
+        #[serde(default)]
+        track: Option<SubtitleSelector>,
+        #[serde(default)]
+        style: SubtitleStyle,
+    },
+    /// Mux subtitle files as selectable tracks
+    AddSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        subtitles: Vec<PathBuf>,
+        /// One per subtitle file, in order; the rest come from file names ("movie.en.srt")
+        #[serde(default)]
+        languages: Vec<Language>,
+        /// Defaults to the input's container
+        #[serde(default)]
+        to: Option<SubtitleContainer>,
+    },
+    ExtractSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        /// Defaults to SRT, or ASS for ASS tracks
+        #[serde(default)]
+        format: Option<SubtitleFormat>,
+        /// 1-based subtitle track; every track when left out
+        #[serde(default)]
+        track: Option<usize>,
+    },
+    ConvertSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        format: SubtitleFormat,
+    },
+    ShiftSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        offset: Offset,
+    },
+    StretchSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        stretch: Stretch,
+    },
+    CutSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        keep: CutList,
+    },
+    MergeSubtitles {
+        inputs: Vec<PathBuf>,
+        /// Where each file starts; all at 0 when left out
+        #[serde(default)]
+        at: Vec<Timestamp>,
+    },
+    SplitSubtitles {
+        #[serde(default)]
+        input: PathBuf,
+        at: Vec<Timestamp>,
+    },
+    /// Replace a video's chapters with those of a chapter list
+    AddChapters {
+        #[serde(default)]
+        input: PathBuf,
+        chapters: PathBuf,
+    },
+    ListChapters {
+        #[serde(default)]
+        input: PathBuf,
+        #[serde(default)]
+        json: bool,
+    },
+    ExtractChapters {
+        #[serde(default)]
+        input: PathBuf,
+    },
+    /// Write a chapter list starting a chapter at scene changes
+    ChaptersFromScenes {
+        #[serde(default)]
+        input: PathBuf,
+        /// Scene times from `detect-scenes`; detected afresh when left out
+        #[serde(default)]
+        scenes: Option<PathBuf>,
+        #[serde(default)]
+        min_length: Option<Duration>,
+    },
+    /// Mix 5.1/7.1 down to stereo
+    Downmix {
+        #[serde(default)]
+        input: PathBuf,
+    },
+    /// Mix down to mono, or keep only the left or right channel
+    ToMono {
+        #[serde(default)]
+        input: PathBuf,
+        #[serde(default)]
+        pick: Option<ChannelPick>,
+    },
+    SwapChannels {
+        #[serde(default)]
         input: PathBuf,
-        subtitle: PathBuf,
     },
+    /// Write each channel to its own mono WAV
+    SplitChannels {
+        #[serde(default)]
+        input: PathBuf,
+    },
+    /// Join mono files into one multichannel file, in channel order
+    CombineChannels {
+        inputs: Vec<PathBuf>,
+        /// The usual layout for the number of files when left out
+        #[serde(default)]
+        layout: Option<ChannelLayout>,
+    },
+    #[serde(skip)]
     Batch {
         pattern: String,
         operation: BatchOperation,
+        options: BatchOptions,
     },
     Compare {
         video1: PathBuf,
         video2: PathBuf,
+        #[serde(default)]
         show_psnr: bool,
     },
     SetMetadata {
+        #[serde(default)]
         input: PathBuf,
         field: MetadataField,
         value: String,
@@ -170,6 +343,7 @@ pub enum Intent {
         duration: Duration,
     },
     Timelapse {
+        #[serde(default)]
         input: PathBuf,
         speed: SpeedFactor,
     },
@@ -179,6 +353,7 @@ pub enum Intent {
         position: PipPosition,
     },
     RemoveBackground {
+        #[serde(default)]
         input: PathBuf,
         color: ChromaKeyColor,
     },
@@ -186,101 +361,130 @@ pub enum Intent {
         overlay_video: PathBuf,
         base_video: PathBuf,
         position: WatermarkPosition,
+        #[serde(default)]
         opacity: Opacity,
     },
     Concat {
         videos: Vec<PathBuf>,
     },
     DetectScenes {
+        #[serde(default)]
         input: PathBuf,
     },
     DetectBlack {
+        #[serde(default)]
         input: PathBuf,
     },
     FixRotation {
+        #[serde(default)]
         input: PathBuf,
     },
     AnalyzeQuality {
+        #[serde(default)]
         input: PathBuf,
     },
     Preview {
+        #[serde(default)]
         input: PathBuf,
     },
     SuggestFormat {
+        #[serde(default)]
         input: PathBuf,
     },
     Workflow {
         config_file: PathBuf,
+        #[serde(default)]
+        vars: Vars,
     },
     MotionBlur {
+        #[serde(default)]
         input: PathBuf,
         radius: Option<u32>,
     },
     Vignette {
+        #[serde(default)]
         input: PathBuf,
         intensity: Option<f32>,
         size: Option<f32>,
     },
     LensCorrect {
+        #[serde(default)]
         input: PathBuf,
     },
     Interpolate {
+        #[serde(default)]
         input: PathBuf,
         fps: u32,
     },
     ExtractMetadata {
+        #[serde(default)]
         input: PathBuf,
         format: MetadataFormat,
     },
     Info {
+        #[serde(default)]
         input: PathBuf,
     },
     ThumbnailGrid {
+        #[serde(default)]
         input: PathBuf,
         layout: ThumbnailGridLayout,
     },
+    #[serde(alias = "social")]
     SocialMediaConvert {
+        #[serde(default)]
         input: PathBuf,
         platform: SocialPlatform,
     },
     SocialCrop {
+        #[serde(default)]
         input: PathBuf,
         shape: SocialCropShape,
     },
     VerticalConvert {
+        #[serde(default)]
         input: PathBuf,
     },
     StoryFormat {
+        #[serde(default)]
         input: PathBuf,
     },
     NoiseReduction {
+        #[serde(default)]
         input: PathBuf,
     },
     EchoRemoval {
+        #[serde(default)]
         input: PathBuf,
     },
     AudioDucking {
+        #[serde(default)]
         input: PathBuf,
     },
     AudioEqualizer {
+        #[serde(default)]
         input: PathBuf,
         bass: Option<i32>,
         treble: Option<i32>,
         mid: Option<i32>,
     },
     VoiceIsolation {
+        #[serde(default)]
         input: PathBuf,
     },
     AudioSpeedKeepPitch {
+        #[serde(default)]
         input: PathBuf,
         factor: SpeedFactor,
     },
     Glitch {
+        #[serde(default)]
         input: PathBuf,
         shift: Option<u32>,
         noise: Option<u32>,
     },
     VintageFilm {
+        #[serde(default)]
         input: PathBuf,
         era: Option<String>,
     },
@@ -290,18 +494,22 @@ pub enum Intent {
         orientation: crate::model::types::SplitScreenOrientation,
     },
     Mirror {
+        #[serde(default)]
         input: PathBuf,
         direction: crate::model::types::MirrorDirection,
     },
     ColorGrade {
+        #[serde(default)]
         input: PathBuf,
         preset: crate::model::types::ColorGradePreset,
     },
     AnimatedText {
+        #[serde(default)]
         input: PathBuf,
         text: String,
         position: crate::model::types::TextPosition,
         animation: crate::model::types::TextAnimation,
+        #[serde(default)]
         style: crate::model::types::TextStyle,
     },
     Transition {
@@ -317,25 +525,39 @@ pub enum Intent {
         duration: Duration,
     },
     AddTimecode {
+        #[serde(default)]
         input: PathBuf,
     },
     Proxy {
+        #[serde(default)]
         input: PathBuf,
     },
     ExportEdl {
+        #[serde(default)]
         input: PathBuf,
     },
     ConvertColorspace {
+        #[serde(default)]
         input: PathBuf,
         target: Colorspace,
     },
     DetectSilence {
+        #[serde(default)]
         input: PathBuf,
     },
     AnalyzeLoudness {
+        #[serde(default)]
+        input: PathBuf,
+    },
+    QcAudio {
+        #[serde(default)]
         input: PathBuf,
+        #[serde(default)]
+        spec: LoudnessTarget,
+        max_silence: Option<Duration>,
     },
     DetectDuplicates {
+        #[serde(default)]
         input: PathBuf,
     },
     Collage {
@@ -346,67 +568,378 @@ pub enum Intent {
         images: Vec<PathBuf>,
         duration: Duration,
     },
+    SequenceToVideo {
+        /// A folder, a glob, a numbered pattern, or the frames themselves
+        images: Vec<PathBuf>,
+        fps: u32,
+        codec: Option<VideoCodec>,
+        quality: Option<QualityPreset>,
+    },
+    VideoToSequence {
+        #[serde(default)]
+        input: PathBuf,
+        format: FrameFormat,
+        /// File name pattern like "shot_%04d"; defaults to the input's name
+        pattern: Option<String>,
+        /// First frame number (1 when not given)
+        start: Option<u32>,
+    },
     Visualize {
         audio: PathBuf,
         style: VisualizationStyle,
     },
     AnimatedGif {
+        #[serde(default)]
         input: PathBuf,
+        #[serde(default)]
         loop_video: bool,
+        #[serde(default)]
         optimize: bool,
     },
     Tile {
+        #[serde(default)]
         input: PathBuf,
         layout: MontageLayout,
     },
     Doctor,
     Repair {
+        #[serde(default)]
         input: PathBuf,
     },
     Validate {
+        #[serde(default)]
         input: PathBuf,
     },
     ExtractKeyframes {
+        #[serde(default)]
         input: PathBuf,
     },
     Stats {
+        #[serde(default)]
         input: PathBuf,
     },
     Convert360 {
+        #[serde(default)]
         input: PathBuf,
     },
     ConvertHdrToSdr {
+        #[serde(default)]
         input: PathBuf,
     },
     FixFramerate {
+        #[serde(default)]
         input: PathBuf,
     },
+    #[serde(skip)]
     WatchFolder {
         folder: PathBuf,
-        operation: BatchOperation,
+        /// Operation for every file; None when `options.rules` routes files instead
+        operation: Option<BatchOperation>,
+        options: WatchOptions,
     },
     ApplyTemplate {
+        #[serde(default)]
         input: PathBuf,
         template_file: PathBuf,
+        #[serde(default)]
+        vars: Vars,
     },
     Pipeline {
+        #[serde(default)]
         input: PathBuf,
         steps_file: PathBuf,
+        #[serde(default)]
+        vars: Vars,
+    },
+    #[serde(skip)]
+    Shell {
+        file: Option<PathBuf>,
+    },
+    #[serde(skip)]
+    History {
+        id: Option<u64>,
+        limit: usize,
+    },
+    #[serde(skip)]
+    Redo {
+        id: u64,
+        input: Option<PathBuf>,
+    },
+    #[serde(skip)]
+    Undo {
+        id: u64,
+    },
+    #[serde(skip)]
+    Sentence {
+        text: String,
     },
+    #[serde(skip)]
     ConditionalBatch {
         pattern: String,
         operation: BatchOperation,
         condition: ProcessingCondition,
+        options: BatchOptions,
     },
 }
 
+impl Intent {
+    /// The main input file, for intents that take one.
+    /// Workflows fill it in when a step leaves `input` out.
+    pub fn input_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            Intent::Convert { input, .. }
+            | Intent::Compress { input, .. }
+            | Intent::Trim { input, .. }
+            | Intent::ExtractAudio { input, .. }
+            | Intent::AdjustVolume { input, .. }
+            | Intent::SyncAudio { input, .. }
+            | Intent::ExtractAudioRange { input, .. }
+            | Intent::Resize { input, .. }
+            | Intent::SpeedUp { input, .. }
+            | Intent::SlowDown { input, .. }
+            | Intent::Reverse { input, .. }
+            | Intent::Mute { input, .. }
+            | Intent::Rotate { input, .. }
+            | Intent::Flip { input, .. }
+            | Intent::Thumbnail { input, .. }
+            | Intent::Crop { input, .. }
+            | Intent::SetFps { input, .. }
+            | Intent::Loop { input, .. }
+            | Intent::Grayscale { input, .. }
+            | Intent::Stabilize { input, .. }
+            | Intent::Denoise { input, .. }
+            | Intent::Watermark { input, .. }
+            | Intent::AddText { input, .. }
+            | Intent::Filter { input, .. }
+            | Intent::Blur { input, .. }
+            | Intent::Normalize { input, .. }
+            | Intent::Fade { input, .. }
+            | Intent::Split { input, .. }
+            | Intent::ExtractFrames { input, .. }
+            | Intent::VideoToSequence { input, .. }
+            | Intent::BurnSubtitle { input, .. }
+            | Intent::AddSubtitles { input, .. }
+            | Intent::ExtractSubtitles { input, .. }
+            | Intent::ConvertSubtitles { input, .. }
+            | Intent::ShiftSubtitles { input, .. }
+            | Intent::StretchSubtitles { input, .. }
+            | Intent::CutSubtitles { input, .. }
+            | Intent::SplitSubtitles { input, .. }
+            | Intent::AddChapters { input, .. }
+            | Intent::ListChapters { input, .. }
+            | Intent::ExtractChapters { input, .. }
+            | Intent::Downmix { input }
+            | Intent::ToMono { input, .. }
+            | Intent::SwapChannels { input }
+            | Intent::SplitChannels { input }
+            | Intent::ChaptersFromScenes { input, .. }
+            | Intent::SetMetadata { input, .. }
+            | Intent::Timelapse { input, .. }
+            | Intent::RemoveBackground { input, .. }
+            | Intent::DetectScenes { input, .. }
+            | Intent::DetectBlack { input, .. }
+            | Intent::FixRotation { input, .. }
+            | Intent::AnalyzeQuality { input, .. }
+            | Intent::Preview { input, .. }
+            | Intent::SuggestFormat { input, .. }
+            | Intent::MotionBlur { input, .. }
+            | Intent::Vignette { input, .. }
+            | Intent::LensCorrect { input, .. }
+            | Intent::Interpolate { input, .. }
+            | Intent::ExtractMetadata { input, .. }
+            | Intent::Info { input, .. }
+            | Intent::ThumbnailGrid { input, .. }
+            | Intent::SocialMediaConvert { input, .. }
+            | Intent::SocialCrop { input, .. }
+            | Intent::VerticalConvert { input, .. }
+            | Intent::StoryFormat { input, .. }
+            | Intent::NoiseReduction { input, .. }
+            | Intent::EchoRemoval { input, .. }
+            | Intent::AudioDucking { input, .. }
+            | Intent::AudioEqualizer { input, .. }
+            | Intent::VoiceIsolation { input, .. }
+            | Intent::AudioSpeedKeepPitch { input, .. }
+            | Intent::Glitch { input, .. }
+            | Intent::VintageFilm { input, .. }
+            | Intent::Mirror { input, .. }
+            | Intent::ColorGrade { input, .. }
+            | Intent::AnimatedText { input, .. }
+            | Intent::AddTimecode { input, .. }
+            | Intent::Proxy { input, .. }
+            | Intent::ExportEdl { input, .. }
+            | Intent::ConvertColorspace { input, .. }
+            | Intent::DetectSilence { input, .. }
+            | Intent::AnalyzeLoudness { input, .. }
+            | Intent::QcAudio { input, .. }
+            | Intent::DetectDuplicates { input, .. }
+            | Intent::AnimatedGif { input, .. }
+            | Intent::Tile { input, .. }
+            | Intent::Repair { input, .. }
+            | Intent::Validate { input, .. }
+            | Intent::ExtractKeyframes { input, .. }
+            | Intent::Stats { input, .. }
+            | Intent::Convert360 { input, .. }
+            | Intent::ConvertHdrToSdr { input, .. }
+            | Intent::FixFramerate { input, .. }
+            | Intent::ApplyTemplate { input, .. }
+            | Intent::Pipeline { input, .. } => Some(input),
+            _ => None,
+        }
+    }
+
+    /// The step type naming this intent in workflow YAML (e.g. "extract-audio"),
+    /// matching the serde tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Intent::Convert { .. } => "convert",
+            Intent::Compress { .. } => "compress",
+            Intent::Trim { .. } => "trim",
+            Intent::ExtractAudio { .. } => "extract-audio",
+            Intent::AdjustVolume { .. } => "adjust-volume",
+            Intent::SyncAudio { .. } => "sync-audio",
+            Intent::MixAudio { .. } => "mix-audio",
+            Intent::ExtractAudioRange { .. } => "extract-audio-range",
+            Intent::Resize { .. } => "resize",
+            Intent::SpeedUp { .. } => "speed-up",
+            Intent::SlowDown { .. } => "slow-down",
+            Intent::Reverse { .. } => "reverse",
+            Intent::Mute { .. } => "mute",
+            Intent::Rotate { .. } => "rotate",
+            Intent::Flip { .. } => "flip",
+            Intent::Thumbnail { .. } => "thumbnail",
+            Intent::Crop { .. } => "crop",
+            Intent::SetFps { .. } => "set-fps",
+            Intent::Loop { .. } => "loop",
+            Intent::Merge { .. } => "merge",
+            Intent::AddAudio { .. } => "add-audio",
+            Intent::Grayscale { .. } => "grayscale",
+            Intent::Stabilize { .. } => "stabilize",
+            Intent::Denoise { .. } => "denoise",
+            Intent::Watermark { .. } => "watermark",
+            Intent::AddText { .. } => "add-text",
+            Intent::Filter { .. } => "filter",
+            Intent::Blur { .. } => "blur",
+            Intent::Normalize { .. } => "normalize",
+            Intent::Fade { .. } => "fade",
+            Intent::Split { .. } => "split",
+            Intent::ExtractFrames { .. } => "extract-frames",
+            Intent::BurnSubtitle { .. } => "burn-subtitle",
+            Intent::AddSubtitles { .. } => "add-subtitles",
+            Intent::ExtractSubtitles { .. } => "extract-subtitles",
+            Intent::ConvertSubtitles { .. } => "convert-subtitles",
+            Intent::ShiftSubtitles { .. } => "shift-subtitles",
+            Intent::StretchSubtitles { .. } => "stretch-subtitles",
+            Intent::CutSubtitles { .. } => "cut-subtitles",
+            Intent::MergeSubtitles { .. } => "merge-subtitles",
+            Intent::SplitSubtitles { .. } => "split-subtitles",
+            Intent::AddChapters { .. } => "add-chapters",
+            Intent::ListChapters { .. } => "list-chapters",
+            Intent::ExtractChapters { .. } => "extract-chapters",
+            Intent::ChaptersFromScenes { .. } => "chapters-from-scenes",
+            Intent::Downmix { .. } => "downmix",
+            Intent::ToMono { .. } => "to-mono",
+            Intent::SwapChannels { .. } => "swap-channels",
+            Intent::SplitChannels { .. } => "split-channels",
+            Intent::CombineChannels { .. } => "combine-channels",
+            Intent::Batch { .. } => "batch",
+            Intent::Compare { .. } => "compare",
+            Intent::SetMetadata { .. } => "set-metadata",
+            Intent::Montage { .. } => "montage",
+            Intent::Crossfade { .. } => "crossfade",
+            Intent::Timelapse { .. } => "timelapse",
+            Intent::Pip { .. } => "pip",
+            Intent::RemoveBackground { .. } => "remove-background",
+            Intent::Overlay { .. } => "overlay",
+            Intent::Concat { .. } => "concat",
+            Intent::DetectScenes { .. } => "detect-scenes",
+            Intent::DetectBlack { .. } => "detect-black",
+            Intent::FixRotation { .. } => "fix-rotation",
+            Intent::AnalyzeQuality { .. } => "analyze-quality",
+            Intent::Preview { .. } => "preview",
+            Intent::SuggestFormat { .. } => "suggest-format",
+            Intent::Workflow { .. } => "workflow",
+            Intent::MotionBlur { .. } => "motion-blur",
+            Intent::Vignette { .. } => "vignette",
+            Intent::LensCorrect { .. } => "lens-correct",
+            Intent::Interpolate { .. } => "interpolate",
+            Intent::ExtractMetadata { .. } => "extract-metadata",
+            Intent::Info { .. } => "info",
+            Intent::ThumbnailGrid { .. } => "thumbnail-grid",
+            Intent::SocialMediaConvert { .. } => "social-media-convert",
+            Intent::SocialCrop { .. } => "social-crop",
+            Intent::VerticalConvert { .. } => "vertical-convert",
+            Intent::StoryFormat { .. } => "story-format",
+            Intent::NoiseReduction { .. } => "noise-reduction",
+            Intent::EchoRemoval { .. } => "echo-removal",
+            Intent::AudioDucking { .. } => "audio-ducking",
+            Intent::AudioEqualizer { .. } => "audio-equalizer",
+            Intent::VoiceIsolation { .. } => "voice-isolation",
+            Intent::AudioSpeedKeepPitch { .. } => "audio-speed-keep-pitch",
+            Intent::Glitch { .. } => "glitch",
+            Intent::VintageFilm { .. } => "vintage-film",
+            Intent::SplitScreen { .. } => "split-screen",
+            Intent::Mirror { .. } => "mirror",
+            Intent::ColorGrade { .. } => "color-grade",
+            Intent::AnimatedText { .. } => "animated-text",
+            Intent::Transition { .. } => "transition",
+            Intent::SyncCameras { .. } => "sync-cameras",
+            Intent::GenerateTestPattern { .. } => "generate-test-pattern",
+            Intent::AddTimecode { .. } => "add-timecode",
+            Intent::Proxy { .. } => "proxy",
+            Intent::ExportEdl { .. } => "export-edl",
+            Intent::ConvertColorspace { .. } => "convert-colorspace",
+            Intent::DetectSilence { .. } => "detect-silence",
+            Intent::AnalyzeLoudness { .. } => "analyze-loudness",
+            Intent::QcAudio { .. } => "qc-audio",
+            Intent::DetectDuplicates { .. } => "detect-duplicates",
+            Intent::Collage { .. } => "collage",
+            Intent::Slideshow { .. } => "slideshow",
+            Intent::SequenceToVideo { .. } => "sequence-to-video",
+            Intent::VideoToSequence { .. } => "video-to-sequence",
+            Intent::Visualize { .. } => "visualize",
+            Intent::AnimatedGif { .. } => "animated-gif",
+            Intent::Tile { .. } => "tile",
+            Intent::Doctor => "doctor",
+            Intent::Repair { .. } => "repair",
+            Intent::Validate { .. } => "validate",
+            Intent::ExtractKeyframes { .. } => "extract-keyframes",
+            Intent::Stats { .. } => "stats",
+            Intent::Convert360 { .. } => "convert360",
+            Intent::ConvertHdrToSdr { .. } => "convert-hdr-to-sdr",
+            Intent::FixFramerate { .. } => "fix-framerate",
+            Intent::WatchFolder { .. } => "watch-folder",
+            Intent::ApplyTemplate { .. } => "apply-template",
+            Intent::Pipeline { .. } => "pipeline",
+            Intent::Shell { .. } => "shell",
+            Intent::History { .. } => "history",
+            Intent::Redo { .. } => "redo",
+            Intent::Undo { .. } => "undo",
+            Intent::Sentence { .. } => "sentence",
+            Intent::ConditionalBatch { .. } => "conditional-batch",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConvertFormat {
     Gif,
+    /// Animated WebP
+    Webp,
+    /// Animated PNG
+    Apng,
+    /// Animated AVIF
+    Avif,
     Mp4,
     Webm,
+    Mkv,
+    Mov,
     Mp3,
     Wav,
+    Flac,
+    M4a,
+    Opus,
+    Ogg,
+    Aiff,
     Iphone,
     Android,
     Hls,
@@ -414,10 +947,246 @@ pub enum ConvertFormat {
     Video360,
 }
 
+impl ConvertFormat {
+    /// Parse convert format from the names used on the command line: "gif", "mp4", "360", ...
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "gif" => Ok(ConvertFormat::Gif),
+            "webp" => Ok(ConvertFormat::Webp),
+            "apng" => Ok(ConvertFormat::Apng),
+            "avif" => Ok(ConvertFormat::Avif),
+            "mp4" => Ok(ConvertFormat::Mp4),
+            "webm" => Ok(ConvertFormat::Webm),
+            "mkv" => Ok(ConvertFormat::Mkv),
+            "mov" => Ok(ConvertFormat::Mov),
+            "mp3" => Ok(ConvertFormat::Mp3),
+            "wav" => Ok(ConvertFormat::Wav),
+            "flac" => Ok(ConvertFormat::Flac),
+            "m4a" | "aac" => Ok(ConvertFormat::M4a),
+            "opus" => Ok(ConvertFormat::Opus),
+            "ogg" | "vorbis" => Ok(ConvertFormat::Ogg),
+            "aiff" | "aif" => Ok(ConvertFormat::Aiff),
+            "iphone" => Ok(ConvertFormat::Iphone),
+            "android" => Ok(ConvertFormat::Android),
+            "hls" => Ok(ConvertFormat::Hls),
+            "dash" => Ok(ConvertFormat::Dash),
+            "360" | "video360" => Ok(ConvertFormat::Video360),
+            _ => bail!("Invalid convert format: {s} (try gif, webp, apng, avif, mp4, webm, mkv, mov, mp3, wav, flac, m4a, opus, ogg, or aiff)"),
+        }
+    }
+
+    /// Animated image targets, which share fps/width/palette settings
+    pub fn is_animation(&self) -> bool {
+        matches!(self, ConvertFormat::Gif | ConvertFormat::Webp | ConvertFormat::Apng | ConvertFormat::Avif)
+    }
+
+    /// The audio format for audio-only targets
+    pub fn audio_format(&self) -> Option<AudioFormat> {
+        match self {
+            ConvertFormat::Mp3 => Some(AudioFormat::Mp3),
+            ConvertFormat::Wav => Some(AudioFormat::Wav),
+            ConvertFormat::Flac => Some(AudioFormat::Flac),
+            ConvertFormat::M4a => Some(AudioFormat::Aac),
+            ConvertFormat::Opus => Some(AudioFormat::Opus),
+            ConvertFormat::Ogg => Some(AudioFormat::Vorbis),
+            ConvertFormat::Aiff => Some(AudioFormat::Aiff),
+            _ => None,
+        }
+    }
+
+    /// Refuse a codec the target container can't hold, e.g. ProRes in mp4
+    pub fn check_codec(&self, codec: VideoCodec) -> Result<()> {
+        let ext = self.to_string();
+        if !codec.fits_container(&ext) {
+            bail!("{codec} video doesn't go in {ext} files (use {} instead)", codec.containers());
+        }
+        Ok(())
+    }
+
+    /// Output suffix and extension used by the convert handler, or None when
+    /// the output is a directory (HLS/DASH) or produced by another handler
+    pub fn output_naming(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            ConvertFormat::Gif => Some(("gif", "gif")),
+            ConvertFormat::Webp => Some(("webp", "webp")),
+            ConvertFormat::Apng => Some(("apng", "png")),
+            ConvertFormat::Avif => Some(("avif", "avif")),
+            ConvertFormat::Mp4 => Some(("convert", "mp4")),
+            ConvertFormat::Webm => Some(("convert", "webm")),
+            ConvertFormat::Mkv => Some(("convert", "mkv")),
+            ConvertFormat::Mov => Some(("convert", "mov")),
+            ConvertFormat::Mp3 => Some(("audio", "mp3")),
+            ConvertFormat::Wav => Some(("audio", "wav")),
+            ConvertFormat::Flac => Some(("audio", "flac")),
+            ConvertFormat::M4a => Some(("audio", "m4a")),
+            ConvertFormat::Opus => Some(("audio", "opus")),
+            ConvertFormat::Ogg => Some(("audio", "ogg")),
+            ConvertFormat::Aiff => Some(("audio", "aiff")),
+            ConvertFormat::Iphone => Some(("iphone", "mp4")),
+            ConvertFormat::Android => Some(("android", "mp4")),
+            ConvertFormat::Hls | ConvertFormat::Dash | ConvertFormat::Video360 => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ConvertFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ConvertFormat::Gif => "gif",
+            ConvertFormat::Webp => "webp",
+            ConvertFormat::Apng => "apng",
+            ConvertFormat::Avif => "avif",
+            ConvertFormat::Mp4 => "mp4",
+            ConvertFormat::Webm => "webm",
+            ConvertFormat::Mkv => "mkv",
+            ConvertFormat::Mov => "mov",
+            ConvertFormat::Mp3 => "mp3",
+            ConvertFormat::Wav => "wav",
+            ConvertFormat::Flac => "flac",
+            ConvertFormat::M4a => "m4a",
+            ConvertFormat::Opus => "opus",
+            ConvertFormat::Ogg => "ogg",
+            ConvertFormat::Aiff => "aiff",
+            ConvertFormat::Iphone => "iphone",
+            ConvertFormat::Android => "android",
+            ConvertFormat::Hls => "hls",
+            ConvertFormat::Dash => "dash",
+            ConvertFormat::Video360 => "360",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AudioFormat {
     Mp3,
     Wav,
+    Flac,
+    /// AAC in an .m4a container
+    Aac,
+    Opus,
+    /// Vorbis in an .ogg container
+    Vorbis,
+    Aiff,
+    /// Copy the existing audio stream into a matching container when possible
+    Auto,
+}
+
+impl AudioFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "mp3" => Ok(AudioFormat::Mp3),
+            "wav" => Ok(AudioFormat::Wav),
+            "flac" => Ok(AudioFormat::Flac),
+            "aac" | "m4a" => Ok(AudioFormat::Aac),
+            "opus" => Ok(AudioFormat::Opus),
+            "ogg" | "vorbis" => Ok(AudioFormat::Vorbis),
+            "aiff" | "aif" => Ok(AudioFormat::Aiff),
+            "auto" | "copy" => Ok(AudioFormat::Auto),
+            _ => bail!("Invalid audio format: {s} (try mp3, wav, flac, aac, opus, ogg, aiff, or auto)"),
+        }
+    }
+
+    /// File extension of the container; Auto resolves to a real format first
+    pub fn ext(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Aac => "m4a",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Vorbis => "ogg",
+            AudioFormat::Aiff => "aiff",
+            AudioFormat::Auto => "mka",
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioFormat::Wav | AudioFormat::Flac | AudioFormat::Aiff)
+    }
+
+    /// The format whose container takes a stream of this codec (as ffprobe
+    /// names it) as-is, for stream copy
+    pub fn for_codec(codec: &str) -> Option<Self> {
+        match codec {
+            "mp3" => Some(AudioFormat::Mp3),
+            "aac" | "alac" => Some(AudioFormat::Aac),
+            "flac" => Some(AudioFormat::Flac),
+            "opus" => Some(AudioFormat::Opus),
+            "vorbis" => Some(AudioFormat::Vorbis),
+            "pcm_s16le" | "pcm_s24le" | "pcm_s32le" | "pcm_f32le" | "pcm_u8" => Some(AudioFormat::Wav),
+            "pcm_s16be" | "pcm_s24be" | "pcm_s32be" => Some(AudioFormat::Aiff),
+            _ => None,
+        }
+    }
+
+    /// Encoder arguments for a quality preset (None keeps the long-standing
+    /// defaults): VBR or bitrate for lossy formats, bit depth for lossless
+    /// ones, and a sample rate where the format needs one
+    pub fn codec_args(&self, quality: Option<QualityPreset>) -> Vec<String> {
+        use QualityPreset::*;
+        let args: Vec<&str> = match (self, quality) {
+            // LAME VBR: V0 ~245k, V2 ~190k, V4 ~165k, V6 ~115k
+            (AudioFormat::Mp3, q) => vec!["-c:a", "libmp3lame", "-q:a", match q {
+                Some(Low) => "6",
+                Some(Medium) => "4",
+                None | Some(High) => "2",
+                Some(Ultra) => "0",
+            }, "-id3v2_version", "3"],
+            (AudioFormat::Aac, q) => vec!["-c:a", "aac", "-b:a", match q {
+                Some(Low) => "96k",
+                Some(Medium) => "128k",
+                None | Some(High) => "192k",
+                Some(Ultra) => "256k",
+            }],
+            // Opus only runs at 48 kHz (ffmpeg would pick it anyway; be explicit)
+            (AudioFormat::Opus, q) => vec!["-c:a", "libopus", "-vbr", "on", "-b:a", match q {
+                Some(Low) => "64k",
+                Some(Medium) => "96k",
+                None | Some(High) => "128k",
+                Some(Ultra) => "192k",
+            }, "-ar", "48000"],
+            // Vorbis quality scale -1..10, 5 is ~160k
+            (AudioFormat::Vorbis, q) => vec!["-c:a", "libvorbis", "-q:a", match q {
+                Some(Low) => "3",
+                None | Some(Medium) => "5",
+                Some(High) => "6",
+                Some(Ultra) => "8",
+            }],
+            // Lossless: quality chooses the bit depth, 24-bit from high up
+            (AudioFormat::Wav, Some(High | Ultra)) => vec!["-c:a", "pcm_s24le"],
+            (AudioFormat::Wav, _) => vec!["-c:a", "pcm_s16le"],
+            (AudioFormat::Aiff, Some(High | Ultra)) => vec!["-c:a", "pcm_s24be", "-write_id3v2", "1"],
+            (AudioFormat::Aiff, _) => vec!["-c:a", "pcm_s16be", "-write_id3v2", "1"],
+            (AudioFormat::Flac, Some(High | Ultra)) => vec!["-c:a", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24", "-compression_level", "8"],
+            (AudioFormat::Flac, _) => vec!["-c:a", "flac", "-sample_fmt", "s16", "-compression_level", "8"],
+            (AudioFormat::Auto, _) => vec!["-c:a", "copy"],
+        };
+        let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+        // Lossy formats at low quality don't need more than CD sample rate
+        if quality == Some(Low) && matches!(self, AudioFormat::Mp3 | AudioFormat::Aac | AudioFormat::Vorbis) {
+            args.extend(["-ar".to_string(), "44100".to_string()]);
+        }
+        args
+    }
+}
+
+impl std::fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Aac => "aac",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Vorbis => "ogg",
+            AudioFormat::Aiff => "aiff",
+            AudioFormat::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -426,6 +1195,17 @@ pub enum MetadataFormat {
     Xml,
 }
 
+impl MetadataFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "json" => Ok(MetadataFormat::Json),
+            "xml" => Ok(MetadataFormat::Xml),
+            _ => bail!("Invalid metadata format: {s} (try json or xml)"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ThumbnailGridLayout {
     pub cols: u32,
@@ -473,9 +1253,32 @@ impl SocialPlatform {
     }
 }
 
+impl std::fmt::Display for SocialPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SocialPlatform::Instagram => "instagram",
+            SocialPlatform::TikTok => "tiktok",
+            SocialPlatform::YoutubeShorts => "youtube-shorts",
+            SocialPlatform::Twitter => "twitter",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SocialCropShape {
     Square,
     Circle,
 }
 
+impl SocialCropShape {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "square" => Ok(SocialCropShape::Square),
+            "circle" => Ok(SocialCropShape::Circle),
+            _ => bail!("Invalid crop shape: {s} (try square or circle)"),
+        }
+    }
+}
+
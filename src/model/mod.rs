@@ -1,5 +1,17 @@
+pub mod channels;
+pub mod chapter;
+pub mod condition;
+pub mod de;
 pub mod intent;
+pub mod preflight;
+pub mod qc;
+pub mod sequence;
+pub mod subtitle;
+pub mod sentence;
+pub mod split;
 pub mod types;
+pub mod vars;
+pub mod workflow;
 
 pub use intent::*;
 pub use types::*;
@@ -0,0 +1,680 @@
+//! Pre-flight checks, run before an intent executes.
+//!
+//! Handlers find many problems only halfway through: a crop larger than the
+//! frame, a watermark logo that doesn't exist, audio taken from a file that
+//! has none. The checks here probe the inputs up front and report every
+//! problem at once, each with a suggestion where there is one:
+//!
+//! ```text
+//! 2 problems found before running:
+//!   - Input not found: vidoe.mp4
+//!     did you mean video.mp4?
+//!   - Crop 1920x1080 is larger than the 1280x720 frame of video.mp4
+//!     crop at most 1280x720, or resize first
+//! ```
+//!
+//! Warnings (e.g. a trim that ends after the video does) are printed but
+//! don't stop the run. Stream, size and time checks need ffprobe and are
+//! skipped without it.
+
+use crate::config::AppConfig;
+use crate::model::condition::{MediaFacts, Property};
+use crate::model::intent::{ConvertFormat, Intent};
+use crate::model::sequence::ImageSequence;
+use crate::model::chapter::Chapters;
+use crate::model::subtitle::{Subtitles, Timestamp};
+use crate::model::types::{BlurType, SplitMode, TargetSize, Time, VideoCodec};
+use crate::util::system::get_command_path;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The run would fail or produce something broken
+    Error,
+    /// The run works but probably not as intended
+    Warning,
+}
+
+/// Something wrong with an intent's inputs or outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Problem {
+    fn error(message: impl Into<String>) -> Self {
+        Problem { severity: Severity::Error, message: message.into(), suggestion: None }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Problem { severity: Severity::Warning, message: message.into(), suggestion: None }
+    }
+
+    fn suggest(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n    {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// What an input has to contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Needs {
+    File,
+    Video,
+    Audio,
+}
+
+/// Runs the checks for one intent
+pub struct Preflight {
+    probe: bool,
+    facts: HashMap<PathBuf, MediaFacts>,
+    problems: Vec<Problem>,
+}
+
+impl Preflight {
+    /// Checks that probe inputs with ffprobe, when it is installed
+    pub fn new() -> Self {
+        let probe = get_command_path("ffprobe").is_some_and(|p| !p.is_empty());
+        Preflight { probe, facts: HashMap::new(), problems: Vec::new() }
+    }
+
+    /// Checks that use already-known facts and never run ffprobe
+    pub fn with_facts(facts: Vec<MediaFacts>) -> Self {
+        let facts = facts.into_iter().map(|f| (f.path().to_path_buf(), f)).collect();
+        Preflight { probe: false, facts, problems: Vec::new() }
+    }
+
+    /// Every problem found with the intent, errors first
+    pub fn check(mut self, config: &AppConfig, intent: &Intent) -> Vec<Problem> {
+        let inputs = inputs(intent);
+        let mut present = Vec::new();
+        for (path, needs) in &inputs {
+            if self.input(path, *needs) {
+                present.push(path.to_path_buf());
+            }
+        }
+        let ok = |p: &Path| present.iter().any(|q| q == p);
+
+        match intent {
+            Intent::Convert { format, codec: Some(codec), .. } => {
+                if let Err(e) = format.check_codec(*codec) {
+                    self.problems.push(Problem::error(e.to_string()));
+                }
+            }
+            Intent::Crop { input, width, height } if ok(input) => {
+                if let Some((w, h)) = self.size(input) {
+                    if *width > w || *height > h {
+                        self.problems.push(Problem::error(format!(
+                            "Crop {width}x{height} is larger than the {w}x{h} frame of {}", input.display()
+                        )).suggest(format!("crop at most {w}x{h}, or resize first")));
+                    }
+                }
+            }
+            Intent::Blur { input, blur_type: BlurType::Region(region) } if ok(input) => {
+                if let Some((w, h)) = self.size(input) {
+                    if region.x + region.width > w || region.y + region.height > h {
+                        self.problems.push(Problem::error(format!(
+                            "Blur region {},{},{},{} reaches outside the {w}x{h} frame of {}",
+                            region.x, region.y, region.width, region.height, input.display()
+                        )).suggest("keep x + width and y + height within the frame"));
+                    }
+                }
+            }
+            Intent::Merge { a, b } if ok(a) && ok(b) => {
+                if let (Some((wa, ha)), Some((wb, hb))) = (self.size(a), self.size(b)) {
+                    if (wa, ha) != (wb, hb) {
+                        self.problems.push(Problem::error(format!(
+                            "{} is {wa}x{ha} but {} is {wb}x{hb}; merged videos need the same size",
+                            a.display(), b.display()
+                        )).suggest(format!("resize first: ffhuman resize {} to {wa}x{ha}", b.display())));
+                    }
+                }
+            }
+            Intent::Trim { input, start, end } | Intent::ExtractAudioRange { input, start, end, .. } => {
+                self.range(input, ok(input), start, end);
+            }
+            Intent::Thumbnail { input, time } if ok(input) => {
+                if let Some(duration) = self.duration(input) {
+                    if time.to_seconds() as f64 >= duration {
+                        self.problems.push(Problem::error(format!(
+                            "Thumbnail at {time} is past the end of {} ({} long)", input.display(), clock(duration)
+                        )).suggest(format!("pick a time before {}", clock(duration))));
+                    }
+                }
+            }
+            Intent::Fade { input, fade_in, fade_out } if ok(input) => {
+                let total = fade_in.map(|d| d.to_seconds()).unwrap_or(0.0) + fade_out.map(|d| d.to_seconds()).unwrap_or(0.0);
+                if let Some(duration) = self.duration(input) {
+                    if total > duration {
+                        self.problems.push(Problem::error(format!(
+                            "Fades of {total}s in total are longer than {} ({} long)", input.display(), clock(duration)
+                        )).suggest("shorten the fades"));
+                    }
+                }
+            }
+            Intent::Crossfade { video1, video2, duration } if ok(video1) && ok(video2) => {
+                let shortest = [self.duration(video1), self.duration(video2)].into_iter().flatten().reduce(f64::min);
+                if let Some(shortest) = shortest {
+                    if duration.to_seconds() >= shortest {
+                        self.problems.push(Problem::error(format!(
+                            "Crossfade of {duration} is as long as the shorter video ({})", clock(shortest)
+                        )).suggest("use a shorter crossfade"));
+                    }
+                }
+            }
+            Intent::Split { input, mode: SplitMode::Every(every) } if ok(input) => {
+                if let Some(duration) = self.duration(input) {
+                    if every.to_seconds() >= duration {
+                        self.problems.push(Problem::warning(format!(
+                            "Splitting every {every} gives a single part: {} is {} long", input.display(), clock(duration)
+                        )));
+                    }
+                }
+            }
+            Intent::Split { input, mode: SplitMode::Size(limit) } if ok(input) => {
+                if let Ok(metadata) = std::fs::metadata(input) {
+                    if metadata.len() <= limit.bytes {
+                        self.problems.push(Problem::error(format!(
+                            "{} is {}, already under {limit}", input.display(), TargetSize { bytes: metadata.len() }
+                        )).suggest("pick a smaller chunk size, or use the file as it is"));
+                    }
+                }
+            }
+            Intent::BurnSubtitle { subtitle: Some(_), track: Some(track), .. } => {
+                self.problems.push(Problem::error(format!(
+                    "--track picks a subtitle track of the video, but a subtitle file was given too ({track})"
+                )).suggest("leave out either the subtitle file or --track"));
+            }
+            Intent::AddSubtitles { subtitles, languages, .. } => {
+                for subtitle in subtitles.iter().filter(|s| ok(s)) {
+                    if let Err(e) = Subtitles::read(subtitle) {
+                        self.problems.push(Problem::error(format!("{e:#}")));
+                    }
+                }
+                if languages.len() > subtitles.len() {
+                    self.problems.push(Problem::error(format!(
+                        "{} languages for {} subtitle files", languages.len(), subtitles.len()
+                    )).suggest("give one language per subtitle file, in order"));
+                }
+            }
+            Intent::ConvertSubtitles { input, .. }
+            | Intent::ShiftSubtitles { input, .. }
+            | Intent::StretchSubtitles { input, .. }
+            | Intent::CutSubtitles { input, .. }
+            | Intent::SplitSubtitles { input, .. } if ok(input) => {
+                if let Err(e) = Subtitles::read(input) {
+                    self.problems.push(Problem::error(format!("{e:#}")));
+                }
+            }
+            Intent::AddChapters { input, chapters } if ok(chapters) => {
+                match Chapters::read(chapters) {
+                    Err(e) => self.problems.push(Problem::error(format!("{e:#}"))),
+                    Ok(list) => {
+                        let duration = if ok(input) { self.duration(input) } else { None };
+                        if let (Some(last), Some(duration)) = (list.chapters.last(), duration) {
+                            if last.start as f64 / 1000.0 >= duration {
+                                self.problems.push(Problem::error(format!(
+                                    "Chapter \"{}\" starts at {}, after the end of {} ({} long)",
+                                    last.title, Timestamp(last.start), input.display(), clock(duration)
+                                )).suggest("check the chapter times against the video"));
+                            }
+                        }
+                    }
+                }
+            }
+            Intent::MergeSubtitles { inputs, at } => {
+                for input in inputs.iter().filter(|input| ok(input)) {
+                    if let Err(e) = Subtitles::read(input) {
+                        self.problems.push(Problem::error(format!("{e:#}")));
+                    }
+                }
+                if !at.is_empty() && at.len() != inputs.len() {
+                    self.problems.push(Problem::error(format!(
+                        "{} start times for {} subtitle files", at.len(), inputs.len()
+                    )).suggest("give one --at time per file, or none to overlay them"));
+                }
+            }
+            Intent::CombineChannels { inputs, layout: Some(layout) } if layout.count() != inputs.len() => {
+                self.problems.push(Problem::error(format!(
+                    "{layout} has {} channels, but {} files were given", layout.count(), inputs.len()
+                )).suggest("give one mono file per channel, in the layout's order"));
+            }
+            Intent::SequenceToVideo { images, codec, .. } => {
+                if let Err(e) = ImageSequence::find(images) {
+                    self.problems.push(Problem::error(e.to_string()));
+                }
+                if *codec == Some(VideoCodec::Copy) {
+                    self.problems.push(Problem::error("Frames have to be encoded, so --codec copy doesn't apply")
+                        .suggest("leave out --codec for H.264, or pick one like prores or ffv1"));
+                }
+            }
+            Intent::ExtractFrames { input, interval } if ok(input) => {
+                if let Some(duration) = self.duration(input) {
+                    if interval.to_seconds() > duration {
+                        self.problems.push(Problem::warning(format!(
+                            "A frame every {interval} gives a single frame: {} is {} long", input.display(), clock(duration)
+                        )));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if writes_output(intent) {
+            let first = inputs.first().map(|(p, _)| *p);
+            if let Some(dir) = output_dir(config, first) {
+                self.output_dir(&dir, &present);
+            }
+        }
+
+        self.problems.sort_by_key(|p| p.severity == Severity::Warning);
+        self.problems
+    }
+
+    /// Check an input exists and has the streams it needs; false when it's missing
+    fn input(&mut self, path: &Path, needs: Needs) -> bool {
+        if !path.exists() {
+            let problem = Problem::error(format!("Input not found: {}", path.display()));
+            self.problems.push(match similar_file(path) {
+                Some(similar) => problem.suggest(format!("did you mean {}?", similar.display())),
+                None => problem,
+            });
+            return false;
+        }
+        if path.is_dir() {
+            self.problems.push(Problem::error(format!("{} is a folder, not a file", path.display()))
+                .suggest("use batch or watch to process every file in a folder"));
+            return false;
+        }
+        let property = match needs {
+            Needs::File => return true,
+            Needs::Video => Property::HasVideo,
+            Needs::Audio => Property::HasAudio,
+        };
+        // Only trust a missing stream when the file could be probed at all
+        let missing = self.facts(path)
+            .and_then(|f| f.number(Property::Duration).and(f.number(property))) == Some(0.0);
+        if missing {
+            let problem = match needs {
+                Needs::Video => Problem::error(format!("{} has no video stream", path.display()))
+                    .suggest("this operation needs a video or image input"),
+                _ => Problem::error(format!("{} has no audio stream", path.display()))
+                    .suggest("pick a file with sound, or check it with: ffhuman info"),
+            };
+            self.problems.push(problem);
+        }
+        true
+    }
+
+    fn range(&mut self, input: &Path, present: bool, start: &Time, end: &Time) {
+        if start.to_seconds() >= end.to_seconds() {
+            self.problems.push(Problem::error(format!("The range {start} to {end} is empty"))
+                .suggest("the end has to come after the start"));
+            return;
+        }
+        let Some(duration) = present.then(|| self.duration(input)).flatten() else {
+            return;
+        };
+        if start.to_seconds() as f64 >= duration {
+            self.problems.push(Problem::error(format!(
+                "The range starts at {start}, after the end of {} ({} long)", input.display(), clock(duration)
+            )).suggest(format!("start before {}", clock(duration))));
+        } else if end.to_seconds() as f64 > duration.ceil() {
+            self.problems.push(Problem::warning(format!(
+                "The range ends at {end} but {} is only {} long; the result stops there",
+                input.display(), clock(duration)
+            )));
+        }
+    }
+
+    /// Check the output directory (or the folder it will be created in) is
+    /// writable and has room for at least the size of the inputs
+    fn output_dir(&mut self, dir: &Path, inputs: &[PathBuf]) {
+        let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+            return;
+        };
+        if !existing.is_dir() {
+            self.problems.push(Problem::error(format!("Output folder {} is a file", existing.display()))
+                .suggest("choose another with --output-dir"));
+            return;
+        }
+        if let Err(e) = tempfile::Builder::new().prefix(".ffhuman-check").tempfile_in(existing) {
+            self.problems.push(Problem::error(format!("Can't write to {}: {}", existing.display(), e.kind()))
+                .suggest("choose another folder with --output-dir"));
+            return;
+        }
+        let needed: u64 = inputs.iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum();
+        if let Some(free) = free_space(existing) {
+            if free < needed {
+                self.problems.push(Problem::warning(format!(
+                    "Only {} free in {}, less than the inputs take ({})",
+                    TargetSize { bytes: free }, existing.display(), TargetSize { bytes: needed }
+                )).suggest("free some space or choose another folder with --output-dir"));
+            }
+        }
+    }
+
+    fn facts(&mut self, path: &Path) -> Option<&mut MediaFacts> {
+        if !self.facts.contains_key(path) {
+            if !self.probe {
+                return None;
+            }
+            self.facts.insert(path.to_path_buf(), MediaFacts::new(path));
+        }
+        self.facts.get_mut(path)
+    }
+
+    fn size(&mut self, path: &Path) -> Option<(u32, u32)> {
+        let facts = self.facts(path)?;
+        let width = facts.number(Property::Width)? as u32;
+        let height = facts.number(Property::Height)? as u32;
+        (width > 0 && height > 0).then_some((width, height))
+    }
+
+    fn duration(&mut self, path: &Path) -> Option<f64> {
+        self.facts(path)?.number(Property::Duration).filter(|d| *d > 0.0)
+    }
+}
+
+impl Default for Preflight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run the checks and fail with all errors at once; warnings are printed
+pub fn ensure_ready(config: &AppConfig, intent: &Intent) -> Result<()> {
+    let (errors, warnings): (Vec<Problem>, Vec<Problem>) = Preflight::new().check(config, intent)
+        .into_iter()
+        .partition(|p| p.severity == Severity::Error);
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let mut report = match errors.len() {
+        1 => "1 problem found before running:".to_string(),
+        n => format!("{n} problems found before running:"),
+    };
+    for error in &errors {
+        report.push_str(&format!("\n  - {}", error));
+    }
+    report.push_str("\n(use --no-checks to run anyway)");
+    bail!(report)
+}
+
+/// The files an intent reads, with what each has to contain. Paths a
+/// workflow fills in later (empty) are left out.
+fn inputs(intent: &Intent) -> Vec<(&Path, Needs)> {
+    use Needs::*;
+    let mut inputs: Vec<(&PathBuf, Needs)> = Vec::new();
+    match intent {
+        Intent::Resize { input, .. }
+        | Intent::Rotate { input, .. }
+        | Intent::Flip { input, .. }
+        | Intent::Thumbnail { input, .. }
+        | Intent::Crop { input, .. }
+        | Intent::SetFps { input, .. }
+        | Intent::Grayscale { input }
+        | Intent::Stabilize { input }
+        | Intent::Denoise { input }
+        | Intent::AddText { input, .. }
+        | Intent::Filter { input, .. }
+        | Intent::Blur { input, .. }
+        | Intent::ExtractFrames { input, .. }
+        | Intent::VideoToSequence { input, .. }
+        | Intent::Timelapse { input, .. }
+        | Intent::RemoveBackground { input, .. }
+        | Intent::DetectScenes { input }
+        | Intent::DetectBlack { input }
+        | Intent::FixRotation { input }
+        | Intent::MotionBlur { input, .. }
+        | Intent::Vignette { input, .. }
+        | Intent::LensCorrect { input }
+        | Intent::Interpolate { input, .. }
+        | Intent::ThumbnailGrid { input, .. }
+        | Intent::SocialCrop { input, .. }
+        | Intent::VerticalConvert { input }
+        | Intent::StoryFormat { input }
+        | Intent::Glitch { input, .. }
+        | Intent::VintageFilm { input, .. }
+        | Intent::Mirror { input, .. }
+        | Intent::ColorGrade { input, .. }
+        | Intent::AnimatedText { input, .. }
+        | Intent::AddTimecode { input }
+        | Intent::Proxy { input }
+        | Intent::AnimatedGif { input, .. }
+        | Intent::Tile { input, .. }
+        | Intent::ExtractKeyframes { input }
+        | Intent::Convert360 { input }
+        | Intent::ConvertHdrToSdr { input }
+        | Intent::FixFramerate { input } => inputs.push((input, Video)),
+        Intent::ExtractAudio { input, .. }
+        | Intent::ExtractAudioRange { input, .. }
+        | Intent::AdjustVolume { input, .. }
+        | Intent::SyncAudio { input, .. }
+        | Intent::Normalize { input, .. }
+        | Intent::DetectSilence { input }
+        | Intent::AnalyzeLoudness { input }
+        | Intent::QcAudio { input, .. }
+        | Intent::Downmix { input }
+        | Intent::ToMono { input, .. }
+        | Intent::SwapChannels { input }
+        | Intent::SplitChannels { input }
+        | Intent::NoiseReduction { input }
+        | Intent::EchoRemoval { input }
+        | Intent::AudioDucking { input }
+        | Intent::AudioEqualizer { input, .. }
+        | Intent::VoiceIsolation { input }
+        | Intent::AudioSpeedKeepPitch { input, .. } => inputs.push((input, Audio)),
+        Intent::Convert { input, format, .. } => inputs.push((input, match format {
+            _ if format.audio_format().is_some() => Audio,
+            ConvertFormat::Mp4 | ConvertFormat::Webm | ConvertFormat::Mkv | ConvertFormat::Mov => File,
+            _ => Video,
+        })),
+        Intent::Watermark { input, logo, .. } => {
+            inputs.push((input, Video));
+            inputs.push((logo, Video));
+        }
+        Intent::BurnSubtitle { input, subtitle, .. } => {
+            inputs.push((input, Video));
+            if let Some(subtitle) = subtitle {
+                inputs.push((subtitle, File));
+            }
+        }
+        Intent::AddSubtitles { input, subtitles, .. } => {
+            inputs.push((input, Video));
+            inputs.extend(subtitles.iter().map(|subtitle| (subtitle, File)));
+        }
+        Intent::ExtractSubtitles { input, .. }
+        | Intent::ConvertSubtitles { input, .. }
+        | Intent::ShiftSubtitles { input, .. }
+        | Intent::StretchSubtitles { input, .. }
+        | Intent::CutSubtitles { input, .. }
+        | Intent::SplitSubtitles { input, .. } => inputs.push((input, File)),
+        Intent::AddChapters { input, chapters } => {
+            inputs.push((input, File));
+            inputs.push((chapters, File));
+        }
+        Intent::ListChapters { input, .. } | Intent::ExtractChapters { input } => inputs.push((input, File)),
+        Intent::ChaptersFromScenes { input, scenes, .. } => {
+            inputs.push((input, Video));
+            if let Some(scenes) = scenes {
+                inputs.push((scenes, File));
+            }
+        }
+        Intent::CombineChannels { inputs: files, .. } => inputs.extend(files.iter().map(|file| (file, Audio))),
+        Intent::MergeSubtitles { inputs: files, .. } => inputs.extend(files.iter().map(|file| (file, File))),
+        Intent::MixAudio { audio1, audio2 } => {
+            inputs.push((audio1, Audio));
+            inputs.push((audio2, Audio));
+        }
+        Intent::AddAudio { audio, video } => {
+            inputs.push((video, Video));
+            inputs.push((audio, Audio));
+        }
+        Intent::Merge { a: video1, b: video2 }
+        | Intent::Compare { video1, video2, .. }
+        | Intent::Crossfade { video1, video2, .. }
+        | Intent::SplitScreen { video1, video2, .. }
+        | Intent::Transition { video1, video2, .. }
+        | Intent::Pip { base_video: video1, overlay_video: video2, .. }
+        | Intent::Overlay { base_video: video1, overlay_video: video2, .. } => {
+            inputs.push((video1, Video));
+            inputs.push((video2, Video));
+        }
+        Intent::Montage { videos, .. }
+        | Intent::Concat { videos }
+        | Intent::SyncCameras { videos }
+        | Intent::Collage { videos, .. }
+        | Intent::Slideshow { images: videos, .. } => inputs.extend(videos.iter().map(|v| (v, Video))),
+        Intent::Visualize { audio, .. } => inputs.push((audio, Audio)),
+        Intent::ApplyTemplate { input, template_file, .. } => {
+            inputs.push((input, File));
+            inputs.push((template_file, File));
+        }
+        Intent::Pipeline { input, steps_file, .. } => {
+            inputs.push((input, File));
+            inputs.push((steps_file, File));
+        }
+        Intent::Workflow { config_file, .. } => inputs.push((config_file, File)),
+        Intent::Shell { file: Some(file) } => inputs.push((file, File)),
+        Intent::Compress { input, .. }
+        | Intent::Trim { input, .. }
+        | Intent::SpeedUp { input, .. }
+        | Intent::SlowDown { input, .. }
+        | Intent::Reverse { input }
+        | Intent::Mute { input }
+        | Intent::Loop { input, .. }
+        | Intent::Fade { input, .. }
+        | Intent::Split { input, .. }
+        | Intent::SetMetadata { input, .. }
+        | Intent::AnalyzeQuality { input }
+        | Intent::Preview { input }
+        | Intent::SuggestFormat { input }
+        | Intent::ExtractMetadata { input, .. }
+        | Intent::Info { input }
+        | Intent::SocialMediaConvert { input, .. }
+        | Intent::ExportEdl { input }
+        | Intent::ConvertColorspace { input, .. }
+        | Intent::DetectDuplicates { input }
+        | Intent::Repair { input }
+        | Intent::Validate { input }
+        | Intent::Stats { input } => inputs.push((input, File)),
+        _ => {}
+    }
+    inputs.into_iter()
+        .filter(|(p, _)| !p.as_os_str().is_empty())
+        .map(|(p, needs)| (p.as_path(), needs))
+        .collect()
+}
+
+/// Intents that only read their inputs (or handle outputs themselves)
+fn writes_output(intent: &Intent) -> bool {
+    !matches!(
+        intent,
+        Intent::Info { .. }
+            | Intent::Stats { .. }
+            | Intent::Validate { .. }
+            | Intent::Preview { .. }
+            | Intent::SuggestFormat { .. }
+            | Intent::Compare { .. }
+            | Intent::DetectScenes { .. }
+            | Intent::ListChapters { .. }
+            | Intent::DetectBlack { .. }
+            | Intent::DetectSilence { .. }
+            | Intent::DetectDuplicates { .. }
+            | Intent::AnalyzeQuality { .. }
+            | Intent::AnalyzeLoudness { .. }
+            | Intent::Shell { .. }
+            | Intent::Doctor
+    )
+}
+
+/// Where outputs will go: the folder of --out, --output-dir, or next to the first input
+fn output_dir(config: &AppConfig, first_input: Option<&Path>) -> Option<PathBuf> {
+    let dir = match (&config.out, &config.output_dir) {
+        (Some(out), _) => out.parent()?.to_path_buf(),
+        (None, Some(dir)) => dir.clone(),
+        (None, None) => first_input?.parent()?.to_path_buf(),
+    };
+    // Template variables in the folder are only known per file
+    if dir.to_string_lossy().contains('{') {
+        return None;
+    }
+    Some(if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir })
+}
+
+/// A file next to a missing one with a close name: the same stem with
+/// another extension, or a name a couple of typos away
+fn similar_file(missing: &Path) -> Option<PathBuf> {
+    let name = missing.file_name()?.to_string_lossy().to_lowercase();
+    let stem = missing.file_stem()?.to_string_lossy().to_lowercase();
+    let dir = match missing.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut best: Option<(usize, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let candidate = entry.file_name().to_string_lossy().to_lowercase();
+        let same_stem = path.file_stem().map(|s| s.to_string_lossy().to_lowercase()) == Some(stem.clone());
+        let distance = if same_stem { 0 } else { edit_distance(&name, &candidate) };
+        if distance <= 2 && best.as_ref().is_none_or(|(d, _)| distance < *d) {
+            best = Some((distance, missing.with_file_name(entry.file_name())));
+        }
+    }
+    best.map(|(_, path)| path)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Free bytes on the filesystem holding `dir`, from `df` (None where it isn't available)
+fn free_space(dir: &Path) -> Option<u64> {
+    let out = Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let available: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available * 1024)
+}
+
+/// Seconds as a time, e.g. 90.4 -> "1:30"
+fn clock(seconds: f64) -> String {
+    let total = seconds.floor() as u32;
+    Time { hours: total / 3600, minutes: total / 60 % 60, seconds: total % 60 }.to_string()
+}
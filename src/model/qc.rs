@@ -0,0 +1,303 @@
+//! The audio report `qc-audio` builds from one decoding pass, and the checks
+//! that decide whether a file meets a loudness spec.
+//!
+//! ffmpeg runs ebur128, astats, silencedetect and (for stereo) aphasemeter
+//! over the audio and logs what they measure; everything here is parsed
+//! from that log:
+//!
+//! ```text
+//! [Parsed_ebur128_0 @ 0x..] t: 1.2   TARGET:-23 LUFS    M: -21.4 S: -22.0   I: -22.3 LUFS ...
+//! [Parsed_astats_1 @ 0x..] Peak level dB: -0.000000
+//! [silencedetect @ 0x..] silence_start: 58.2
+//! [Parsed_ametadata_4 @ 0x..] lavfi.aphasemeter.phase=0.912
+//! ```
+
+use crate::ffmpeg::probe::parse_silencedetect;
+use crate::model::LoudnessTarget;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// silencedetect settings for head and tail silence: near-digital silence
+pub const SILENCE_NOISE: f64 = -50.0;
+pub const SILENCE_MIN: f64 = 0.1;
+
+/// Loudness over time is summarized in windows this long (seconds)
+const WINDOW: f64 = 10.0;
+
+/// Peaks at or above this (dBFS) count as clipped
+const CLIP_LEVEL: f64 = -0.1;
+
+/// Largest DC offset (as a fraction of full scale) that passes
+const MAX_DC_OFFSET: f64 = 0.001;
+
+/// The loudest point of a loudness curve
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessPeak {
+    /// Seconds into the file
+    pub time: f64,
+    pub lufs: f64,
+}
+
+/// Momentary and short-term maxima over one window of the file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessWindow {
+    pub start: f64,
+    pub momentary_max: f64,
+    pub short_term_max: f64,
+}
+
+/// Phase correlation between left and right, -1 (opposed) to 1 (mono)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PhaseCorrelation {
+    pub mean: f64,
+    pub min: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioQc {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// Loudness range in LU
+    pub range: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+    pub momentary_max: Option<LoudnessPeak>,
+    pub short_term_max: Option<LoudnessPeak>,
+    pub timeline: Vec<LoudnessWindow>,
+    /// Samples at full scale, summed over channels
+    pub clipped_samples: u64,
+    /// Largest DC offset of any channel, as a fraction of full scale
+    pub dc_offset: f64,
+    /// Stereo files only
+    pub phase_correlation: Option<PhaseCorrelation>,
+    /// Seconds of silence before the first sound and after the last
+    pub head_silence: f64,
+    pub tail_silence: f64,
+}
+
+impl AudioQc {
+    /// Parse the log of the `qc-audio` filter chain over a file `duration` seconds long
+    pub fn parse(log: &str, duration: f64) -> Result<Self> {
+        let (integrated, range, true_peak) = parse_ebur128_summary(log)?;
+        let frames = parse_ebur128_frames(log);
+        let loudest = |value: fn(&(f64, f64, f64)) -> f64| {
+            frames.iter()
+                .filter(|f| value(f).is_finite())
+                .max_by(|a, b| value(a).total_cmp(&value(b)))
+                .map(|f| LoudnessPeak { time: f.0, lufs: value(f) })
+        };
+        let (clipped_samples, dc_offset) = parse_astats(log);
+
+        let silences = parse_silencedetect(log);
+        let head_silence = silences.first()
+            .filter(|(start, _)| *start <= SILENCE_MIN)
+            .map(|(_, end)| end.unwrap_or(duration))
+            .unwrap_or(0.0);
+        let tail_silence = silences.last()
+            .filter(|(_, end)| end.is_none_or(|end| end >= duration - SILENCE_MIN))
+            .map(|(start, _)| (duration - start).max(0.0))
+            .unwrap_or(0.0);
+
+        Ok(AudioQc {
+            integrated,
+            range,
+            true_peak,
+            momentary_max: loudest(|f| f.1),
+            short_term_max: loudest(|f| f.2),
+            timeline: windows(&frames),
+            clipped_samples,
+            dc_offset,
+            phase_correlation: parse_phase(log),
+            head_silence,
+            tail_silence,
+        })
+    }
+
+    /// Check the measurements against `spec`; head and tail silence only
+    /// when a longest allowed silence is given
+    pub fn check(&self, spec: &LoudnessTarget, max_silence: Option<f64>) -> Vec<QcCheck> {
+        let target = spec.integrated();
+        let tolerance = spec.tolerance();
+        let mut checks = vec![
+            QcCheck {
+                name: "integrated loudness",
+                measured: format!("{:.1} LUFS", self.integrated),
+                expected: format!("{target} ±{tolerance} LUFS"),
+                pass: (self.integrated - target).abs() <= tolerance,
+            },
+            QcCheck {
+                name: "true peak",
+                measured: format!("{:.1} dBTP", self.true_peak),
+                expected: format!("<= {} dBTP", spec.true_peak()),
+                pass: self.true_peak <= spec.true_peak(),
+            },
+            QcCheck {
+                name: "clipping",
+                measured: format!("{} samples", self.clipped_samples),
+                expected: "0 samples".to_string(),
+                pass: self.clipped_samples == 0,
+            },
+            QcCheck {
+                name: "DC offset",
+                measured: format!("{:.6}", self.dc_offset),
+                expected: format!("<= {MAX_DC_OFFSET}"),
+                pass: self.dc_offset <= MAX_DC_OFFSET,
+            },
+        ];
+        if let Some(phase) = self.phase_correlation {
+            checks.push(QcCheck {
+                name: "phase correlation",
+                measured: format!("{:.2} mean", phase.mean),
+                expected: ">= 0".to_string(),
+                pass: phase.mean >= 0.0,
+            });
+        }
+        if let Some(max) = max_silence {
+            for (name, silence) in [("head silence", self.head_silence), ("tail silence", self.tail_silence)] {
+                checks.push(QcCheck {
+                    name,
+                    measured: format!("{silence:.2}s"),
+                    expected: format!("<= {max}s"),
+                    pass: silence <= max,
+                });
+            }
+        }
+        checks
+    }
+}
+
+/// One line of the verdict
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QcCheck {
+    pub name: &'static str,
+    pub measured: String,
+    pub expected: String,
+    pub pass: bool,
+}
+
+impl fmt::Display for QcCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.pass { "PASS" } else { "FAIL" };
+        write!(f, "{verdict}  {:<20} {} (expected {})", self.name, self.measured, self.expected)
+    }
+}
+
+/// The report written as JSON next to the input
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QcReport {
+    pub source: String,
+    pub spec: String,
+    pub pass: bool,
+    pub checks: Vec<QcCheck>,
+    pub measurements: AudioQc,
+}
+
+impl QcReport {
+    pub fn new(source: &Path, spec: &LoudnessTarget, measurements: AudioQc, max_silence: Option<f64>) -> Self {
+        let checks = measurements.check(spec, max_silence);
+        QcReport {
+            source: source.to_string_lossy().to_string(),
+            spec: spec.to_string(),
+            pass: checks.iter().all(|c| c.pass),
+            checks,
+            measurements,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("failed to serialize the QC report")?;
+        std::fs::write(path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Integrated loudness, loudness range and true peak from ebur128's summary
+fn parse_ebur128_summary(log: &str) -> Result<(f64, f64, f64)> {
+    let summary = log.rsplit_once("Summary:")
+        .map(|(_, summary)| summary)
+        .ok_or_else(|| anyhow!("No ebur128 summary in ffmpeg output"))?;
+    let value = |pattern: &str, name: &str| -> Result<f64> {
+        Regex::new(pattern).ok()
+            .and_then(|re| re.captures(summary))
+            .and_then(|c| c[1].parse().ok())
+            .ok_or_else(|| anyhow!("ebur128 summary has no {name}"))
+    };
+    Ok((
+        value(r"(?m)^\s*I:\s+(\S+)\s+LUFS", "integrated loudness")?,
+        value(r"(?m)^\s*LRA:\s+(\S+)\s+LU\b", "loudness range")?,
+        value(r"(?m)^\s*Peak:\s+(\S+)\s+dBFS", "true peak")?,
+    ))
+}
+
+/// (time, momentary, short-term) of each ebur128 frame line
+fn parse_ebur128_frames(log: &str) -> Vec<(f64, f64, f64)> {
+    let Ok(re) = Regex::new(r"\bt:\s*(\S+)\s.*\bM:\s*(\S+)\s+S:\s*(\S+)") else {
+        return Vec::new();
+    };
+    log.lines()
+        .filter(|line| line.contains("ebur128"))
+        .filter_map(|line| {
+            let c = re.captures(line)?;
+            Some((c[1].parse().ok()?, c[2].parse().ok()?, c[3].parse().ok()?))
+        })
+        .collect()
+}
+
+/// Frames grouped into windows, keeping the loudest of each
+fn windows(frames: &[(f64, f64, f64)]) -> Vec<LoudnessWindow> {
+    let mut windows: Vec<LoudnessWindow> = Vec::new();
+    for &(time, momentary, short_term) in frames {
+        let start = (time / WINDOW).floor() * WINDOW;
+        match windows.last_mut() {
+            Some(window) if window.start == start => {
+                window.momentary_max = window.momentary_max.max(momentary);
+                window.short_term_max = window.short_term_max.max(short_term);
+            }
+            _ => windows.push(LoudnessWindow { start, momentary_max: momentary, short_term_max: short_term }),
+        }
+    }
+    windows
+}
+
+/// Clipped samples and the largest DC offset from astats' per-channel
+/// stats. astats doesn't count clipping as such: a channel whose peak is at
+/// full scale counts its samples at that peak.
+fn parse_astats(log: &str) -> (u64, f64) {
+    let mut clipped = 0;
+    let mut dc_offset: f64 = 0.0;
+    let mut peak_db = f64::NEG_INFINITY;
+    for line in log.lines().filter(|line| line.contains("astats")) {
+        let Some((_, stat)) = line.split_once("] ") else { continue };
+        // Overall figures are averages over channels; the channels' own are enough
+        if stat.trim() == "Overall" {
+            break;
+        }
+        let Some((key, value)) = stat.split_once(':') else { continue };
+        let Ok(value) = value.trim().parse::<f64>() else { continue };
+        match key.trim() {
+            "Channel" => peak_db = f64::NEG_INFINITY,
+            "DC offset" => dc_offset = dc_offset.max(value.abs()),
+            "Peak level dB" => peak_db = value,
+            "Peak count" if peak_db >= CLIP_LEVEL => clipped += value as u64,
+            _ => {}
+        }
+    }
+    (clipped, dc_offset)
+}
+
+/// Mean and lowest phase correlation from the aphasemeter values ametadata prints
+fn parse_phase(log: &str) -> Option<PhaseCorrelation> {
+    let values: Vec<f64> = log.lines()
+        .filter_map(|line| line.split("lavfi.aphasemeter.phase=").nth(1))
+        .filter_map(|value| value.trim().parse().ok())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(PhaseCorrelation {
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        min: values.iter().copied().fold(f64::INFINITY, f64::min),
+    })
+}
@@ -0,0 +1,898 @@
+//! Free-form commands written as one sentence, e.g.
+//! `ffhuman "make video.mp4 a 480p gif without sound, 10 seconds from 1:00"`.
+//!
+//! This is a small offline grammar: the sentence is split into words, known
+//! phrases and their synonyms are matched to operations, and the matches
+//! become intents applied in a fixed order (cut, speed, audio, picture,
+//! size, format). Anything the grammar had to guess, and any word it didn't
+//! understand, is kept so the caller can ask before running.
+
+use crate::model::intent::{AudioFormat, ConvertFormat, Intent};
+use crate::model::types::{
+    CompressTarget, FlipDirection, LoudnessTarget, QualityPreset, ResizeTarget, RotateDegrees, SpeedFactor, TargetSize, Time,
+    VolumeAdjustment,
+};
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Dropped before matching, so "remove the audio" reads as "remove audio"
+const ARTICLES: &[&str] = &["a", "an", "the", "please"];
+
+/// Words that carry no operation of their own
+const FILLERS: &[&str] = &[
+    "make", "turn", "convert", "change", "into", "to", "as", "in", "with", "and", "of", "me", "my", "this",
+    "that", "it", "its", "file", "video", "clip", "movie", "version", "so", "then", "also", "i", "want",
+    "would", "like", "can", "you", "could", "save", "create", "give", "get", "from", "at", "for", "by", "be",
+    "is", "should", "only", "just", "format", "called", "named", "take", "use", "output",
+];
+
+/// Words that drop a range rather than keep it: "cut the first 10 seconds"
+const REMOVE: &[&str] = &["cut", "remove", "drop", "delete", "skip"];
+
+/// Words that end a time range: "from 1:00 to 2:00"
+const RANGE_TO: &[&str] = &["to", "until", "till", "til", "through", "thru", "-", "and"];
+
+/// Words that introduce a start time: "10 seconds from 1:00"
+const START_AT: &[&str] = &["from", "starting", "start", "beginning", "at"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phrase {
+    Mute,
+    AudioOnly,
+    Normalize,
+    Reverse,
+    Grayscale,
+    Stabilize,
+    Faster,
+    Slower,
+    Louder,
+    Quieter,
+    Volume,
+    Compress,
+    Rotate,
+    Trim,
+    UpsideDown,
+    FlipHorizontal,
+    FlipVertical,
+    Flip,
+}
+
+/// Phrases and their synonyms, after articles are dropped
+const PHRASES: &[(&str, Phrase)] = &[
+    ("without sound", Phrase::Mute),
+    ("without audio", Phrase::Mute),
+    ("no sound", Phrase::Mute),
+    ("no audio", Phrase::Mute),
+    ("remove audio", Phrase::Mute),
+    ("remove sound", Phrase::Mute),
+    ("strip audio", Phrase::Mute),
+    ("drop audio", Phrase::Mute),
+    ("cut audio", Phrase::Mute),
+    ("cut sound", Phrase::Mute),
+    ("mute", Phrase::Mute),
+    ("muted", Phrase::Mute),
+    ("silent", Phrase::Mute),
+    ("audio only", Phrase::AudioOnly),
+    ("only audio", Phrase::AudioOnly),
+    ("just audio", Phrase::AudioOnly),
+    ("sound only", Phrase::AudioOnly),
+    ("extract audio", Phrase::AudioOnly),
+    ("normalize", Phrase::Normalize),
+    ("normalise", Phrase::Normalize),
+    ("normalized", Phrase::Normalize),
+    ("normalize audio", Phrase::Normalize),
+    ("even out audio", Phrase::Normalize),
+    ("fix audio levels", Phrase::Normalize),
+    ("reverse", Phrase::Reverse),
+    ("reversed", Phrase::Reverse),
+    ("backwards", Phrase::Reverse),
+    ("play backwards", Phrase::Reverse),
+    ("black and white", Phrase::Grayscale),
+    ("grayscale", Phrase::Grayscale),
+    ("greyscale", Phrase::Grayscale),
+    ("monochrome", Phrase::Grayscale),
+    ("stabilize", Phrase::Stabilize),
+    ("stabilise", Phrase::Stabilize),
+    ("stabilized", Phrase::Stabilize),
+    ("remove shake", Phrase::Stabilize),
+    ("less shaky", Phrase::Stabilize),
+    ("speed up", Phrase::Faster),
+    ("sped up", Phrase::Faster),
+    ("faster", Phrase::Faster),
+    ("twice as fast", Phrase::Faster),
+    ("double speed", Phrase::Faster),
+    ("slow down", Phrase::Slower),
+    ("slower", Phrase::Slower),
+    ("slow motion", Phrase::Slower),
+    ("slow-motion", Phrase::Slower),
+    ("slowmo", Phrase::Slower),
+    ("half speed", Phrase::Slower),
+    ("louder", Phrase::Louder),
+    ("boost volume", Phrase::Louder),
+    ("boost audio", Phrase::Louder),
+    ("turn up volume", Phrase::Louder),
+    ("increase volume", Phrase::Louder),
+    ("volume up", Phrase::Louder),
+    ("quieter", Phrase::Quieter),
+    ("softer", Phrase::Quieter),
+    ("turn down volume", Phrase::Quieter),
+    ("lower volume", Phrase::Quieter),
+    ("reduce volume", Phrase::Quieter),
+    ("decrease volume", Phrase::Quieter),
+    ("volume down", Phrase::Quieter),
+    ("volume", Phrase::Volume),
+    ("compress", Phrase::Compress),
+    ("compressed", Phrase::Compress),
+    ("shrink", Phrase::Compress),
+    ("smaller", Phrase::Compress),
+    ("rotate", Phrase::Rotate),
+    ("rotated", Phrase::Rotate),
+    ("trim", Phrase::Trim),
+    ("trimmed", Phrase::Trim),
+    ("cut", Phrase::Trim),
+    ("upside down", Phrase::UpsideDown),
+    ("flip horizontally", Phrase::FlipHorizontal),
+    ("flip horizontal", Phrase::FlipHorizontal),
+    ("mirror", Phrase::FlipHorizontal),
+    ("mirrored", Phrase::FlipHorizontal),
+    ("flip vertically", Phrase::FlipVertical),
+    ("flip vertical", Phrase::FlipVertical),
+    ("flip", Phrase::Flip),
+];
+
+/// Words before a target size: "under 10mb"
+const SIZE_LIMIT: &[&str] = &["under", "below", "within", "max", "maximum", "less", "than", "at", "most", "fit", "in", "to"];
+
+/// How a sentence was understood
+#[derive(Debug, Clone)]
+pub struct Sentence {
+    pub input: PathBuf,
+    /// Where the final result goes, when the sentence names it ("... as clip.gif")
+    pub output: Option<PathBuf>,
+    /// The operations in the order they run, inputs left empty: the first
+    /// reads `input` and each later one the previous result
+    pub steps: Vec<Intent>,
+    /// Each step as the equivalent command, without the input file
+    pub summaries: Vec<String>,
+    /// Guesses the grammar made
+    pub assumptions: Vec<String>,
+    /// Words that weren't understood
+    pub ignored: Vec<String>,
+}
+
+/// Whether a sentence can begin with `word`: a file, or a word the grammar
+/// reads. Anything else that resembles a command is more likely a typo.
+pub fn starts_sentence(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let known = |words: &[&str]| words.contains(&word.as_str());
+    word.contains(['.', '/', '\\', ':'])
+        || word.parse::<f64>().is_ok()
+        || known(ARTICLES)
+        || known(FILLERS)
+        || known(START_AT)
+        || known(&["between", "first", "last"])
+        || PHRASES.iter().any(|(text, _)| text.split(' ').next() == Some(word.as_str()))
+}
+
+impl Sentence {
+    /// Parse a sentence. Fails when it names no file, nothing to do, or
+    /// contradicts itself.
+    pub fn parse(text: &str) -> Result<Self> {
+        Parser::new(text)?.run()
+    }
+
+    /// Whether the interpretation involved guessing and should be confirmed
+    pub fn is_ambiguous(&self) -> bool {
+        !self.assumptions.is_empty() || !self.ignored.is_empty()
+    }
+
+    /// A readable account of the interpretation
+    pub fn describe(&self) -> String {
+        let mut out = format!("Input: {}\n", self.input.display());
+        for (idx, summary) in self.summaries.iter().enumerate() {
+            out.push_str(&format!("  {}. {}\n", idx + 1, summary));
+        }
+        if let Some(output) = &self.output {
+            out.push_str(&format!("Output: {}\n", output.display()));
+        }
+        for assumption in &self.assumptions {
+            out.push_str(&format!("Assuming {}\n", assumption));
+        }
+        if !self.ignored.is_empty() {
+            out.push_str(&format!("Not understood: {}\n", self.ignored.join(" ")));
+        }
+        out.trim_end().to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Speed {
+    Up(SpeedFactor),
+    Down(SpeedFactor),
+}
+
+/// Operations found so far, one slot each
+#[derive(Default)]
+struct Plan {
+    trim: Option<(u32, u32)>,
+    /// The sentence says "trim", so it must also name a range
+    trim_asked: bool,
+    speed: Option<Speed>,
+    reverse: bool,
+    mute: bool,
+    audio_only: bool,
+    normalize: bool,
+    volume: Option<VolumeAdjustment>,
+    rotate: Option<RotateDegrees>,
+    flip: Option<FlipDirection>,
+    grayscale: bool,
+    stabilize: bool,
+    fps: Option<u32>,
+    resize: Option<ResizeTarget>,
+    compress: Option<(CompressTarget, String)>,
+    format: Option<ConvertFormat>,
+}
+
+/// Set a slot once; a second, different value is a contradiction
+fn set<T: PartialEq>(slot: &mut Option<T>, value: T, what: &str) -> Result<()> {
+    match slot {
+        Some(existing) if *existing != value => bail!("The sentence asks for two different {what}"),
+        _ => {
+            *slot = Some(value);
+            Ok(())
+        }
+    }
+}
+
+fn time(seconds: u32) -> Time {
+    Time { hours: seconds / 3600, minutes: seconds % 3600 / 60, seconds: seconds % 60 }
+}
+
+struct Parser {
+    text: String,
+    /// Words as written (file names keep their case)
+    words: Vec<String>,
+    lower: Vec<String>,
+    used: Vec<bool>,
+    plan: Plan,
+    files: Vec<(usize, PathBuf)>,
+    assumptions: Vec<String>,
+    ignored: Vec<String>,
+    units: Regex,
+    file_name: Regex,
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Self> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c| matches!(c, ',' | ';' | '!' | '?' | '"' | '\'')))
+            .map(|w| w.strip_suffix('.').filter(|s| !s.contains('.')).unwrap_or(w))
+            .filter(|w| !w.is_empty() && !ARTICLES.contains(&w.to_lowercase().as_str()))
+            .map(str::to_string)
+            .collect();
+        let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        Ok(Self {
+            text: text.trim().to_string(),
+            used: vec![false; words.len()],
+            words,
+            lower,
+            plan: Plan::default(),
+            files: Vec::new(),
+            assumptions: Vec::new(),
+            ignored: Vec::new(),
+            units: Regex::new(r"^(?:(\d+(?:\.\d+)?)h)?(?:(\d+(?:\.\d+)?)m(?:in|ins)?)?(?:(\d+(?:\.\d+)?)s(?:ec|ecs)?)?$")
+                .map_err(|e| anyhow!("Invalid regex: {}", e))?,
+            file_name: Regex::new(r"^[^\s]*[^\s.]\.[A-Za-z][A-Za-z0-9]{1,4}$")
+                .map_err(|e| anyhow!("Invalid regex: {}", e))?,
+        })
+    }
+
+    fn run(mut self) -> Result<Sentence> {
+        self.find_files();
+        self.find_ranges()?;
+        self.find_phrases()?;
+        self.find_values()?;
+        self.build()
+    }
+
+    fn word(&self, i: usize) -> Option<&str> {
+        self.lower.get(i).filter(|_| !self.used[i]).map(String::as_str)
+    }
+
+    fn is(&self, i: usize, options: &[&str]) -> bool {
+        self.word(i).is_some_and(|w| options.contains(&w))
+    }
+
+    fn take(&mut self, range: std::ops::Range<usize>) {
+        for i in range {
+            self.used[i] = true;
+        }
+    }
+
+    fn find_files(&mut self) {
+        for i in 0..self.words.len() {
+            let word = &self.words[i];
+            if self.file_name.is_match(word) || (word.contains(['/', '\\']) && Path::new(word).exists()) {
+                self.files.push((i, PathBuf::from(word)));
+                self.used[i] = true;
+            }
+        }
+    }
+
+    /// A time or length at word `i`: "1:30", "90", "90s", "1m30s",
+    /// "10 seconds", "2 minutes". Returns seconds, the number of words, and
+    /// whether it had a unit or a colon.
+    fn time_at(&self, i: usize) -> Option<(u32, usize, bool)> {
+        let word = self.word(i)?;
+        if let Ok(n) = word.parse::<f64>() {
+            let unit = match self.word(i + 1) {
+                Some("s" | "sec" | "secs" | "second" | "seconds") => Some(1.0),
+                Some("m" | "min" | "mins" | "minute" | "minutes") => Some(60.0),
+                Some("h" | "hr" | "hrs" | "hour" | "hours") => Some(3600.0),
+                _ => None,
+            };
+            return match unit {
+                Some(unit) => Some(((n * unit).round() as u32, 2, true)),
+                None => Some((n.round() as u32, 1, false)),
+            };
+        }
+        if word.contains(':') {
+            return Time::parse(word).ok().map(|t| (t.to_seconds(), 1, true));
+        }
+        let caps = self.units.captures(word)?;
+        if caps.iter().skip(1).all(|c| c.is_none()) {
+            return None;
+        }
+        let part = |idx: usize, unit: f64| caps.get(idx).and_then(|m| m.as_str().parse::<f64>().ok()).unwrap_or(0.0) * unit;
+        Some(((part(1, 3600.0) + part(2, 60.0) + part(3, 1.0)).round() as u32, 1, true))
+    }
+
+    /// "from A to B", "between A and B", "A to B", "A-B", "N seconds from A",
+    /// "from A for N seconds", "first N seconds"
+    fn find_ranges(&mut self) -> Result<()> {
+        let mut i = 0;
+        while i < self.words.len() {
+            if let Some((range, end)) = self.range_at(i)? {
+                set(&mut self.plan.trim, range, "time ranges")?;
+                self.take(i..end);
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn range_at(&self, i: usize) -> Result<Option<((u32, u32), usize)>> {
+        if self.word(i).is_none() {
+            return Ok(None);
+        }
+        // "between A and B"
+        if self.is(i, &["between"]) {
+            if let Some((a, len, _)) = self.time_at(i + 1) {
+                let j = i + 1 + len;
+                if let (true, Some((b, len, _))) = (self.is(j, RANGE_TO), self.time_at(j + 1)) {
+                    return Ok(Some(((a, b), j + 1 + len)));
+                }
+            }
+            return Ok(None);
+        }
+        // "from A to B", "from A for N seconds", or a lone "from A"
+        if self.is(i, START_AT) {
+            let mut j = i + 1;
+            if self.is(i, &["starting", "start", "beginning"]) && self.is(j, &["at", "from"]) {
+                j += 1;
+            }
+            let Some((a, len, _)) = self.time_at(j) else { return Ok(None) };
+            let k = j + len;
+            if self.is(k, RANGE_TO) {
+                if let Some((b, len, _)) = self.time_at(k + 1) {
+                    return Ok(Some(((a, b), k + 1 + len)));
+                }
+            }
+            if self.is(k, &["for"]) {
+                if let Some((length, len, _)) = self.time_at(k + 1) {
+                    return Ok(Some(((a, a + length), k + 1 + len)));
+                }
+            }
+            if self.is(i, &["from", "starting", "start", "beginning"]) {
+                let start = &self.words[j..k].join(" ");
+                bail!("'{} {start}' needs an end: say 'from {start} to ...' or '10 seconds from {start}'", self.words[i]);
+            }
+            return Ok(None);
+        }
+        // "first N seconds"
+        if self.is(i, &["first"]) {
+            if let Some((length, len, _)) = self.time_at(i + 1) {
+                // "cut the first 10 seconds" keeps everything after them
+                if let Some(verb) = i.checked_sub(1).map(|p| self.lower[p].as_str()).filter(|w| REMOVE.contains(w)) {
+                    bail!("'{verb} the first ...' needs the video's length; say 'from {} to B' instead", time(length));
+                }
+                return Ok(Some(((0, length), i + 1 + len)));
+            }
+            return Ok(None);
+        }
+        if self.is(i, &["last"]) && self.time_at(i + 1).is_some() {
+            bail!("'last ...' needs the video's length; say 'from A to B' instead");
+        }
+        // "1:00-1:10"
+        if let Some((a, b)) = self.word(i).and_then(|w| w.split_once('-')) {
+            let parse = |s: &str| Time::parse(s).ok().map(|t| t.to_seconds());
+            if let (Some(a), Some(b)) = (parse(a), parse(b)) {
+                return Ok(Some(((a, b), i + 1)));
+            }
+        }
+        // "A to B", "N seconds from A"
+        let Some((a, len, unit)) = self.time_at(i) else { return Ok(None) };
+        let j = i + len;
+        if self.is(j, &["to", "until", "till", "through", "-"]) {
+            if let Some((b, len, _)) = self.time_at(j + 1) {
+                return Ok(Some(((a, b), j + 1 + len)));
+            }
+        }
+        if unit && self.is(j, START_AT) {
+            let mut k = j + 1;
+            if self.is(j, &["starting", "start", "beginning"]) && self.is(k, &["at", "from"]) {
+                k += 1;
+            }
+            if let Some((start, len, _)) = self.time_at(k) {
+                return Ok(Some(((start, start + a), k + len)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_phrases(&mut self) -> Result<()> {
+        let mut phrases: Vec<(Vec<&str>, Phrase)> = PHRASES.iter()
+            .map(|(text, phrase)| (text.split(' ').collect(), *phrase))
+            .collect();
+        phrases.sort_by_key(|(words, _)| std::cmp::Reverse(words.len()));
+
+        let mut i = 0;
+        while i < self.words.len() {
+            let found = phrases.iter().find(|(words, _)| {
+                words.iter().enumerate().all(|(k, w)| self.word(i + k) == Some(*w))
+            });
+            match found {
+                Some((words, phrase)) => {
+                    let end = i + words.len();
+                    let phrase_text = words.join(" ");
+                    self.take(i..end);
+                    self.phrase(*phrase, &phrase_text, i, end)?;
+                    i = end;
+                }
+                None => i += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// The first argument after a phrase, skipping connecting words and the
+    /// file ("rotate video.mp4 90 degrees"), or the word just before it
+    /// ("2x faster")
+    fn argument<T>(&mut self, start: usize, end: usize, parse: impl Fn(&Self, usize) -> Option<(T, usize)>) -> Option<T> {
+        let mut j = end;
+        while self.is(j, &["by", "to", "at", "of", "than", "less", "more", "under", "below", "within", "max", "in"])
+            || self.files.iter().any(|(i, _)| *i == j)
+        {
+            j += 1;
+        }
+        if let Some((value, len)) = parse(self, j) {
+            self.take(end..j + len);
+            return Some(value);
+        }
+        if start > 0 {
+            if let Some((value, 1)) = parse(self, start - 1) {
+                self.take(start - 1..start);
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn speed_at(&self, i: usize) -> Option<(f64, usize)> {
+        let word = self.word(i)?;
+        match word {
+            "twice" | "double" => return Some((2.0, 1)),
+            "triple" => return Some((3.0, 1)),
+            "half" => return Some((2.0, 1)),
+            _ => {}
+        }
+        if let Ok(factor) = SpeedFactor::parse(word) {
+            return Some((factor.factor, 1));
+        }
+        let n = word.parse::<f64>().ok()?;
+        self.is(i + 1, &["x", "times"]).then_some((n, 2))
+    }
+
+    fn volume_at(&self, i: usize) -> Option<(VolumeAdjustment, usize)> {
+        let word = self.word(i)?;
+        if let Ok(adjustment) = VolumeAdjustment::parse(word) {
+            return Some((adjustment, 1));
+        }
+        let unit = match self.word(i + 1)? {
+            "db" | "decibel" | "decibels" => "db",
+            "%" | "percent" => "%",
+            _ => return None,
+        };
+        VolumeAdjustment::parse(&format!("{word}{unit}")).ok().map(|v| (v, 2))
+    }
+
+    fn size_at(&self, i: usize) -> Option<((CompressTarget, String), usize)> {
+        let word = self.word(i)?;
+        // Only kb/mb/gb: "10m" is ten minutes
+        let (text, len) = match TargetSize::parse(word) {
+            Ok(_) if word.ends_with('b') => (word.to_string(), 1),
+            _ => {
+                word.parse::<f64>().ok()?;
+                let unit = match self.word(i + 1)? {
+                    "kb" | "kilobytes" => "kb",
+                    "mb" | "megabytes" | "megs" => "mb",
+                    "gb" | "gigabytes" | "gigs" => "gb",
+                    _ => return None,
+                };
+                (format!("{word}{unit}"), 2)
+            }
+        };
+        let size = TargetSize::parse(&text).ok()?;
+        Some(((CompressTarget::Size(size), text), len))
+    }
+
+    /// "left", "right", "90", "90°" or "90 degrees"
+    fn degrees_at(&self, i: usize) -> Option<(RotateDegrees, usize)> {
+        let degrees = match self.word(i)? {
+            "left" | "counterclockwise" | "counter-clockwise" | "anticlockwise" => 270,
+            "right" | "clockwise" => 90,
+            word => {
+                let degrees = RotateDegrees::parse(word.trim_end_matches('°')).ok()?;
+                let len = if self.is(i + 1, &["degrees", "degree", "deg"]) { 2 } else { 1 };
+                return Some((degrees, len));
+            }
+        };
+        RotateDegrees::new(degrees).ok().map(|d| (d, 1))
+    }
+
+    fn phrase(&mut self, phrase: Phrase, text: &str, start: usize, end: usize) -> Result<()> {
+        match phrase {
+            Phrase::Mute => self.plan.mute = true,
+            Phrase::AudioOnly => self.plan.audio_only = true,
+            Phrase::Normalize => self.plan.normalize = true,
+            Phrase::Reverse => self.plan.reverse = true,
+            Phrase::Grayscale => self.plan.grayscale = true,
+            Phrase::Stabilize => self.plan.stabilize = true,
+            Phrase::Faster | Phrase::Slower => {
+                let fixed = match text {
+                    "twice as fast" | "double speed" | "half speed" => Some(2.0),
+                    _ => None,
+                };
+                let factor = match fixed.or_else(|| self.argument(start, end, Self::speed_at)) {
+                    Some(factor) => factor,
+                    None => {
+                        self.assumptions.push(format!("'{text}' means 2x"));
+                        2.0
+                    }
+                };
+                let factor = SpeedFactor::parse(&format!("{factor}x"))?;
+                let speed = if phrase == Phrase::Faster { Speed::Up(factor) } else { Speed::Down(factor) };
+                set(&mut self.plan.speed, speed, "speeds")?;
+            }
+            Phrase::Louder | Phrase::Quieter | Phrase::Volume => {
+                let adjustment = match (self.argument(start, end, Self::volume_at), phrase) {
+                    (Some(VolumeAdjustment::Decibels(db)), Phrase::Louder) => VolumeAdjustment::Decibels(db.abs()),
+                    (Some(VolumeAdjustment::Decibels(db)), Phrase::Quieter) => VolumeAdjustment::Decibels(-db.abs()),
+                    (Some(adjustment), _) => adjustment,
+                    (None, Phrase::Volume) => bail!("'volume' needs an amount, e.g. 'volume 50%' or 'louder by 6db'"),
+                    (None, _) => {
+                        let db = if phrase == Phrase::Louder { 6.0 } else { -6.0 };
+                        self.assumptions.push(format!("'{text}' means {}", VolumeAdjustment::Decibels(db)));
+                        VolumeAdjustment::Decibels(db)
+                    }
+                };
+                set(&mut self.plan.volume, adjustment, "volume changes")?;
+            }
+            Phrase::Compress => {
+                if let Some(target) = self.argument(start, end, Self::size_at) {
+                    set(&mut self.plan.compress, target, "target sizes")?;
+                } else if self.plan.compress.is_none() {
+                    // A size elsewhere in the sentence ("under 10mb") fills this in later
+                    self.plan.compress = Some((CompressTarget::Quality(QualityPreset::Medium), "medium-quality".to_string()));
+                }
+            }
+            Phrase::Rotate => {
+                let degrees = match self.argument(start, end, Self::degrees_at) {
+                    Some(degrees) => degrees,
+                    None => {
+                        self.assumptions.push(format!("'{text}' means 90 degrees clockwise"));
+                        RotateDegrees::new(90)?
+                    }
+                };
+                set(&mut self.plan.rotate, degrees, "rotations")?;
+            }
+            // The range itself was read before the phrases
+            Phrase::Trim => self.plan.trim_asked = true,
+            Phrase::UpsideDown => set(&mut self.plan.rotate, RotateDegrees::new(180)?, "rotations")?,
+            Phrase::FlipHorizontal => set(&mut self.plan.flip, FlipDirection::Horizontal, "flips")?,
+            Phrase::FlipVertical => set(&mut self.plan.flip, FlipDirection::Vertical, "flips")?,
+            Phrase::Flip => {
+                self.assumptions.push("'flip' means a horizontal mirror".to_string());
+                set(&mut self.plan.flip, FlipDirection::Horizontal, "flips")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Values that stand on their own: formats, resolutions, sizes, speeds,
+    /// volumes, frame rates and lengths. Anything else left is not understood.
+    fn find_values(&mut self) -> Result<()> {
+        let mut ignored = Vec::new();
+        let mut i = 0;
+        while i < self.words.len() {
+            let Some(word) = self.word(i).map(str::to_string) else {
+                i += 1;
+                continue;
+            };
+            let mut len = 1;
+            // "under 10mb", "less than 10 mb": the limit words go with the size
+            let mut j = i;
+            while self.is(j, SIZE_LIMIT) {
+                j += 1;
+            }
+            if j > i && self.size_at(j).is_some() {
+                self.take(i..j);
+                i = j;
+                continue;
+            }
+            if let Some((target, n)) = self.size_at(i) {
+                let after_limit = i > 0 && SIZE_LIMIT.contains(&self.lower[i - 1].as_str());
+
+                if !after_limit && !matches!(self.plan.compress, Some((CompressTarget::Quality(_), _))) {
+                    self.assumptions.push(format!("'{}' is a target file size", target.1));
+                }
+                if let Some((CompressTarget::Quality(_), _)) = self.plan.compress {
+                    self.plan.compress = None;
+                }
+                set(&mut self.plan.compress, target, "target sizes")?;
+                len = n;
+            } else if let Some(fps) = word.strip_suffix("fps").and_then(|n| n.parse::<u32>().ok()) {
+                set(&mut self.plan.fps, fps, "frame rates")?;
+            } else if let (Ok(fps), true) = (word.parse::<u32>(), self.is(i + 1, &["fps"])) {
+                set(&mut self.plan.fps, fps, "frame rates")?;
+                len = 2;
+            } else if let Some(target) = resolution(&word) {
+                set(&mut self.plan.resize, target, "sizes")?;
+            } else if let Some(format) = format_word(&word) {
+                set(&mut self.plan.format, format, "formats")?;
+            } else if let Ok(factor) = SpeedFactor::parse(&word) {
+                self.assumptions.push(format!("'{word}' means {word} as fast"));
+                set(&mut self.plan.speed, Speed::Up(factor), "speeds")?;
+            } else if let Some((adjustment, n)) = self.volume_at(i) {
+                if let VolumeAdjustment::Percentage(_) = adjustment {
+                    self.assumptions.push(format!("'{adjustment}' is a volume"));
+                }
+                set(&mut self.plan.volume, adjustment, "volume changes")?;
+                len = n;
+            } else if let Some((length, n, true)) = self.time_at(i) {
+                self.assumptions.push(format!("'{}' means the first {}", self.words[i..i + n].join(" "), time(length)));
+                set(&mut self.plan.trim, (0, length), "time ranges")?;
+                len = n;
+            } else if !FILLERS.contains(&word.as_str()) {
+                ignored.push(self.words[i].clone());
+            }
+            self.take(i..i + len);
+            i += len;
+        }
+        self.ignored = ignored;
+        Ok(())
+    }
+
+    fn build(self) -> Result<Sentence> {
+        let Parser { text, lower, mut plan, files, mut assumptions, ignored, .. } = self;
+
+        // The first file is the input; a later one after "as"/"into"/... is the output
+        let mut input = None;
+        let mut output = None;
+        let mut extra = Vec::new();
+        for (i, path) in files {
+            let names_output = i > 0 && ["as", "to", "into", "called", "named"].contains(&lower[i - 1].as_str());
+            if input.is_none() {
+                input = Some(path);
+            } else if names_output && output.is_none() {
+                output = Some(path);
+            } else {
+                extra.push(path.display().to_string());
+            }
+        }
+        let input = input.ok_or_else(|| anyhow!("Which file? Name it in the sentence, e.g. \"make video.mp4 a gif\""))?;
+        if !extra.is_empty() {
+            bail!("The sentence names more than one input file ({}); name the result with 'as FILE'", extra.join(", "));
+        }
+        if plan.trim_asked && plan.trim.is_none() {
+            bail!("'trim' needs a time range, e.g. 'trim video.mp4 from 1:00 to 2:00'");
+        }
+
+        let ext = |p: &Path| p.extension().map(|e| e.to_string_lossy().to_lowercase());
+        let format_ext = |f: &ConvertFormat| f.output_naming().map(|(_, e)| e.to_string());
+        if let Some(out_ext) = output.as_deref().and_then(ext) {
+            match &plan.format {
+                Some(format) if format_ext(format).is_some_and(|e| e != out_ext) => {
+                    bail!("The sentence asks for {format} but saves to .{out_ext}")
+                }
+                Some(_) => {}
+                None => {
+                    if let Some(format) = format_word(&out_ext).filter(|f| format_ext(f).as_deref() == Some(out_ext.as_str())) {
+                        if ext(&input).as_deref() != Some(out_ext.as_str()) {
+                            plan.format = Some(format);
+                        }
+                    }
+                }
+            }
+        }
+
+        let audio_format = plan.format.and_then(|f| f.audio_format()).is_some();
+        if plan.audio_only {
+            if plan.mute {
+                bail!("The sentence asks for no audio and for only the audio");
+            }
+            match plan.format {
+                Some(_) if audio_format => {}
+                Some(format) => bail!("The sentence asks for only the audio but also for {format}"),
+                None => {
+                    assumptions.push("the audio is saved as mp3".to_string());
+                    plan.format = Some(ConvertFormat::Mp3);
+                }
+            }
+        }
+        if plan.mute && (plan.normalize || plan.volume.is_some()) {
+            bail!("The sentence asks to remove the audio and to change it");
+        }
+        if plan.mute && audio_format {
+            bail!("The sentence asks for no audio but for an audio format");
+        }
+        if let Some((target, _)) = &plan.compress {
+            if !matches!(plan.format, None | Some(ConvertFormat::Mp4)) {
+                bail!("Compressing to a size works for mp4 video, not {}", plan.format.map(|f| f.to_string()).unwrap_or_default());
+            }
+            if *target == CompressTarget::Quality(QualityPreset::Medium) {
+                assumptions.push("'smaller' means medium quality".to_string());
+            }
+        }
+
+        let mut planned: Vec<(Intent, String)> = Vec::new();
+        let input_path = PathBuf::new();
+        if let Some((start, end)) = plan.trim {
+            if end <= start {
+                bail!("The time range ends before it starts ({} to {})", time(start), time(end));
+            }
+            planned.push((Intent::Trim { input: input_path.clone(), start: time(start), end: time(end) },
+                format!("trim from {} to {}", time(start), time(end))));
+        }
+        match plan.speed {
+            Some(Speed::Up(factor)) => planned.push((Intent::SpeedUp { input: input_path.clone(), factor }, format!("speed-up by {factor}"))),
+            Some(Speed::Down(factor)) => planned.push((Intent::SlowDown { input: input_path.clone(), factor }, format!("slow-down by {factor}"))),
+            None => {}
+        }
+        if plan.reverse {
+            planned.push((Intent::Reverse { input: input_path.clone() }, "reverse".to_string()));
+        }
+        // GIFs and other animated images have no sound, so "without sound"
+        // needs no step of its own
+        if plan.mute && !plan.format.is_some_and(|f| f.is_animation()) {
+            planned.push((Intent::Mute { input: input_path.clone() }, "mute".to_string()));
+        }
+        if plan.normalize {
+            planned.push((Intent::Normalize { input: input_path.clone(), target: LoudnessTarget::default() }, "normalize".to_string()));
+        }
+        if let Some(adjustment) = plan.volume {
+            let word = if matches!(adjustment, VolumeAdjustment::Percentage(_)) { "to" } else { "by" };
+            planned.push((Intent::AdjustVolume { input: input_path.clone(), adjustment }, format!("adjust-volume {word} {adjustment}")));
+        }
+        if plan.audio_only {
+            let format = plan.format.and_then(|f| f.audio_format()).unwrap_or(AudioFormat::Mp3);
+            let summary = match format {
+                AudioFormat::Mp3 => "extract-audio".to_string(),
+                format => format!("extract-audio --format {format}"),
+            };
+            planned.push((Intent::ExtractAudio { input: input_path.clone(), format, quality: None }, summary));
+            plan.format = None;
+        }
+        if let Some(degrees) = plan.rotate {
+            planned.push((Intent::Rotate { input: input_path.clone(), degrees }, format!("rotate by {}", degrees.0)));
+        }
+        if let Some(direction) = plan.flip {
+            let name = if direction == FlipDirection::Horizontal { "horizontal" } else { "vertical" };
+            planned.push((Intent::Flip { input: input_path.clone(), direction }, format!("flip {name}")));
+        }
+        if plan.grayscale {
+            planned.push((Intent::Grayscale { input: input_path.clone() }, "grayscale".to_string()));
+        }
+        if plan.stabilize {
+            planned.push((Intent::Stabilize { input: input_path.clone() }, "stabilize".to_string()));
+        }
+        if let Some(fps) = plan.fps {
+            planned.push((Intent::SetFps { input: input_path.clone(), fps }, format!("fps to {fps}")));
+        }
+        if let Some(target) = plan.resize {
+            planned.push((Intent::Resize { input: input_path.clone(), target: target.clone() }, format!("resize to {target}")));
+        }
+        if let Some(format) = plan.format {
+            // Converting to the container the other steps already write is a no-op
+            let same = format_ext(&format).is_some_and(|e| ext(&input).as_deref() == Some(e.as_str()));
+            if !same || planned.is_empty() {
+                planned.push((Intent::Convert { input: input_path.clone(), format, quality: None, codec: None, max_size: None }, format!("convert to {format}")));
+            }
+        }
+        if let Some((target, label)) = plan.compress {
+            planned.push((Intent::Compress { input: input_path.clone(), target, two_pass: false }, format!("compress to {label}")));
+        }
+
+        if planned.is_empty() {
+            bail!(
+                "Couldn't find anything to do in \"{text}\". Try e.g. \"make {} a 480p gif without sound\", or see ffhuman --help",
+                input.display()
+            );
+        }
+        let (steps, summaries) = planned.into_iter().unzip();
+        Ok(Sentence { input, output, steps, summaries, assumptions, ignored })
+    }
+}
+
+/// A resolution word: "480p", "720p", "1080p", "4k", "1280x720"
+fn resolution(word: &str) -> Option<ResizeTarget> {
+    let looks_like = word == "4k" || word.ends_with('p') || word.contains('x');
+    looks_like.then(|| ResizeTarget::parse(word).ok()).flatten()
+}
+
+/// A format name: "gif" and the other animated images, "mp4", "webm", an
+/// audio format like "mp3" or "flac", "iphone", "android"
+fn format_word(word: &str) -> Option<ConvertFormat> {
+    match word {
+        "gif" | "webp" | "apng" | "avif" | "mp4" | "webm" | "iphone" | "android"
+        | "mp3" | "wav" | "flac" | "m4a" | "aac" | "opus" | "ogg" | "vorbis" | "aiff" => ConvertFormat::parse(word).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str) -> Result<Option<(u32, u32)>> {
+        let mut parser = Parser::new(text)?;
+        parser.find_files();
+        parser.find_ranges()?;
+        Ok(parser.plan.trim)
+    }
+
+    #[test]
+    fn test_first_n() {
+        assert_eq!(range("first 10 seconds of video.mp4").unwrap(), Some((0, 10)));
+        assert_eq!(range("the first 2 minutes").unwrap(), Some((0, 120)));
+        assert_eq!(range("first 1m30s").unwrap(), Some((0, 90)));
+        assert_eq!(range("first video.mp4").unwrap(), None);
+
+        assert!(range("cut the first 5 seconds").is_err());
+        assert!(range("drop first 5s").is_err());
+    }
+
+    #[test]
+    fn test_last_n() {
+        assert!(range("last 10 seconds of video.mp4").is_err());
+        assert_eq!(range("the last video.mp4").unwrap(), None);
+    }
+
+    #[test]
+    fn test_from_to() {
+        assert_eq!(range("from 1:00 to 2:00").unwrap(), Some((60, 120)));
+        assert_eq!(range("from 5 until 20").unwrap(), Some((5, 20)));
+        assert_eq!(range("between 0:05 and 0:20").unwrap(), Some((5, 20)));
+        assert_eq!(range("starting at 1:00 for 30 seconds").unwrap(), Some((60, 90)));
+        assert_eq!(range("10 seconds from 1:00").unwrap(), Some((60, 70)));
+        assert_eq!(range("1:00-1:10").unwrap(), Some((60, 70)));
+        assert_eq!(range("1:00 to 1:30").unwrap(), Some((60, 90)));
+
+        assert!(range("from 1:00").is_err());
+        assert!(range("from 1:00 to 2:00 and from 3:00 to 4:00").is_err());
+    }
+}
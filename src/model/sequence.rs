@@ -0,0 +1,380 @@
+//! Image sequences for `sequence-to-video` and `video-to-sequence`.
+//!
+//! Frames can be given as a folder, a glob, a numbered pattern or a list of
+//! files:
+//!
+//! ```text
+//! shots/
+//! "shots/*.JPG"
+//! render/frame_%04d.exr
+//! IMG_0001.jpg IMG_0002.jpg ...
+//! ```
+//!
+//! Frames are sorted naturally (`IMG_9` before `IMG_10`) and gaps in their
+//! numbering are reported.
+
+use crate::model::types::VideoCodec;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Extensions read as frames when a folder is given
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "exr", "tif", "tiff", "dpx", "bmp", "webp"];
+
+/// Frames to turn into a video, in natural order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageSequence {
+    pub frames: Vec<PathBuf>,
+    /// Present when every file name is a shared prefix plus a frame number
+    pub numbering: Option<Numbering>,
+}
+
+/// Frame numbers of a sequence like frame_0001.png, frame_0002.png, ...
+#[derive(Debug, Clone, PartialEq)]
+pub struct Numbering {
+    /// File name before the number, e.g. "frame_"
+    pub prefix: String,
+    /// Digits every number is padded to (4 for 0001), if they all are
+    pub digits: Option<usize>,
+    /// Frame numbers in order
+    pub numbers: Vec<u64>,
+    /// printf-style file name for ffmpeg's image2 demuxer, e.g. "frame_%04d.png",
+    /// unless the padding is mixed
+    pub pattern: Option<String>,
+}
+
+impl ImageSequence {
+    /// Collect frames from a folder, a glob, a numbered pattern, or two or more files
+    pub fn find(inputs: &[PathBuf]) -> Result<Self> {
+        let frames = match inputs {
+            [] => bail!("No images given"),
+            [one] => expand(one)?,
+            many => {
+                if let Some(missing) = many.iter().find(|p| !p.is_file()) {
+                    bail!("Image not found: {}", missing.display());
+                }
+                many.to_vec()
+            }
+        };
+        Self::from_frames(frames)
+    }
+
+    /// Sort frames naturally and work out their numbering
+    pub fn from_frames(mut frames: Vec<PathBuf>) -> Result<Self> {
+        if frames.len() < 2 {
+            bail!("An image sequence needs at least 2 images, found {}", frames.len());
+        }
+        let first_ext = extension(&frames[0]);
+        if let Some(other) = frames.iter().find(|f| extension(f) != first_ext) {
+            bail!("The sequence mixes .{} and .{} images ({})", first_ext, extension(other), other.display());
+        }
+        frames.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        let numbering = Numbering::of(&frames);
+        Ok(ImageSequence { frames, numbering })
+    }
+
+    /// Missing frame numbers, as inclusive ranges
+    pub fn gaps(&self) -> Vec<(u64, u64)> {
+        let Some(numbering) = &self.numbering else {
+            return Vec::new();
+        };
+        numbering.numbers.windows(2)
+            .filter(|pair| pair[1] > pair[0] + 1)
+            .map(|pair| (pair[0] + 1, pair[1] - 1))
+            .collect()
+    }
+
+    /// How many frames the gaps leave out
+    pub fn missing(&self) -> u64 {
+        self.gaps().iter().map(|(from, to)| to - from + 1).sum()
+    }
+
+    /// Gaps as frame names, e.g. "0012-0014, 0040"
+    pub fn describe_gaps(&self) -> String {
+        let label = |n: u64| match self.numbering.as_ref().and_then(|n| n.digits) {
+            Some(digits) => format!("{n:0digits$}"),
+            None => n.to_string(),
+        };
+        self.gaps().iter()
+            .map(|&(from, to)| if from == to { label(from) } else { format!("{}-{}", label(from), label(to)) })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The image2 input (pattern path and first number) when the frames are
+    /// numbered without gaps in one folder; otherwise they are listed one by one
+    pub fn image2_input(&self) -> Option<(PathBuf, u64)> {
+        let numbering = self.numbering.as_ref()?;
+        let pattern = numbering.pattern.as_ref()?;
+        let dir = self.frames[0].parent().unwrap_or_else(|| Path::new(""));
+        if !self.gaps().is_empty() || numbering.prefix.contains('%') || dir.to_string_lossy().contains('%') {
+            return None;
+        }
+        Some((dir.join(pattern), numbering.numbers[0]))
+    }
+
+    /// Name for the output: the numbering prefix ("frame_" -> "frame"), else the folder's name
+    pub fn name(&self) -> String {
+        let prefix = self.numbering.as_ref()
+            .map(|n| n.prefix.trim_end_matches(['_', '-', '.', ' ']).to_string())
+            .unwrap_or_default();
+        if !prefix.is_empty() {
+            return prefix;
+        }
+        self.frames[0].parent()
+            .and_then(|dir| std::fs::canonicalize(dir).ok())
+            .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "sequence".to_string())
+    }
+
+    /// Lower-case extension shared by all frames
+    pub fn extension(&self) -> String {
+        extension(&self.frames[0])
+    }
+
+    /// Whether the frames carry more than 8 bits per channel, from the first
+    /// frame's probed pixel format or, failing that, its file type
+    pub fn is_high_bit_depth(&self, pix_fmt: Option<&str>) -> bool {
+        match pix_fmt {
+            Some(pix_fmt) => is_high_bit_depth(pix_fmt),
+            None => matches!(self.extension().as_str(), "exr" | "dpx"),
+        }
+    }
+}
+
+impl Numbering {
+    fn of(frames: &[PathBuf]) -> Option<Self> {
+        let re = Regex::new(r"^(.*?)(\d+)$").ok()?;
+        let dir = frames[0].parent();
+        let mut prefix: Option<String> = None;
+        let mut digit_strings = Vec::with_capacity(frames.len());
+        for frame in frames {
+            if frame.parent() != dir {
+                return None;
+            }
+            let stem = frame.file_stem()?.to_string_lossy().to_string();
+            let caps = re.captures(&stem)?;
+            let this_prefix = caps.get(1)?.as_str();
+            match &prefix {
+                Some(p) if p != this_prefix => return None,
+                Some(_) => {}
+                None => prefix = Some(this_prefix.to_string()),
+            }
+            digit_strings.push(caps.get(2)?.as_str().to_string());
+        }
+        let prefix = prefix?;
+        let numbers = digit_strings.iter().map(|d| d.parse::<u64>()).collect::<Result<Vec<_>, _>>().ok()?;
+
+        let len = digit_strings[0].len();
+        let same_length = digit_strings.iter().all(|d| d.len() == len);
+        let unpadded = digit_strings.iter().all(|d| d.len() == 1 || !d.starts_with('0'));
+        let digits = (same_length && len > 1 && digit_strings.iter().any(|d| d.starts_with('0'))).then_some(len);
+        let spec = match digits {
+            Some(digits) => Some(format!("%0{digits}d")),
+            None if unpadded => Some("%d".to_string()),
+            None => None,
+        };
+        let ext = frames[0].extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let pattern = spec.map(|spec| format!("{prefix}{spec}{ext}"));
+        Some(Numbering { prefix, digits, numbers, pattern })
+    }
+}
+
+/// Frames from a single argument: a folder, a numbered pattern, or a glob
+fn expand(input: &Path) -> Result<Vec<PathBuf>> {
+    if input.is_dir() {
+        let mut frames = Vec::new();
+        for entry in std::fs::read_dir(input).with_context(|| format!("Failed to read {}", input.display()))? {
+            let path = entry?.path();
+            let hidden = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if path.is_file() && !hidden && IMAGE_EXTENSIONS.contains(&extension(&path).as_str()) {
+                frames.push(path);
+            }
+        }
+        if frames.is_empty() {
+            bail!("No images ({}) in {}", IMAGE_EXTENSIONS.join(", "), input.display());
+        }
+        return Ok(frames);
+    }
+
+    let text = input.to_string_lossy();
+    let name = input.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let printf = Regex::new(r"%(0?)(\d*)d").map_err(|e| anyhow!("Invalid regex: {}", e))?;
+    if let Some(caps) = printf.captures(&name) {
+        // frame_%04d.png: list the folder and keep the names that fit
+        let whole = caps.get(0).unwrap();
+        let digits = match caps.get(2).unwrap().as_str() {
+            "" => r"\d+".to_string(),
+            width => format!(r"\d{{{width}}}"),
+        };
+        let re = Regex::new(&format!(
+            "^{}{}{}$",
+            regex::escape(&name[..whole.start()]),
+            digits,
+            regex::escape(&name[whole.end()..])
+        ))?;
+        let dir = input.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        let mut frames = Vec::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_file() && path.file_name().is_some_and(|n| re.is_match(&n.to_string_lossy())) {
+                frames.push(path);
+            }
+        }
+        if frames.is_empty() {
+            bail!("No images match {}", input.display());
+        }
+        return Ok(frames);
+    }
+
+    if text.contains(['*', '?', '[']) {
+        let frames: Vec<PathBuf> = glob::glob(&text)
+            .map_err(|e| anyhow!("Invalid pattern {}: {}", text, e))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .collect();
+        if frames.is_empty() {
+            bail!("No images match {}", text);
+        }
+        return Ok(frames);
+    }
+
+    if input.is_file() {
+        bail!(
+            "{} is a single image; give a folder, a pattern like frame_%04d.png, a glob, or several images",
+            input.display()
+        );
+    }
+    bail!("Input not found: {}", input.display())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+/// Compare names the way people count: runs of digits by value, so
+/// "IMG_9.jpg" sorts before "IMG_10.jpg"
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<(bool, &str)> {
+        let mut out = Vec::new();
+        let mut start = 0;
+        let bytes = s.as_bytes();
+        for i in 1..=bytes.len() {
+            if i == bytes.len() || bytes[i].is_ascii_digit() != bytes[start].is_ascii_digit() {
+                out.push((bytes[start].is_ascii_digit(), &s[start..i]));
+                start = i;
+            }
+        }
+        out
+    }
+    let (ca, cb) = (chunks(a), chunks(b));
+    for (x, y) in ca.iter().zip(cb.iter()) {
+        let order = match (x, y) {
+            ((true, x), (true, y)) => {
+                let (tx, ty) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                tx.len().cmp(&ty.len()).then_with(|| tx.cmp(ty))
+            }
+            ((_, x), (_, y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    ca.len().cmp(&cb.len()).then_with(|| a.cmp(b))
+}
+
+/// Whether an ffmpeg pixel format has more than 8 bits per channel
+/// ("rgb48be", "yuv420p10le", "gbrpf32le"; not "rgb24")
+pub fn is_high_bit_depth(pix_fmt: &str) -> bool {
+    ["16", "48", "64", "f32", "10le", "10be", "12le", "12be"].iter().any(|d| pix_fmt.contains(d))
+}
+
+/// Pixel format to encode a sequence with, or None where the codec settings
+/// already choose one (ProRes, DNxHR and AV1 profiles)
+pub fn video_pix_fmt(codec: VideoCodec, high_bit_depth: bool) -> Option<&'static str> {
+    match codec {
+        VideoCodec::H265 | VideoCodec::Vp9 if high_bit_depth => Some("yuv420p10le"),
+        VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 => Some("yuv420p"),
+        // Lossless RGB, like the frames
+        VideoCodec::Ffv1 => Some(if high_bit_depth { "gbrp16le" } else { "gbrp" }),
+        VideoCodec::Av1 | VideoCodec::Av1Aom | VideoCodec::ProRes(_) | VideoCodec::Dnxhr(_) | VideoCodec::Copy => None,
+    }
+}
+
+/// Image format for `video-to-sequence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    Png,
+    Tiff,
+    Exr,
+    /// Lossy; small files for previews
+    Jpg,
+}
+
+impl FrameFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "png" => Ok(FrameFormat::Png),
+            "tif" | "tiff" => Ok(FrameFormat::Tiff),
+            "exr" => Ok(FrameFormat::Exr),
+            "jpg" | "jpeg" => Ok(FrameFormat::Jpg),
+            _ => bail!("Invalid frame format: {s} (try png, tiff, exr, or jpg)"),
+        }
+    }
+
+    pub fn ext(&self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Tiff => "tif",
+            FrameFormat::Exr => "exr",
+            FrameFormat::Jpg => "jpg",
+        }
+    }
+
+    /// Encoder arguments; PNG and TIFF keep 16 bits per channel for
+    /// high-bit-depth video, EXR is always 32-bit float
+    pub fn codec_args(&self, high_bit_depth: bool) -> Vec<String> {
+        let args: &[&str] = match (self, high_bit_depth) {
+            (FrameFormat::Png, false) => &["-c:v", "png", "-pix_fmt", "rgb24"],
+            (FrameFormat::Png, true) => &["-c:v", "png", "-pix_fmt", "rgb48be"],
+            (FrameFormat::Tiff, false) => &["-c:v", "tiff", "-compression_algo", "deflate", "-pix_fmt", "rgb24"],
+            (FrameFormat::Tiff, true) => &["-c:v", "tiff", "-compression_algo", "deflate", "-pix_fmt", "rgb48le"],
+            (FrameFormat::Exr, _) => &["-c:v", "exr", "-compression", "zip1", "-pix_fmt", "gbrpf32le"],
+            (FrameFormat::Jpg, _) => &["-c:v", "mjpeg", "-q:v", "2"],
+        };
+        args.iter().map(|s| s.to_string()).collect()
+    }
+}
+
+impl fmt::Display for FrameFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Tiff => "tiff",
+            FrameFormat::Exr => "exr",
+            FrameFormat::Jpg => "jpg",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// File name pattern for exported frames. A pattern without a number
+/// placeholder gets one appended ("shot" -> "shot_%06d"); the extension comes
+/// from the format.
+pub fn frame_pattern(pattern: Option<&str>, stem: &str, format: FrameFormat) -> Result<String> {
+    let pattern = pattern.unwrap_or(stem);
+    if pattern.contains(['/', '\\']) {
+        bail!("The frame pattern is a file name, not a path: {pattern} (use --output-dir for the folder)");
+    }
+    let printf = Regex::new(r"%0?\d*d").map_err(|e| anyhow!("Invalid regex: {}", e))?;
+    let pattern = pattern.strip_suffix(&format!(".{}", format.ext())).unwrap_or(pattern);
+    let placeholders = printf.find_iter(pattern).count();
+    let without = printf.replace_all(pattern, "");
+    if without.contains('%') || placeholders > 1 {
+        bail!("The frame pattern needs at most one number placeholder like %04d: {pattern}");
+    }
+    let pattern = if placeholders == 1 { pattern.to_string() } else { format!("{pattern}_%06d") };
+    Ok(format!("{pattern}.{}", format.ext()))
+}
@@ -0,0 +1,173 @@
+//! Where `split` cuts a file, and the manifest of parts it writes next to them.
+//!
+//! Every mode first picks the times parts start at. When the video is
+//! stream-copied those are moved to the nearest keyframe, since a copied part
+//! can only start on one; then each part runs to the start of the next:
+//!
+//! ```text
+//! starts 0, 29.7, 61.2  ->  [0, 29.7) [29.7, 61.2) [61.2, end)
+//! ```
+
+use crate::ffmpeg::probe::Packet;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Parts shorter than this (in seconds) are dropped rather than written
+const MIN_PART: f64 = 0.05;
+
+/// Room left in a size-limited chunk for the container's own overhead
+const SIZE_HEADROOM: f64 = 0.97;
+
+/// A part starts at a time in seconds, titled when it's a chapter
+pub type Start = (f64, Option<String>);
+
+/// One part of the split, `start` to `end` seconds of the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitPart {
+    pub output: PathBuf,
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+/// Starts every `interval` seconds
+pub fn every_starts(duration: f64, interval: f64) -> Vec<Start> {
+    let mut starts = Vec::new();
+    let mut start = 0.0;
+    while start < duration && interval > 0.0 {
+        starts.push((start, None));
+        start += interval;
+    }
+    starts
+}
+
+/// Starts of `parts` equal parts
+pub fn equal_starts(duration: f64, parts: u32) -> Vec<Start> {
+    (0..parts).map(|i| (i as f64 * duration / parts as f64, None)).collect()
+}
+
+/// Starts at the given times (scene changes), plus one at 0
+pub fn starts_at(times: &[f64]) -> Vec<Start> {
+    std::iter::once(0.0).chain(times.iter().copied()).map(|t| (t, None)).collect()
+}
+
+/// Starts in the middle of each silence, skipping silences at either end
+pub fn silence_starts(silences: &[(f64, Option<f64>)]) -> Vec<Start> {
+    let middles = silences.iter()
+        .filter(|(start, _)| *start > MIN_PART)
+        .filter_map(|&(start, end)| end.map(|end| (start + end) / 2.0));
+    std::iter::once(0.0).chain(middles).map(|t| (t, None)).collect()
+}
+
+/// Starts that keep every chunk under `limit` bytes, on video keyframes (or
+/// any packet, for audio). Also says whether some keyframes were too far
+/// apart to stay under the limit.
+pub fn size_starts(packets: &[Packet], limit: u64) -> (Vec<Start>, bool) {
+    let budget = (limit as f64 * SIZE_HEADROOM) as u64;
+    let has_video = packets.iter().any(|p| p.video);
+    let mut starts = vec![(0.0, None)];
+    let mut chunk_start = 0.0;
+    let mut chunk_bytes = 0u64;
+    // The latest place the chunk could end, and its bytes up to there
+    let mut candidate: Option<(f64, u64)> = None;
+    let mut oversized = false;
+
+    for packet in packets {
+        let can_cut = if has_video { packet.video && packet.key } else { true };
+        if can_cut && packet.time > chunk_start {
+            candidate = Some((packet.time, chunk_bytes));
+        }
+        chunk_bytes += packet.size;
+        if chunk_bytes > budget {
+            match candidate.take() {
+                Some((cut, before)) => {
+                    starts.push((cut, None));
+                    chunk_start = cut;
+                    chunk_bytes -= before;
+                    oversized |= chunk_bytes > budget;
+                }
+                None => oversized = true,
+            }
+        }
+    }
+    (starts, oversized)
+}
+
+/// Move each start to the nearest keyframe; the first part keeps its start
+pub fn snap_to_keyframes(starts: Vec<Start>, keyframes: &[f64]) -> Vec<Start> {
+    if keyframes.is_empty() {
+        return starts;
+    }
+    starts.into_iter()
+        .enumerate()
+        .map(|(i, (start, title))| {
+            if i == 0 {
+                return (start, title);
+            }
+            let nearest = keyframes.iter()
+                .copied()
+                .min_by(|a, b| (a - start).abs().total_cmp(&(b - start).abs()))
+                .unwrap_or(start);
+            // Round down to the millisecond, so the cut lands just before the keyframe
+            ((nearest * 1000.0).floor() / 1000.0, title)
+        })
+        .collect()
+}
+
+/// Parts from their starts, each ending where the next begins and the last
+/// at `duration`; parts too short to keep (e.g. two starts snapped to the
+/// same keyframe) are left out
+pub fn spans(starts: Vec<Start>, duration: f64) -> Vec<(f64, f64, Option<String>)> {
+    let mut starts: Vec<Start> = starts.into_iter().filter(|(t, _)| *t >= 0.0 && *t < duration - MIN_PART).collect();
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let ends: Vec<f64> = starts.iter().skip(1).map(|(t, _)| *t).chain([duration]).collect();
+    starts.into_iter()
+        .zip(ends)
+        .filter(|((start, _), end)| end - start >= MIN_PART)
+        .map(|((start, title), end)| (start, end, title))
+        .collect()
+}
+
+/// The parts a split produced, written as parts.json in the output folder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitManifest {
+    pub source: String,
+    pub mode: String,
+    pub parts: Vec<ManifestPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestPart {
+    pub file: String,
+    pub start: f64,
+    pub end: f64,
+    pub duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Bytes on disk, once written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl SplitManifest {
+    pub fn new(source: &Path, mode: &str, parts: &[SplitPart]) -> Self {
+        let ms = |t: f64| (t * 1000.0).round() / 1000.0;
+        let parts = parts.iter()
+            .map(|part| ManifestPart {
+                file: part.output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                start: ms(part.start),
+                end: ms(part.end),
+                duration: ms(part.end - part.start),
+                title: part.title.clone(),
+                size: std::fs::metadata(&part.output).ok().map(|m| m.len()),
+            })
+            .collect();
+        SplitManifest { source: source.to_string_lossy().to_string(), mode: mode.to_string(), parts }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("failed to serialize the parts manifest")?;
+        std::fs::write(path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+}
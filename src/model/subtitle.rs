@@ -0,0 +1,1105 @@
+//! Subtitle files for `add-subtitles`, `extract-subtitles`, `convert-subtitles`
+//! and the `subtitle` retiming commands, and the caption styles
+//! `burn-subtitle` draws with.
+//!
+//! SRT, WebVTT and ASS are read and written here rather than by ffmpeg, so
+//! converting between them doesn't need ffmpeg at all. Cue text is kept in
+//! SRT's markup, which all three formats can express:
+//!
+//! ```text
+//! <i>italic</i>, <b>bold</b>, <u>underlined</u>
+//! and a line break
+//! ```
+//!
+//! ASS styles and positions, and WebVTT cue settings, are not carried over.
+
+use anyhow::{anyhow, bail, Context, Result};
+use crate::model::types::TextColor;
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+/// A text subtitle format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    /// WebVTT
+    Vtt,
+    /// Advanced SubStation Alpha (also reads SSA)
+    Ass,
+}
+
+impl SubtitleFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().trim_start_matches('.').to_lowercase().as_str() {
+            "srt" | "subrip" => Ok(SubtitleFormat::Srt),
+            "vtt" | "webvtt" => Ok(SubtitleFormat::Vtt),
+            "ass" | "ssa" => Ok(SubtitleFormat::Ass),
+            _ => bail!("Invalid subtitle format: {s} (try srt, vtt, or ass)"),
+        }
+    }
+
+    /// The format of a subtitle file, from its extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+        Self::parse(&ext).map_err(|_| anyhow!("{} is not an .srt, .vtt or .ass file", path.display()))
+    }
+
+    /// The format ffmpeg's subtitle decoder of this name reads, for text subtitles
+    pub fn for_codec(codec: &str) -> Option<Self> {
+        match codec {
+            "subrip" | "srt" | "mov_text" | "text" => Some(SubtitleFormat::Srt),
+            "webvtt" => Some(SubtitleFormat::Vtt),
+            "ass" | "ssa" => Some(SubtitleFormat::Ass),
+            _ => None,
+        }
+    }
+
+    pub fn ext(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+
+    /// ffmpeg encoder that writes this format
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "webvtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+}
+
+impl fmt::Display for SubtitleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SubtitleFormat::Srt => "SRT",
+            SubtitleFormat::Vtt => "WebVTT",
+            SubtitleFormat::Ass => "ASS",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where `add-subtitles` puts the tracks, which decides their codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleContainer {
+    Mp4,
+    Mov,
+    Mkv,
+    Webm,
+    /// An HLS folder with WebVTT renditions
+    Hls,
+}
+
+impl SubtitleContainer {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "mp4" | "m4v" => Ok(SubtitleContainer::Mp4),
+            "mov" => Ok(SubtitleContainer::Mov),
+            "mkv" | "matroska" => Ok(SubtitleContainer::Mkv),
+            "webm" => Ok(SubtitleContainer::Webm),
+            "hls" => Ok(SubtitleContainer::Hls),
+            _ => bail!("Invalid subtitle container: {s} (try mp4, mov, mkv, webm, or hls)"),
+        }
+    }
+
+    /// The container of a video file, if subtitle tracks can go in it
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        Self::parse(&ext).ok().filter(|c| *c != SubtitleContainer::Hls)
+    }
+
+    /// File extension; HLS output is a folder
+    pub fn ext(&self) -> Option<&'static str> {
+        match self {
+            SubtitleContainer::Mp4 => Some("mp4"),
+            SubtitleContainer::Mov => Some("mov"),
+            SubtitleContainer::Mkv => Some("mkv"),
+            SubtitleContainer::Webm => Some("webm"),
+            SubtitleContainer::Hls => None,
+        }
+    }
+
+    /// Subtitle codec for a track read from a file of this format:
+    /// mov_text for MP4/MOV, SRT or ASS as they are for MKV, WebVTT for WebM and HLS
+    pub fn codec_for(&self, format: SubtitleFormat) -> &'static str {
+        match (self, format) {
+            (SubtitleContainer::Mp4 | SubtitleContainer::Mov, _) => "mov_text",
+            (SubtitleContainer::Mkv, SubtitleFormat::Ass) => "ass",
+            (SubtitleContainer::Mkv, _) => "srt",
+            (SubtitleContainer::Webm | SubtitleContainer::Hls, _) => "webvtt",
+        }
+    }
+
+    /// Whether language tags are ISO 639-2/B codes ("ger") rather than /T ("deu")
+    pub fn uses_bibliographic_codes(&self) -> bool {
+        matches!(self, SubtitleContainer::Mkv | SubtitleContainer::Webm)
+    }
+}
+
+impl fmt::Display for SubtitleContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SubtitleContainer::Mp4 => "mp4",
+            SubtitleContainer::Mov => "mov",
+            SubtitleContainer::Mkv => "mkv",
+            SubtitleContainer::Webm => "webm",
+            SubtitleContainer::Hls => "hls",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// ISO 639-1, ISO 639-2/T, ISO 639-2/B and English name of common languages
+const LANGUAGES: &[(&str, &str, &str, &str)] = &[
+    ("ar", "ara", "ara", "Arabic"),
+    ("cs", "ces", "cze", "Czech"),
+    ("da", "dan", "dan", "Danish"),
+    ("de", "deu", "ger", "German"),
+    ("el", "ell", "gre", "Greek"),
+    ("en", "eng", "eng", "English"),
+    ("es", "spa", "spa", "Spanish"),
+    ("fi", "fin", "fin", "Finnish"),
+    ("fr", "fra", "fre", "French"),
+    ("he", "heb", "heb", "Hebrew"),
+    ("hi", "hin", "hin", "Hindi"),
+    ("hu", "hun", "hun", "Hungarian"),
+    ("id", "ind", "ind", "Indonesian"),
+    ("it", "ita", "ita", "Italian"),
+    ("ja", "jpn", "jpn", "Japanese"),
+    ("ko", "kor", "kor", "Korean"),
+    ("nl", "nld", "dut", "Dutch"),
+    ("no", "nor", "nor", "Norwegian"),
+    ("pl", "pol", "pol", "Polish"),
+    ("pt", "por", "por", "Portuguese"),
+    ("ro", "ron", "rum", "Romanian"),
+    ("ru", "rus", "rus", "Russian"),
+    ("sv", "swe", "swe", "Swedish"),
+    ("th", "tha", "tha", "Thai"),
+    ("tr", "tur", "tur", "Turkish"),
+    ("uk", "ukr", "ukr", "Ukrainian"),
+    ("vi", "vie", "vie", "Vietnamese"),
+    ("zh", "zho", "chi", "Chinese"),
+];
+
+/// The language of a subtitle track, given as an ISO 639 code ("en", "fra",
+/// "pt-BR") or an English name ("German")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Language {
+    /// BCP 47 tag for HLS playlists, e.g. "en" or "pt-BR"
+    pub tag: String,
+    /// ISO 639-2/T code for MP4 and MOV, e.g. "deu"
+    pub terminologic: String,
+    /// ISO 639-2/B code for Matroska, e.g. "ger"
+    pub bibliographic: String,
+    pub name: String,
+}
+
+impl Language {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(language) = Self::known(s) {
+            return Ok(language);
+        }
+        let code = s.to_lowercase();
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_lowercase()) {
+            return Ok(Language {
+                tag: code.clone(),
+                terminologic: code.clone(),
+                bibliographic: code.clone(),
+                name: code,
+            });
+        }
+        bail!("Unknown language: {s} (use a code like en, fra or pt-BR, or a three-letter ISO 639-2 code)")
+    }
+
+    /// A language from the table of common ones, by code or English name
+    fn known(s: &str) -> Option<Self> {
+        let lower = s.trim().to_lowercase();
+        let (primary, region) = match lower.split_once(['-', '_']) {
+            Some((primary, region)) if !region.is_empty() => (primary, Some(region)),
+            _ => (lower.as_str(), None),
+        };
+        let &(two, t, b, name) = LANGUAGES.iter().find(|(two, t, b, name)| {
+            primary == *two || primary == *t || primary == *b || (region.is_none() && primary == name.to_lowercase())
+        })?;
+        let tag = match region {
+            Some(region) => format!("{two}-{}", region.to_uppercase()),
+            None => two.to_string(),
+        };
+        Some(Language { tag, terminologic: t.to_string(), bibliographic: b.to_string(), name: name.to_string() })
+    }
+
+    /// The language named at the end of a subtitle file name
+    /// ("movie.en.srt", "movie.German.srt", "fr.vtt"), if any
+    pub fn from_file_name(path: &Path) -> Option<Self> {
+        let stem = path.file_stem()?.to_string_lossy().to_string();
+        let last = stem.rsplit('.').next()?;
+        Self::known(last)
+    }
+
+    /// The code to tag a track with in this container
+    pub fn code_for(&self, container: SubtitleContainer) -> &str {
+        if container.uses_bibliographic_codes() { &self.bibliographic } else { &self.terminologic }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tag)
+    }
+}
+
+/// One subtitle: shown from `start` to `end`, in milliseconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub start: u64,
+    pub end: u64,
+    /// Lines separated by '\n', with SRT markup
+    pub text: String,
+}
+
+/// The cues of a subtitle file, in order of their start
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Subtitles {
+    pub cues: Vec<Cue>,
+}
+
+impl Subtitles {
+    /// Read a subtitle file, in the format its extension names
+    pub fn read(path: &Path) -> Result<Self> {
+        let format = SubtitleFormat::from_path(path)?;
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let text = String::from_utf8_lossy(&bytes);
+        Self::parse(&text, format).with_context(|| format!("Failed to read {} as {}", path.display(), format))
+    }
+
+    pub fn parse(text: &str, format: SubtitleFormat) -> Result<Self> {
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+        let mut cues = match format {
+            SubtitleFormat::Srt => parse_srt(&text)?,
+            SubtitleFormat::Vtt => parse_vtt(&text)?,
+            SubtitleFormat::Ass => parse_ass(&text)?,
+        };
+        if cues.is_empty() {
+            bail!("No subtitles found");
+        }
+        cues.sort_by_key(|cue| cue.start);
+        Ok(Subtitles { cues })
+    }
+
+    pub fn render(&self, format: SubtitleFormat) -> String {
+        match format {
+            SubtitleFormat::Srt => render_srt(&self.cues),
+            SubtitleFormat::Vtt => render_vtt(&self.cues),
+            SubtitleFormat::Ass => render_ass(&self.cues),
+        }
+    }
+
+    pub fn write(&self, path: &Path, format: SubtitleFormat) -> Result<()> {
+        std::fs::write(path, self.render(format)).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// When the last cue ends, in milliseconds
+    pub fn end(&self) -> u64 {
+        self.cues.iter().map(|cue| cue.end).max().unwrap_or(0)
+    }
+
+    /// Move every cue by `offset` milliseconds; cues pushed before 0 are
+    /// clipped, or dropped when they end there
+    pub fn shift(&mut self, offset: i64) {
+        let moved = |t: u64| (t as i64 + offset).max(0) as u64;
+        self.cues = std::mem::take(&mut self.cues)
+            .into_iter()
+            .map(|cue| Cue { start: moved(cue.start), end: moved(cue.end), ..cue })
+            .filter(|cue| cue.end > cue.start)
+            .collect();
+    }
+
+    pub fn stretch(&mut self, stretch: &Stretch) {
+        for cue in &mut self.cues {
+            cue.start = stretch.map(cue.start);
+            cue.end = stretch.map(cue.end).max(cue.start);
+        }
+    }
+
+    /// Keep what is shown within the ranges (start and end in milliseconds),
+    /// played back to back from 0 the way a trimmed or cut video is
+    pub fn cut(&self, keep: &[(u64, u64)]) -> Subtitles {
+        let mut cues = Vec::new();
+        let mut at = 0;
+        for &(from, to) in keep {
+            for cue in &self.cues {
+                let (start, end) = (cue.start.max(from), cue.end.min(to));
+                if end > start {
+                    cues.push(Cue { start: start - from + at, end: end - from + at, text: cue.text.clone() });
+                }
+            }
+            at += to.saturating_sub(from);
+        }
+        Subtitles { cues }
+    }
+
+    /// Parts between the split points, each starting from 0
+    pub fn split(&self, at: &[u64]) -> Vec<Subtitles> {
+        let mut bounds = vec![0];
+        bounds.extend(at.iter().copied());
+        bounds.push(u64::MAX);
+        bounds.windows(2).map(|pair| self.cut(&[(pair[0], pair[1])])).collect()
+    }
+
+    /// All cues of several files in one, each file starting at its offset
+    pub fn merge(parts: Vec<(Subtitles, u64)>) -> Subtitles {
+        let mut cues: Vec<Cue> = parts
+            .into_iter()
+            .flat_map(|(part, offset)| {
+                part.cues.into_iter().map(move |cue| Cue { start: cue.start + offset, end: cue.end + offset, ..cue })
+            })
+            .collect();
+        cues.sort_by_key(|cue| cue.start);
+        Subtitles { cues }
+    }
+}
+
+/// A point in a subtitle file, in milliseconds: "1:02:03.5", "2:03,250",
+/// "62.5" or "62.5s"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    pub fn parse(s: &str) -> Result<Self> {
+        let t = s.trim();
+        let ms = if t.contains(':') {
+            parse_timestamp(t)
+        } else {
+            seconds_to_ms(t.strip_suffix('s').unwrap_or(t)).and_then(|ms| u64::try_from(ms).ok())
+        };
+        ms.map(Timestamp).ok_or_else(|| anyhow!("Invalid time: {s} (use 1:02:03.5, 2:03.25 or 62.5s)"))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0;
+        let (h, m, s, frac) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+        if h > 0 {
+            write!(f, "{h}:{m:02}:{s:02}")?;
+        } else {
+            write!(f, "{m}:{s:02}")?;
+        }
+        if frac > 0 {
+            write!(f, ".{}", format!("{frac:03}").trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+/// Seconds like "2.5" in milliseconds
+fn seconds_to_ms(s: &str) -> Option<i64> {
+    let seconds: f64 = s.parse().ok()?;
+    seconds.is_finite().then(|| (seconds * 1000.0).round() as i64)
+}
+
+/// How far to move subtitles, in milliseconds: "+2.5s", "-1.2", "-0:01.5" or "-500ms"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offset(pub i64);
+
+impl Offset {
+    pub fn parse(s: &str) -> Result<Self> {
+        let t = s.trim();
+        let (sign, body) = match t.strip_prefix('-') {
+            Some(body) => (-1, body),
+            None => (1, t.strip_prefix('+').unwrap_or(t)),
+        };
+        let ms = if let Some(ms) = body.strip_suffix("ms") {
+            ms.trim().parse::<i64>().ok()
+        } else if body.contains(':') {
+            parse_timestamp(body).map(|ms| ms as i64)
+        } else {
+            seconds_to_ms(body.strip_suffix('s').unwrap_or(body).trim())
+        };
+        ms.map(|ms| Offset(sign * ms)).ok_or_else(|| anyhow!("Invalid offset: {s} (use +2.5s, -1.2s, -500ms or -0:01.5)"))
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "+" };
+        write!(f, "{sign}{}s", self.0.unsigned_abs() as f64 / 1000.0)
+    }
+}
+
+/// Ranges to keep, played back to back: "0:10-0:30,1:00-1:20"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutList(pub Vec<(Timestamp, Timestamp)>);
+
+impl CutList {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for range in s.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let Some((start, end)) = range.split_once('-') else {
+                bail!("Invalid range: {range} (use start-end, e.g. 0:10-0:30)");
+            };
+            let (start, end) = (Timestamp::parse(start)?, Timestamp::parse(end)?);
+            if end <= start {
+                bail!("Range {range} ends before it starts");
+            }
+            ranges.push((start, end));
+        }
+        if ranges.is_empty() {
+            bail!("Give at least one range to keep, e.g. 0:10-0:30");
+        }
+        Ok(CutList(ranges))
+    }
+
+    /// The ranges in milliseconds
+    pub fn ranges(&self) -> Vec<(u64, u64)> {
+        self.0.iter().map(|(start, end)| (start.0, end.0)).collect()
+    }
+}
+
+impl fmt::Display for CutList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<String> = self.0.iter().map(|(start, end)| format!("{start}-{end}")).collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+/// A linear retiming of subtitles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stretch {
+    /// Two cues whose times were (old, new), in milliseconds: "1:00=1:02.5,1:30:00=1:33:45"
+    Anchors((u64, u64), (u64, u64)),
+    /// Subtitles timed for one frame rate moved to video at another: "25=23.976fps"
+    Fps(f64, f64),
+}
+
+impl Stretch {
+    pub fn parse(s: &str) -> Result<Self> {
+        let t = s.trim().to_lowercase();
+        if t.contains("fps") {
+            let t = t.replace("fps", "");
+            let rates = t.split_once('=')
+                .and_then(|(from, to)| Some((from.trim().parse::<f64>().ok()?, to.trim().parse::<f64>().ok()?)))
+                .filter(|(from, to)| *from > 0.0 && *to > 0.0 && from.is_finite() && to.is_finite());
+            let Some((from, to)) = rates else {
+                bail!("Invalid frame rates: {s} (use the subtitles' rate and the video's, e.g. 25=23.976fps)");
+            };
+            return Ok(Stretch::Fps(from, to));
+        }
+
+        let anchors = t.split(',').map(|anchor| -> Result<(u64, u64)> {
+            let Some((old, new)) = anchor.split_once('=') else {
+                bail!("Invalid anchor: {anchor} (use old=new, e.g. 1:00=1:02.5)");
+            };
+            Ok((Timestamp::parse(old)?.0, Timestamp::parse(new)?.0))
+        }).collect::<Result<Vec<_>>>()?;
+        match anchors.as_slice() {
+            [a, b] if a.0 != b.0 => Ok(Stretch::Anchors(*a, *b)),
+            [_, _] => bail!("The two anchors need different times"),
+            _ => bail!("Give two anchors, e.g. 1:00=1:02.5,1:30:00=1:33:45, or frame rates like 25=23.976fps"),
+        }
+    }
+
+    /// Where a time (in milliseconds) moves to
+    pub fn map(&self, t: u64) -> u64 {
+        let t = t as f64;
+        let mapped = match *self {
+            Stretch::Anchors((old1, new1), (old2, new2)) => {
+                let scale = (new2 as f64 - new1 as f64) / (old2 as f64 - old1 as f64);
+                new1 as f64 + (t - old1 as f64) * scale
+            }
+            Stretch::Fps(from, to) => t * from / to,
+        };
+        mapped.round().max(0.0) as u64
+    }
+}
+
+impl fmt::Display for Stretch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stretch::Anchors((old1, new1), (old2, new2)) => write!(
+                f, "{}={},{}={}", Timestamp(*old1), Timestamp(*new1), Timestamp(*old2), Timestamp(*new2)
+            ),
+            Stretch::Fps(from, to) => write!(f, "{from}={to}fps"),
+        }
+    }
+}
+
+/// A ready-made look for burned-in captions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionPreset {
+    /// Big bold captions in the middle of the frame, for phones
+    Social,
+    /// Boxed captions inside the 5% title-safe margins
+    Broadcast,
+}
+
+impl CaptionPreset {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "social" => Ok(CaptionPreset::Social),
+            "broadcast" => Ok(CaptionPreset::Broadcast),
+            _ => bail!("Unknown caption preset: {s} (use social or broadcast)"),
+        }
+    }
+
+    /// The preset's ASS style, on the 384x288 script scale SRT is rendered at
+    fn look(&self) -> CaptionLook {
+        match self {
+            CaptionPreset::Social => CaptionLook {
+                font: "Arial",
+                size: 22,
+                bold: true,
+                outline: 2,
+                background: None,
+                position: CaptionPosition::Middle,
+                margin_v: 10,
+                margin_h: 20,
+            },
+            CaptionPreset::Broadcast => CaptionLook {
+                font: "Arial",
+                size: 16,
+                bold: false,
+                outline: 1,
+                background: Some(TextColor { r: 0, g: 0, b: 0 }),
+                position: CaptionPosition::Bottom,
+                margin_v: 15,
+                margin_h: 20,
+            },
+        }
+    }
+}
+
+impl fmt::Display for CaptionPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptionPreset::Social => write!(f, "social"),
+            CaptionPreset::Broadcast => write!(f, "broadcast"),
+        }
+    }
+}
+
+struct CaptionLook {
+    font: &'static str,
+    size: u32,
+    bold: bool,
+    outline: u32,
+    background: Option<TextColor>,
+    position: CaptionPosition,
+    margin_v: u32,
+    margin_h: u32,
+}
+
+/// Where burned-in captions sit in the frame, always centered across it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionPosition {
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl CaptionPosition {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "top" => Ok(CaptionPosition::Top),
+            "middle" | "center" | "centre" => Ok(CaptionPosition::Middle),
+            "bottom" => Ok(CaptionPosition::Bottom),
+            _ => bail!("Unknown caption position: {s} (use top, middle or bottom)"),
+        }
+    }
+
+    /// ASS numpad alignment
+    fn alignment(&self) -> u32 {
+        match self {
+            CaptionPosition::Top => 8,
+            CaptionPosition::Middle => 5,
+            CaptionPosition::Bottom => 2,
+        }
+    }
+}
+
+impl fmt::Display for CaptionPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptionPosition::Top => write!(f, "top"),
+            CaptionPosition::Middle => write!(f, "middle"),
+            CaptionPosition::Bottom => write!(f, "bottom"),
+        }
+    }
+}
+
+/// How `burn-subtitle` draws the subtitles. Sizes are in pixels of the
+/// video; what isn't set comes from the preset, then from the subtitle file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubtitleStyle {
+    pub preset: Option<CaptionPreset>,
+    pub font: Option<String>,
+    pub size: Option<u32>,
+    pub color: Option<TextColor>,
+    /// Outline width, or the padding around the background box
+    pub outline: Option<u32>,
+    /// Draw a semi-transparent box of this colour behind the text
+    pub background: Option<TextColor>,
+    pub position: Option<CaptionPosition>,
+    /// Distance from the top or bottom edge
+    pub margin: Option<u32>,
+}
+
+impl SubtitleStyle {
+    pub fn is_empty(&self) -> bool {
+        *self == SubtitleStyle::default()
+    }
+
+    /// The `force_style` override for a video `height` pixels tall, whose
+    /// subtitles are laid out `play_res` lines high (288 unless an ASS file
+    /// says otherwise)
+    pub fn force_style(&self, height: u32, play_res: u32) -> String {
+        let from_pixels = |px: u32| (px as f64 * play_res as f64 / height.max(1) as f64).round() as u32;
+        let from_preset = |units: u32| (units as f64 * play_res as f64 / 288.0).round() as u32;
+        let look = self.preset.map(|preset| preset.look());
+        let mut fields = Vec::new();
+
+        if let Some(font) = self.font.as_deref().or(look.as_ref().map(|l| l.font)) {
+            fields.push(format!("FontName={font}"));
+        }
+        if let Some(size) = self.size.map(from_pixels).or(look.as_ref().map(|l| from_preset(l.size))) {
+            fields.push(format!("FontSize={}", size.max(1)));
+        }
+        if look.as_ref().is_some_and(|l| l.bold) {
+            fields.push("Bold=1".to_string());
+        }
+        if let Some(color) = self.color {
+            fields.push(format!("PrimaryColour={}", ass_colour(color, 0x00)));
+        }
+        let outline = self.outline.map(from_pixels).or(look.as_ref().map(|l| from_preset(l.outline)));
+        match self.background.or(look.as_ref().and_then(|l| l.background)) {
+            Some(background) => {
+                // BorderStyle 3 draws the outline as an opaque box
+                let colour = ass_colour(background, 0x60);
+                fields.push("BorderStyle=3".to_string());
+                fields.push(format!("OutlineColour={colour}"));
+                fields.push(format!("BackColour={colour}"));
+                fields.push(format!("Outline={}", outline.unwrap_or(1)));
+                fields.push("Shadow=0".to_string());
+            }
+            None => {
+                if let Some(outline) = outline {
+                    fields.push("BorderStyle=1".to_string());
+                    fields.push(format!("OutlineColour={}", ass_colour(TextColor { r: 0, g: 0, b: 0 }, 0x00)));
+                    fields.push(format!("Outline={outline}"));
+                    fields.push("Shadow=0".to_string());
+                }
+            }
+        }
+        if let Some(position) = self.position.or(look.as_ref().map(|l| l.position)) {
+            fields.push(format!("Alignment={}", position.alignment()));
+        }
+        if let Some(margin) = self.margin.map(from_pixels).or(look.as_ref().map(|l| from_preset(l.margin_v))) {
+            fields.push(format!("MarginV={margin}"));
+        }
+        if let Some(look) = &look {
+            let margin = from_preset(look.margin_h);
+            fields.push(format!("MarginL={margin}"));
+            fields.push(format!("MarginR={margin}"));
+        }
+        fields.join(",")
+    }
+}
+
+/// An ASS colour, &HAABBGGRR with 00 for opaque
+fn ass_colour(color: TextColor, alpha: u8) -> String {
+    format!("&H{alpha:02X}{:02X}{:02X}{:02X}", color.b, color.g, color.r)
+}
+
+/// The PlayResY an ASS script lays itself out at (288 when it doesn't say)
+pub fn ass_play_res(text: &str) -> u32 {
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix("PlayResY:"))
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|&res| res > 0)
+        .unwrap_or(288)
+}
+
+/// A subtitle track of a video, by number from 1 or by language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtitleSelector {
+    Index(usize),
+    Language(Language),
+}
+
+impl SubtitleSelector {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            let index: usize = s.parse().with_context(|| format!("Invalid track number: {s}"))?;
+            if index == 0 {
+                bail!("Subtitle tracks are numbered from 1");
+            }
+            return Ok(SubtitleSelector::Index(index));
+        }
+        Ok(SubtitleSelector::Language(Language::parse(s)?))
+    }
+
+    /// Whether a track tagged with this ISO 639 code ("eng", "ger") matches
+    pub fn matches_language(&self, code: &str) -> bool {
+        match self {
+            SubtitleSelector::Index(_) => false,
+            SubtitleSelector::Language(language) => Language::parse(code).is_ok_and(|track| {
+                track.terminologic == language.terminologic || track.bibliographic == language.bibliographic
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SubtitleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubtitleSelector::Index(index) => write!(f, "track {index}"),
+            SubtitleSelector::Language(language) => write!(f, "{} ({})", language.name, language.tag),
+        }
+    }
+}
+
+/// Blocks of non-blank lines
+fn blocks(text: &str) -> Vec<Vec<&str>> {
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// A cue from a block with a "start --> end" line followed by its text;
+/// None for blocks without one (SRT numbers on their own, VTT notes)
+fn timed_block(block: &[&str], number: usize) -> Result<Option<Cue>> {
+    let Some(at) = block.iter().position(|line| line.contains("-->")) else {
+        return Ok(None);
+    };
+    let (start, end) = block[at].split_once("-->").unwrap();
+    // WebVTT cue settings ("align:start") follow the end time
+    let end = end.split_whitespace().next().unwrap_or("");
+    let (Some(start), Some(end)) = (parse_timestamp(start), parse_timestamp(end)) else {
+        bail!("Subtitle {number} has an invalid time: {}", block[at].trim());
+    };
+    let text = block[at + 1..].iter().map(|line| line.trim()).collect::<Vec<_>>().join("\n");
+    Ok(Some(Cue { start, end: end.max(start), text }))
+}
+
+fn parse_srt(text: &str) -> Result<Vec<Cue>> {
+    let mut cues = Vec::new();
+    for (i, block) in blocks(text).iter().enumerate() {
+        if let Some(cue) = timed_block(block, i + 1)? {
+            cues.push(cue);
+        }
+    }
+    Ok(cues)
+}
+
+fn parse_vtt(text: &str) -> Result<Vec<Cue>> {
+    if !text.starts_with("WEBVTT") {
+        bail!("Not a WebVTT file (it has to start with WEBVTT)");
+    }
+    let mut cues = Vec::new();
+    for (i, block) in blocks(text).iter().enumerate().skip(1) {
+        let first = block[0].trim_start();
+        if ["NOTE", "STYLE", "REGION"].iter().any(|kind| first.starts_with(kind)) {
+            continue;
+        }
+        if let Some(mut cue) = timed_block(block, i)? {
+            cue.text = from_vtt_text(&cue.text);
+            cues.push(cue);
+        }
+    }
+    Ok(cues)
+}
+
+fn parse_ass(text: &str) -> Result<Vec<Cue>> {
+    // Field order as in [Events] "Format:"; this is the ASS default
+    let mut fields: Vec<String> = ["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let mut in_events = false;
+    let mut cues = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(format) = line.strip_prefix("Format:") {
+            fields = format.split(',').map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        let values: Vec<&str> = dialogue.splitn(fields.len(), ',').collect();
+        let field = |name: &str| fields.iter().position(|f| f == name).and_then(|at| values.get(at)).copied();
+        let times = (field("start").and_then(parse_timestamp), field("end").and_then(parse_timestamp));
+        let (Some(start), Some(end)) = times else {
+            bail!("Line {} has an invalid time: {}", i + 1, line);
+        };
+        let text = from_ass_text(field("text").unwrap_or(""));
+        if !text.trim().is_empty() {
+            cues.push(Cue { start, end: end.max(start), text });
+        }
+    }
+    Ok(cues)
+}
+
+/// Milliseconds from "01:02:03,456" (SRT), "01:02:03.456" or "02:03.456"
+/// (WebVTT), or "1:02:03.45" (ASS)
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (clock, fraction) = match s.rfind([',', '.']) {
+        Some(at) => (&s[..at], &s[at + 1..]),
+        None => (s, ""),
+    };
+    let parts: Vec<u64> = clock.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let seconds = match parts.as_slice() {
+        [h, m, s] => h * 3600 + m * 60 + s,
+        [m, s] => m * 60 + s,
+        _ => return None,
+    };
+    if !fraction.chars().all(|c| c.is_ascii_digit()) || fraction.len() > 9 {
+        return None;
+    }
+    let millis = format!("{fraction:0<3}")[..3].parse::<u64>().ok()?;
+    Some(seconds * 1000 + millis)
+}
+
+fn timestamp(ms: u64, separator: char) -> String {
+    format!("{:02}:{:02}:{:02}{separator}{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+fn ass_timestamp(ms: u64) -> String {
+    let cs = (ms + 5) / 10;
+    format!("{}:{:02}:{:02}.{:02}", cs / 360_000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+/// The text, with empty lines dropped (they would end the cue)
+fn cue_lines(text: &str) -> String {
+    text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+fn render_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ','),
+            cue_lines(&cue.text)
+        ));
+    }
+    out
+}
+
+fn render_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            cue_lines(&to_vtt_text(&cue.text))
+        ));
+    }
+    out
+}
+
+const ASS_HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+PlayResX: 384
+PlayResY: 288
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,16,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+fn render_ass(cues: &[Cue]) -> String {
+    let mut out = String::from(ASS_HEADER);
+    for cue in cues {
+        out.push_str(&format!(
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+            ass_timestamp(cue.start),
+            ass_timestamp(cue.end),
+            to_ass_text(&cue_lines(&cue.text))
+        ));
+    }
+    out
+}
+
+/// Text and markup of a cue, in order
+enum Piece<'a> {
+    Text(&'a str),
+    Tag(&'a str),
+}
+
+fn pieces(text: &str) -> Vec<Piece<'_>> {
+    let tag = Regex::new(r"<[^<>\n]*>").unwrap();
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for m in tag.find_iter(text) {
+        if m.start() > last {
+            pieces.push(Piece::Text(&text[last..m.start()]));
+        }
+        pieces.push(Piece::Tag(m.as_str()));
+        last = m.end();
+    }
+    if last < text.len() {
+        pieces.push(Piece::Text(&text[last..]));
+    }
+    pieces
+}
+
+/// "i", "b" or "u" and whether the tag opens, for the markup every format shares
+fn simple_tag(tag: &str) -> Option<(char, bool)> {
+    let inner = tag.trim_start_matches('<').trim_end_matches('>').trim().to_lowercase();
+    let (name, open) = match inner.strip_prefix('/') {
+        Some(name) => (name.to_string(), false),
+        None => (inner, true),
+    };
+    match name.as_str() {
+        "i" | "b" | "u" => name.chars().next().map(|c| (c, open)),
+        _ => None,
+    }
+}
+
+fn from_vtt_text(text: &str) -> String {
+    pieces(text).iter().map(|piece| match piece {
+        Piece::Tag(tag) => match simple_tag(tag) {
+            Some((name, true)) => format!("<{name}>"),
+            Some((name, false)) => format!("</{name}>"),
+            // Voices, classes, ruby and karaoke timestamps
+            None => String::new(),
+        },
+        Piece::Text(text) => text
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&nbsp;", "\u{a0}")
+            .replace("&lrm;", "\u{200e}")
+            .replace("&rlm;", "\u{200f}")
+            .replace("&amp;", "&"),
+    }).collect()
+}
+
+fn to_vtt_text(text: &str) -> String {
+    let overrides = Regex::new(r"\{\\[^}]*\}").unwrap();
+    let text = overrides.replace_all(text, "");
+    pieces(&text).iter().map(|piece| match piece {
+        Piece::Tag(tag) => match simple_tag(tag) {
+            Some((name, true)) => format!("<{name}>"),
+            Some((name, false)) => format!("</{name}>"),
+            // <font color=...> has no WebVTT counterpart without a style sheet
+            None => String::new(),
+        },
+        Piece::Text(text) => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+    }).collect()
+}
+
+fn from_ass_text(text: &str) -> String {
+    let block = Regex::new(r"\{([^}]*)\}").unwrap();
+    let tag = Regex::new(r"\\([ibu])(\d+)").unwrap();
+    let text = text.replace("\\N", "\n").replace("\\n", "\n").replace("\\h", "\u{a0}");
+    block.replace_all(&text, |caps: &regex::Captures| {
+        tag.captures_iter(&caps[1])
+            .map(|t| if &t[2] == "0" { format!("</{}>", &t[1]) } else { format!("<{}>", &t[1]) })
+            .collect::<String>()
+    }).into_owned()
+}
+
+fn to_ass_text(text: &str) -> String {
+    pieces(text).iter().map(|piece| match piece {
+        Piece::Tag(tag) => match simple_tag(tag) {
+            Some((name, open)) => format!("{{\\{name}{}}}", if open { 1 } else { 0 }),
+            None => String::new(),
+        },
+        Piece::Text(text) => text.replace('\n', "\\N"),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: u64, end: u64, text: &str) -> Cue {
+        Cue { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("01:02:03.456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("02:03.456"), Some(123_456));
+        assert_eq!(parse_timestamp("1:02:03.45"), Some(3_723_450));
+        assert_eq!(parse_timestamp(" 00:00:01,5 "), Some(1_500));
+        assert_eq!(parse_timestamp("00:00:01"), Some(1_000));
+
+        assert_eq!(parse_timestamp("1.5"), None);
+        assert_eq!(parse_timestamp("00:00:01,5x"), None);
+        assert_eq!(parse_timestamp("aa:bb:cc"), None);
+    }
+
+    #[test]
+    fn test_parse_srt() {
+        let text = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\n<i>world</i>\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n";
+        let subs = Subtitles::parse(text, SubtitleFormat::Srt).unwrap();
+        assert_eq!(subs.cues, vec![cue(1000, 2500, "Hello\n<i>world</i>"), cue(3000, 4000, "Bye")]);
+
+        // Cues are sorted, and an end before the start is clamped
+        let text = "1\n00:00:05,000 --> 00:00:04,000\nLate\n\n2\n00:00:01,000 --> 00:00:02,000\nEarly\n";
+        let subs = Subtitles::parse(text, SubtitleFormat::Srt).unwrap();
+        assert_eq!(subs.cues, vec![cue(1000, 2000, "Early"), cue(5000, 5000, "Late")]);
+
+        assert!(Subtitles::parse("1\n00:00:xx,000 --> 00:00:02,000\nBad\n", SubtitleFormat::Srt).is_err());
+        assert!(Subtitles::parse("just text\n", SubtitleFormat::Srt).is_err());
+    }
+
+    #[test]
+    fn test_parse_vtt() {
+        let text = "\u{feff}WEBVTT - title\n\nNOTE a comment\n--> not a cue\n\nSTYLE\n::cue { color: red }\n\n\
+                    intro\n00:01.000 --> 00:02.000 align:start position:10%\n<b>Hi</b> <c.yellow>there</c>\n\n\
+                    00:00:03.000 --> 00:00:04.000 line:0\nBye\n";
+        let subs = Subtitles::parse(text, SubtitleFormat::Vtt).unwrap();
+        assert_eq!(subs.cues, vec![cue(1000, 2000, "<b>Hi</b> there"), cue(3000, 4000, "Bye")]);
+
+        assert!(Subtitles::parse("00:01.000 --> 00:02.000\nHi\n", SubtitleFormat::Vtt).is_err());
+    }
+
+    #[test]
+    fn test_srt_vtt_round_trip() {
+        let subs = Subtitles { cues: vec![cue(1000, 2500, "Hello\n<i>world</i>"), cue(3_723_456, 3_724_000, "Bye")] };
+        for format in [SubtitleFormat::Srt, SubtitleFormat::Vtt] {
+            assert_eq!(Subtitles::parse(&subs.render(format), format).unwrap(), subs);
+        }
+    }
+}
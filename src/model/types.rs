@@ -1,7 +1,9 @@
 use crate::model::ConvertFormat;
 use anyhow::{anyhow, bail, Result};
 use regex::Regex;
+use serde::Deserialize;
 use std::fmt;
+use std::path::PathBuf;
 
 /// Represents a time duration that can be parsed from various formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -158,11 +160,13 @@ impl fmt::Display for TargetBitrate {
     }
 }
 
-/// Represents a resize target - either a preset (720p, 1080p, 4k) or explicit dimensions (WxH)
+/// Represents a resize target - a preset (720p, 1080p, 4k), a height (480p) or explicit dimensions (WxH)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResizeTarget {
     Preset(ResolutionPreset),
     Dimensions { width: u32, height: u32 },
+    /// Height only ("480p"); the width follows the aspect ratio
+    Height(u32),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -173,7 +177,7 @@ pub enum ResolutionPreset {
 }
 
 impl ResizeTarget {
-    /// Parse resize target from string: "720p", "1080p", "4k" or "1280x720"
+    /// Parse resize target from string: "720p", "1080p", "4k", "480p" or "1280x720"
     pub fn parse(s: &str) -> Result<Self> {
         let s_lower = s.to_lowercase();
         
@@ -183,7 +187,10 @@ impl ResizeTarget {
                 "720p" => ResolutionPreset::P720,
                 "1080p" => ResolutionPreset::P1080,
                 "2160p" => ResolutionPreset::P4K,
-                _ => bail!("Unknown preset: {s} (try 720p, 1080p, 4k)"),
+                other => match other.trim_end_matches('p').parse::<u32>() {
+                    Ok(height) if height >= 2 && height % 2 == 0 => return Ok(ResizeTarget::Height(height)),
+                    _ => bail!("Unknown preset: {s} (try 480p, 720p, 1080p, 4k)"),
+                },
             };
             Ok(ResizeTarget::Preset(preset))
         } else if s_lower == "4k" {
@@ -214,6 +221,7 @@ impl ResizeTarget {
             ResizeTarget::Dimensions { width, height } => {
                 format!("scale={width}:{height}")
             }
+            ResizeTarget::Height(height) => format!("scale=-2:{height}"),
         }
     }
 }
@@ -229,6 +237,7 @@ impl fmt::Display for ResizeTarget {
             ResizeTarget::Dimensions { width, height } => {
                 write!(f, "{}x{}", width, height)
             }
+            ResizeTarget::Height(height) => write!(f, "{}p", height),
         }
     }
 }
@@ -271,6 +280,18 @@ pub enum FlipDirection {
     Vertical,
 }
 
+impl FlipDirection {
+    /// Parse flip direction from string: "horizontal", "vertical"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "horizontal" | "h" => Ok(FlipDirection::Horizontal),
+            "vertical" | "v" => Ok(FlipDirection::Vertical),
+            _ => bail!("Invalid flip direction: {s} (try horizontal or vertical)"),
+        }
+    }
+}
+
 /// Represents rotation degrees (0, 90, 180, 270)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RotateDegrees(pub i32);
@@ -283,6 +304,15 @@ impl RotateDegrees {
             _ => bail!("Rotate supports 0/90/180/270 for now."),
         }
     }
+
+    /// Parse rotation from string formats: "90", "90deg", "-90"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        let num_str = s_lower.trim_end_matches("degrees").trim_end_matches("deg").trim_end_matches('°').trim();
+        let degrees = num_str.parse::<i32>()
+            .map_err(|_| anyhow!("Invalid rotation: {s} (try 90, 180, or 270)"))?;
+        Self::new(degrees)
+    }
 }
 
 /// Represents watermark position - corners or custom coordinates
@@ -381,6 +411,24 @@ impl Opacity {
         }
         Ok(Opacity(value))
     }
+
+    /// Parse opacity from string formats: "0.5", "50%"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let value = if let Some(percent) = s.strip_suffix('%') {
+            percent.trim().parse::<f64>().map(|p| p / 100.0)
+        } else {
+            s.parse::<f64>()
+        };
+        let value = value.map_err(|_| anyhow!("Invalid opacity: {s} (try 0.5 or 50%)"))?;
+        Self::new(value)
+    }
+}
+
+impl Default for Opacity {
+    fn default() -> Self {
+        Opacity(1.0)
+    }
 }
 
 impl fmt::Display for Opacity {
@@ -486,7 +534,8 @@ impl Default for TextColor {
 }
 
 /// Represents text style configuration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct TextStyle {
     pub font_size: Option<u32>,
     pub font_file: Option<String>,
@@ -494,7 +543,8 @@ pub struct TextStyle {
 }
 
 /// Represents video filter adjustments (brightness, contrast, saturation)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct FilterAdjustments {
     pub brightness: Option<f64>,  // -1.0 to 1.0, default 0.0
     pub contrast: Option<f64>,    // -1.0 to 1.0, default 0.0
@@ -596,6 +646,15 @@ pub enum BlurType {
     // Face detection could be added later
 }
 
+impl BlurType {
+    /// Parse blur type from string formats: "100,100,200,200", "region 100,100,200,200"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let region = s.strip_prefix("region").unwrap_or(s);
+        Ok(BlurType::Region(BlurRegion::parse(region)?))
+    }
+}
+
 /// Represents a duration in seconds (for fade in/out, etc.)
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Duration {
@@ -645,6 +704,33 @@ pub enum CompressTarget {
     Quality(QualityPreset),
 }
 
+impl CompressTarget {
+    /// Parse compress target from string formats: "high-quality", "2000kbps", "500k", "10mb"
+    pub fn parse(s: &str) -> Result<Self> {
+        // Try to parse as quality preset first (e.g., "high-quality")
+        let target_lower = s.trim().to_lowercase();
+        if let Some(quality_str) = target_lower.strip_suffix("-quality") {
+            return Ok(CompressTarget::Quality(QualityPreset::parse(quality_str.trim())?));
+        }
+
+        // Try to parse as bitrate first (e.g., "2000kbps", "2mbps", "500k")
+        // Bitrate patterns: ends with bps/kbps/mbps/gbps, or just k/m/g (but not mb/gb which are sizes)
+        let looks_like_bitrate = target_lower.ends_with("bps")
+            || (target_lower.ends_with('k') && !target_lower.ends_with("mk"))
+            || (target_lower.ends_with('m') && !target_lower.ends_with("mb") && !target_lower.ends_with("gb"))
+            || (target_lower.ends_with('g') && !target_lower.ends_with("gb"));
+
+        if looks_like_bitrate {
+            if let Ok(bitrate) = TargetBitrate::parse(s) {
+                return Ok(CompressTarget::Bitrate(bitrate));
+            }
+        }
+
+        // Fall back to size parsing
+        Ok(CompressTarget::Size(TargetSize::parse(s)?))
+    }
+}
+
 /// Represents how to split a video
 #[derive(Debug, Clone, PartialEq)]
 pub enum SplitMode {
@@ -652,6 +738,144 @@ pub enum SplitMode {
     Every(Duration),
     /// Split into N equal parts
     IntoParts(u32),
+    /// Split into the video's chapters
+    ByChapters,
+    /// Split where the picture changes by more than `threshold` (0 to 1)
+    Scenes { threshold: f64 },
+    /// Split in the middle of silences at least `min_silence` long and
+    /// quieter than `noise` dB
+    Silence { min_silence: Duration, noise: f64 },
+    /// Split into chunks under a file size
+    Size(TargetSize),
+}
+
+const SPLIT_MODE_HINT: &str = "try 'every 30s', 'into 3 parts', 'by chapters', 'by scenes', 'on silence' or 'into chunks under 25mb'";
+
+impl SplitMode {
+    /// Scene change threshold `detect-scenes` uses too
+    pub const DEFAULT_THRESHOLD: f64 = 0.3;
+    /// Shortest silence to split on, in seconds, as `detect-silence` finds them
+    pub const DEFAULT_MIN_SILENCE: f64 = 0.5;
+    /// Noise floor in dB below which audio counts as silence
+    pub const DEFAULT_NOISE: f64 = -30.0;
+
+    /// Parse split mode from string formats: "every 30s", "into 3 parts",
+    /// "by chapters", "by scenes threshold 0.4", "on silence min 1s noise -35dB",
+    /// "into chunks under 25mb"
+    pub fn parse(s: &str) -> Result<Self> {
+        let mode_lower = s.trim().to_lowercase();
+        let words: Vec<&str> = mode_lower.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["by", "chapters" | "chapter"] | ["chapters"] => return Ok(SplitMode::ByChapters),
+            ["by", "scenes" | "scene", options @ ..] => {
+                let mode = SplitMode::Scenes { threshold: Self::DEFAULT_THRESHOLD };
+                return mode.with_options(options);
+            }
+            ["on" | "by", "silence", options @ ..] => {
+                let mode = SplitMode::Silence {
+                    min_silence: Duration { seconds: Self::DEFAULT_MIN_SILENCE },
+                    noise: Self::DEFAULT_NOISE,
+                };
+                return mode.with_options(options);
+            }
+            ["into", "chunks", "under", size] | ["under", size] | ["by", "size", size] => {
+                let size = TargetSize::parse(size)?;
+                if size.bytes == 0 {
+                    bail!("Chunks have to be larger than 0 bytes");
+                }
+                return Ok(SplitMode::Size(size));
+            }
+            _ => {}
+        }
+
+        if let Some(duration_str) = mode_lower.strip_prefix("every ") {
+            Ok(SplitMode::Every(Duration::parse(duration_str.trim())?))
+        } else if mode_lower.starts_with("into ") {
+            let re = Regex::new(r"^into\s+(\d+)\s+parts?$")
+                .map_err(|e| anyhow!("Invalid regex: {}", e))?;
+            let caps = re.captures(&mode_lower)
+                .ok_or_else(|| anyhow!("Invalid split mode: {s} ({SPLIT_MODE_HINT})"))?;
+            let parts = caps.get(1).unwrap().as_str().parse::<u32>()?;
+            if parts < 2 {
+                bail!("Split must be into at least 2 parts");
+            }
+            Ok(SplitMode::IntoParts(parts))
+        } else {
+            bail!("Invalid split mode: {s} ({SPLIT_MODE_HINT})");
+        }
+    }
+
+    /// Apply "threshold 0.4", "min 1s" and "noise -35db" pairs
+    fn with_options(self, options: &[&str]) -> Result<Self> {
+        let mut mode = self;
+        for pair in options.chunks(2) {
+            let &[key, value] = pair else {
+                bail!("Missing a value for '{}' in the split mode", pair[0]);
+            };
+            mode = match key {
+                "threshold" => mode.tune(Some(value.parse().map_err(|_| anyhow!("Invalid scene threshold: {value}"))?), None, None)?,
+                "min" => mode.tune(None, Some(Duration::parse(value)?), None)?,
+                "noise" => mode.tune(None, None, Some(parse_noise(value)?))?,
+                _ => bail!("Unknown split option: {key} (use threshold with scenes, min and noise with silence)"),
+            };
+        }
+        Ok(mode)
+    }
+
+    /// Override the scene threshold or the silence settings
+    pub fn tune(self, threshold: Option<f64>, min_silence: Option<Duration>, noise: Option<f64>) -> Result<Self> {
+        match self {
+            SplitMode::Scenes { threshold: default } => {
+                if min_silence.is_some() || noise.is_some() {
+                    bail!("--min-silence and --noise only apply to 'on silence'");
+                }
+                let threshold = threshold.unwrap_or(default);
+                if !(threshold > 0.0 && threshold < 1.0) {
+                    bail!("Scene threshold must be between 0 and 1 (e.g., 0.3)");
+                }
+                Ok(SplitMode::Scenes { threshold })
+            }
+            SplitMode::Silence { min_silence: default_min, noise: default_noise } => {
+                if threshold.is_some() {
+                    bail!("--threshold only applies to 'by scenes'");
+                }
+                let min_silence = min_silence.unwrap_or(default_min);
+                if min_silence.to_seconds() <= 0.0 {
+                    bail!("Silences to split on have to be longer than 0s");
+                }
+                Ok(SplitMode::Silence { min_silence, noise: noise.unwrap_or(default_noise) })
+            }
+            mode if threshold.is_some() || min_silence.is_some() || noise.is_some() => {
+                bail!("--threshold applies to 'by scenes', --min-silence and --noise to 'on silence'; not to '{mode}'")
+            }
+            mode => Ok(mode),
+        }
+    }
+}
+
+/// A noise floor in dB: "-35db", "-35dB" or "-35"
+pub fn parse_noise(s: &str) -> Result<f64> {
+    let t = s.trim();
+    let number = t.strip_suffix("db").or_else(|| t.strip_suffix("dB")).or_else(|| t.strip_suffix("DB")).unwrap_or(t);
+    let noise: f64 = number.trim().parse().map_err(|_| anyhow!("Invalid noise floor: {s} (try -30dB)"))?;
+    if noise > 0.0 {
+        bail!("Noise floor is below full scale, so it's negative (e.g., -30dB)");
+    }
+    Ok(noise)
+}
+
+impl fmt::Display for SplitMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitMode::Every(interval) => write!(f, "every {interval}"),
+            SplitMode::IntoParts(parts) => write!(f, "into {parts} parts"),
+            SplitMode::ByChapters => write!(f, "by chapters"),
+            SplitMode::Scenes { threshold } => write!(f, "by scenes threshold {threshold}"),
+            SplitMode::Silence { min_silence, noise } => write!(f, "on silence min {min_silence} noise {noise}dB"),
+            SplitMode::Size(size) => write!(f, "into chunks under {}", size.to_string().replace(' ', "").to_lowercase()),
+        }
+    }
 }
 
 /// Represents a batch operation to apply to multiple files
@@ -661,6 +885,163 @@ pub enum BatchOperation {
     // Other operations can be added here
 }
 
+impl BatchOperation {
+    /// Parse a batch operation in command form: "convert to gif"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        let words: Vec<&str> = s_lower.split_whitespace().collect();
+        match words.as_slice() {
+            ["convert", "to", format] | ["convert", format] => Ok(BatchOperation::Convert(ConvertFormat::parse(format)?)),
+            _ => bail!("Invalid batch operation: {s} (try 'convert to gif')"),
+        }
+    }
+}
+
+impl fmt::Display for BatchOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchOperation::Convert(format) => write!(f, "convert to {}", format),
+        }
+    }
+}
+
+/// Options controlling how batch runs skip and record work
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchOptions {
+    /// Re-run every file even if the manifest says its output is up to date
+    pub force: bool,
+    /// Only process inputs modified after this cutoff
+    pub since: Option<SinceCutoff>,
+    /// Job-state file (defaults to .ffhuman-batch-<id>.json next to the batch's files)
+    pub state_file: Option<PathBuf>,
+    /// Continue the pending and interrupted jobs of an existing state file
+    pub resume: bool,
+    /// Re-run the failed jobs of an existing state file
+    pub retry_failed: bool,
+}
+
+/// Options controlling how a watch folder picks up and files away inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchOptions {
+    /// Also watch subfolders
+    pub recursive: bool,
+    /// Seconds a file's size and mtime must stay unchanged before it is processed
+    pub settle_seconds: f64,
+    /// Glob patterns (file name or path relative to the folder) to ignore
+    pub ignore: Vec<String>,
+    /// Move sources into done/ or failed/ after processing
+    pub move_sources: bool,
+    /// YAML rules routing files to operations or pipelines
+    pub rules: Option<PathBuf>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            settle_seconds: 2.0,
+            ignore: Vec::new(),
+            move_sources: true,
+            rules: None,
+        }
+    }
+}
+
+/// Represents a "modified since" cutoff for incremental batch runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinceCutoff {
+    /// Seconds since the Unix epoch
+    pub unix_seconds: u64,
+}
+
+impl SinceCutoff {
+    /// Parse cutoff from a relative age ("90m", "24h", "7d") or a date ("2024-05-01")
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let age_re = Regex::new(r"^(?i)(\d+)\s*(s|m|h|d|w)$")
+            .map_err(|e| anyhow!("Invalid regex: {}", e))?;
+        let date_re = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$")
+            .map_err(|e| anyhow!("Invalid regex: {}", e))?;
+
+        if let Some(caps) = age_re.captures(s) {
+            let amount = caps.get(1).unwrap().as_str().parse::<u64>()?;
+            let unit = match caps.get(2).unwrap().as_str().to_lowercase().as_str() {
+                "s" => 1,
+                "m" => 60,
+                "h" => 3_600,
+                "d" => 86_400,
+                _ => 604_800,
+            };
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            return Ok(SinceCutoff { unix_seconds: now.saturating_sub(amount * unit) });
+        }
+
+        if let Some(caps) = date_re.captures(s) {
+            let year = caps.get(1).unwrap().as_str().parse::<i64>()?;
+            let month = caps.get(2).unwrap().as_str().parse::<i64>()?;
+            let day = caps.get(3).unwrap().as_str().parse::<i64>()?;
+            if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                bail!("Invalid date: {s}");
+            }
+            // Days-from-civil (Howard Hinnant's algorithm)
+            let y = if month <= 2 { year - 1 } else { year };
+            let era = y.div_euclid(400);
+            let yoe = y - era * 400;
+            let mp = (month + 9) % 12;
+            let doy = (153 * mp + 2) / 5 + day - 1;
+            let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+            let days = era * 146_097 + doe - 719_468;
+            if days < 0 {
+                bail!("Date must be after 1970-01-01: {s}");
+            }
+            return Ok(SinceCutoff { unix_seconds: days as u64 * 86_400 });
+        }
+
+        bail!("Invalid --since value: {s} (try 24h, 7d, or 2024-05-01)")
+    }
+}
+
+/// Represents what to do when an output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Leave the existing file alone and skip the operation
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Pick the next free name: out.mp4, out_1.mp4, out_2.mp4, ...
+    AutoNumber,
+    /// Stop with an error
+    Fail,
+}
+
+impl CollisionPolicy {
+    /// Parse collision policy from string: "skip", "overwrite", "auto-number", "fail"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "skip" => Ok(CollisionPolicy::Skip),
+            "overwrite" => Ok(CollisionPolicy::Overwrite),
+            "auto-number" | "autonumber" | "number" => Ok(CollisionPolicy::AutoNumber),
+            "fail" | "error" => Ok(CollisionPolicy::Fail),
+            _ => bail!("Invalid collision policy: {s} (try skip, overwrite, auto-number, or fail)"),
+        }
+    }
+}
+
+impl fmt::Display for CollisionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollisionPolicy::Skip => write!(f, "skip"),
+            CollisionPolicy::Overwrite => write!(f, "overwrite"),
+            CollisionPolicy::AutoNumber => write!(f, "auto-number"),
+            CollisionPolicy::Fail => write!(f, "fail"),
+        }
+    }
+}
+
 /// Represents a condition for conditional batch processing
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProcessingCondition {
@@ -670,6 +1051,39 @@ pub enum ProcessingCondition {
     // More conditions can be added here (file size, resolution, etc.)
 }
 
+impl ProcessingCondition {
+    /// Parse condition from string formats: "duration < 30s", "duration > 1:00", "duration = 30s"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let re = Regex::new(r"(?i)^\s*duration\s*(<|>|=)\s*(.+)$")
+            .map_err(|e| anyhow!("Invalid regex: {}", e))?;
+
+        let caps = re.captures(s)
+            .ok_or_else(|| anyhow!("Invalid condition: {s} (try 'duration < 30s')"))?;
+
+        let operator = caps.get(1).unwrap().as_str();
+        let value_str = caps.get(2).unwrap().as_str();
+        let duration = Duration::parse(value_str)?;
+
+        match operator {
+            "<" => Ok(ProcessingCondition::DurationLessThan(duration)),
+            ">" => Ok(ProcessingCondition::DurationGreaterThan(duration)),
+            "=" => Ok(ProcessingCondition::DurationEquals(duration)),
+            _ => bail!("Invalid operator: {operator} (use <, >, or =)"),
+        }
+    }
+}
+
+impl fmt::Display for ProcessingCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessingCondition::DurationLessThan(d) => write!(f, "duration < {}", d),
+            ProcessingCondition::DurationGreaterThan(d) => write!(f, "duration > {}", d),
+            ProcessingCondition::DurationEquals(d) => write!(f, "duration = {}", d),
+        }
+    }
+}
+
 /// Represents quality presets for compression and conversion
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QualityPreset {
@@ -730,19 +1144,243 @@ pub enum VideoCodec {
     H264,   // libx264
     H265,   // libx265 (HEVC)
     Vp9,    // libvpx-vp9
+    Av1,    // libsvtav1
+    Av1Aom, // libaom-av1 (slower, for builds without SVT-AV1)
+    /// prores_ks; without a profile the quality preset picks one
+    ProRes(Option<ProResProfile>),
+    /// dnxhd in DNxHR mode; without a profile the quality preset picks one
+    Dnxhr(Option<DnxhrProfile>),
+    Ffv1,   // lossless
     Copy,   // Copy without re-encoding
 }
 
 impl VideoCodec {
+    /// Parse video codec from string: "h264", "h265", "vp9", "av1", "av1-aom",
+    /// "prores", "prores-hq", "dnxhr", "dnxhr-sq", "ffv1", "copy"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase().replace(['_', ':', ' '], "-");
+        if let Some(profile) = s_lower.strip_prefix("prores-") {
+            return Ok(VideoCodec::ProRes(Some(ProResProfile::parse(profile)?)));
+        }
+        if let Some(profile) = s_lower.strip_prefix("dnxhr-") {
+            return Ok(VideoCodec::Dnxhr(Some(DnxhrProfile::parse(profile)?)));
+        }
+        match s_lower.as_str() {
+            "h264" | "x264" => Ok(VideoCodec::H264),
+            "h265" | "x265" | "hevc" => Ok(VideoCodec::H265),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" | "svt-av1" | "svtav1" => Ok(VideoCodec::Av1),
+            "av1-aom" | "aom" | "libaom" => Ok(VideoCodec::Av1Aom),
+            "prores" => Ok(VideoCodec::ProRes(None)),
+            "dnxhr" | "dnxhd" => Ok(VideoCodec::Dnxhr(None)),
+            "ffv1" => Ok(VideoCodec::Ffv1),
+            "copy" => Ok(VideoCodec::Copy),
+            _ => bail!("Invalid codec: {s} (try h264, h265, vp9, av1, prores, dnxhr, ffv1, or copy)"),
+        }
+    }
+
     /// Get FFmpeg codec name
     pub fn ffmpeg_name(&self) -> &'static str {
         match self {
             VideoCodec::H264 => "libx264",
             VideoCodec::H265 => "libx265",
             VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1 => "libsvtav1",
+            VideoCodec::Av1Aom => "libaom-av1",
+            VideoCodec::ProRes(_) => "prores_ks",
+            VideoCodec::Dnxhr(_) => "dnxhd",
+            VideoCodec::Ffv1 => "ffv1",
             VideoCodec::Copy => "copy",
         }
     }
+
+    /// Editing intermediates (ProRes, DNxHR, FFV1): large files meant for
+    /// post-production rather than delivery
+    pub fn is_intermediate(&self) -> bool {
+        matches!(self, VideoCodec::ProRes(_) | VideoCodec::Dnxhr(_) | VideoCodec::Ffv1)
+    }
+
+    /// Whether a container (by extension) can hold this codec
+    pub fn fits_container(&self, ext: &str) -> bool {
+        match ext {
+            "mkv" => true,
+            "mp4" => matches!(self, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Av1Aom | VideoCodec::Copy),
+            "webm" => matches!(self, VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Av1Aom | VideoCodec::Copy),
+            "mov" => matches!(self, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::ProRes(_) | VideoCodec::Dnxhr(_) | VideoCodec::Copy),
+            _ => true,
+        }
+    }
+
+    /// Containers to suggest when this codec doesn't fit the requested one
+    pub fn containers(&self) -> &'static str {
+        match self {
+            VideoCodec::ProRes(_) | VideoCodec::Dnxhr(_) => "mov or mkv",
+            VideoCodec::Ffv1 => "mkv",
+            VideoCodec::Av1 | VideoCodec::Av1Aom => "mp4, webm or mkv",
+            VideoCodec::Vp9 => "webm, mp4 or mkv",
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Copy => "mp4, mov or mkv",
+        }
+    }
+}
+
+impl fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoCodec::H264 => write!(f, "h264"),
+            VideoCodec::H265 => write!(f, "h265"),
+            VideoCodec::Vp9 => write!(f, "vp9"),
+            VideoCodec::Av1 => write!(f, "av1"),
+            VideoCodec::Av1Aom => write!(f, "av1-aom"),
+            VideoCodec::ProRes(None) => write!(f, "prores"),
+            VideoCodec::ProRes(Some(profile)) => write!(f, "prores-{}", profile),
+            VideoCodec::Dnxhr(None) => write!(f, "dnxhr"),
+            VideoCodec::Dnxhr(Some(profile)) => write!(f, "dnxhr-{}", profile),
+            VideoCodec::Ffv1 => write!(f, "ffv1"),
+            VideoCodec::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+/// Apple ProRes profiles, from smallest to highest quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProResProfile {
+    Proxy,
+    Lt,
+    Standard,
+    Hq,
+    P4444,
+    P4444Xq,
+}
+
+impl ProResProfile {
+    /// Parse profile: "proxy", "lt", "standard", "hq", "4444", "4444xq"
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "proxy" => Ok(ProResProfile::Proxy),
+            "lt" => Ok(ProResProfile::Lt),
+            "standard" | "422" => Ok(ProResProfile::Standard),
+            "hq" | "422hq" => Ok(ProResProfile::Hq),
+            "4444" => Ok(ProResProfile::P4444),
+            "4444xq" | "xq" => Ok(ProResProfile::P4444Xq),
+            _ => bail!("Invalid ProRes profile: {s} (try proxy, lt, standard, hq, 4444, or 4444xq)"),
+        }
+    }
+
+    /// The profile a quality preset stands for
+    pub fn for_quality(quality: QualityPreset) -> Self {
+        match quality {
+            QualityPreset::Low => ProResProfile::Lt,
+            QualityPreset::Medium => ProResProfile::Standard,
+            QualityPreset::High => ProResProfile::Hq,
+            QualityPreset::Ultra => ProResProfile::P4444,
+        }
+    }
+
+    /// prores_ks `-profile:v` number
+    pub fn number(&self) -> u8 {
+        match self {
+            ProResProfile::Proxy => 0,
+            ProResProfile::Lt => 1,
+            ProResProfile::Standard => 2,
+            ProResProfile::Hq => 3,
+            ProResProfile::P4444 => 4,
+            ProResProfile::P4444Xq => 5,
+        }
+    }
+
+    /// 4:2:2 10-bit, or 4:4:4 10-bit with alpha for the 4444 profiles
+    pub fn pix_fmt(&self) -> &'static str {
+        match self {
+            ProResProfile::P4444 | ProResProfile::P4444Xq => "yuva444p10le",
+            _ => "yuv422p10le",
+        }
+    }
+}
+
+impl fmt::Display for ProResProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProResProfile::Proxy => "proxy",
+            ProResProfile::Lt => "lt",
+            ProResProfile::Standard => "standard",
+            ProResProfile::Hq => "hq",
+            ProResProfile::P4444 => "4444",
+            ProResProfile::P4444Xq => "4444xq",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Avid DNxHR profiles (resolution independent)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnxhrProfile {
+    /// Low bandwidth (offline editing)
+    Lb,
+    /// Standard quality
+    Sq,
+    /// High quality, 8-bit
+    Hq,
+    /// High quality, 10/12-bit
+    Hqx,
+    /// 4:4:4 finishing
+    P444,
+}
+
+impl DnxhrProfile {
+    /// Parse profile: "lb", "sq", "hq", "hqx", "444"
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().trim_start_matches("dnxhr_") {
+            "lb" => Ok(DnxhrProfile::Lb),
+            "sq" => Ok(DnxhrProfile::Sq),
+            "hq" => Ok(DnxhrProfile::Hq),
+            "hqx" => Ok(DnxhrProfile::Hqx),
+            "444" => Ok(DnxhrProfile::P444),
+            _ => bail!("Invalid DNxHR profile: {s} (try lb, sq, hq, hqx, or 444)"),
+        }
+    }
+
+    /// The profile a quality preset stands for
+    pub fn for_quality(quality: QualityPreset) -> Self {
+        match quality {
+            QualityPreset::Low => DnxhrProfile::Lb,
+            QualityPreset::Medium => DnxhrProfile::Sq,
+            QualityPreset::High => DnxhrProfile::Hq,
+            QualityPreset::Ultra => DnxhrProfile::Hqx,
+        }
+    }
+
+    /// dnxhd `-profile:v` name
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            DnxhrProfile::Lb => "dnxhr_lb",
+            DnxhrProfile::Sq => "dnxhr_sq",
+            DnxhrProfile::Hq => "dnxhr_hq",
+            DnxhrProfile::Hqx => "dnxhr_hqx",
+            DnxhrProfile::P444 => "dnxhr_444",
+        }
+    }
+
+    /// Pixel format each profile requires
+    pub fn pix_fmt(&self) -> &'static str {
+        match self {
+            DnxhrProfile::Lb | DnxhrProfile::Sq | DnxhrProfile::Hq => "yuv422p",
+            DnxhrProfile::Hqx => "yuv422p10le",
+            DnxhrProfile::P444 => "yuv444p10le",
+        }
+    }
+}
+
+impl fmt::Display for DnxhrProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DnxhrProfile::Lb => "lb",
+            DnxhrProfile::Sq => "sq",
+            DnxhrProfile::Hq => "hq",
+            DnxhrProfile::Hqx => "hqx",
+            DnxhrProfile::P444 => "444",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 /// Represents montage layout (grid dimensions)
@@ -863,8 +1501,11 @@ mod tests {
         } else {
             panic!("Expected Dimensions variant");
         }
+        assert_eq!(ResizeTarget::parse("480p").unwrap(), ResizeTarget::Height(480));
+        assert_eq!(ResizeTarget::parse("480p").unwrap().to_ffmpeg_scale(), "scale=-2:480");
         
         assert!(ResizeTarget::parse("invalid").is_err());
+        assert!(ResizeTarget::parse("481p").is_err());
     }
 
     #[test]
@@ -919,6 +1560,54 @@ mod tests {
         assert!(BlurRegion::parse("100,100,200,0").is_err()); // height = 0
     }
 
+    #[test]
+    fn test_collision_policy_parse() {
+        assert_eq!(CollisionPolicy::parse("skip").unwrap(), CollisionPolicy::Skip);
+        assert_eq!(CollisionPolicy::parse("Overwrite").unwrap(), CollisionPolicy::Overwrite);
+        assert_eq!(CollisionPolicy::parse("auto-number").unwrap(), CollisionPolicy::AutoNumber);
+        assert_eq!(CollisionPolicy::parse(" fail ").unwrap(), CollisionPolicy::Fail);
+
+        assert!(CollisionPolicy::parse("rename").is_err());
+    }
+
+    #[test]
+    fn test_since_cutoff_parse() {
+        assert_eq!(SinceCutoff::parse("1970-01-02").unwrap().unix_seconds, 86_400);
+        assert_eq!(SinceCutoff::parse("2024-02-29").unwrap().unix_seconds, 1_709_164_800);
+
+        let day_ago = SinceCutoff::parse("24h").unwrap().unix_seconds;
+        let week_ago = SinceCutoff::parse("7d").unwrap().unix_seconds;
+        assert!(week_ago < day_ago);
+
+        assert!(SinceCutoff::parse("yesterday").is_err());
+        assert!(SinceCutoff::parse("2024-13-01").is_err());
+    }
+
+    #[test]
+    fn test_video_codec_parse() {
+        assert_eq!(VideoCodec::parse("av1").unwrap(), VideoCodec::Av1);
+        assert_eq!(VideoCodec::parse("libaom").unwrap(), VideoCodec::Av1Aom);
+        assert_eq!(VideoCodec::parse("prores").unwrap(), VideoCodec::ProRes(None));
+        assert_eq!(VideoCodec::parse("ProRes_HQ").unwrap(), VideoCodec::ProRes(Some(ProResProfile::Hq)));
+        assert_eq!(VideoCodec::parse("prores-4444xq").unwrap(), VideoCodec::ProRes(Some(ProResProfile::P4444Xq)));
+        assert_eq!(VideoCodec::parse("dnxhr-hqx").unwrap(), VideoCodec::Dnxhr(Some(DnxhrProfile::Hqx)));
+        assert_eq!(VideoCodec::parse("ffv1").unwrap(), VideoCodec::Ffv1);
+
+        // Display round-trips
+        for name in ["h264", "av1-aom", "prores", "prores-lt", "dnxhr-444", "ffv1"] {
+            assert_eq!(VideoCodec::parse(name).unwrap().to_string(), name);
+        }
+
+        assert!(VideoCodec::parse("prores-ultra").is_err());
+        assert!(VideoCodec::parse("dnxhr-xq").is_err());
+        assert!(VideoCodec::parse("mpeg2").is_err());
+
+        assert!(VideoCodec::ProRes(None).fits_container("mov"));
+        assert!(!VideoCodec::ProRes(None).fits_container("mp4"));
+        assert!(!VideoCodec::Ffv1.fits_container("mov"));
+        assert!(VideoCodec::Av1.fits_container("webm"));
+    }
+
     #[test]
     fn test_duration_parse() {
         let dur = Duration::parse("2s").unwrap();
@@ -1003,6 +1692,96 @@ impl fmt::Display for VolumeAdjustment {
     }
 }
 
+/// The loudness to normalize to: a delivery spec by name, or integrated LUFS
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LoudnessTarget {
+    /// -16 LUFS, -1.5 dBTP (Apple Podcasts and most podcast hosts)
+    #[default]
+    Podcast,
+    /// -14 LUFS, -1 dBTP (Spotify, YouTube)
+    Streaming,
+    /// -23 LUFS, -1 dBTP (EBU R128)
+    Broadcast,
+    /// -24 LKFS, -2 dBTP (ATSC A/85, US television)
+    Atsc,
+    /// Integrated loudness in LUFS, with the podcast peak and range
+    Custom(f64),
+}
+
+impl LoudnessTarget {
+    /// Parse "podcast", "streaming", "ebu-r128", "atsc-a85", or "-18" / "-18lufs"
+    pub fn parse(s: &str) -> Result<Self> {
+        let s_lower = s.trim().to_lowercase();
+        match s_lower.as_str() {
+            "podcast" => return Ok(LoudnessTarget::Podcast),
+            "streaming" | "spotify" | "youtube" => return Ok(LoudnessTarget::Streaming),
+            "broadcast" | "ebu" | "ebu-r128" | "r128" => return Ok(LoudnessTarget::Broadcast),
+            "atsc" | "atsc-a85" | "a85" => return Ok(LoudnessTarget::Atsc),
+            _ => {}
+        }
+        let num_str = s_lower.trim_end_matches("lufs").trim_end_matches("lkfs").trim();
+        let lufs = num_str.parse::<f64>()
+            .map_err(|_| anyhow!("Unknown loudness target: {s} (try podcast, streaming, ebu-r128, atsc-a85 or -18lufs)"))?;
+        LoudnessTarget::lufs(lufs)
+    }
+
+    /// A custom target; loudnorm accepts -70 to -5 LUFS
+    pub fn lufs(lufs: f64) -> Result<Self> {
+        if !(-70.0..=-5.0).contains(&lufs) {
+            bail!("Loudness target must be between -70 and -5 LUFS, got {lufs}");
+        }
+        Ok(LoudnessTarget::Custom(lufs))
+    }
+
+    /// Integrated loudness in LUFS
+    pub fn integrated(&self) -> f64 {
+        match self {
+            LoudnessTarget::Podcast => -16.0,
+            LoudnessTarget::Streaming => -14.0,
+            LoudnessTarget::Broadcast => -23.0,
+            LoudnessTarget::Atsc => -24.0,
+            LoudnessTarget::Custom(lufs) => *lufs,
+        }
+    }
+
+    /// Maximum true peak in dBTP
+    pub fn true_peak(&self) -> f64 {
+        match self {
+            LoudnessTarget::Podcast | LoudnessTarget::Custom(_) => -1.5,
+            LoudnessTarget::Streaming | LoudnessTarget::Broadcast => -1.0,
+            LoudnessTarget::Atsc => -2.0,
+        }
+    }
+
+    /// How far integrated loudness may stray from the target and still comply, in LU
+    pub fn tolerance(&self) -> f64 {
+        match self {
+            LoudnessTarget::Atsc => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Loudness range in LU that loudnorm aims for
+    pub fn range(&self) -> f64 {
+        match self {
+            LoudnessTarget::Podcast | LoudnessTarget::Streaming | LoudnessTarget::Custom(_) => 11.0,
+            LoudnessTarget::Broadcast | LoudnessTarget::Atsc => 20.0,
+        }
+    }
+}
+
+impl fmt::Display for LoudnessTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoudnessTarget::Podcast => write!(f, "podcast"),
+            LoudnessTarget::Streaming => write!(f, "streaming"),
+            LoudnessTarget::Broadcast => write!(f, "ebu-r128"),
+            LoudnessTarget::Atsc => write!(f, "atsc-a85"),
+            LoudnessTarget::Custom(lufs) => write!(f, "{lufs}lufs"),
+        }
+    }
+}
+
 /// Represents audio sync adjustment direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AudioSyncDirection {
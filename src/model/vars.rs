@@ -0,0 +1,313 @@
+//! Variables and typed parameters for workflow files.
+//!
+//! A workflow declares `params:` (typed, optionally with defaults) and
+//! `vars:` (derived values), and refers to them as `${name}` anywhere in the
+//! file. Values come from `--set name=value`, the declarations, and finally
+//! the environment.
+
+use crate::model::de::{node_location, PathPart};
+use crate::model::types::{Duration, Time};
+use anyhow::{anyhow, bail, Context, Result};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Variable values by name
+pub type Vars = BTreeMap<String, String>;
+
+/// Parse `--set name=value` arguments
+pub fn parse_assignments(items: &[String]) -> Result<Vars> {
+    let mut vars = Vars::new();
+    for item in items {
+        let (name, value) = item.split_once('=')
+            .ok_or_else(|| anyhow!("Invalid --set value: {item} (expected NAME=VALUE)"))?;
+        let name = name.trim();
+        if !is_valid_name(name) {
+            bail!("Invalid variable name in --set: {name} (use letters, digits and underscores)");
+        }
+        vars.insert(name.to_string(), value.to_string());
+    }
+    Ok(vars)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Declared type of a workflow parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Number,
+    Bool,
+    Path,
+    /// A path that must exist
+    File,
+    Time,
+    Duration,
+}
+
+impl ParamType {
+    /// Check that a value has this type and return it as it's substituted:
+    /// a bool given as yes/no becomes true/false
+    pub fn check(&self, value: &str) -> Result<String> {
+        let valid = match self {
+            ParamType::String | ParamType::Path => Ok(()),
+            ParamType::Int => value.trim().parse::<i64>().map(|_| ()).map_err(|_| anyhow!("not an integer: {value}")),
+            ParamType::Number => value.trim().parse::<f64>().map(|_| ()).map_err(|_| anyhow!("not a number: {value}")),
+            ParamType::Bool => match value.trim().to_lowercase().as_str() {
+                "true" | "yes" => return Ok("true".to_string()),
+                "false" | "no" => return Ok("false".to_string()),
+                _ => bail!("not true or false: {value}"),
+            },
+            ParamType::File => {
+                if Path::new(value).exists() {
+                    Ok(())
+                } else {
+                    bail!("file not found: {value}")
+                }
+            }
+            ParamType::Time => Time::parse(value).map(|_| ()),
+            ParamType::Duration => Duration::parse(value).map(|_| ()),
+        };
+        valid.map(|_| value.to_string())
+    }
+}
+
+/// One entry under `params:`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParamSpec {
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+    /// Value used when `--set` doesn't give one; without it the parameter is required
+    pub default: Option<Value>,
+    /// Allowed values
+    pub choices: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// Resolve a file's `params:` and `vars:` into a scope.
+///
+/// `inherited` is the including file's scope (empty at the top level) and
+/// `sets` the values passed in (`--set`, or an include's `vars:`), which win
+/// over declarations. A `file` parameter's default is relative to `base_dir`,
+/// the folder of the file declaring it. Names used along the way are added
+/// to `used`.
+pub fn resolve_scope(
+    params: Option<&Value>,
+    vars: Option<&Value>,
+    sets: &Vars,
+    inherited: &Vars,
+    base_dir: &Path,
+    source: &str,
+    used: &mut BTreeSet<String>,
+) -> Result<Vars> {
+    let mut scope = inherited.clone();
+    scope.extend(sets.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    for (name, spec) in mapping(params, "params", source)? {
+        let spec: ParamSpec = serde_yaml::from_value(spec.clone())
+            .map_err(|e| anyhow!("{source}: parameter '{name}': {e}"))?;
+        used.insert(name.clone());
+        let value = match (sets.get(&name), &spec.default) {
+            (Some(value), _) => value.clone(),
+            (None, Some(default)) => {
+                let value = interpolate_value(&scalar(default, source, &name)?, &scope, used)
+                    .with_context(|| format!("{source}: default of parameter '{name}'"))?;
+                match spec.kind {
+                    ParamType::File => in_dir(base_dir, &value),
+                    _ => value,
+                }
+            }
+            (None, None) => bail!("{source}: parameter '{name}' is required (pass --set {name}=...)"),
+        };
+        let value = spec.kind.check(&value)
+            .with_context(|| format!("{source}: parameter '{name}' ({})", format!("{:?}", spec.kind).to_lowercase()))?;
+        if !spec.choices.is_empty() && !spec.choices.contains(&value) {
+            bail!("{source}: parameter '{name}' must be one of {}, got {value}", spec.choices.join(", "));
+        }
+        scope.insert(name, value);
+    }
+
+    for (name, raw) in mapping(vars, "vars", source)? {
+        used.insert(name.clone());
+        let value = match sets.get(&name) {
+            Some(value) => value.clone(),
+            None => interpolate_value(&scalar(raw, source, &name)?, &scope, used)
+                .with_context(|| format!("{source}: variable '{name}'"))?,
+        };
+        scope.insert(name, value);
+    }
+
+    Ok(scope)
+}
+
+/// A path written in a file in `dir`, as a path from the current folder
+fn in_dir(dir: &Path, value: &str) -> String {
+    if dir.as_os_str().is_empty() || dir == Path::new(".") || Path::new(value).is_absolute() {
+        return value.to_string();
+    }
+    dir.join(value).to_string_lossy().into_owned()
+}
+
+/// Entries of a `params:`/`vars:` block in file order
+fn mapping<'a>(block: Option<&'a Value>, key: &str, source: &str) -> Result<Vec<(String, &'a Value)>> {
+    let Some(block) = block else { return Ok(Vec::new()) };
+    if block.is_null() {
+        return Ok(Vec::new());
+    }
+    let map: &Mapping = block.as_mapping()
+        .ok_or_else(|| anyhow!("{source}: '{key}' must be a mapping of names to values"))?;
+    map.iter()
+        .map(|(k, v)| {
+            let name = k.as_str().filter(|n| is_valid_name(n))
+                .ok_or_else(|| anyhow!("{source}: invalid name under '{key}': {k:?}"))?;
+            if name == "item" || name == "steps" {
+                bail!("{source}: '{name}' is reserved and can't be declared under '{key}'");
+            }
+            Ok((name.to_string(), v))
+        })
+        .collect()
+}
+
+/// A scalar YAML value as text
+pub fn scalar(value: &Value, source: &str, name: &str) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => bail!("{source}: value of '{name}' must be a string, number or boolean"),
+    }
+}
+
+fn variable_regex() -> Result<Regex> {
+    Regex::new(r"\$(\$?)\{([^}]*)\}").map_err(|e| anyhow!("Invalid regex: {}", e))
+}
+
+/// Replace `${name}` in one value. `$${name}` stays as a literal `${name}`.
+fn interpolate_value(text: &str, scope: &Vars, used: &mut BTreeSet<String>) -> Result<String> {
+    let re = variable_regex()?;
+    let mut missing = None;
+    let result = re.replace_all(text, |caps: &Captures| {
+        if !caps[1].is_empty() {
+            return format!("${{{}}}", &caps[2]);
+        }
+        let name = caps[2].trim();
+        // Step outputs are only known while the workflow runs, and items per foreach copy
+        if name.starts_with("steps.") || name == "item" || name.starts_with("item.") {
+            return caps[0].to_string();
+        }
+        used.insert(name.to_string());
+        match scope.get(name).cloned().or_else(|| std::env::var(name).ok()) {
+            Some(value) => value,
+            None => {
+                missing.get_or_insert_with(|| name.to_string());
+                String::new()
+            }
+        }
+    });
+    match missing {
+        Some(name) => Err(anyhow!(
+            "undefined variable ${{{name}}} (declare it under vars: or params:, pass --set {name}=..., or set the environment variable)"
+        )),
+        None => Ok(result.into_owned()),
+    }
+}
+
+/// Replace `${name}` in every string value of a parsed workflow file.
+///
+/// Values are inserted as text and never read as YAML, so a `:`, `#` or
+/// newline in a value can't change the file's structure. A string that is
+/// only a `${name}` becomes a number or true/false when its value is one, as
+/// it would written out in the file. `content` is the file's text, used to
+/// point errors at the failing value.
+pub fn interpolate(doc: &mut Value, content: &str, scope: &Vars, source: &str, used: &mut BTreeSet<String>) -> Result<()> {
+    let Some(map) = doc.as_mapping_mut() else { return Ok(()) };
+    for (key, value) in map.iter_mut() {
+        let Some(key) = key.as_str() else { continue };
+        // Already resolved into the scope
+        if key == "params" || key == "vars" {
+            continue;
+        }
+        let mut path = vec![Segment::Key(key.to_string())];
+        interpolate_node(value, &mut path, scope, used).map_err(|e| {
+            let parts: Vec<PathPart> = path.iter()
+                .map(|segment| match segment {
+                    Segment::Key(key) => PathPart::Key(key),
+                    Segment::Index(idx) => PathPart::Index(*idx),
+                })
+                .collect();
+            match node_location(content, &parts) {
+                Some(loc) => anyhow!("{source}:{}:{}: {e}", loc.line(), loc.column()),
+                None => anyhow!("{source}: {e}"),
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Where a value sits in the file; left pointing at the failing value on error
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn interpolate_node(value: &mut Value, path: &mut Vec<Segment>, scope: &Vars, used: &mut BTreeSet<String>) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            let whole = is_single_reference(text);
+            let filled = interpolate_value(text, scope, used)?;
+            *value = match typed_scalar(&filled) {
+                Some(typed) if whole => typed,
+                _ => Value::String(filled),
+            };
+        }
+        Value::Sequence(items) => {
+            for (idx, item) in items.iter_mut().enumerate() {
+                path.push(Segment::Index(idx));
+                interpolate_node(item, path, scope, used)?;
+                path.pop();
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                path.push(Segment::Key(key.as_str().unwrap_or_default().to_string()));
+                interpolate_node(item, path, scope, used)?;
+                path.pop();
+            }
+        }
+        Value::Tagged(tagged) => interpolate_node(&mut tagged.value, path, scope, used)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// True if the text is exactly one `${name}` reference
+fn is_single_reference(text: &str) -> bool {
+    variable_regex().ok()
+        .and_then(|re| re.captures(text))
+        .is_some_and(|caps| caps[0].len() == text.len() && caps[1].is_empty())
+}
+
+/// A number or true/false, as YAML reads it from a plain scalar
+fn typed_scalar(text: &str) -> Option<Value> {
+    match text {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if let Ok(n) = text.parse::<i64>() {
+        return Some(Value::Number(n.into()));
+    }
+    text.parse::<f64>().ok()
+        .filter(|n| n.is_finite() && text.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .map(|n| Value::Number(n.into()))
+}
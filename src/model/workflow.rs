@@ -0,0 +1,735 @@
+use crate::model::condition::StepCondition;
+use crate::model::de::{node_location, PathPart};
+use crate::model::intent::Intent;
+use crate::model::vars::{interpolate, resolve_scope, scalar, Vars};
+use anyhow::{anyhow, bail, Context, Result};
+use regex::{Captures, Regex};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_yaml::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How workflow steps get their input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkflowMode {
+    /// Each step works on the previous step's output (pipelines)
+    Chain,
+    /// Every step works on the original input (templates)
+    FanOut,
+}
+
+/// A workflow file, shared by `workflow`, `pipeline` and `apply-template`:
+///
+/// ```yaml
+/// input: raw/interview.mov   # optional; the command line input wins
+/// mode: chain                # or fan-out
+/// params:
+///   start: { type: time, default: 0 }
+///   logo: { type: file }     # required: --set logo=acme.png
+/// vars:
+///   out_dir: exports/${USER}
+/// steps:
+///   - type: trim
+///     start: ${start}
+///     end: 1:30
+///   - type: include
+///     file: lib/branding.yaml
+///     vars: { position: top-right }
+///   - type: convert
+///     format: mp4
+///     output: ${out_dir}/interview.mp4
+/// ```
+///
+/// Each step is any intent, named by `type` in kebab-case with its fields
+/// written as on the command line. `operations` is accepted for `steps`.
+/// `${name}` is replaced inside values before the steps are read, so every
+/// value is checked before anything runs. `${steps.<id>.output}` is the output of the
+/// earlier step with that `id`, filled in when the step runs. A step with
+/// `when:` only runs if the condition holds for its input, and a step with
+/// `foreach:` runs once per list item or matching file as `${item}`.
+#[derive(Debug, Clone)]
+pub struct WorkflowSpec {
+    pub input: Option<PathBuf>,
+    pub mode: Option<WorkflowMode>,
+    pub steps: Vec<WorkflowStep>,
+}
+
+/// One step: an intent plus per-step settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkflowStep {
+    /// Name other steps use to refer to this step's output
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub intent: Intent,
+    /// Output path for this step, instead of the default name
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Run the step only if this holds for its input; otherwise the input passes through
+    #[serde(default)]
+    pub when: Option<StepCondition>,
+    /// For a copy of a `foreach:` step, the item it's for
+    #[serde(skip)]
+    pub item: Option<ForeachItem>,
+    /// The step as written, kept when it refers to other steps' outputs
+    #[serde(skip)]
+    raw: Option<Value>,
+}
+
+/// One item of a `foreach:` step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeachItem {
+    /// Which `foreach:` step this copy came from
+    pub group: usize,
+    pub value: String,
+}
+
+/// A workflow file after variable substitution
+#[derive(Debug)]
+struct WorkflowFile {
+    input: Option<PathBuf>,
+    mode: Option<WorkflowMode>,
+    steps: Vec<StepEntry>,
+}
+
+/// A step, `type: include` of another workflow file's steps, or a
+/// `foreach:` step to expand
+#[derive(Debug)]
+enum StepEntry {
+    Step(Box<WorkflowStep>),
+    Include(IncludeStep),
+    Foreach,
+}
+
+/// How to read each entry of `steps:`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    Step,
+    Include,
+    /// Read later, once per item, since `${item}` can stand for any value
+    Foreach,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IncludeStep {
+    #[serde(rename = "type")]
+    _type: IgnoredAny,
+    file: PathBuf,
+    /// Values for the included file's params and vars
+    #[serde(default)]
+    vars: BTreeMap<String, Value>,
+}
+
+const FILE_FIELDS: &[&str] = &["input", "mode", "params", "vars", "steps", "operations"];
+
+/// Where reading a workflow file got to: the top-level key and, under
+/// `steps:`, the entry being read. After an error it is the failing node.
+#[derive(Debug, Default)]
+struct ReadPath {
+    key: RefCell<Option<String>>,
+    step: Cell<Option<usize>>,
+}
+
+/// Deserializes a workflow file, with `kinds[i]` telling how to read step `i`
+struct FileSeed<'a> {
+    kinds: &'a [EntryKind],
+    at: &'a ReadPath,
+}
+
+impl<'de> DeserializeSeed<'de> for FileSeed<'_> {
+    type Value = WorkflowFile;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<WorkflowFile, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FileSeed<'_> {
+    type Value = WorkflowFile;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a workflow with a list of steps")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<WorkflowFile, A::Error> {
+        let mut file = WorkflowFile { input: None, mode: None, steps: Vec::new() };
+        let mut has_steps = false;
+        while let Some(key) = map.next_key::<String>()? {
+            *self.at.key.borrow_mut() = Some(key.clone());
+            match key.as_str() {
+                "input" => file.input = map.next_value()?,
+                "mode" => file.mode = map.next_value()?,
+                // Already resolved into the scope before parsing
+                "params" | "vars" => { map.next_value::<IgnoredAny>()?; }
+                "steps" | "operations" => {
+                    if has_steps {
+                        return Err(de::Error::duplicate_field("steps"));
+                    }
+                    has_steps = true;
+                    file.steps = map.next_value_seed(StepsSeed { kinds: self.kinds, at: self.at })?;
+                }
+                other => return Err(de::Error::unknown_field(other, FILE_FIELDS)),
+            }
+        }
+        *self.at.key.borrow_mut() = None;
+        if !has_steps {
+            return Err(de::Error::missing_field("steps"));
+        }
+        Ok(file)
+    }
+}
+
+struct StepsSeed<'a> {
+    kinds: &'a [EntryKind],
+    at: &'a ReadPath,
+}
+
+impl<'de> DeserializeSeed<'de> for StepsSeed<'_> {
+    type Value = Vec<StepEntry>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Vec<StepEntry>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StepsSeed<'_> {
+    type Value = Vec<StepEntry>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of steps")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Vec<StepEntry>, A::Error> {
+        let mut steps = Vec::new();
+        loop {
+            self.at.step.set(Some(steps.len()));
+            let entry = match self.kinds.get(steps.len()).copied().unwrap_or(EntryKind::Step) {
+                EntryKind::Step => seq.next_element::<WorkflowStep>()?.map(|step| StepEntry::Step(Box::new(step))),
+                EntryKind::Include => seq.next_element::<IncludeStep>()?.map(StepEntry::Include),
+                EntryKind::Foreach => seq.next_element::<IgnoredAny>()?.map(|_| StepEntry::Foreach),
+            };
+            match entry {
+                Some(entry) => steps.push(entry),
+                None => {
+                    self.at.step.set(None);
+                    return Ok(steps);
+                }
+            }
+        }
+    }
+}
+
+/// The steps of a workflow file as plain YAML values
+fn raw_steps(doc: &Value) -> Vec<Value> {
+    doc.get("steps").or_else(|| doc.get("operations"))
+        .and_then(Value::as_sequence)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn step_reference_regex() -> Result<Regex> {
+    Regex::new(r"\$\{\s*steps\.([^.}\s]+)\.(outputs?)\s*\}").map_err(|e| anyhow!("Invalid regex: {}", e))
+}
+
+fn item_regex() -> Result<Regex> {
+    Regex::new(r"\$\{\s*item(?:\.(stem|name|ext))?\s*\}").map_err(|e| anyhow!("Invalid regex: {}", e))
+}
+
+/// Replace `${item}`, `${item.stem}`, `${item.name}` and `${item.ext}`
+fn fill_item(text: &str, re: &Regex, item: &str) -> String {
+    let path = Path::new(item);
+    re.replace_all(text, |caps: &Captures| {
+        let part = match caps.get(1).map(|m| m.as_str()) {
+            Some("stem") => path.file_stem(),
+            Some("name") => path.file_name(),
+            Some("ext") => path.extension(),
+            _ => return item.to_string(),
+        };
+        part.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default()
+    }).into_owned()
+}
+
+fn has_step_references(raw: &Value) -> bool {
+    strings(raw).iter().any(|s| s.contains("${steps."))
+}
+
+/// Every string inside a YAML value
+fn strings(value: &Value) -> Vec<&str> {
+    match value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Sequence(items) => items.iter().flat_map(strings).collect(),
+        Value::Mapping(map) => map.values().flat_map(strings).collect(),
+        Value::Tagged(tagged) => strings(&tagged.value),
+        _ => Vec::new(),
+    }
+}
+
+/// Apply `f` to every string inside a YAML value
+fn map_strings(value: &mut Value, f: &dyn Fn(&str) -> String) {
+    match value {
+        Value::String(s) => *s = f(s),
+        Value::Sequence(items) => items.iter_mut().for_each(|v| map_strings(v, f)),
+        Value::Mapping(map) => map.values_mut().for_each(|v| map_strings(v, f)),
+        Value::Tagged(tagged) => map_strings(&mut tagged.value, f),
+        _ => {}
+    }
+}
+
+/// Fill in `${steps.<id>.output}` (the step's result) and, as a whole list
+/// item, `${steps.<id>.outputs}` (the results of every copy of a foreach step)
+fn fill_references(value: &mut Value, re: &Regex, outputs: &BTreeMap<String, Vec<PathBuf>>) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            let mut error = None;
+            let filled = re.replace_all(text, |caps: &Captures| {
+                if &caps[2] == "outputs" {
+                    error.get_or_insert_with(|| anyhow!("{} can only be a whole list item", &caps[0]));
+                }
+                outputs.get(&caps[1]).and_then(|o| o.last())
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| caps[0].to_string())
+            }).into_owned();
+            if let Some(e) = error {
+                return Err(e);
+            }
+            *text = filled;
+        }
+        Value::Sequence(items) => {
+            let mut filled = Vec::with_capacity(items.len());
+            for mut item in items.drain(..) {
+                let all = item.as_str()
+                    .and_then(|s| re.captures(s).filter(|caps| &caps[0] == s.trim() && &caps[2] == "outputs"))
+                    .map(|caps| outputs.get(&caps[1]).cloned().unwrap_or_default());
+                match all {
+                    Some(paths) => filled.extend(paths.iter().map(|p| Value::String(p.display().to_string()))),
+                    None => {
+                        fill_references(&mut item, re, outputs)?;
+                        filled.push(item);
+                    }
+                }
+            }
+            *items = filled;
+        }
+        Value::Mapping(map) => {
+            for v in map.values_mut() {
+                fill_references(v, re, outputs)?;
+            }
+        }
+        Value::Tagged(tagged) => fill_references(&mut tagged.value, re, outputs)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+impl WorkflowStep {
+    /// A plain step for an intent built in code
+    pub fn new(intent: Intent) -> Self {
+        Self { id: None, intent, output: None, when: None, item: None, raw: None }
+    }
+
+    /// Ids of the steps whose outputs this step uses, via `${steps.<id>.output}`
+    /// or `${steps.<id>.outputs}`
+    pub fn references(&self) -> Vec<String> {
+        let (Some(raw), Ok(re)) = (&self.raw, step_reference_regex()) else { return Vec::new() };
+        let mut ids: Vec<String> = Vec::new();
+        for text in strings(raw) {
+            for caps in re.captures_iter(text) {
+                if !ids.iter().any(|id| id == &caps[1]) {
+                    ids.push(caps[1].to_string());
+                }
+            }
+        }
+        ids
+    }
+
+    /// This step with references replaced by the results of earlier steps,
+    /// given as the results of each id in step order
+    pub fn resolve(&self, outputs: &BTreeMap<String, Vec<PathBuf>>) -> Result<WorkflowStep> {
+        let Some(raw) = &self.raw else { return Ok(self.clone()) };
+        let mut raw = raw.clone();
+        fill_references(&mut raw, &step_reference_regex()?, outputs)?;
+        let mut step: WorkflowStep = serde_yaml::from_value(raw)
+            .map_err(|e| anyhow!("Invalid step after filling in step outputs: {e}"))?;
+        step.item = self.item.clone();
+        Ok(step)
+    }
+
+    /// The step's id, or its type as written in YAML (e.g. "extract-audio"),
+    /// with the item for copies of a foreach step
+    pub fn name(&self) -> String {
+        let name = self.id.clone().unwrap_or_else(|| self.intent.kind().to_string());
+        match &self.item {
+            Some(item) => format!("{name} [{}]", item.value),
+            None => name,
+        }
+    }
+}
+
+/// What a step's input is wired to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepInput {
+    /// The step sets its own inputs (or takes none)
+    Own,
+    /// The workflow's input
+    Original,
+    /// The output of the step at this index
+    Previous(usize),
+}
+
+/// The validated dependency graph of a workflow
+#[derive(Debug, Clone)]
+pub struct WorkflowGraph {
+    /// For each step: where its empty `input` comes from
+    pub inputs: Vec<StepInput>,
+    /// For each step: indexes of the steps it must wait for
+    pub deps: Vec<Vec<usize>>,
+}
+
+impl WorkflowGraph {
+    /// Work out step dependencies: `${steps.<id>.output}` references, plus
+    /// the previous step for chained steps that leave `input` empty
+    pub fn build(spec: &WorkflowSpec, mode: WorkflowMode) -> Result<Self> {
+        let mut ids: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut inputs: Vec<StepInput> = Vec::new();
+        let mut deps = Vec::new();
+
+        for (idx, step) in spec.steps.iter().enumerate() {
+            let label = format!("Step {} ({})", idx + 1, step.name());
+            let mut step_deps = Vec::new();
+            for id in step.references() {
+                match ids.get(id.as_str()) {
+                    Some(members) => step_deps.extend(members),
+                    None if spec.steps.iter().any(|s| s.id.as_deref() == Some(id.as_str())) => {
+                        bail!("{label} uses ${{steps.{id}.output}}, but step '{id}' comes later; steps can only use earlier outputs")
+                    }
+                    None => bail!("{label} uses ${{steps.{id}.output}}, but no step has id '{id}'"),
+                }
+            }
+
+            let mut intent = step.intent.clone();
+            let slot = intent.input_mut();
+            if step.when.is_some() && slot.is_none() {
+                bail!("{label}: 'when' needs a step that takes an input");
+            }
+            let same_group = idx > 0 && step.item.is_some()
+                && step.item.as_ref().map(|i| i.group) == spec.steps[idx - 1].item.as_ref().map(|i| i.group);
+            let input = match slot {
+                // Copies of a foreach step all read what the first copy reads
+                Some(slot) if slot.as_os_str().is_empty() && same_group => inputs[idx - 1].clone(),
+                Some(slot) if slot.as_os_str().is_empty() => match mode {
+                    WorkflowMode::Chain if idx > 0 => StepInput::Previous(idx - 1),
+                    _ => StepInput::Original,
+                },
+                _ => StepInput::Own,
+            };
+            if let StepInput::Previous(prev) = input {
+                if !step_deps.contains(&prev) {
+                    step_deps.push(prev);
+                }
+            }
+            step_deps.sort_unstable();
+            step_deps.dedup();
+
+            if let Some(id) = &step.id {
+                let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if !valid {
+                    bail!("{label}: invalid id '{id}' (use letters, digits, '-' and '_')");
+                }
+                match ids.get_mut(id.as_str()) {
+                    Some(members) if same_group => members.push(idx),
+                    Some(_) => bail!("{label}: id '{id}' is used by more than one step"),
+                    None => {
+                        ids.insert(id, vec![idx]);
+                    }
+                }
+            }
+            inputs.push(input);
+            deps.push(step_deps);
+        }
+        Ok(WorkflowGraph { inputs, deps })
+    }
+
+    /// Steps no other step depends on: the workflow's results
+    pub fn sinks(&self) -> Vec<usize> {
+        (0..self.deps.len())
+            .filter(|idx| !self.deps.iter().any(|d| d.contains(idx)))
+            .collect()
+    }
+
+    /// Stage of each step: steps in the same stage can run at the same time
+    pub fn stages(&self) -> Vec<usize> {
+        let mut stages: Vec<usize> = Vec::with_capacity(self.deps.len());
+        for deps in &self.deps {
+            stages.push(deps.iter().map(|&d| stages[d] + 1).max().unwrap_or(0));
+        }
+        stages
+    }
+
+    /// Human-readable graph, as printed by `--dry-run`
+    pub fn describe(&self, spec: &WorkflowSpec) -> String {
+        let stages = self.stages();
+        let mut lines = vec![format!("Workflow graph ({} steps):", spec.steps.len())];
+        for (idx, step) in spec.steps.iter().enumerate() {
+            let kind = step.intent.kind();
+            let mut from: Vec<String> = self.deps[idx].iter().map(|&d| spec.steps[d].name()).collect();
+            if self.inputs[idx] == StepInput::Original {
+                from.insert(0, "input".to_string());
+            }
+            let name = match &step.id {
+                Some(id) if id != kind => format!("{id} ({kind})"),
+                _ => kind.to_string(),
+            };
+            let name = match &step.item {
+                Some(item) => format!("{name} [{}]", item.value),
+                None => name,
+            };
+            let from = if from.is_empty() { String::new() } else { format!(" <- {}", from.join(", ")) };
+            let when = step.when.as_ref().map(|w| format!(" (when {w})")).unwrap_or_default();
+            lines.push(format!("  [{}] stage {}: {}{}{}", idx + 1, stages[idx] + 1, name, from, when));
+        }
+        lines.join("\n")
+    }
+}
+
+impl WorkflowSpec {
+    /// Read and validate a workflow file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with_vars(path, &Vars::new())
+    }
+
+    /// Read and validate a workflow file with `--set` values
+    pub fn load_with_vars(path: &Path, sets: &Vars) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read workflow file: {}", path.display()))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut loader = Loader { stack: vec![canonical(path)], used: BTreeSet::new(), groups: 0 };
+        let spec = loader.parse(&content, &path.display().to_string(), base_dir, sets, &Vars::new())?;
+        loader.check_used(sets, &path.display().to_string())?;
+        Ok(spec)
+    }
+
+    /// Parse workflow YAML; `source` names the file in error messages
+    pub fn parse(content: &str, source: &str) -> Result<Self> {
+        let mut loader = Loader { stack: Vec::new(), used: BTreeSet::new(), groups: 0 };
+        loader.parse(content, source, Path::new("."), &Vars::new(), &Vars::new())
+    }
+}
+
+/// Resolves variables and includes while loading a workflow
+struct Loader {
+    /// Files being loaded, to catch include cycles
+    stack: Vec<PathBuf>,
+    /// Variable names declared or referenced anywhere
+    used: BTreeSet<String>,
+    /// Number of foreach steps expanded so far
+    groups: usize,
+}
+
+impl Loader {
+    fn parse(&mut self, content: &str, source: &str, base_dir: &Path, sets: &Vars, inherited: &Vars) -> Result<WorkflowSpec> {
+        let mut doc: Value = serde_yaml::from_str(content).map_err(|e| yaml_error(source, &e))?;
+        let scope = resolve_scope(doc.get("params"), doc.get("vars"), sets, inherited, base_dir, source, &mut self.used)?;
+        interpolate(&mut doc, content, &scope, source, &mut self.used)?;
+
+        let raw_steps = raw_steps(&doc);
+        let kinds: Vec<EntryKind> = raw_steps.iter()
+            .map(|s| if s.get("foreach").is_some() {
+                EntryKind::Foreach
+            } else if s.get("type").and_then(Value::as_str) == Some("include") {
+                EntryKind::Include
+            } else {
+                EntryKind::Step
+            })
+            .collect();
+        let at = ReadPath::default();
+        let file = FileSeed { kinds: &kinds, at: &at }
+            .deserialize(doc.clone())
+            .map_err(|e| file_error(content, &at, source, &e))?;
+        let item_re = item_regex()?;
+        let mut steps = Vec::new();
+        for (idx, entry) in file.steps.into_iter().enumerate() {
+            let raw = &raw_steps[idx];
+            match entry {
+                StepEntry::Step(mut step) => {
+                    if strings(raw).iter().any(|s| item_re.is_match(s)) {
+                        bail!("{source}: step {}: ${{item}} is only available in foreach steps", idx + 1);
+                    }
+                    step.raw = has_step_references(raw).then(|| raw.clone());
+                    self.check_nested(&step, source)?;
+                    steps.push(*step);
+                }
+                StepEntry::Include(include) => steps.extend(self.include(&include, base_dir, &scope, source)?),
+                StepEntry::Foreach => {
+                    let copies = self.foreach(raw, idx + 1, &item_re, source)?;
+                    for step in &copies {
+                        self.check_nested(step, source)?;
+                    }
+                    steps.extend(copies);
+                }
+            }
+        }
+        if steps.is_empty() {
+            return Err(anyhow!("{source}: workflow has no steps"));
+        }
+        Ok(WorkflowSpec { input: file.input, mode: file.mode, steps })
+    }
+
+    /// Expand a `foreach:` step into one copy per item of a list or per file
+    /// matching a glob
+    fn foreach(&mut self, raw: &Value, number: usize, item_re: &Regex, source: &str) -> Result<Vec<WorkflowStep>> {
+        let mut raw = raw.clone();
+        let over = raw.as_mapping_mut().and_then(|m| m.remove("foreach")).unwrap_or(Value::Null);
+        let items: Vec<String> = match &over {
+            Value::Sequence(items) => items.iter()
+                .map(|v| scalar(v, source, "foreach"))
+                .collect::<Result<_>>()?,
+            Value::String(pattern) => {
+                let mut files: Vec<String> = glob::glob(pattern)
+                    .with_context(|| format!("{source}: step {number}: invalid foreach pattern: {pattern}"))?
+                    .filter_map(|entry| entry.ok())
+                    .filter(|path| path.is_file())
+                    .map(|path| path.display().to_string())
+                    .collect();
+                files.sort();
+                if files.is_empty() {
+                    bail!("{source}: step {number}: foreach pattern '{pattern}' matches no files");
+                }
+                files
+            }
+            _ => bail!("{source}: step {number}: foreach takes a list or a glob pattern"),
+        };
+
+        self.groups += 1;
+        let mut steps = Vec::with_capacity(items.len());
+        for item in items {
+            let mut step_raw = raw.clone();
+            map_strings(&mut step_raw, &|text| fill_item(text, item_re, &item));
+            let mut step: WorkflowStep = serde_yaml::from_value(step_raw.clone())
+                .map_err(|e| anyhow!("{source}: step {number} (foreach item {item}): {e}"))?;
+            step.item = Some(ForeachItem { group: self.groups, value: item });
+            step.raw = has_step_references(&step_raw).then_some(step_raw);
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+
+    fn include(&mut self, include: &IncludeStep, base_dir: &Path, scope: &Vars, source: &str) -> Result<Vec<WorkflowStep>> {
+        let path = base_dir.join(&include.file);
+        let key = canonical(&path);
+        if self.stack.contains(&key) {
+            bail!("{source}: include cycle through {}", path.display());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("{source}: failed to read included file {}", path.display()))?;
+        let include_source = path.display().to_string();
+        let mut sets = Vars::new();
+        for (name, value) in &include.vars {
+            sets.insert(name.clone(), scalar(value, source, name)?);
+        }
+
+        self.stack.push(key);
+        let spec = self.parse(&content, &include_source, path.parent().unwrap_or(base_dir), &sets, scope)?;
+        self.stack.pop();
+        self.check_used(&sets, &include_source)?;
+        Ok(spec.steps)
+    }
+
+    /// Load the workflow a `type: workflow` step runs, so a file that runs
+    /// itself (directly or through others) is refused instead of recursing forever
+    fn check_nested(&self, step: &WorkflowStep, source: &str) -> Result<()> {
+        let Intent::Workflow { config_file, vars } = &step.intent else { return Ok(()) };
+        // Known only once earlier steps have run
+        if step.raw.is_some() || !config_file.exists() {
+            return Ok(());
+        }
+        let key = canonical(config_file);
+        if self.stack.contains(&key) {
+            let chain: Vec<String> = self.stack.iter()
+                .skip_while(|p| **p != key)
+                .chain(std::iter::once(&key))
+                .map(|p| p.display().to_string())
+                .collect();
+            bail!("{source}: workflow step runs a workflow that is already running: {}", chain.join(" -> "));
+        }
+        let content = fs::read_to_string(config_file)
+            .with_context(|| format!("{source}: failed to read workflow file {}", config_file.display()))?;
+        let nested_source = config_file.display().to_string();
+        let mut stack = self.stack.clone();
+        stack.push(key);
+        let mut nested = Loader { stack, used: BTreeSet::new(), groups: 0 };
+        let base_dir = config_file.parent().unwrap_or_else(|| Path::new("."));
+        nested.parse(&content, &nested_source, base_dir, vars, &Vars::new())?;
+        nested.check_used(vars, &nested_source)
+    }
+
+    /// Passed values must be declared or used somewhere, so typos don't go unnoticed
+    fn check_used(&self, sets: &Vars, source: &str) -> Result<()> {
+        match sets.keys().find(|name| !self.used.contains(*name)) {
+            Some(name) => bail!("{source}: '{name}' is set but not used by the workflow"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Format a YAML error as "file:line:column: message", without serde's long
+/// list of every step type
+fn yaml_error(source: &str, e: &serde_yaml::Error) -> anyhow::Error {
+    located_error(source, e.location(), &e.to_string())
+}
+
+fn located_error(source: &str, location: Option<serde_yaml::Location>, message: &str) -> anyhow::Error {
+    let mut message = message.to_string();
+    if let Some(idx) = message.find(" at line ") {
+        message.truncate(idx);
+    }
+    if message.contains("unknown variant") {
+        if let Some(idx) = message.find(", expected one of") {
+            message.truncate(idx);
+            message.push_str(" (step types are the command names, e.g. trim, extract-audio, social-media-convert)");
+        }
+    }
+    match location {
+        Some(loc) => anyhow!("{source}:{}:{}: {message}", loc.line(), loc.column()),
+        None => anyhow!("{source}: {message}"),
+    }
+}
+
+/// Values are read from the parsed file, which has no positions, so look
+/// the node that failed up in the file's text: the step, or the field of it
+/// the error names
+fn file_error(content: &str, at: &ReadPath, source: &str, e: &serde_yaml::Error) -> anyhow::Error {
+    let error = e.to_string();
+    let key = at.key.borrow();
+    let mut path: Vec<PathPart> = key.as_deref().map(PathPart::Key).into_iter().collect();
+    let message = match (key.as_deref(), at.step.get()) {
+        (Some(key), Some(idx)) => {
+            path.push(PathPart::Index(idx));
+            path.extend(named_field(&error).map(PathPart::Key));
+            format!("{key}[{idx}]: {error}")
+        }
+        _ => error,
+    };
+    let location = if path.is_empty() { None } else { node_location(content, &path) };
+    located_error(source, location, &message)
+}
+
+/// The field a serde error is about, when it names one: an unknown or
+/// repeated field, or the `type` of an unknown step
+fn named_field(message: &str) -> Option<&str> {
+    if message.starts_with("unknown variant") {
+        return Some("type");
+    }
+    ["unknown field `", "duplicate field `"].iter()
+        .find_map(|prefix| message.strip_prefix(prefix))
+        .and_then(|rest| rest.split('`').next())
+}
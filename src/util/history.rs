@@ -0,0 +1,195 @@
+use crate::ffmpeg::step::Step;
+use crate::util::manifest::hash_file;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Journal file name inside the data directory
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// A file a run read or wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    /// SHA-256 of the contents (hex), when the file existed after the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The output overwrote a file that was already there
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replaced: bool,
+}
+
+impl FileRecord {
+    /// Describe a file as it is now; directories and missing files get no hash
+    pub fn of(path: &Path, replaced: bool) -> Self {
+        let (sha256, size) = if path.is_file() {
+            (hash_file(path).ok(), fs::metadata(path).ok().map(|m| m.len()))
+        } else {
+            (None, None)
+        };
+        Self { path: path.to_path_buf(), sha256, size, replaced }
+    }
+}
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Ok,
+    Failed,
+    /// The output already existed and the collision policy said skip
+    Skipped,
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RunStatus::Ok => "ok",
+            RunStatus::Failed => "failed",
+            RunStatus::Skipped => "skipped",
+        };
+        f.pad(name)
+    }
+}
+
+/// One run of ffhuman, as recorded in the journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// Start time in seconds since the Unix epoch
+    pub started_at: u64,
+    /// Working directory the command ran in
+    pub cwd: PathBuf,
+    /// Command-line arguments after the program name
+    pub args: Vec<String>,
+    /// Operation name, as in workflow files (e.g. "convert")
+    pub operation: String,
+    pub inputs: Vec<FileRecord>,
+    pub outputs: Vec<FileRecord>,
+    /// Commands executed, in order
+    pub steps: Vec<Step>,
+    pub duration_secs: f64,
+    pub status: RunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The operation journal: one JSON object per line, appended after each run
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// `$XDG_DATA_HOME/ffhuman/history.jsonl`, or `~/.local/share/ffhuman/history.jsonl`
+    pub fn default_path() -> Result<PathBuf> {
+        let data_dir = match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))
+                    .ok_or_else(|| anyhow!("Can't find the home directory for the history journal (set HOME or XDG_DATA_HOME)"))?;
+                PathBuf::from(home).join(".local").join("share")
+            }
+        };
+        Ok(data_dir.join("ffhuman").join(HISTORY_FILE))
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Open the journal at its default location
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::open(Self::default_path()?))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All entries, oldest first. Lines that don't parse (e.g. a write cut
+    /// short) are skipped.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read history: {}", self.path.display()))?;
+        Ok(content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    pub fn get(&self, id: u64) -> Result<HistoryEntry> {
+        self.entries()?.into_iter().find(|e| e.id == id)
+            .ok_or_else(|| anyhow!("No run {id} in the history (see ffhuman history)"))
+    }
+
+    /// The id the next entry gets
+    pub fn next_id(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|e| e.id).max().unwrap_or(0) + 1)
+    }
+
+    /// Append an entry
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create history directory: {}", parent.display()))?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .with_context(|| format!("Failed to open history: {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write history: {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Files a step reads: the arguments after each `-i`
+pub fn step_inputs(step: &Step) -> Vec<PathBuf> {
+    step.args.windows(2)
+        .filter(|pair| pair[0] == "-i")
+        .map(|pair| PathBuf::from(&pair[1]))
+        .collect()
+}
+
+/// `path` made absolute against `cwd`
+pub fn absolute(path: &Path, cwd: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// Remove a recorded output if it still matches its recorded hash.
+/// Returns what happened, for the report.
+pub fn remove_if_unchanged(record: &FileRecord, dry_run: bool) -> Result<String> {
+    let path = &record.path;
+    if record.replaced {
+        return Ok(format!("kept {} (the run overwrote an earlier file)", path.display()));
+    }
+    if !path.exists() {
+        return Ok(format!("{} is already gone", path.display()));
+    }
+    let Some(expected) = &record.sha256 else {
+        return Ok(format!("kept {} (no hash was recorded)", path.display()));
+    };
+    if !path.is_file() {
+        bail!("{} is no longer a file", path.display());
+    }
+    if &hash_file(path)? != expected {
+        return Ok(format!("kept {} (changed since the run)", path.display()));
+    }
+    if dry_run {
+        return Ok(format!("would remove {}", path.display()));
+    }
+    fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(format!("removed {}", path.display()))
+}
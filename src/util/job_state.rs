@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Prefix of the job-state files a batch writes unless `--state` says otherwise
+pub const STATE_FILE_PREFIX: &str = ".ffhuman-batch-";
+
+/// Default job-state file for a batch: in its output folder (or the folder its
+/// pattern matches in), named after the pattern and operation so two batches
+/// over the same folder keep separate state
+pub fn default_state_path(output_dir: Option<&Path>, pattern: &str, operation: &str, condition: Option<&str>) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(pattern.as_bytes());
+    hasher.update([0]);
+    hasher.update(operation.as_bytes());
+    hasher.update([0]);
+    hasher.update(condition.unwrap_or("").as_bytes());
+    let digest = hasher.finalize();
+    let id: String = digest.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+
+    let dir = output_dir.map(Path::to_path_buf).unwrap_or_else(|| pattern_folder(pattern));
+    dir.join(format!("{}{}.json", STATE_FILE_PREFIX, id))
+}
+
+/// The literal folder a glob pattern matches in ("clips/*.mov" -> "clips")
+fn pattern_folder(pattern: &str) -> PathBuf {
+    let mut folder = PathBuf::new();
+    let components: Vec<Component> = Path::new(pattern).components().collect();
+    for (idx, component) in components.iter().enumerate() {
+        let text = component.as_os_str().to_string_lossy();
+        if idx + 1 == components.len() || text.contains(['*', '?', '[', '{']) {
+            break;
+        }
+        folder.push(component);
+    }
+    folder
+}
+
+/// The job-state file to continue: `given` itself, the only one in `given`
+/// when it is a folder, or else the only one in the output folder (or the
+/// current one, the pattern's folder when started from there), which is
+/// where `default_state_path` writes it
+pub fn locate_state_file(given: Option<&Path>, output_dir: Option<&Path>) -> Result<PathBuf> {
+    match given {
+        Some(dir) if dir.is_dir() => find_state_file(dir),
+        Some(file) => Ok(file.to_path_buf()),
+        None => find_state_file(output_dir.unwrap_or_else(|| Path::new("."))),
+    }
+}
+
+/// The only job-state file in a folder
+pub fn find_state_file(dir: &Path) -> Result<PathBuf> {
+    let mut found: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read folder: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(STATE_FILE_PREFIX) && n.ends_with(".json")))
+        .collect();
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        0 => anyhow::bail!("No batch job state in {}; pass the file the batch printed with --state", dir.display()),
+        _ => anyhow::bail!("Several batch job states in {}; choose one with --state", dir.display()),
+    }
+}
+
+/// Where a single file of a batch stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    /// Started but never finished (the run was interrupted)
+    Running,
+    Done,
+    Skipped,
+    Failed,
+}
+
+/// One input file of a batch run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub input: PathBuf,
+    pub status: JobStatus,
+    /// Error text for failed jobs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Wall-clock processing time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// Output the job was writing, recorded only when it didn't exist yet, so
+    /// a partial file left by an interrupted or failed run is known to be ours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+/// `--out`, `--output-dir` and `--name` of the original run, reapplied when
+/// it is resumed so outputs land in the same place. Paths are absolute.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl OutputSettings {
+    pub fn new(out: Option<&Path>, output_dir: Option<&Path>, name: Option<&str>) -> Self {
+        Self {
+            out: out.map(absolute),
+            output_dir: output_dir.map(absolute),
+            name: name.map(str::to_string),
+        }
+    }
+}
+
+/// `path` from the current folder, so it still means the same file after a `cd`
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Persistent state of a batch run, saved after every status change so an
+/// interrupted run can be resumed with `batch --resume`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobState {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Glob pattern the batch was started with
+    pub pattern: String,
+    /// Operation in command form (e.g. "convert to gif")
+    pub operation: String,
+    /// Condition in command form (e.g. "duration < 30s")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Output settings the batch was started with
+    #[serde(default)]
+    pub outputs: OutputSettings,
+    /// Inputs with absolute paths, so the batch can be resumed from any folder
+    pub jobs: Vec<Job>,
+}
+
+impl JobState {
+    /// Start a new job state with every file pending
+    pub fn new(
+        path: &Path,
+        pattern: &str,
+        operation: String,
+        condition: Option<String>,
+        outputs: OutputSettings,
+        files: &[PathBuf],
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            pattern: pattern.to_string(),
+            operation,
+            condition,
+            outputs,
+            jobs: files.iter()
+                .map(|input| Job { input: absolute(input), status: JobStatus::Pending, error: None, duration_secs: None, output: None })
+                .collect(),
+        }
+    }
+
+    /// Load a job state written by an earlier run
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read job state: {}", path.display()))?;
+        let mut state: JobState = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse job state: {}", path.display()))?;
+        state.path = path.to_path_buf();
+        Ok(state)
+    }
+
+    /// Write the state back to disk
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).context("Failed to create job state directory")?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        // Write then rename so a crash mid-write never leaves a truncated state file
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)
+            .with_context(|| format!("Failed to write job state: {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write job state: {}", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Indices of the jobs a resumed run should process: pending and interrupted
+    /// jobs when resuming, plus failed jobs when retrying
+    pub fn jobs_to_run(&self, resume: bool, retry_failed: bool) -> Vec<usize> {
+        self.jobs.iter()
+            .enumerate()
+            .filter(|(_, job)| match job.status {
+                JobStatus::Pending | JobStatus::Running => resume,
+                JobStatus::Failed => retry_failed,
+                JobStatus::Done | JobStatus::Skipped => false,
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Delete the partial output an interrupted or failed job left behind, so
+    /// re-running it doesn't stop at the collision check
+    pub fn remove_partial_output(&mut self, idx: usize) -> Result<Option<PathBuf>> {
+        let job = &mut self.jobs[idx];
+        if !matches!(job.status, JobStatus::Running | JobStatus::Failed) {
+            return Ok(None);
+        }
+        match job.output.take() {
+            Some(output) if output.exists() => {
+                fs::remove_file(&output)
+                    .with_context(|| format!("Failed to remove partial output: {}", output.display()))?;
+                Ok(Some(output))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Number of jobs with the given status
+    pub fn count(&self, status: JobStatus) -> usize {
+        self.jobs.iter().filter(|job| job.status == status).count()
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Name of the sidecar manifest written into each output directory
+pub const MANIFEST_FILE: &str = ".ffhuman-manifest.json";
+
+/// Size, modification time and content hash of an input file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFingerprint {
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: u64,
+    /// SHA-256 of the file contents (hex)
+    pub hash: String,
+}
+
+impl InputFingerprint {
+    /// Fingerprint a file, hashing its full contents
+    pub fn of(path: &Path) -> Result<Self> {
+        let (size, mtime) = size_and_mtime(path)?;
+        Ok(Self { size, mtime, hash: hash_file(path)? })
+    }
+}
+
+/// Everything that went into producing one output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub input: PathBuf,
+    #[serde(flatten)]
+    pub fingerprint: InputFingerprint,
+    /// The intent that produced the output, serialized with its parameters
+    pub intent: String,
+    pub ffmpeg_version: String,
+}
+
+/// Sidecar manifest recording how each output in a directory was produced,
+/// so incremental batch runs can skip work whose inputs and parameters are unchanged
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Entries keyed by output path
+    pub outputs: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Load the manifest from an output directory (empty if none exists yet)
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let mut manifest = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+            serde_json::from_str::<Manifest>(&content)
+                .with_context(|| format!("Failed to parse manifest: {}", path.display()))?
+        } else {
+            Manifest::default()
+        };
+        manifest.path = path;
+        Ok(manifest)
+    }
+
+    /// Write the manifest back to its output directory
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create output directory")?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write manifest: {}", self.path.display()))
+    }
+
+    /// True if the output was recorded by an earlier run
+    pub fn contains(&self, output: &Path) -> bool {
+        self.outputs.contains_key(&key(output))
+    }
+
+    /// True if the output exists and was produced from the same input contents,
+    /// the same intent and the same ffmpeg version.
+    ///
+    /// Unchanged size and mtime are trusted; the input is only re-hashed when
+    /// its mtime moved, so touching a file doesn't force a re-encode.
+    pub fn is_up_to_date(&self, output: &Path, input: &Path, intent: &str, ffmpeg_version: &str) -> Result<bool> {
+        let Some(entry) = self.outputs.get(&key(output)) else {
+            return Ok(false);
+        };
+        if !output.exists()
+            || entry.input != input
+            || entry.intent != intent
+            || entry.ffmpeg_version != ffmpeg_version
+        {
+            return Ok(false);
+        }
+
+        let (size, mtime) = size_and_mtime(input)?;
+        if size != entry.fingerprint.size {
+            return Ok(false);
+        }
+        if mtime == entry.fingerprint.mtime {
+            return Ok(true);
+        }
+        Ok(hash_file(input)? == entry.fingerprint.hash)
+    }
+
+    /// Record how an output was produced
+    pub fn record(&mut self, output: &Path, input: &Path, intent: &str, ffmpeg_version: &str) -> Result<()> {
+        let entry = ManifestEntry {
+            input: input.to_path_buf(),
+            fingerprint: InputFingerprint::of(input)?,
+            intent: intent.to_string(),
+            ffmpeg_version: ffmpeg_version.to_string(),
+        };
+        self.outputs.insert(key(output), entry);
+        Ok(())
+    }
+}
+
+fn key(output: &Path) -> String {
+    output.to_string_lossy().into_owned()
+}
+
+/// File size and modification time (seconds since the Unix epoch)
+pub fn size_and_mtime(path: &Path) -> Result<(u64, u64)> {
+    let meta = fs::metadata(path)
+        .with_context(|| format!("Failed to read file metadata: {}", path.display()))?;
+    let mtime = meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+/// SHA-256 of a file's contents as lowercase hex
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open file for hashing: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
@@ -1,6 +1,11 @@
+pub mod history;
+pub mod job_state;
+pub mod manifest;
+pub mod naming;
 pub mod output;
 pub mod system;
+pub mod watch_rules;
+pub mod watch_state;
 
 pub use output::*;
 pub use system::*;
-
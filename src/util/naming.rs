@@ -0,0 +1,122 @@
+use crate::ffmpeg::probe::get_video_info;
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Variables that can appear in an output name template
+pub const TEMPLATE_VARIABLES: &[&str] = &[
+    "stem", "name", "input_ext", "parent", "op", "ext", "date", "time",
+    "width", "height", "fps", "duration", "vcodec", "acodec",
+];
+
+/// Variables that differ from one input file to the next
+const PER_FILE_VARIABLES: &[&str] = &["stem", "name"];
+
+/// Variables whose values come from probing the input with ffprobe
+const PROBED_VARIABLES: &[&str] = &["width", "height", "fps", "duration", "vcodec", "acodec"];
+
+fn variable_regex() -> Result<Regex> {
+    Regex::new(r"\{([a-z_]+)\}").map_err(|e| anyhow!("Invalid regex: {}", e))
+}
+
+/// List the variable names used in a template, in order of appearance
+pub fn template_variables(template: &str) -> Result<Vec<String>> {
+    let re = variable_regex()?;
+    Ok(re.captures_iter(template)
+        .map(|caps| caps.get(1).unwrap().as_str().to_string())
+        .collect())
+}
+
+/// Check that every variable in the template is known
+pub fn validate_template(template: &str) -> Result<()> {
+    for var in template_variables(template)? {
+        if !TEMPLATE_VARIABLES.contains(&var.as_str()) {
+            bail!(
+                "Unknown variable {{{var}}} in output name template (available: {})",
+                TEMPLATE_VARIABLES.iter().map(|v| format!("{{{v}}}")).collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// True if the template yields a different name for each input file
+pub fn has_per_file_variable(template: &str) -> bool {
+    template_variables(template)
+        .map(|vars| vars.iter().any(|v| PER_FILE_VARIABLES.contains(&v.as_str())))
+        .unwrap_or(false)
+}
+
+/// Render an output name template for an input file and operation.
+///
+/// `op` is the operation suffix (e.g. "trim", "gif") and `ext` the output extension.
+/// Probed variables ({width}, {height}, ...) only run ffprobe when the template uses them.
+pub fn render_template(template: &str, input: &Path, op: &str, ext: &str) -> Result<PathBuf> {
+    validate_template(template)?;
+    let vars = template_variables(template)?;
+
+    let info = if vars.iter().any(|v| PROBED_VARIABLES.contains(&v.as_str())) {
+        Some(get_video_info(input).map_err(|e| {
+            anyhow!("Output name template needs probed properties of {}: {}", input.display(), e)
+        })?)
+    } else {
+        None
+    };
+
+    let (date, time) = utc_date_time(SystemTime::now());
+    let re = variable_regex()?;
+    let rendered = re.replace_all(template, |caps: &regex::Captures| {
+        let info = info.as_ref();
+        match &caps[1] {
+            "stem" => input.file_stem().and_then(OsStr::to_str).unwrap_or("output").to_string(),
+            "name" => input.file_name().and_then(OsStr::to_str).unwrap_or("output").to_string(),
+            "input_ext" => input.extension().and_then(OsStr::to_str).unwrap_or("").to_string(),
+            "parent" => input.parent()
+                .and_then(|p| p.file_name())
+                .and_then(OsStr::to_str)
+                .unwrap_or("")
+                .to_string(),
+            "op" => op.to_string(),
+            "ext" => ext.to_string(),
+            "date" => date.clone(),
+            "time" => time.clone(),
+            "width" => info.map(|i| i.width.to_string()).unwrap_or_default(),
+            "height" => info.map(|i| i.height.to_string()).unwrap_or_default(),
+            "fps" => info.map(|i| format!("{}", i.fps.round() as u32)).unwrap_or_default(),
+            "duration" => info.map(|i| format!("{}", i.duration.round() as u64)).unwrap_or_default(),
+            "vcodec" => info.map(|i| i.video_codec.clone()).unwrap_or_default(),
+            "acodec" => info.map(|i| i.audio_codec.clone()).unwrap_or_default(),
+            _ => String::new(),
+        }
+    });
+
+    if rendered.trim().is_empty() {
+        bail!("Output name template rendered an empty filename: {template}");
+    }
+    Ok(PathBuf::from(rendered.into_owned()))
+}
+
+/// Format a timestamp as ("YYYY-MM-DD", "HHMMSS") in UTC
+pub fn utc_date_time(now: SystemTime) -> (String, String) {
+    let secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        format!("{year:04}-{month:02}-{day:02}"),
+        format!("{:02}{:02}{:02}", rem / 3600, (rem % 3600) / 60, rem % 60),
+    )
+}
@@ -1,9 +1,24 @@
 use crate::config::AppConfig;
-use anyhow::{anyhow, Context, Result};
+use crate::model::CollisionPolicy;
+use crate::util::naming::{has_per_file_variable, render_template};
+use anyhow::{anyhow, bail, Context, Result};
 use std::ffi::OsStr;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::fs;
 
+/// Returned when an output already exists and the collision policy is `skip`
+#[derive(Debug)]
+pub struct OutputSkipped(pub PathBuf);
+
+impl fmt::Display for OutputSkipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Skipped: output already exists: {}", self.0.display())
+    }
+}
+
+impl std::error::Error for OutputSkipped {}
+
 /// Get the base stem (filename without extension) from a path
 pub fn base_stem(path: &Path) -> Result<String> {
     path.file_stem()
@@ -12,22 +27,37 @@ pub fn base_stem(path: &Path) -> Result<String> {
         .ok_or_else(|| anyhow!("Invalid input filename: path must have a valid filename (got: {})", path.display()))
 }
 
-/// Generate default output path based on input, suffix, and extension
+/// Generate default output path based on input, suffix, and extension,
+/// then apply the configured collision policy
 pub fn default_out(
     config: &AppConfig,
     input: &Path,
     suffix: &str,
     ext: &str,
+) -> Result<PathBuf> {
+    let out = planned_out(config, input, suffix, ext)?;
+    let out = resolve_collision(config, out)?;
+    config.outputs.record(out.clone());
+    Ok(out)
+}
+
+/// Compute the output path a handler would write to, without checking for collisions
+pub fn planned_out(
+    config: &AppConfig,
+    input: &Path,
+    suffix: &str,
+    ext: &str,
 ) -> Result<PathBuf> {
     if let Some(out) = &config.out {
+        // --out may itself be a template (e.g. "out/{stem}.gif")
+        let out = render_template(&out.to_string_lossy(), input, suffix, ext)?;
         // If full path is specified, ensure its parent directory exists
         if let Some(parent) = out.parent() {
             fs::create_dir_all(parent).context("Failed to create output directory")?;
         }
-        return Ok(out.clone());
+        return Ok(out);
     }
-    
-    let stem = base_stem(input)?;
+
     let dir = if let Some(output_dir) = &config.output_dir {
         // Create output directory if it doesn't exist
         fs::create_dir_all(output_dir).context("Failed to create output directory")?;
@@ -35,7 +65,72 @@ pub fn default_out(
     } else {
         input.parent().unwrap_or_else(|| Path::new("."))
     };
-    
-    Ok(dir.join(format!("{stem}_{suffix}.{ext}")))
+
+    let out = if let Some(template) = &config.name_template {
+        let name = render_template(template, input, suffix, ext)?;
+        let out = if name.is_absolute() { name } else { dir.join(name) };
+        // Templates may introduce subfolders (e.g. "{date}/{stem}.{ext}")
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent).context("Failed to create output directory")?;
+        }
+        out
+    } else {
+        let stem = base_stem(input)?;
+        dir.join(format!("{stem}_{suffix}.{ext}"))
+    };
+
+    Ok(out)
 }
 
+/// Apply the collision policy to an output path that may already exist
+pub fn resolve_collision(config: &AppConfig, out: PathBuf) -> Result<PathBuf> {
+    match config.collision {
+        CollisionPolicy::AutoNumber => auto_number(config, out),
+        _ if !out.exists() => Ok(out),
+        CollisionPolicy::Overwrite => Ok(out),
+        CollisionPolicy::Skip => Err(OutputSkipped(out).into()),
+        CollisionPolicy::Fail => bail!(
+            "Output already exists: {} (use --overwrite or --on-collision skip|auto-number)",
+            out.display()
+        ),
+    }
+}
+
+/// The path itself if it's free, else the first free `<stem>_<n>.<ext>`,
+/// claimed so no other step of this run picks it too
+fn auto_number(config: &AppConfig, out: PathBuf) -> Result<PathBuf> {
+    if !out.exists() && config.outputs.claim(&out) {
+        return Ok(out);
+    }
+    let stem = base_stem(&out)?;
+    let ext = out.extension().and_then(OsStr::to_str).map(|e| format!(".{e}")).unwrap_or_default();
+    let dir = out.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let mut n = 1;
+    loop {
+        let candidate = dir.join(format!("{stem}_{n}{ext}"));
+        if !candidate.exists() && config.outputs.claim(&candidate) {
+            return Ok(candidate);
+        }
+        n += 1;
+    }
+}
+
+/// Refuse output settings that would make every file of a multi-file run
+/// (batch, watch) write to the same path
+pub fn ensure_per_file_output(config: &AppConfig, mode: &str) -> Result<()> {
+    if let Some(out) = &config.out {
+        let out = out.to_string_lossy();
+        if !has_per_file_variable(&out) {
+            bail!(
+                "{mode} would write every file to {out}; add a per-file variable such as {{stem}} \
+                 (e.g. --out \"out/{{stem}}.{{ext}}\") or use --output-dir / --name instead"
+            );
+        }
+    }
+    if let Some(template) = &config.name_template {
+        if !has_per_file_variable(template) {
+            bail!("{mode} would give every file the same name; add {{stem}} or {{name}} to --name \"{template}\"");
+        }
+    }
+    Ok(())
+}
@@ -262,3 +262,16 @@ pub fn get_command_path(command: &str) -> Option<String> {
     }
 }
 
+
+/// First line of `ffmpeg -version`, e.g. "ffmpeg version 6.1.1 Copyright ..."
+pub fn ffmpeg_version() -> Option<String> {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .ok()
+        .and_then(|output| {
+            String::from_utf8(output.stdout)
+                .ok()
+                .and_then(|s| s.lines().next().map(|l| l.trim().to_string()))
+        })
+}
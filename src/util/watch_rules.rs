@@ -0,0 +1,260 @@
+use crate::ffmpeg::probe::{get_video_info, VideoInfo};
+use crate::model::types::Duration;
+use crate::model::workflow::{WorkflowSpec, WorkflowStep};
+use anyhow::{anyhow, bail, Context, Result};
+use glob::Pattern;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions the watcher treats as media when the rules file doesn't list its own
+pub const DEFAULT_MEDIA_EXTENSIONS: &[&str] = &[
+    "mp4", "avi", "mov", "mkv", "webm", "flv", "wmv", "mpg", "mpeg",
+    "mp3", "wav", "aac", "flac", "ogg", "m4a", "wma",
+];
+
+/// Watch-folder routing rules loaded from YAML:
+///
+/// ```yaml
+/// extensions: [mp4, mov, wav]
+/// rules:
+///   - name: podcasts
+///     match: { extension: wav }
+///     steps:
+///       - type: normalize
+///       - type: convert
+///         format: mp3
+///   - name: vertical
+///     match: { extension: mov, orientation: portrait }
+///     steps:
+///       - type: social
+///         platform: tiktok
+///   - name: everything else
+///     pipeline: pipelines/web.yaml
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchRules {
+    /// Files with these extensions are picked up by the watcher
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    pub rules: Vec<WatchRule>,
+    /// Directory of the rules file, for resolving pipeline paths
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+/// One rule: a match condition and the steps to run on matching files
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchRule {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Conditions that must all hold; an empty match accepts every file
+    #[serde(default, rename = "match")]
+    pub matches: RuleMatch,
+    /// Inline steps, in the same format as a workflow file; they run chained
+    #[serde(default)]
+    pub steps: Vec<WorkflowStep>,
+    /// Pipeline file to run instead of inline steps
+    #[serde(default)]
+    pub pipeline: Option<PathBuf>,
+}
+
+/// Match conditions on a file's name and probed properties
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleMatch {
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub extension: Vec<String>,
+    /// Glob on the file name, e.g. "raw_*"
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub orientation: Option<Orientation>,
+    #[serde(default)]
+    pub min_width: Option<u32>,
+    #[serde(default)]
+    pub max_width: Option<u32>,
+    #[serde(default)]
+    pub min_height: Option<u32>,
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    /// Duration bounds such as "30s"
+    #[serde(default)]
+    pub min_duration: Option<String>,
+    #[serde(default)]
+    pub max_duration: Option<String>,
+    #[serde(default)]
+    pub video_codec: Option<String>,
+    #[serde(default)]
+    pub audio_codec: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+    Square,
+}
+
+fn default_extensions() -> Vec<String> {
+    DEFAULT_MEDIA_EXTENSIONS.iter().map(|e| e.to_string()).collect()
+}
+
+/// Accept either `extension: wav` or `extension: [wav, flac]`
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+impl WatchRules {
+    /// Load and validate a rules file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rules file: {}", path.display()))?;
+        let mut rules: WatchRules = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse rules YAML {}: {}", path.display(), e))?;
+        rules.base_dir = path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+
+        if rules.rules.is_empty() {
+            bail!("Rules file {} has no rules", path.display());
+        }
+        for (idx, rule) in rules.rules.iter().enumerate() {
+            let label = rule.label(idx);
+            match (rule.steps.is_empty(), &rule.pipeline) {
+                (true, None) => bail!("Rule {label} needs 'steps' or 'pipeline'"),
+                (false, Some(_)) => bail!("Rule {label} has both 'steps' and 'pipeline'; use one"),
+                _ => {}
+            }
+            rule.matches.validate().with_context(|| format!("Invalid match in rule {label}"))?;
+        }
+        Ok(rules)
+    }
+
+    /// True if the file's extension is one the watcher handles
+    pub fn is_media_file(&self, path: &Path) -> bool {
+        has_extension(path, &self.extensions)
+    }
+
+    /// The first rule matching the file, probing it only if a rule needs probed properties
+    pub fn first_match(&self, path: &Path) -> Option<(usize, &WatchRule)> {
+        let mut info: Option<Option<VideoInfo>> = None;
+        self.rules.iter().enumerate().find(|(_, rule)| rule.matches.matches(path, &mut info))
+    }
+
+    /// Resolve a rule's pipeline file relative to the rules file
+    pub fn pipeline_path(&self, rule: &WatchRule) -> Option<PathBuf> {
+        rule.pipeline.as_ref().map(|p| if p.is_absolute() { p.clone() } else { self.base_dir.join(p) })
+    }
+
+    /// The workflow a rule runs: its pipeline file, or its inline steps
+    pub fn workflow_for(&self, rule: &WatchRule) -> Result<WorkflowSpec> {
+        match self.pipeline_path(rule) {
+            Some(pipeline) => WorkflowSpec::load(&pipeline),
+            None => Ok(WorkflowSpec { input: None, mode: None, steps: rule.steps.clone() }),
+        }
+    }
+}
+
+impl WatchRule {
+    /// Name for messages: the rule's name, or its position
+    pub fn label(&self, idx: usize) -> String {
+        match &self.name {
+            Some(name) => format!("'{name}'"),
+            None => format!("#{}", idx + 1),
+        }
+    }
+}
+
+impl RuleMatch {
+    fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.name {
+            Pattern::new(name).with_context(|| format!("Invalid name pattern: {name}"))?;
+        }
+        for duration in [&self.min_duration, &self.max_duration].into_iter().flatten() {
+            Duration::parse(duration)?;
+        }
+        Ok(())
+    }
+
+    fn needs_probe(&self) -> bool {
+        self.orientation.is_some()
+            || self.min_width.is_some() || self.max_width.is_some()
+            || self.min_height.is_some() || self.max_height.is_some()
+            || self.min_duration.is_some() || self.max_duration.is_some()
+            || self.video_codec.is_some() || self.audio_codec.is_some()
+    }
+
+    /// Check the file against every condition. `info` caches the probe result across rules.
+    fn matches(&self, path: &Path, info: &mut Option<Option<VideoInfo>>) -> bool {
+        if !self.extension.is_empty() && !has_extension(path, &self.extension) {
+            return false;
+        }
+        if let Some(name) = &self.name {
+            let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if !Pattern::new(name).map(|p| p.matches(&file_name)).unwrap_or(false) {
+                return false;
+            }
+        }
+        if !self.needs_probe() {
+            return true;
+        }
+
+        // Files ffprobe can't read as video never match probed conditions
+        let Some(info) = info.get_or_insert_with(|| get_video_info(path).ok()) else {
+            return false;
+        };
+
+        if let Some(orientation) = self.orientation {
+            let actual = match info.width.cmp(&info.height) {
+                std::cmp::Ordering::Less => Orientation::Portrait,
+                std::cmp::Ordering::Greater => Orientation::Landscape,
+                std::cmp::Ordering::Equal => Orientation::Square,
+            };
+            if actual != orientation {
+                return false;
+            }
+        }
+        if self.min_width.is_some_and(|min| info.width < min)
+            || self.max_width.is_some_and(|max| info.width > max)
+            || self.min_height.is_some_and(|min| info.height < min)
+            || self.max_height.is_some_and(|max| info.height > max)
+        {
+            return false;
+        }
+        let seconds = |d: &Option<String>| d.as_deref().and_then(|d| Duration::parse(d).ok()).map(|d| d.to_seconds());
+        if seconds(&self.min_duration).is_some_and(|min| info.duration < min)
+            || seconds(&self.max_duration).is_some_and(|max| info.duration > max)
+        {
+            return false;
+        }
+        if let Some(codec) = &self.video_codec {
+            if !info.video_codec.eq_ignore_ascii_case(codec) {
+                return false;
+            }
+        }
+        if let Some(codec) = &self.audio_codec {
+            if !info.audio_codec.eq_ignore_ascii_case(codec) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .map(|ext| extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext)))
+        .unwrap_or(false)
+}
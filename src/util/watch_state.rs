@@ -0,0 +1,87 @@
+use crate::util::job_state::JobStatus;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State file kept inside the watched folder
+pub const WATCH_STATE_FILE: &str = ".ffhuman-watch-state.json";
+
+/// What happened to one file picked up by the watcher
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchRecord {
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: u64,
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Output written for this file, so the watcher never picks it up as new input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    /// Outputs of earlier pipeline steps, ignored for the same reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intermediates: Vec<PathBuf>,
+    /// Name of the routing rule that matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Where the source was moved after processing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<PathBuf>,
+}
+
+/// Processed files of a watch folder, persisted so a restart doesn't redo work
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WatchState {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Records keyed by source path
+    pub files: BTreeMap<String, WatchRecord>,
+}
+
+impl WatchState {
+    /// Load the state of a watched folder (empty if none exists yet)
+    pub fn load(folder: &Path) -> Result<Self> {
+        let path = folder.join(WATCH_STATE_FILE);
+        let mut state = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read watch state: {}", path.display()))?;
+            serde_json::from_str::<WatchState>(&content)
+                .with_context(|| format!("Failed to parse watch state: {}", path.display()))?
+        } else {
+            WatchState::default()
+        };
+        state.path = path;
+        Ok(state)
+    }
+
+    /// Write the state back into the watched folder
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write watch state: {}", self.path.display()))
+    }
+
+    /// True if this exact file (same size and mtime) was already handled
+    pub fn is_processed(&self, source: &Path, size: u64, mtime: u64) -> bool {
+        self.files.get(&key(source))
+            .map(|record| record.size == size && record.mtime == mtime)
+            .unwrap_or(false)
+    }
+
+    /// True if the path is an output the watcher wrote itself
+    pub fn is_output(&self, path: &Path) -> bool {
+        self.files.values().any(|record| {
+            record.output.as_deref() == Some(path) || record.intermediates.iter().any(|p| p == path)
+        })
+    }
+
+    pub fn record(&mut self, source: &Path, record: WatchRecord) {
+        self.files.insert(key(source), record);
+    }
+}
+
+fn key(source: &Path) -> String {
+    source.to_string_lossy().into_owned()
+}
@@ -3,7 +3,6 @@ use ffhuman::App;
 use ffhuman::AppConfig;
 use ffhuman::cli::Cli;
 use ffhuman::model::*;
-use clap::Parser;
 use std::path::PathBuf;
 
 /// Test assets paths
@@ -70,11 +69,77 @@ fn test_convert_to_gif() -> Result<()> {
         format: ConvertFormat::Gif,
         quality: None,
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
 }
 
+#[test]
+fn test_convert_to_webp_with_max_size() -> Result<()> {
+    let app = create_test_app_with_output("test_convert_to_webp_with_max_size")?;
+    let intent = Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Webp,
+        quality: Some(QualityPreset::Low),
+        codec: None,
+        max_size: Some(TargetSize::parse("2mb")?),
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_animated_image_settings() -> Result<()> {
+    use ffhuman::ffmpeg::recipes::{animated_image_steps, gif_steps, AnimationSettings};
+    use std::path::Path;
+
+    let steps = |format: ConvertFormat, settings: &AnimationSettings| {
+        animated_image_steps(Path::new("in.mp4"), Path::new("out"), Path::new("palette.png"), format, settings)
+            .iter()
+            .map(|step| step.args.join(" "))
+            .collect::<Vec<_>>()
+    };
+    let medium = AnimationSettings::for_quality(None, 15, 480);
+
+    // GIF output is unchanged; APNG shares the palette passes
+    let gif = gif_steps(Path::new("in.mp4"), Path::new("out"), Path::new("palette.png"), 15, 480, None);
+    assert_eq!(gif.iter().map(|s| s.args.join(" ")).collect::<Vec<_>>(), steps(ConvertFormat::Gif, &medium));
+    assert!(gif[0].args.contains(&"fps=15,scale=480:-1:flags=lanczos,palettegen".to_string()));
+    let apng = steps(ConvertFormat::Apng, &AnimationSettings { colors: 64, ..medium });
+    assert!(apng[0].contains("palettegen=max_colors=64"), "{apng:?}");
+    assert!(apng[1].ends_with("-plays 0 -f apng out"), "{apng:?}");
+
+    let webp = steps(ConvertFormat::Webp, &medium);
+    assert_eq!(webp.len(), 1);
+    assert!(webp[0].contains("-c:v libwebp -lossless 0 -quality 75 -compression_level 6 -loop 0"), "{webp:?}");
+    let avif = steps(ConvertFormat::Avif, &AnimationSettings::for_quality(Some(QualityPreset::Ultra), 15, 480));
+    assert!(avif[0].contains("scale=800:-2") && avif[0].contains("-crf 22") && avif[0].contains("-f avif"), "{avif:?}");
+
+    // Shrinking lowers fps first, then width, then colors or quality
+    let next = medium.shrink(ConvertFormat::Gif, 1.2).unwrap();
+    assert_eq!((next.fps, next.width, next.colors), (11, 480, 256));
+    let next = medium.shrink(ConvertFormat::Gif, 4.0).unwrap();
+    assert_eq!(next.fps, 8);
+    assert!(next.width < 480 && next.width.is_multiple_of(2), "{next:?}");
+    let floor = AnimationSettings { fps: 8, width: 160, colors: 32, quality: 75 };
+    assert_eq!(floor.shrink(ConvertFormat::Gif, 2.0).unwrap().colors, 16);
+    assert_eq!(floor.shrink(ConvertFormat::Webp, 2.0).unwrap().quality, 60);
+    assert_eq!(AnimationSettings { colors: 16, ..floor }.shrink(ConvertFormat::Gif, 2.0), None);
+
+    // --max-size only applies to animated images
+    let app = App::new(AppConfig::new(None, None, false, true, false));
+    let result = app.execute(Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Mp4,
+        quality: None,
+        codec: None,
+        max_size: Some(TargetSize::parse("2mb")?),
+    });
+    assert!(result.unwrap_err().to_string().contains("--max-size works for gif"));
+    Ok(())
+}
+
 #[test]
 fn test_convert_to_webm() -> Result<()> {
     let app = create_test_app_with_output("test_convert_to_webm")?;
@@ -83,6 +148,7 @@ fn test_convert_to_webm() -> Result<()> {
         format: ConvertFormat::Webm,
         quality: Some(QualityPreset::High),
         codec: Some(VideoCodec::Vp9),
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -96,6 +162,7 @@ fn test_convert_to_mp4() -> Result<()> {
         format: ConvertFormat::Mp4,
         quality: Some(QualityPreset::Medium),
         codec: Some(VideoCodec::H264),
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -109,6 +176,7 @@ fn test_convert_to_mp3() -> Result<()> {
         format: ConvertFormat::Mp3,
         quality: None,
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -122,11 +190,97 @@ fn test_convert_to_wav() -> Result<()> {
         format: ConvertFormat::Wav,
         quality: None,
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
 }
 
+#[test]
+fn test_convert_to_mov_prores() -> Result<()> {
+    let app = create_test_app_with_output("test_convert_to_mov_prores")?;
+    let intent = Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Mov,
+        quality: Some(QualityPreset::High),
+        codec: Some(VideoCodec::ProRes(None)),
+        max_size: None,
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_convert_to_mkv_ffv1() -> Result<()> {
+    let app = create_test_app_with_output("test_convert_to_mkv_ffv1")?;
+    let intent = Intent::Convert {
+        input: PathBuf::from(VIDEO_WEBM_ASSET),
+        format: ConvertFormat::Mkv,
+        quality: None,
+        codec: Some(VideoCodec::Ffv1),
+        max_size: None,
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_convert_to_webm_av1() -> Result<()> {
+    let app = create_test_app_with_output("test_convert_to_webm_av1")?;
+    let intent = Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Webm,
+        quality: Some(QualityPreset::Medium),
+        codec: Some(VideoCodec::Av1),
+        max_size: None,
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_convert_codec_settings() -> Result<()> {
+    use ffhuman::ffmpeg::recipes::convert_generic_steps;
+    use std::path::Path;
+
+    let args = |out: &str, quality: Option<QualityPreset>, codec: VideoCodec| {
+        convert_generic_steps(Path::new("in.mp4"), Path::new(out), false, quality, Some(codec))[0].args.join(" ")
+    };
+    // Quality picks the ProRes/DNxHR profile, with matching pixel format and PCM audio
+    let prores = args("out.mov", Some(QualityPreset::High), VideoCodec::ProRes(None));
+    assert!(prores.contains("-c:v prores_ks -profile:v 3 -vendor apl0 -pix_fmt yuv422p10le"), "{prores}");
+    assert!(prores.contains("-c:a pcm_s16le"), "{prores}");
+    let prores = args("out.mov", Some(QualityPreset::Low), VideoCodec::ProRes(Some(ProResProfile::P4444)));
+    assert!(prores.contains("-profile:v 4 -vendor apl0 -pix_fmt yuva444p10le"), "{prores}");
+    let dnxhr = args("out.mov", None, VideoCodec::Dnxhr(None));
+    assert!(dnxhr.contains("-c:v dnxhd -profile:v dnxhr_sq -pix_fmt yuv422p "), "{dnxhr}");
+
+    // AV1 has its own CRF scale, not x264's
+    let av1 = args("out.webm", Some(QualityPreset::High), VideoCodec::Av1);
+    assert!(av1.contains("-c:v libsvtav1 -crf 26 -preset 6 -pix_fmt yuv420p"), "{av1}");
+    assert!(av1.contains("-c:a libopus"), "{av1}");
+    let aom = args("out.mp4", None, VideoCodec::Av1Aom);
+    assert!(aom.contains("-crf 32 -b:v 0"), "{aom}");
+
+    let ffv1 = args("out.mkv", Some(QualityPreset::Low), VideoCodec::Ffv1);
+    assert!(ffv1.contains("-c:v ffv1 -level 3") && !ffv1.contains("-crf") && ffv1.contains("-c:a flac"), "{ffv1}");
+
+    // Codecs are checked against the container
+    assert!(ConvertFormat::Mp4.check_codec(VideoCodec::ProRes(None)).is_err());
+    assert!(ConvertFormat::Mov.check_codec(VideoCodec::Ffv1).is_err());
+    assert!(ConvertFormat::Mkv.check_codec(VideoCodec::Ffv1).is_ok());
+    let app = App::new(AppConfig::new(None, None, false, true, false));
+    let result = app.execute(Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Mp4,
+        quality: None,
+        codec: Some(VideoCodec::Dnxhr(None)),
+        max_size: None,
+    });
+    assert!(result.unwrap_err().to_string().contains("use mov or mkv"));
+    Ok(())
+}
+
 #[test]
 fn test_convert_avi_to_mp4() -> Result<()> {
     let app = create_test_app_with_output("test_convert_avi_to_mp4")?;
@@ -135,6 +289,7 @@ fn test_convert_avi_to_mp4() -> Result<()> {
         format: ConvertFormat::Mp4,
         quality: Some(QualityPreset::Medium),
         codec: Some(VideoCodec::H264),
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -148,6 +303,7 @@ fn test_convert_wmv_to_mp4() -> Result<()> {
         format: ConvertFormat::Mp4,
         quality: Some(QualityPreset::Medium),
         codec: Some(VideoCodec::H264),
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -183,6 +339,7 @@ fn test_extract_audio_from_avi() -> Result<()> {
     let intent = Intent::ExtractAudio {
         input: PathBuf::from(VIDEO_AVI_ASSET),
         format: AudioFormat::Mp3,
+        quality: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -296,6 +453,7 @@ fn test_extract_audio_mp3() -> Result<()> {
     let intent = Intent::ExtractAudio {
         input: PathBuf::from(VIDEO_ASSET),
         format: AudioFormat::Mp3,
+        quality: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -307,11 +465,82 @@ fn test_extract_audio_wav() -> Result<()> {
     let intent = Intent::ExtractAudio {
         input: PathBuf::from(VIDEO_ASSET),
         format: AudioFormat::Wav,
+        quality: None,
     };
     app.execute(intent)?;
     Ok(())
 }
 
+#[test]
+fn test_extract_audio_flac() -> Result<()> {
+    let app = create_test_app_with_output("test_extract_audio_flac")?;
+    let intent = Intent::ExtractAudio {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: AudioFormat::Flac,
+        quality: Some(QualityPreset::High),
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_convert_to_opus() -> Result<()> {
+    let app = create_test_app_with_output("test_convert_to_opus")?;
+    let intent = Intent::Convert {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: ConvertFormat::Opus,
+        quality: None,
+        codec: None,
+        max_size: None,
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_audio_format_settings() -> Result<()> {
+    use ffhuman::ffmpeg::recipes::extract_audio_steps;
+    use std::path::Path;
+
+    assert_eq!(AudioFormat::parse("m4a")?, AudioFormat::Aac);
+    assert_eq!(AudioFormat::parse("Vorbis")?, AudioFormat::Vorbis);
+    assert_eq!(AudioFormat::parse("copy")?, AudioFormat::Auto);
+    assert!(AudioFormat::parse("wma").is_err());
+    assert_eq!(AudioFormat::Aac.ext(), "m4a");
+    assert_eq!(ConvertFormat::parse("aif")?.audio_format(), Some(AudioFormat::Aiff));
+    assert_eq!(ConvertFormat::Mkv.audio_format(), None);
+
+    let args = |format: AudioFormat, quality: Option<QualityPreset>| {
+        extract_audio_steps(Path::new("in.mp4"), Path::new("out"), format, quality, false)[0].args.join(" ")
+    };
+    // Tags are kept; mp3 writes ID3v2.3, which every player reads
+    let mp3 = args(AudioFormat::Mp3, None);
+    assert!(mp3.contains("-vn -map_metadata 0 -c:a libmp3lame -q:a 2 -id3v2_version 3"), "{mp3}");
+    assert!(args(AudioFormat::Mp3, Some(QualityPreset::Low)).contains("-q:a 6 -id3v2_version 3 -ar 44100"));
+    assert!(args(AudioFormat::Opus, None).contains("-c:a libopus -vbr on -b:a 128k -ar 48000"));
+    assert!(args(AudioFormat::Aac, Some(QualityPreset::Ultra)).contains("-c:a aac -b:a 256k"));
+    assert!(args(AudioFormat::Vorbis, None).contains("-c:a libvorbis -q:a 5"));
+
+    // Lossless bit depth follows the quality preset
+    assert!(args(AudioFormat::Flac, None).contains("-sample_fmt s16"));
+    assert!(args(AudioFormat::Flac, Some(QualityPreset::High)).contains("-sample_fmt s32 -bits_per_raw_sample 24"));
+    assert!(args(AudioFormat::Wav, Some(QualityPreset::Ultra)).contains("-c:a pcm_s24le"));
+    assert!(args(AudioFormat::Aiff, None).contains("-c:a pcm_s16be -write_id3v2 1"));
+
+    // Auto copies the stream into the container that takes it
+    assert!(args(AudioFormat::Auto, None).ends_with("-c:a copy out"));
+    assert_eq!(AudioFormat::for_codec("aac"), Some(AudioFormat::Aac));
+    assert_eq!(AudioFormat::for_codec("pcm_s24be"), Some(AudioFormat::Aiff));
+    assert_eq!(AudioFormat::for_codec("ac3"), None);
+
+    let cli = Cli::try_parse_from(["ffhuman", "extract-audio", VIDEO_ASSET, "--format", "flac", "--quality", "high"])?;
+    assert!(matches!(
+        cli.into_intent()?,
+        Intent::ExtractAudio { format: AudioFormat::Flac, quality: Some(QualityPreset::High), .. }
+    ));
+    Ok(())
+}
+
 #[test]
 fn test_resize_to_preset() -> Result<()> {
     let app = create_test_app_with_output("test_resize_to_preset")?;
@@ -570,6 +799,7 @@ fn test_normalize() -> Result<()> {
     let app = create_test_app_with_output("test_normalize")?;
     let intent = Intent::Normalize {
         input: PathBuf::from(VIDEO_ASSET),
+        target: LoudnessTarget::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -609,6 +839,66 @@ fn test_split_into_parts() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_split_modes() -> Result<()> {
+    use ffhuman::ffmpeg::probe::{parse_showinfo_times, parse_silencedetect, Packet};
+    use ffhuman::model::split::{
+        every_starts, silence_starts, size_starts, snap_to_keyframes, spans, SplitManifest, SplitPart,
+    };
+
+    // Modes and their options, as words or flags
+    assert_eq!(SplitMode::parse("by scenes")?, SplitMode::Scenes { threshold: 0.3 });
+    assert_eq!(SplitMode::parse("by scenes threshold 0.45")?, SplitMode::Scenes { threshold: 0.45 });
+    let silence = SplitMode::parse("on silence min 1.5s noise -35dB")?;
+    assert_eq!(silence, SplitMode::Silence { min_silence: Duration::parse("1.5s")?, noise: -35.0 });
+    assert_eq!(SplitMode::parse(&silence.to_string())?, silence);
+    assert_eq!(SplitMode::parse("into chunks under 25mb")?, SplitMode::Size(TargetSize::parse("25mb")?));
+    assert_eq!(SplitMode::parse("into chunks under 25mb")?.to_string(), "into chunks under 25.00mb");
+    assert!(SplitMode::parse("by scenes threshold 2").is_err());
+    assert!(SplitMode::parse("on silence noise").is_err());
+    assert!(SplitMode::parse("every 10s")?.tune(Some(0.5), None, None).is_err());
+    let cli = Cli::try_parse_from(["ffhuman", "split", AUDIO_ASSET, "on", "silence", "--noise", "-40dB", "--min-silence", "2s"])?;
+    assert!(matches!(cli.into_intent()?, Intent::Split { mode: SplitMode::Silence { noise, .. }, .. } if noise == -40.0));
+
+    // Copied parts start on the nearest keyframe; parts that collapse are dropped
+    let starts = snap_to_keyframes(every_starts(25.0, 10.0), &[0.0, 4.0, 9.3337, 21.0]);
+    assert_eq!(starts, [(0.0, None), (9.333, None), (21.0, None)]);
+    let snapped = snap_to_keyframes(every_starts(25.0, 5.0), &[0.0, 9.0, 21.0]);
+    let parts: Vec<_> = spans(snapped, 25.0).into_iter().map(|(s, e, _)| (s, e)).collect();
+    assert_eq!(parts, [(0.0, 9.0), (9.0, 21.0), (21.0, 25.0)]);
+
+    // Silences split in their middle; leading and trailing silence doesn't
+    let log = "[silencedetect @ 0x1] silence_start: 0\n[silencedetect @ 0x1] silence_end: 1.2 | silence_duration: 1.2\n\
+               [silencedetect @ 0x1] silence_start: 30.5\n[silencedetect @ 0x1] silence_end: 31.5 | silence_duration: 1\n\
+               [silencedetect @ 0x1] silence_start: 58\n";
+    let silences = parse_silencedetect(log);
+    assert_eq!(silences, [(0.0, Some(1.2)), (30.5, Some(31.5)), (58.0, None)]);
+    assert_eq!(silence_starts(&silences), [(0.0, None), (31.0, None)]);
+    assert_eq!(parse_showinfo_times("[Parsed_showinfo_1 @ 0x2] n:   0 pts:  12800 pts_time:12.8 duration:512\n"), [12.8]);
+
+    // Chunks under the size limit, cut on keyframes
+    let packet = |time: f64, key: bool| Packet { time, size: 100, video: true, key };
+    let packets: Vec<_> = (0..30).map(|i| packet(i as f64, i % 5 == 0)).collect();
+    let (starts, oversized) = size_starts(&packets, 1200);
+    assert_eq!(starts.iter().map(|s| s.0).collect::<Vec<_>>(), [0.0, 10.0, 20.0]);
+    assert!(!oversized);
+    assert!(size_starts(&packets, 300).1, "keyframes 5 packets apart can't fit 300 bytes");
+
+    // The manifest lists each part's file and time range
+    let parts = [SplitPart { output: PathBuf::from("out/01_Intro.mp4"), start: 0.0, end: 65.5004, title: Some("Intro".to_string()) }];
+    let json = serde_json::to_value(SplitManifest::new(&PathBuf::from("talk.mp4"), "by chapters", &parts))?;
+    assert_eq!(json["parts"][0]["file"], "01_Intro.mp4");
+    assert_eq!(json["parts"][0]["end"], 65.5);
+    assert_eq!(json["parts"][0]["title"], "Intro");
+    assert!(json["parts"][0].get("size").is_none());
+
+    // Splitting a file that's already small enough
+    let app = create_test_app_with_output("test_split_modes")?;
+    let intent = Intent::Split { input: PathBuf::from(VIDEO_ASSET), mode: SplitMode::parse("into chunks under 1gb")? };
+    assert!(app.execute(intent).is_err());
+    Ok(())
+}
+
 #[test]
 fn test_extract_frames() -> Result<()> {
     let app = create_test_app_with_output("test_extract_frames")?;
@@ -625,9 +915,77 @@ fn test_burn_subtitle() -> Result<()> {
     let app = create_test_app_with_output("test_burn_subtitle")?;
     let intent = Intent::BurnSubtitle {
         input: PathBuf::from(VIDEO_ASSET),
-        subtitle: PathBuf::from(SUBTITLE_ASSET),
-    };
+        subtitle: Some(PathBuf::from(SUBTITLE_ASSET)),
+        track: None,
+        style: Default::default(),
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_burn_subtitle_styles() -> Result<()> {
+    use ffhuman::ffmpeg::recipes::{burn_subtitle_steps, BurnSource};
+    use ffhuman::model::subtitle::{ass_play_res, CaptionPosition, CaptionPreset, SubtitleSelector, SubtitleStyle};
+
+    // Pixel sizes scale to the 288-line script SRT is drawn at
+    let style = SubtitleStyle {
+        font: Some("Open Sans".to_string()),
+        size: Some(60),
+        color: Some(TextColor::parse("yellow")?),
+        background: Some(TextColor::parse("black")?),
+        position: Some(CaptionPosition::parse("top")?),
+        margin: Some(30),
+        ..Default::default()
+    };
+    assert_eq!(
+        style.force_style(1080, 288),
+        "FontName=Open Sans,FontSize=16,PrimaryColour=&H0000FFFF,BorderStyle=3,OutlineColour=&H60000000,\
+         BackColour=&H60000000,Outline=1,Shadow=0,Alignment=8,MarginV=8"
+    );
+
+    // Presets, with options on top
+    let social = SubtitleStyle { preset: Some(CaptionPreset::parse("social")?), outline: Some(0), ..Default::default() };
+    let social = social.force_style(1920, 288);
+    assert!(social.starts_with("FontName=Arial,FontSize=22,Bold=1,BorderStyle=1"), "{social}");
+    assert!(social.contains("Outline=0") && social.contains("Alignment=5"), "{social}");
+    let broadcast = SubtitleStyle { preset: Some(CaptionPreset::Broadcast), ..Default::default() }.force_style(720, 576);
+    assert!(broadcast.contains("BorderStyle=3") && broadcast.ends_with("Alignment=2,MarginV=30,MarginL=40,MarginR=40"), "{broadcast}");
+    assert!(CaptionPreset::parse("cinema").is_err());
+    assert!(SubtitleStyle::default().is_empty());
+
+    assert_eq!(ass_play_res("[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n"), 1080);
+    assert_eq!(ass_play_res("[Script Info]\n"), 288);
+
+    // Embedded tracks by number from 1, or by language in either ISO 639-2 form
+    assert_eq!(SubtitleSelector::parse("2")?, SubtitleSelector::Index(2));
+    assert!(SubtitleSelector::parse("0").is_err());
+    let german = SubtitleSelector::parse("de")?;
+    assert!(german.matches_language("ger") && german.matches_language("deu") && !german.matches_language("eng"));
+
+    // ASS keeps its styles unless overridden; tracks of the input are picked with si
+    let args = |source, style| burn_subtitle_steps(&PathBuf::from("in.mkv"), source, style, &PathBuf::from("out.mp4"), true)[0].args.join(" ");
+    let ass = PathBuf::from("styled.ass");
+    assert!(args(BurnSource::File(&ass), None).contains("-vf ass='styled.ass' -sn"));
+    assert!(args(BurnSource::File(&ass), Some("FontSize=20")).contains("-vf subtitles='styled.ass':force_style='FontSize=20'"));
+    assert!(args(BurnSource::Text(1), None).contains("-vf subtitles='in.mkv':si=1 -sn"));
+    assert!(args(BurnSource::Bitmap(0), None).contains("-filter_complex [0:v][0:s:0]overlay[v] -map [v] -map 0:a?"));
+    // Quotes close and reopen around an apostrophe; : and \ are escaped
+    let odd = PathBuf::from(r"C:\subs\it's.srt");
+    let filter = burn_subtitle_steps(&PathBuf::from("in.mkv"), BurnSource::File(&odd), None, &PathBuf::from("out.mp4"), true)[0]
+        .args.iter().find(|a| a.starts_with("subtitles=")).cloned();
+    assert_eq!(filter.as_deref(), Some(r"subtitles='C\:\\subs\\it\'\''s.srt'"));
+
+    // Styled burn through the CLI; a subtitle file and --track can't go together
+    let app = create_test_app_with_output("test_burn_subtitle_styles")?;
+    let cli = Cli::try_parse_from([
+        "ffhuman", "burn-subtitle", VIDEO_ASSET, SUBTITLE_ASSET, "--preset", "broadcast", "--color", "#FFCC00",
+    ])?;
+    let intent = cli.into_intent()?;
+    assert!(matches!(&intent, Intent::BurnSubtitle { style, .. } if style.preset == Some(CaptionPreset::Broadcast)));
     app.execute(intent)?;
+    let cli = Cli::try_parse_from(["ffhuman", "burn-subtitle", VIDEO_ASSET, SUBTITLE_ASSET, "--track", "eng"])?;
+    assert!(app.execute(cli.into_intent()?).is_err());
     Ok(())
 }
 
@@ -710,6 +1068,7 @@ fn test_batch() -> Result<()> {
     let intent = Intent::Batch {
         pattern: "src/assets/*.webm".to_string(),
         operation: BatchOperation::Convert(ConvertFormat::Gif),
+        options: BatchOptions::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -994,6 +1353,7 @@ fn test_extract_audio_range() -> Result<()> {
         start: Time::parse("0:30")?,
         end: Time::parse("2:00")?,
         format: AudioFormat::Mp3,
+        quality: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1007,6 +1367,7 @@ fn test_extract_audio_range_wav() -> Result<()> {
         start: Time::parse("0:05")?,
         end: Time::parse("0:15")?,
         format: AudioFormat::Wav,
+        quality: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1062,6 +1423,31 @@ fn test_concat() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_temp_files_are_per_step() -> Result<()> {
+    use ffhuman::ffmpeg::recipes;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    // Two-pass encodes keep their pass logs where they are told to
+    let log = Path::new("/tmp/ffhuman-passlog-1/pass");
+    let steps = recipes::compress_steps(Path::new("in.mp4"), Path::new("out.mp4"), 10_000_000, 60.0, false, Some(log));
+    assert_eq!(steps.len(), 2);
+    for step in &steps {
+        let at = step.args.iter().position(|a| a == "-passlogfile").expect("no -passlogfile");
+        assert_eq!(step.args[at + 1], log.to_string_lossy());
+    }
+    let steps = recipes::compress_bitrate_steps(Path::new("in.mp4"), Path::new("out.mp4"), 2_000_000, false, None);
+    assert!(!steps.iter().any(|s| s.args.iter().any(|a| a.starts_with("-pass"))));
+
+    // Concat lists are temporary files, not one shared file in the output folder
+    let dir = TempDir::new()?;
+    let config = AppConfig::new(None, Some(dir.path().to_path_buf()), false, true, false);
+    App::new(config).execute(Intent::Concat { videos: vec![PathBuf::from(VIDEO_ASSET), PathBuf::from(VIDEO_ASSET)] })?;
+    assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+    Ok(())
+}
+
 #[test]
 fn test_detect_scenes() -> Result<()> {
     let app = create_test_app_with_output("test_detect_scenes")?;
@@ -1072,6 +1458,28 @@ fn test_detect_scenes() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_detect_scenes_output_follows_collision_policy() -> Result<()> {
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    let existing = dir.path().join("file_example_MP4_480_1_5MG_scenes.txt");
+    std::fs::write(&existing, b"0.000\n")?;
+    let intent = || Intent::DetectScenes { input: PathBuf::from(VIDEO_ASSET) };
+    let config = || AppConfig::new(None, Some(dir.path().to_path_buf()), false, true, false);
+
+    // An existing list is neither replaced nor silently reused
+    assert!(App::new(config()).execute(intent()).is_err());
+    assert_eq!(std::fs::read(&existing)?, b"0.000\n");
+
+    let numbered = config().with_naming(None, Some(CollisionPolicy::AutoNumber));
+    App::new(numbered.clone()).execute(intent())?;
+    assert_eq!(numbered.outputs.since(0), vec![dir.path().join("file_example_MP4_480_1_5MG_scenes_1.txt")]);
+    // A dry run writes nothing
+    assert!(!dir.path().join("file_example_MP4_480_1_5MG_scenes_1.txt").exists());
+    Ok(())
+}
+
 #[test]
 fn test_detect_black() -> Result<()> {
     let app = create_test_app_with_output("test_detect_black")?;
@@ -1101,6 +1509,7 @@ fn test_convert_to_iphone() -> Result<()> {
         format: ConvertFormat::Iphone,
         quality: None,
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1114,6 +1523,7 @@ fn test_convert_to_android() -> Result<()> {
         format: ConvertFormat::Android,
         quality: Some(QualityPreset::High),
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1127,6 +1537,7 @@ fn test_convert_to_hls() -> Result<()> {
         format: ConvertFormat::Hls,
         quality: Some(QualityPreset::Medium),
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1140,6 +1551,7 @@ fn test_convert_to_dash() -> Result<()> {
         format: ConvertFormat::Dash,
         quality: Some(QualityPreset::High),
         codec: None,
+        max_size: None,
     };
     app.execute(intent)?;
     Ok(())
@@ -1185,6 +1597,7 @@ fn test_batch_with_progress() -> Result<()> {
     let intent = Intent::Batch {
         pattern: "src/assets/*.webm".to_string(),
         operation: BatchOperation::Convert(ConvertFormat::Mp4),
+        options: BatchOptions::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -1235,6 +1648,7 @@ fn test_workflow() -> Result<()> {
     let app = create_test_app_with_output("test_workflow")?;
     let intent = Intent::Workflow {
         config_file: workflow_file.clone(),
+        vars: Default::default(),
     };
     
     // Execute workflow (will fail in dry-run mode but that's OK for testing)
@@ -1893,7 +2307,8 @@ fn test_watch_folder() -> Result<()> {
     let app = create_test_app_with_output("test_watch_folder")?;
     let intent = Intent::WatchFolder {
         folder: PathBuf::from("src/assets"),
-        operation: BatchOperation::Convert(ConvertFormat::Mp4),
+        operation: Some(BatchOperation::Convert(ConvertFormat::Mp4)),
+        options: WatchOptions::default(),
     };
     // Note: This test validates the intent structure and basic setup
     // In a real scenario, you'd want to test with actual file system events
@@ -1928,6 +2343,7 @@ operations:
     let intent = Intent::ApplyTemplate {
         input: PathBuf::from(VIDEO_ASSET),
         template_file,
+        vars: Default::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -1960,6 +2376,7 @@ steps:
     let intent = Intent::Pipeline {
         input: PathBuf::from(VIDEO_ASSET),
         steps_file,
+        vars: Default::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -1974,6 +2391,7 @@ fn test_conditional_batch() -> Result<()> {
         pattern: "src/assets/*.webm".to_string(),
         operation: BatchOperation::Convert(ConvertFormat::Gif),
         condition: ProcessingCondition::DurationLessThan(Duration::parse("60s")?),
+        options: BatchOptions::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -1988,6 +2406,7 @@ fn test_conditional_batch_duration_greater_than() -> Result<()> {
         pattern: "src/assets/*.webm".to_string(),
         operation: BatchOperation::Convert(ConvertFormat::Mp4),
         condition: ProcessingCondition::DurationGreaterThan(Duration::parse("1s")?),
+        options: BatchOptions::default(),
     };
     app.execute(intent)?;
     Ok(())
@@ -2002,8 +2421,1448 @@ fn test_conditional_batch_duration_equals() -> Result<()> {
         pattern: "src/assets/*.webm".to_string(),
         operation: BatchOperation::Convert(ConvertFormat::Webm),
         condition: ProcessingCondition::DurationEquals(Duration::parse("10s")?),
+        options: BatchOptions::default(),
     };
     app.execute(intent)?;
     Ok(())
 }
 
+
+#[test]
+fn test_output_name_template() -> Result<()> {
+    use ffhuman::util::planned_out;
+
+    let config = AppConfig::new(None, Some(PathBuf::from("target/test-naming")), false, true, false)
+        .with_naming(Some("{stem}-{op}.{ext}".to_string()), None);
+    let out = planned_out(&config, &PathBuf::from("clips/interview.mov"), "trim", "mp4")?;
+    assert_eq!(out, PathBuf::from("target/test-naming/interview-trim.mp4"));
+
+    let out = ffhuman::util::naming::render_template("{parent}/{name}.{ext}", &PathBuf::from("clips/a.mov"), "gif", "gif")?;
+    assert_eq!(out, PathBuf::from("clips/a.mov.gif"));
+
+    assert!(ffhuman::util::naming::validate_template("{stem}-{bogus}.{ext}").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_output_collision_policies() -> Result<()> {
+    use ffhuman::util::{default_out, OutputSkipped};
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("clip.mp4");
+    fs::write(temp_dir.path().join("clip_trim.mp4"), b"existing")?;
+
+    let numbered = AppConfig::new(None, None, false, true, false)
+        .with_naming(None, Some(CollisionPolicy::AutoNumber));
+    assert_eq!(default_out(&numbered, &input, "trim", "mp4")?, temp_dir.path().join("clip_trim_1.mp4"));
+    // A name already handed out isn't given again, even before its file is written
+    assert_eq!(default_out(&numbered, &input, "trim", "mp4")?, temp_dir.path().join("clip_trim_2.mp4"));
+    // Names are held for one run only: the next run (a new config) starts over
+    let next_run = AppConfig::new(None, None, false, true, false)
+        .with_naming(None, Some(CollisionPolicy::AutoNumber));
+    assert_eq!(default_out(&next_run, &input, "trim", "mp4")?, temp_dir.path().join("clip_trim_1.mp4"));
+    // Steps of a workflow share their run's names
+    let mut step = next_run.clone();
+    step.outputs = next_run.outputs.child();
+    assert_eq!(default_out(&step, &input, "trim", "mp4")?, temp_dir.path().join("clip_trim_2.mp4"));
+
+    let skip = AppConfig::new(None, None, false, true, false)
+        .with_naming(None, Some(CollisionPolicy::Skip));
+    let err = default_out(&skip, &input, "trim", "mp4").unwrap_err();
+    assert!(err.downcast_ref::<OutputSkipped>().is_some());
+
+    let fail = AppConfig::new(None, None, false, true, false);
+    assert!(default_out(&fail, &input, "trim", "mp4").is_err());
+
+    let overwrite = AppConfig::new(None, None, false, true, true);
+    assert_eq!(default_out(&overwrite, &input, "trim", "mp4")?, temp_dir.path().join("clip_trim.mp4"));
+    Ok(())
+}
+
+#[test]
+fn test_batch_refuses_shared_out() -> Result<()> {
+    use ffhuman::util::ensure_per_file_output;
+
+    let shared = AppConfig::new(Some(PathBuf::from("out/final.gif")), None, false, true, false);
+    assert!(ensure_per_file_output(&shared, "Batch").is_err());
+
+    let per_file = AppConfig::new(Some(PathBuf::from("out/{stem}.gif")), None, false, true, false);
+    assert!(ensure_per_file_output(&per_file, "Batch").is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_batch_manifest_tracks_inputs() -> Result<()> {
+    use ffhuman::util::manifest::{Manifest, MANIFEST_FILE};
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("clip.mov");
+    let output = temp_dir.path().join("clip_convert.mp4");
+    fs::write(&input, b"source frames")?;
+    fs::write(&output, b"encoded")?;
+
+    let mut manifest = Manifest::load(temp_dir.path())?;
+    assert!(!manifest.is_up_to_date(&output, &input, "Convert(mp4)", "ffmpeg 6.1")?);
+    manifest.record(&output, &input, "Convert(mp4)", "ffmpeg 6.1")?;
+    manifest.save()?;
+    assert!(temp_dir.path().join(MANIFEST_FILE).exists());
+
+    let manifest = Manifest::load(temp_dir.path())?;
+    assert!(manifest.is_up_to_date(&output, &input, "Convert(mp4)", "ffmpeg 6.1")?);
+    // Different parameters or ffmpeg version invalidate the output
+    assert!(!manifest.is_up_to_date(&output, &input, "Convert(webm)", "ffmpeg 6.1")?);
+    assert!(!manifest.is_up_to_date(&output, &input, "Convert(mp4)", "ffmpeg 7.0")?);
+
+    // Changed contents invalidate it too
+    fs::write(&input, b"new source frames")?;
+    assert!(!manifest.is_up_to_date(&output, &input, "Convert(mp4)", "ffmpeg 6.1")?);
+    Ok(())
+}
+
+#[test]
+fn test_batch_operation_fingerprint_is_stable() {
+    use ffhuman::commands::batch::operation_fingerprint;
+
+    // Recorded in manifests on disk, so this form must not change between releases
+    assert_eq!(
+        operation_fingerprint(&BatchOperation::Convert(ConvertFormat::Mp4)),
+        r#"{"format":"mp4","operation":"convert"}"#
+    );
+    assert_ne!(
+        operation_fingerprint(&BatchOperation::Convert(ConvertFormat::Mp4)),
+        operation_fingerprint(&BatchOperation::Convert(ConvertFormat::Webm))
+    );
+}
+
+#[test]
+fn test_batch_job_state_resume() -> Result<()> {
+    use ffhuman::util::job_state::{JobState, JobStatus, OutputSettings};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("state.json");
+    let files = vec![PathBuf::from("a.mov"), PathBuf::from("b.mov"), PathBuf::from("c.mov"), PathBuf::from("d.mov")];
+    let outputs = OutputSettings::new(None, Some(Path::new("renders")), Some("{stem}-final.{ext}"));
+    let mut state = JobState::new(&path, "*.mov", "convert to mp4".to_string(), None, outputs.clone(), &files);
+    state.jobs[0].status = JobStatus::Done;
+    state.jobs[1].status = JobStatus::Failed;
+    state.jobs[1].error = Some("Invalid data found when processing input".to_string());
+    state.jobs[2].status = JobStatus::Running;
+    state.save()?;
+
+    let loaded = JobState::load(&path)?;
+    assert_eq!(loaded.operation, "convert to mp4");
+    assert_eq!(loaded.jobs[1].error.as_deref(), Some("Invalid data found when processing input"));
+    assert_eq!(loaded.jobs_to_run(true, false), vec![2, 3]);
+    assert_eq!(loaded.jobs_to_run(false, true), vec![1]);
+    assert_eq!(loaded.jobs_to_run(true, true), vec![1, 2, 3]);
+    assert_eq!(loaded.count(JobStatus::Done), 1);
+    // Inputs and the output folder are stored absolute, so a resume from another folder finds them
+    assert_eq!(loaded.jobs[0].input, std::env::current_dir()?.join("a.mov"));
+    assert_eq!(loaded.outputs, outputs);
+    assert_eq!(loaded.outputs.output_dir, Some(std::env::current_dir()?.join("renders")));
+
+    // The stored operation round-trips back into a batch operation
+    assert_eq!(BatchOperation::parse(&loaded.operation)?, BatchOperation::Convert(ConvertFormat::Mp4));
+    let condition = ProcessingCondition::parse("duration < 30s")?;
+    assert_eq!(ProcessingCondition::parse(&condition.to_string())?, condition);
+    Ok(())
+}
+
+#[test]
+fn test_batch_resume_removes_partial_output() -> Result<()> {
+    use ffhuman::util::job_state::{JobState, JobStatus};
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("state.json");
+    let files = vec![temp_dir.path().join("a.mov"), temp_dir.path().join("b.mov")];
+    let partial = temp_dir.path().join("a_convert.mp4");
+    let existing = temp_dir.path().join("b_convert.mp4");
+    fs::write(&partial, b"half an encode")?;
+    fs::write(&existing, b"someone else's file")?;
+
+    // The process died while a.mov was running; b.mov failed on an output that was already there
+    let mut state = JobState::new(&path, "*.mov", "convert to mp4".to_string(), None, Default::default(), &files);
+    state.jobs[0].status = JobStatus::Running;
+    state.jobs[0].output = Some(partial.clone());
+    state.jobs[1].status = JobStatus::Failed;
+    state.save()?;
+
+    let mut state = JobState::load(&path)?;
+    assert_eq!(state.remove_partial_output(0)?, Some(partial.clone()));
+    assert!(!partial.exists());
+    assert_eq!(state.jobs[0].output, None);
+    // Outputs the job didn't create are left alone
+    assert_eq!(state.remove_partial_output(1)?, None);
+    assert!(existing.exists());
+    Ok(())
+}
+
+#[test]
+fn test_batch_manifest_records_auto_numbered_output() -> Result<()> {
+    use ffhuman::util::manifest::Manifest;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let input = temp_dir.path().join("clip.mp4");
+    std::fs::copy(VIDEO_ASSET, &input)?;
+    std::fs::write(temp_dir.path().join("clip_convert.mp4"), b"someone else's file")?;
+
+    let config = AppConfig::new(None, None, false, false, false)
+        .with_naming(None, Some(CollisionPolicy::AutoNumber));
+    App::new(config).execute(Intent::Batch {
+        pattern: input.to_string_lossy().to_string(),
+        operation: BatchOperation::Convert(ConvertFormat::Mp4),
+        options: BatchOptions::default(),
+    })?;
+
+    // The manifest names the file that was written, not the one that was taken
+    let manifest = Manifest::load(temp_dir.path())?;
+    assert!(manifest.contains(&temp_dir.path().join("clip_convert_1.mp4")));
+    assert!(!manifest.contains(&temp_dir.path().join("clip_convert.mp4")));
+    Ok(())
+}
+
+#[test]
+fn test_batch_resume_finds_default_state() -> Result<()> {
+    use ffhuman::util::job_state::{default_state_path, locate_state_file, JobState};
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let renders = temp_dir.path().join("renders");
+    let path = default_state_path(Some(&renders), "clips/*.mov", "convert to mp4", None);
+    JobState::new(&path, "clips/*.mov", "convert to mp4".to_string(), None, Default::default(), &[]).save()?;
+
+    // A plain --resume looks where the state was written: the output folder, or a folder given to it
+    assert_eq!(locate_state_file(None, Some(&renders))?, path);
+    assert_eq!(locate_state_file(Some(&renders), None)?, path);
+    assert!(locate_state_file(None, Some(temp_dir.path())).is_err());
+
+    let output_dir = renders.to_string_lossy().to_string();
+    let cli = Cli::try_parse_from(["ffhuman", "--output-dir", &output_dir, "batch", "--resume"])?;
+    match cli.into_intent()? {
+        Intent::Batch { pattern, options, .. } => {
+            assert_eq!(pattern, "clips/*.mov");
+            assert_eq!(options.state_file, Some(path));
+            assert!(options.resume);
+        }
+        other => panic!("expected a batch, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_batch_default_state_path() {
+    use ffhuman::util::job_state::default_state_path;
+    use std::path::Path;
+
+    let mov = default_state_path(None, "clips/*.mov", "convert to mp4", None);
+    assert_eq!(mov.parent(), Some(Path::new("clips")));
+    assert!(mov.file_name().unwrap().to_string_lossy().starts_with(".ffhuman-batch-"));
+    // Another batch over the same folder gets its own state file
+    assert_ne!(mov, default_state_path(None, "clips/*.mp4", "convert to mp4", None));
+    assert_ne!(mov, default_state_path(None, "clips/*.mov", "convert to webm", None));
+    // An output folder takes precedence
+    let out = default_state_path(Some(Path::new("renders")), "clips/*.mov", "convert to mp4", None);
+    assert_eq!(out.parent(), Some(Path::new("renders")));
+    assert_eq!(default_state_path(None, "*.mov", "convert to mp4", None).parent(), Some(Path::new("")));
+}
+
+#[test]
+fn test_watch_state_persists() -> Result<()> {
+    use ffhuman::util::job_state::JobStatus;
+    use ffhuman::util::watch_state::{WatchRecord, WatchState};
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let source = temp_dir.path().join("upload.mov");
+    let output = temp_dir.path().join("upload_convert.mp4");
+
+    let mut state = WatchState::load(temp_dir.path())?;
+    state.record(&source, WatchRecord {
+        size: 1024,
+        mtime: 1_700_000_000,
+        status: JobStatus::Done,
+        error: None,
+        output: Some(output.clone()),
+        intermediates: Vec::new(),
+        rule: None,
+        moved_to: Some(temp_dir.path().join("done/upload.mov")),
+    });
+    state.save()?;
+
+    // A restarted watcher remembers what it already handled
+    let state = WatchState::load(temp_dir.path())?;
+    assert!(state.is_processed(&source, 1024, 1_700_000_000));
+    assert!(!state.is_processed(&source, 2048, 1_700_000_100));
+    assert!(state.is_output(&output));
+    assert!(!state.is_output(&source));
+    Ok(())
+}
+
+#[test]
+fn test_watch_rules_first_match() -> Result<()> {
+    use ffhuman::util::watch_rules::WatchRules;
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let rules_file = temp_dir.path().join("rules.yaml");
+    fs::write(&rules_file, r#"
+extensions: [wav, mov, mp4]
+rules:
+  - name: podcasts
+    match: { extension: wav }
+    steps:
+      - type: normalize
+      - type: convert
+        format: mp3
+  - name: raw footage
+    match: { extension: [mov, mp4], name: "raw_*" }
+    pipeline: pipelines/raw.yaml
+  - steps:
+      - type: compress
+        target: medium-quality
+"#)?;
+
+    let rules = WatchRules::load(&rules_file)?;
+    assert!(rules.is_media_file(std::path::Path::new("in/episode.WAV")));
+    assert!(!rules.is_media_file(std::path::Path::new("in/episode.mp3")));
+
+    let (idx, rule) = rules.first_match(std::path::Path::new("in/episode.wav")).unwrap();
+    assert_eq!((idx, rule.name.as_deref()), (0, Some("podcasts")));
+    assert_eq!(rule.steps.len(), 2);
+
+    let (idx, rule) = rules.first_match(std::path::Path::new("in/raw_take1.mov")).unwrap();
+    assert_eq!(idx, 1);
+    assert_eq!(rules.pipeline_path(rule), Some(temp_dir.path().join("pipelines/raw.yaml")));
+
+    let (idx, rule) = rules.first_match(std::path::Path::new("in/final.mov")).unwrap();
+    assert_eq!((idx, rule.label(idx)), (2, "#3".to_string()));
+
+    // Rules need exactly one of steps or pipeline
+    fs::write(&rules_file, "rules:\n  - match: { extension: wav }\n")?;
+    assert!(WatchRules::load(&rules_file).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_workflow_spec_parses_every_step_type() -> Result<()> {
+    use ffhuman::model::workflow::{WorkflowMode, WorkflowSpec};
+
+    let spec = WorkflowSpec::parse(r#"
+input: raw/interview.mov
+mode: fan-out
+operations:
+  - type: trim
+    start: 5
+    end: "1:30"
+  - type: extract-audio
+    format: mp3
+    output: audio/interview.mp3
+  - type: compress
+    target: 25MB
+    two_pass: true
+"#, "template.yaml")?;
+
+    assert_eq!(spec.mode, Some(WorkflowMode::FanOut));
+    assert_eq!(spec.input, Some(PathBuf::from("raw/interview.mov")));
+    assert_eq!(spec.steps.len(), 3);
+    match &spec.steps[0].intent {
+        Intent::Trim { input, start, end } => {
+            assert!(input.as_os_str().is_empty());
+            assert_eq!(start.to_seconds(), 5);
+            assert_eq!(end.to_seconds(), 90);
+        }
+        other => panic!("Expected trim, got {:?}", other),
+    }
+    assert_eq!(spec.steps[1].output, Some(PathBuf::from("audio/interview.mp3")));
+    assert!(matches!(spec.steps[2].intent, Intent::Compress { two_pass: true, .. }));
+
+    // Step names are the `type` they were read from
+    let kinds: Vec<&str> = spec.steps.iter().map(|s| s.intent.kind()).collect();
+    assert_eq!(kinds, ["trim", "extract-audio", "compress"]);
+    let spec = WorkflowSpec::parse("steps:\n  - type: convert360\n  - type: social\n    platform: tiktok\n  - type: to-mono\n", "wf.yaml")?;
+    let kinds: Vec<&str> = spec.steps.iter().map(|s| s.intent.kind()).collect();
+    assert_eq!(kinds, ["convert360", "social-media-convert", "to-mono"]);
+    assert_eq!(spec.steps[1].name(), "social-media-convert");
+
+    // Errors point at the offending step, or at the field the error names
+    let err = WorkflowSpec::parse("steps:\n  - type: convert\n    format: mp4\n  - type: trim\n    start: 0\n    finish: 10\n", "bad.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("bad.yaml:6:13:"), "{}", err);
+    assert!(err.to_string().contains("steps[1]: unknown field `finish`"), "{}", err);
+    let err = WorkflowSpec::parse("steps:\n  - type: sharpen-edges\n", "bad.yaml").unwrap_err();
+    assert!(err.to_string().contains("sharpen-edges"), "{}", err);
+    let err = WorkflowSpec::parse("steps:\n  - type: resize\n    target: huge\n", "bad.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("bad.yaml:2:5:"), "{}", err);
+    let err = WorkflowSpec::parse("steps:\n  - type: trim\n    start: bogus\n    end: 5\n", "wf.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("wf.yaml:2:5: steps[0]: Invalid time format: bogus"), "{}", err);
+    let err = WorkflowSpec::parse("steps:\n  - type: trim\n    start: 0\n", "wf.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("wf.yaml:2:5:"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_workflow_vars_params_and_includes() -> Result<()> {
+    use ffhuman::model::vars::parse_assignments;
+    use ffhuman::model::workflow::WorkflowSpec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    fs::create_dir(temp_dir.path().join("lib"))?;
+    fs::write(temp_dir.path().join("lib/finish.yaml"), r#"
+params:
+  size: { type: string, default: 720p }
+steps:
+  - type: resize
+    target: ${size}
+  - type: compress
+    target: ${quality}
+"#)?;
+    let workflow = temp_dir.path().join("wf.yaml");
+    fs::write(&workflow, r#"
+params:
+  start: { type: time, default: 0 }
+  end: { type: time }
+  quality: { type: string, choices: [high-quality, medium-quality], default: high-quality }
+vars:
+  clip: ${start}-${end}
+steps:
+  - type: trim
+    start: ${start}
+    end: ${end}
+    output: out/${clip}.mp4
+  - type: include
+    file: lib/finish.yaml
+    vars: { size: 1080p }
+"#)?;
+
+    let spec = WorkflowSpec::load_with_vars(&workflow, &parse_assignments(&["end=90".to_string()])?)?;
+    assert_eq!(spec.steps.len(), 3);
+    assert_eq!(spec.steps[0].output, Some(PathBuf::from("out/0-90.mp4")));
+    assert!(matches!(spec.steps[1].intent, Intent::Resize { target: ResizeTarget::Preset(ResolutionPreset::P1080), .. }));
+    assert!(matches!(spec.steps[2].intent, Intent::Compress { target: CompressTarget::Quality(QualityPreset::High), .. }));
+
+    // Bad or missing values fail at load time
+    let load = |sets: &[&str]| {
+        let sets: Vec<String> = sets.iter().map(|s| s.to_string()).collect();
+        WorkflowSpec::load_with_vars(&workflow, &parse_assignments(&sets)?)
+    };
+    assert!(load(&[]).unwrap_err().to_string().contains("parameter 'end' is required"));
+    assert!(load(&["end=soon"]).is_err());
+    assert!(load(&["end=90", "quality=ultra"]).unwrap_err().to_string().contains("must be one of"));
+    assert!(load(&["end=90", "qualty=medium-quality"]).unwrap_err().to_string().contains("not used"));
+    assert!(parse_assignments(&["novalue".to_string()]).is_err());
+
+    let err = WorkflowSpec::parse("steps:\n  - type: trim\n    start: ${nope_not_defined}\n    end: 5\n", "wf.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("wf.yaml:3:12: undefined variable ${nope_not_defined}"), "{}", err);
+
+    // yes/no bools substitute as true/false, and a file default is found
+    // next to the workflow, not in the current folder
+    fs::write(temp_dir.path().join("lib/logo.png"), "png")?;
+    let promo = temp_dir.path().join("lib/promo.yaml");
+    fs::write(&promo, r#"
+params:
+  logo: { type: file, default: logo.png }
+  thorough: { type: bool, default: "no" }
+steps:
+  - type: watermark
+    logo: ${logo}
+    position: top-right
+  - type: compress
+    target: 25MB
+    two_pass: ${thorough}
+"#)?;
+    let spec = WorkflowSpec::load(&promo)?;
+    match &spec.steps[0].intent {
+        Intent::Watermark { logo, .. } => assert_eq!(logo, &temp_dir.path().join("lib/logo.png")),
+        other => panic!("Expected watermark, got {:?}", other),
+    }
+    assert!(matches!(spec.steps[1].intent, Intent::Compress { two_pass: false, .. }));
+    let spec = WorkflowSpec::load_with_vars(&promo, &parse_assignments(&["thorough=Yes".to_string()])?)?;
+    assert!(matches!(spec.steps[1].intent, Intent::Compress { two_pass: true, .. }));
+    Ok(())
+}
+
+#[test]
+fn test_workflow_values_are_not_read_as_yaml() -> Result<()> {
+    use ffhuman::model::vars::parse_assignments;
+    use ffhuman::model::workflow::WorkflowSpec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let workflow = temp_dir.path().join("wf.yaml");
+    fs::write(&workflow, r#"
+params:
+  title: { type: string }
+  at: { type: time, default: 5 }
+steps:
+  - type: add-text
+    text: ${title}
+    position: top
+  - type: trim
+    start: ${at}
+    end: 10
+    output: "out/${title}.mp4"
+"#)?;
+
+    let sets = parse_assignments(&["title=Part 1: Intro #2 - draft".to_string()])?;
+    let spec = WorkflowSpec::load_with_vars(&workflow, &sets)?;
+    match &spec.steps[0].intent {
+        Intent::AddText { text, .. } => assert_eq!(text, "Part 1: Intro #2 - draft"),
+        other => panic!("Expected add-text, got {:?}", other),
+    }
+    assert_eq!(spec.steps[1].output, Some(PathBuf::from("out/Part 1: Intro #2 - draft.mp4")));
+
+    // Newlines stay inside the value too
+    let sets = parse_assignments(&["title=line one\nsteps: []".to_string()])?;
+    let spec = WorkflowSpec::load_with_vars(&workflow, &sets)?;
+    assert_eq!(spec.steps.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_workflow_refuses_nested_cycles() -> Result<()> {
+    use ffhuman::model::workflow::WorkflowSpec;
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let a = temp_dir.path().join("a.yaml");
+    let b = temp_dir.path().join("b.yaml");
+    fs::write(&a, format!("steps:\n  - type: workflow\n    config_file: {}\n", a.display()))?;
+    let err = WorkflowSpec::load(&a).unwrap_err();
+    assert!(err.to_string().contains("already running"), "{}", err);
+
+    // Through another file as well
+    fs::write(&a, format!("steps:\n  - type: workflow\n    config_file: {}\n", b.display()))?;
+    fs::write(&b, format!("steps:\n  - type: workflow\n    config_file: {}\n", a.display()))?;
+    let err = format!("{:#}", WorkflowSpec::load(&a).unwrap_err());
+    assert!(err.contains("a.yaml -> ") && err.contains("b.yaml -> "), "{}", err);
+
+    // Running another workflow is fine
+    fs::write(&b, "steps:\n  - type: convert\n    format: mp4\n")?;
+    assert_eq!(WorkflowSpec::load(&a)?.steps.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_workflow_graph_from_step_references() -> Result<()> {
+    use ffhuman::model::workflow::{StepInput, WorkflowGraph, WorkflowMode, WorkflowSpec};
+    use std::collections::BTreeMap;
+
+    let spec = WorkflowSpec::parse(r#"
+steps:
+  - id: clip
+    type: trim
+    start: 0
+    end: 10
+  - id: gif
+    type: convert
+    input: ${steps.clip.output}
+    format: gif
+  - id: thumb
+    type: thumbnail
+    input: ${steps.clip.output}
+    time: 2
+  - type: montage
+    videos:
+      - ${steps.gif.output}
+      - ${steps.thumb.output}
+    layout: 2x1
+  - type: compress
+    target: medium-quality
+"#, "dag.yaml")?;
+
+    let graph = WorkflowGraph::build(&spec, WorkflowMode::Chain)?;
+    assert_eq!(graph.deps, vec![vec![], vec![0], vec![0], vec![1, 2], vec![3]]);
+    assert_eq!(graph.inputs[0], StepInput::Original);
+    assert_eq!(graph.inputs[1], StepInput::Own);
+    assert_eq!(graph.inputs[4], StepInput::Previous(3));
+    assert_eq!(graph.stages(), vec![0, 1, 1, 2, 3]);
+    assert_eq!(graph.sinks(), vec![4]);
+    assert!(graph.describe(&spec).contains("[4] stage 3: montage <- gif, thumb"));
+
+    // Fan-out steps without an input read the original instead of the previous step
+    let graph = WorkflowGraph::build(&spec, WorkflowMode::FanOut)?;
+    assert_eq!(graph.inputs[4], StepInput::Original);
+    assert_eq!(graph.sinks(), vec![3, 4]);
+
+    // References are filled in from finished steps
+    let outputs = BTreeMap::from([
+        ("gif".to_string(), vec![PathBuf::from("out/clip.gif")]),
+        ("thumb".to_string(), vec![PathBuf::from("out/clip.jpg")]),
+    ]);
+    match spec.steps[3].resolve(&outputs)?.intent {
+        Intent::Montage { videos, .. } => assert_eq!(videos, vec![PathBuf::from("out/clip.gif"), PathBuf::from("out/clip.jpg")]),
+        other => panic!("Expected montage, got {:?}", other),
+    }
+
+    let build = |yaml: &str| WorkflowSpec::parse(yaml, "bad.yaml").and_then(|s| WorkflowGraph::build(&s, WorkflowMode::Chain));
+    let err = build("steps:\n  - type: convert\n    input: ${steps.nope.output}\n    format: gif\n").unwrap_err();
+    assert!(err.to_string().contains("no step has id 'nope'"), "{}", err);
+    let err = build("steps:\n  - type: convert\n    input: ${steps.b.output}\n    format: gif\n  - id: b\n    type: convert\n    format: webm\n").unwrap_err();
+    assert!(err.to_string().contains("comes later"), "{}", err);
+    let err = build("steps:\n  - id: a\n    type: convert\n    format: gif\n  - id: a\n    type: convert\n    format: webm\n").unwrap_err();
+    assert!(err.to_string().contains("more than one step"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_step_conditions() -> Result<()> {
+    use ffhuman::model::condition::{MediaFacts, Property, StepCondition};
+    use std::fs;
+    use tempfile::TempDir;
+
+    let cond = StepCondition::parse("width > 1920 and (has_audio or not loudness in -17..-15)")?;
+    assert_eq!(cond.properties(), vec![Property::Width, Property::HasAudio, Property::Loudness]);
+    assert_eq!(cond.to_string(), "width > 1920 and (has_audio or not loudness in -17..-15)");
+    assert!(StepCondition::parse("duration >= 1:30").is_ok());
+    assert!(StepCondition::parse("video_codec in [h264, 'hevc']").is_ok());
+
+    assert!(StepCondition::parse("colour > 3").unwrap_err().to_string().contains("Unknown property"));
+    assert!(StepCondition::parse("width > wide").is_err());
+    assert!(StepCondition::parse("video_codec > h264").is_err());
+    assert!(StepCondition::parse("width > 1920 and").is_err());
+    assert!(StepCondition::parse("(width > 1920").is_err());
+
+    // File-level properties don't need ffprobe
+    let temp_dir = TempDir::new()?;
+    let file = temp_dir.path().join("Clip.MP4");
+    fs::write(&file, vec![0u8; 2048])?;
+    let check = |expr: &str| -> Result<bool> { Ok(StepCondition::parse(expr)?.evaluate(&mut MediaFacts::new(&file))) };
+    assert!(check("extension == mp4")?);
+    assert!(check("extension in [mov, mp4]")?);
+    assert!(!check("extension != mp4")?);
+    assert!(check("size > 1k and size < 1mb")?);
+    assert!(check("name == 'Clip.MP4' or size > 10mb")?);
+    assert!(!check("not size in 1000..3000")?);
+    Ok(())
+}
+
+#[test]
+fn test_parse_loudnorm_output() -> Result<()> {
+    use ffhuman::ffmpeg::probe::parse_loudnorm_output;
+
+    let log = r#"[Parsed_loudnorm_0 @ 0x55d5c8a4c880]
+{
+	"input_i" : "-23.54",
+	"input_tp" : "-4.10",
+	"input_lra" : "7.20",
+	"input_thresh" : "-34.01",
+	"output_i" : "-16.02",
+	"output_tp" : "-1.50",
+	"output_lra" : "6.10",
+	"output_thresh" : "-26.40",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}
+"#;
+    let loudness = parse_loudnorm_output(log)?;
+    assert_eq!(loudness.integrated, -23.54);
+    assert_eq!(loudness.true_peak, -4.10);
+    assert_eq!(loudness.range, 7.20);
+    assert_eq!(loudness.threshold, -34.01);
+    assert_eq!(loudness.offset, 0.02);
+    assert!(parse_loudnorm_output("no measurements here").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_channels() -> Result<()> {
+    use ffhuman::ffmpeg::probe::AudioEncoding;
+    use ffhuman::ffmpeg::recipes::{
+        combine_channels_steps, downmix_filter, mono_filter, remix_channels_steps, split_channels_steps, swap_filter,
+    };
+    use ffhuman::model::channels::{ChannelLayout, ChannelPick};
+
+    let surround = ChannelLayout::parse("5.1")?;
+    assert_eq!(surround.channels, ["FL", "FR", "FC", "LFE", "BL", "BR"]);
+    assert_eq!(ChannelLayout::parse("5.1(Side)")?.channels[4], "SL");
+    assert_eq!(ChannelLayout::parse("2.0")?, ChannelLayout::parse("stereo")?);
+    assert_eq!(ChannelLayout::for_count(8)?.name, "7.1");
+    assert!(ChannelLayout::for_count(12).is_err());
+    assert!(ChannelLayout::parse("9.1").is_err());
+
+    // Only a missing layout is guessed; an unfamiliar name is an error that names it
+    assert_eq!(ChannelLayout::reported(Some("5.1(side)"), Some(6))?.name, "5.1(side)");
+    assert_eq!(ChannelLayout::reported(Some("unknown"), Some(6))?.name, "5.1");
+    assert_eq!(ChannelLayout::reported(None, Some(2))?.name, "stereo");
+    let err = ChannelLayout::reported(Some("hexagonal"), Some(6)).unwrap_err();
+    assert!(err.to_string().contains("hexagonal"), "{err}");
+
+    // Centre and surrounds at -3 dB, no LFE
+    assert_eq!(downmix_filter(&surround), "pan=stereo|c0<c0+0.707*c2+0.707*c4|c1<c1+0.707*c2+0.707*c5");
+    let stereo = ChannelLayout::parse("stereo")?;
+    assert_eq!(mono_filter(&stereo, None)?, "pan=mono|c0<c0+c1");
+    assert_eq!(mono_filter(&surround, Some(ChannelPick::parse("right")?))?, "pan=mono|c0=c1");
+    assert!(mono_filter(&ChannelLayout::parse("mono")?, Some(ChannelPick::Left)).is_err());
+    assert_eq!(swap_filter(&stereo), "pan=stereo|c0=c1|c1=c0");
+    assert_eq!(
+        swap_filter(&ChannelLayout::parse("5.1(side)")?),
+        "pan=5.1(side)|c0=c1|c1=c0|c2=c2|c3=c3|c4=c5|c5=c4"
+    );
+
+    let outputs: Vec<PathBuf> = surround.channels.iter().map(|c| PathBuf::from(format!("movie_{c}.wav"))).collect();
+    let args = &split_channels_steps(&PathBuf::from("movie.mkv"), &surround, &outputs, true)[0].args;
+    assert!(args.contains(&"[0:a:0]channelsplit=channel_layout=5.1[FL][FR][FC][LFE][BL][BR]".to_string()));
+    assert!(args.windows(3).any(|w| w[0] == "-map" && w[1] == "[LFE]" && w[2] == "movie_LFE.wav"));
+
+    // A 24-bit wav stays 24-bit through the pan filter
+    let pcm24 = AudioEncoding { codec: "pcm_s24le".into(), sample_fmt: Some("s32".into()) };
+    let args = &remix_channels_steps(&PathBuf::from("in.wav"), &PathBuf::from("out.wav"), "pan=mono|c0=c0", Some(&pcm24), true)[0].args;
+    assert!(args.windows(2).any(|w| w[0] == "-c:a" && w[1] == "pcm_s24le"), "{args:?}");
+
+    let inputs = [PathBuf::from("L.wav"), PathBuf::from("R.wav")];
+    let args = &combine_channels_steps(&inputs, &stereo, &PathBuf::from("out.wav"), true)[0].args;
+    assert!(args.contains(&"[0:a][1:a]join=inputs=2:channel_layout=stereo:map=0.0-FL|1.0-FR[a]".to_string()));
+
+    let cli = Cli::try_parse_from(["ffhuman", "channels", "mono", AUDIO_ASSET, "--pick", "left"])?;
+    assert!(matches!(cli.into_intent()?, Intent::ToMono { pick: Some(ChannelPick::Left), .. }));
+    let cli = Cli::try_parse_from(["ffhuman", "channels", "combine", "a.wav", "b.wav", "c.wav", "--layout", "3.0"])?;
+    assert!(matches!(cli.into_intent()?, Intent::CombineChannels { layout: Some(layout), .. } if layout.count() == 3));
+    assert!(Cli::try_parse_from(["ffhuman", "channels", "combine", "a.wav"]).is_err());
+    let intent: Intent = serde_yaml::from_str("type: combine-channels\ninputs: [a.wav, b.wav]\nlayout: 2.1\n")?;
+    assert!(matches!(intent, Intent::CombineChannels { layout: Some(layout), .. } if layout.name == "2.1"));
+
+    let app = create_test_app_with_output("test_channels")?;
+    let inputs = vec![PathBuf::from(AUDIO_ASSET), PathBuf::from(AUDIO_ASSET)];
+    app.execute(Intent::CombineChannels { inputs: inputs.clone(), layout: None })?;
+    let mismatch = Intent::CombineChannels { inputs, layout: Some(surround) };
+    assert!(app.execute(mismatch).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_qc_audio() -> Result<()> {
+    use ffhuman::ffmpeg::recipes::qc_audio_filter;
+    use ffhuman::model::qc::{AudioQc, QcReport};
+
+    let log = "\
+[silencedetect @ 0x1] silence_start: 0
+[silencedetect @ 0x1] silence_end: 1.5 | silence_duration: 1.5
+[Parsed_ebur128_0 @ 0x2] t: 1.99998    TARGET:-23 LUFS    M: -25.1 S:-120.7     I: -25.1 LUFS       LRA:   0.0 LU  FTPK: -8.1 -8.3 dBFS  TPK: -8.1 -8.3 dBFS
+[Parsed_ebur128_0 @ 0x2] t: 12.5       TARGET:-23 LUFS    M: -20.2 S: -22.4     I: -23.0 LUFS       LRA:   3.1 LU  FTPK: -2.1 -2.2 dBFS  TPK: -1.9 -2.0 dBFS
+[Parsed_ebur128_0 @ 0x2] t: 14.0       TARGET:-23 LUFS    M: -21.0 S: -21.9     I: -23.0 LUFS       LRA:   3.2 LU  FTPK: -3.0 -3.1 dBFS  TPK: -1.9 -2.0 dBFS
+[Parsed_ametadata_4 @ 0x3] frame:0    pts:0       pts_time:0
+[Parsed_ametadata_4 @ 0x3] lavfi.aphasemeter.phase=0.900000
+[Parsed_ametadata_4 @ 0x3] lavfi.aphasemeter.phase=0.500000
+[silencedetect @ 0x1] silence_start: 28.6
+[silencedetect @ 0x1] silence_end: 30 | silence_duration: 1.4
+[Parsed_astats_1 @ 0x4] Channel: 1
+[Parsed_astats_1 @ 0x4] DC offset: -0.000200
+[Parsed_astats_1 @ 0x4] Peak level dB: -2.000000
+[Parsed_astats_1 @ 0x4] Peak count: 3
+[Parsed_astats_1 @ 0x4] Channel: 2
+[Parsed_astats_1 @ 0x4] DC offset: 0.000300
+[Parsed_astats_1 @ 0x4] Peak level dB: 0.000000
+[Parsed_astats_1 @ 0x4] Peak count: 4
+[Parsed_astats_1 @ 0x4] Overall
+[Parsed_astats_1 @ 0x4] DC offset: 0.000050
+[Parsed_astats_1 @ 0x4] Peak count: 3.500000
+[Parsed_ebur128_0 @ 0x2] Summary:
+
+  Integrated loudness:
+    I:         -23.0 LUFS
+    Threshold: -33.4 LUFS
+
+  Loudness range:
+    LRA:         3.2 LU
+    Threshold:  -43.3 LUFS
+    LRA low:    -24.6 LUFS
+    LRA high:   -21.4 LUFS
+
+  True peak:
+    Peak:       -1.9 dBFS
+";
+    let qc = AudioQc::parse(log, 30.0)?;
+    assert_eq!((qc.integrated, qc.range, qc.true_peak), (-23.0, 3.2, -1.9));
+    assert_eq!(qc.momentary_max.map(|p| (p.time, p.lufs)), Some((12.5, -20.2)));
+    assert_eq!(qc.short_term_max.map(|p| (p.time, p.lufs)), Some((14.0, -21.9)));
+    assert_eq!(qc.timeline.len(), 2);
+    assert_eq!(qc.timeline[1].start, 10.0);
+    assert_eq!(qc.clipped_samples, 4, "only the channel peaking at full scale counts");
+    assert_eq!(qc.dc_offset, 0.0003);
+    assert_eq!(qc.phase_correlation.map(|p| (p.mean, p.min)), Some((0.7, 0.5)));
+    assert_eq!((qc.head_silence, qc.tail_silence), (1.5, 30.0 - 28.6));
+    assert!(AudioQc::parse("no summary", 30.0).is_err());
+
+    // Loudness and peak pass EBU R128, but the clipped samples and long silences fail it
+    let report = QcReport::new(&PathBuf::from("master.wav"), &LoudnessTarget::Broadcast, qc.clone(), Some(1.0));
+    let verdicts: Vec<_> = report.checks.iter().map(|c| (c.name, c.pass)).collect();
+    assert_eq!(verdicts, [
+        ("integrated loudness", true), ("true peak", true), ("clipping", false), ("DC offset", true),
+        ("phase correlation", true), ("head silence", false), ("tail silence", false),
+    ]);
+    assert!(!report.pass);
+    assert!(report.checks[2].to_string().starts_with("FAIL  clipping"));
+    let report = QcReport::new(&PathBuf::from("master.wav"), &LoudnessTarget::Atsc, qc, None);
+    assert_eq!(report.checks.len(), 5);
+    assert!(report.checks[0].pass, "ATSC A/85 allows ±2 LU");
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(json["spec"], "atsc-a85");
+    assert_eq!(json["measurements"]["clipped_samples"], 4);
+
+    assert!(qc_audio_filter(true).contains("aphasemeter"));
+    assert!(!qc_audio_filter(false).contains("aphasemeter"));
+    let cli = Cli::try_parse_from(["ffhuman", "qc-audio", AUDIO_ASSET, "--spec", "-18lufs", "--max-silence", "2s"])?;
+    assert!(matches!(cli.into_intent()?, Intent::QcAudio { spec: LoudnessTarget::Custom(_), max_silence: Some(_), .. }));
+
+    let app = create_test_app_with_output("test_qc_audio")?;
+    app.execute(Intent::QcAudio { input: PathBuf::from(AUDIO_ASSET), spec: LoudnessTarget::Broadcast, max_silence: None })?;
+    Ok(())
+}
+
+#[test]
+fn test_loudness_targets() -> Result<()> {
+    use ffhuman::ffmpeg::probe::Loudness;
+    use ffhuman::ffmpeg::probe::AudioEncoding;
+    use ffhuman::ffmpeg::recipes::{keep_audio_args, loudnorm_filter, normalize_steps};
+
+    assert_eq!(LoudnessTarget::parse("podcast")?.integrated(), -16.0);
+    assert_eq!(LoudnessTarget::parse("Streaming")?.integrated(), -14.0);
+    assert_eq!(LoudnessTarget::parse("ebu")?, LoudnessTarget::Broadcast);
+    assert_eq!(LoudnessTarget::parse("atsc-a85")?.true_peak(), -2.0);
+    assert_eq!(LoudnessTarget::parse("-18 LUFS")?, LoudnessTarget::Custom(-18.0));
+    for target in ["podcast", "streaming", "ebu-r128", "atsc-a85", "-19.5lufs"] {
+        assert_eq!(LoudnessTarget::parse(target)?.to_string(), target);
+    }
+    assert!(LoudnessTarget::parse("loud").is_err());
+    assert!(LoudnessTarget::lufs(3.0).is_err());
+
+    // The second pass feeds the first pass' measurements back, as one linear gain
+    let target = LoudnessTarget::Podcast;
+    assert_eq!(loudnorm_filter(&target, None), "loudnorm=I=-16:TP=-1.5:LRA=11:print_format=json");
+    let measured = Loudness { integrated: -23.54, true_peak: -4.1, range: 14.2, threshold: -34.01, offset: 0.02 };
+    let filter = loudnorm_filter(&target, Some(&measured));
+    assert!(filter.contains("measured_I=-23.54:measured_TP=-4.1:measured_LRA=14.2:measured_thresh=-34.01:offset=0.02"), "{filter}");
+    assert!(filter.contains("linear=true"));
+    assert!(filter.contains("LRA=14.2"), "the target range widens to keep linear mode: {filter}");
+    assert_eq!(measured.to_string(), "-23.5 LUFS, true peak -4.1 dBTP, range 14.2 LU");
+
+    let aac = ["-c:a".to_string(), "aac".to_string()];
+    let steps = normalize_steps(&PathBuf::from(AUDIO_ASSET), &PathBuf::from("out.mp4"), &target, Some(&measured), Some(44100), &aac, true);
+    let args = &steps[0].args;
+    assert!(args.windows(2).any(|w| w[0] == "-ar" && w[1] == "44100"));
+
+    // Audio files keep their container, lossless ones their codec and bit depth
+    let encoding = |codec: &str, sample_fmt: &str| AudioEncoding { codec: codec.into(), sample_fmt: Some(sample_fmt.into()) };
+    assert_eq!(keep_audio_args("wav", Some(&encoding("pcm_s24le", "s32"))), ["-c:a", "pcm_s24le"]);
+    assert_eq!(keep_audio_args("aiff", Some(&encoding("pcm_s16be", "s16"))), ["-c:a", "pcm_s16be"]);
+    assert_eq!(keep_audio_args("wav", None), ["-c:a", "pcm_s16le"]);
+    assert_eq!(keep_audio_args("flac", Some(&encoding("flac", "s32"))), ["-c:a", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24"]);
+    assert_eq!(keep_audio_args("mp3", None)[..2], ["-c:a", "libmp3lame"]);
+    assert!(keep_audio_args("mp4", None).is_empty());
+    let dir = tempfile::TempDir::new()?;
+    let wav = dir.path().join("voice.wav");
+    std::fs::write(&wav, b"audio")?;
+    let config = AppConfig::new(None, None, false, true, false);
+    App::new(config.clone()).execute(Intent::Normalize { input: wav, target: LoudnessTarget::Podcast })?;
+    assert_eq!(config.outputs.since(0), vec![dir.path().join("voice_normalized.wav")]);
+
+    let cli = Cli::try_parse_from(["ffhuman", "normalize", VIDEO_ASSET, "--lufs", "-18"])?;
+    assert!(matches!(cli.into_intent()?, Intent::Normalize { target: LoudnessTarget::Custom(lufs), .. } if lufs == -18.0));
+    let cli = Cli::try_parse_from(["ffhuman", "normalize", VIDEO_ASSET, "--target", "streaming"])?;
+    assert!(matches!(cli.into_intent()?, Intent::Normalize { target: LoudnessTarget::Streaming, .. }));
+    assert!(Cli::try_parse_from(["ffhuman", "normalize", VIDEO_ASSET, "--target", "podcast", "--lufs", "-18"]).is_err());
+
+    let intent: Intent = serde_yaml::from_str("type: normalize\ntarget: ebu-r128\n")?;
+    assert!(matches!(intent, Intent::Normalize { target: LoudnessTarget::Broadcast, .. }));
+
+    let app = create_test_app_with_output("test_loudness_targets")?;
+    app.execute(Intent::Normalize { input: PathBuf::from(AUDIO_ASSET), target: LoudnessTarget::Atsc })?;
+    Ok(())
+}
+
+#[test]
+fn test_workflow_foreach_and_when() -> Result<()> {
+    use ffhuman::model::workflow::{StepInput, WorkflowGraph, WorkflowMode, WorkflowSpec};
+
+    let spec = WorkflowSpec::parse(r#"
+steps:
+  - type: resize
+    target: 1080p
+    when: width > 1920
+  - id: thumbs
+    foreach: [1, 5, "0:10"]
+    type: thumbnail
+    time: ${item}
+    output: thumbs/at-${item}.jpg
+  - type: montage
+    videos:
+      - ${steps.thumbs.outputs}
+    layout: 3x1
+"#, "wf.yaml")?;
+
+    assert_eq!(spec.steps.len(), 5);
+    assert!(spec.steps[0].when.is_some());
+    assert_eq!(spec.steps[2].name(), "thumbs [5]");
+    assert_eq!(spec.steps[3].output, Some(PathBuf::from("thumbs/at-0:10.jpg")));
+    assert!(matches!(&spec.steps[3].intent, Intent::Thumbnail { time, .. } if time.to_seconds() == 10));
+
+    // Every copy reads the resized video; the montage waits for all of them
+    let graph = WorkflowGraph::build(&spec, WorkflowMode::Chain)?;
+    assert_eq!(graph.inputs[1..4], [StepInput::Previous(0), StepInput::Previous(0), StepInput::Previous(0)]);
+    assert_eq!(graph.deps[4], vec![1, 2, 3]);
+    assert_eq!(graph.stages(), vec![0, 1, 1, 1, 2]);
+
+    let outputs = std::collections::BTreeMap::from([
+        ("thumbs".to_string(), vec![PathBuf::from("a.jpg"), PathBuf::from("b.jpg")]),
+    ]);
+    match spec.steps[4].resolve(&outputs)?.intent {
+        Intent::Montage { videos, .. } => assert_eq!(videos, vec![PathBuf::from("a.jpg"), PathBuf::from("b.jpg")]),
+        other => panic!("Expected montage, got {:?}", other),
+    }
+
+    assert!(WorkflowSpec::parse("steps:\n  - type: convert\n    format: gif\n    when: fps >\n", "wf.yaml").is_err());
+    let err = WorkflowSpec::parse("steps:\n  - type: convert\n    input: ${item}\n    format: gif\n", "wf.yaml").unwrap_err();
+    assert!(err.to_string().contains("only available in foreach steps"), "{}", err);
+    let err = WorkflowSpec::parse("steps:\n  - foreach: 'no-such-dir/*.mp4'\n    type: convert\n    input: ${item}\n    format: gif\n", "wf.yaml").unwrap_err();
+    assert!(err.to_string().contains("matches no files"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_shell_command_args_and_pipeline_steps() -> Result<()> {
+    use ffhuman::commands::shell::{command_args, pipeline_step, split_words};
+    use std::path::Path;
+
+    assert_eq!(split_words(r#"add-text "Hello world" at top"#)?, vec!["add-text", "Hello world", "at", "top"]);
+    assert!(split_words("add-text 'unclosed").is_err());
+
+    // The current file is the input; left-out keywords are filled in
+    let current = Path::new("clip.mp4");
+    assert_eq!(command_args("trim 1:00 to 3:00", Some(current))?, vec!["trim", "clip.mp4", "from", "1:00", "to", "3:00"]);
+    assert_eq!(
+        command_args("watermark logo.png bottom-right --opacity 0.8", Some(current))?,
+        vec!["watermark", "clip.mp4", "logo.png", "at", "bottom-right", "--opacity", "0.8"]
+    );
+    assert_eq!(command_args("normalize", Some(current))?, vec!["normalize", "clip.mp4"]);
+    assert!(command_args("normalize", None).is_err());
+    assert!(command_args("frobnicate", Some(current)).is_err());
+
+    let args = command_args("watermark logo.png bottom-right --opacity 0.8", Some(current))?;
+    let step = pipeline_step(&args)?;
+    let yaml = serde_yaml::to_string(&step)?;
+    assert!(yaml.contains("type: watermark"), "{}", yaml);
+    assert!(yaml.contains("opacity: 0.8"), "{}", yaml);
+    assert!(!yaml.contains("clip.mp4"), "{}", yaml);
+
+    // Text that looks like a number stays text
+    let args = command_args("add-text 2024 at top", Some(current))?;
+    let step = pipeline_step(&args)?;
+    assert_eq!(step.get("text"), Some(&serde_yaml::Value::String("2024".into())));
+
+    // Arguments the step names or scales differently, and defaults the
+    // step requires, are written the way pipeline files take them
+    let saved = |line: &str| -> Result<String> {
+        let step = pipeline_step(&command_args(line, Some(current))?)?;
+        let mut doc = serde_yaml::Mapping::new();
+        doc.insert("steps".into(), serde_yaml::Value::Sequence(vec![serde_yaml::Value::Mapping(step)]));
+        let yaml = serde_yaml::to_string(&doc)?;
+        ffhuman::model::workflow::WorkflowSpec::parse(&yaml, "saved.yaml")?;
+        Ok(yaml)
+    };
+    let yaml = saved("crop 640x480")?;
+    assert!(yaml.contains("width: 640") && yaml.contains("height: 480"), "{}", yaml);
+    let yaml = saved("filter --brightness 10 --contrast=-20")?;
+    assert!(yaml.contains("brightness: 0.1") && yaml.contains("contrast: -0.2"), "{}", yaml);
+    let yaml = saved("extract-audio")?;
+    assert!(yaml.contains("type: extract-audio") && yaml.contains("format: mp3"), "{}", yaml);
+    let yaml = saved("fps 30")?;
+    assert!(yaml.contains("type: set-fps"), "{}", yaml);
+    let step = pipeline_step(&["subtitle", "shift", "movie.srt", "by", "-2s"].map(String::from))?;
+    assert_eq!(step.get("type"), Some(&serde_yaml::Value::String("shift-subtitles".into())));
+    Ok(())
+}
+
+#[test]
+fn test_sentence_parser() -> Result<()> {
+    use ffhuman::model::sentence::Sentence;
+
+    let sentence = Sentence::parse("make video.mp4 a 480p gif without sound, 10 seconds from 1:00")?;
+    assert_eq!(sentence.input, PathBuf::from("video.mp4"));
+    assert_eq!(sentence.summaries, vec!["trim from 1:00 to 1:10", "resize to 480p", "convert to gif"]);
+    assert!(matches!(&sentence.steps[0], Intent::Trim { start, end, .. } if start.to_seconds() == 60 && end.to_seconds() == 70));
+    assert!(matches!(&sentence.steps[1], Intent::Resize { target: ResizeTarget::Height(480), .. }));
+    assert!(!sentence.is_ambiguous(), "{}", sentence.describe());
+
+    let sentence = Sentence::parse("take clip.mov between 0:05 and 0:20, twice as fast, normalize the audio, save as out.webm")?;
+    assert_eq!(sentence.summaries, vec!["trim from 5 to 20", "speed-up by 2x", "normalize", "convert to webm"]);
+    assert_eq!(sentence.output, Some(PathBuf::from("out.webm")));
+
+    let sentence = Sentence::parse("shrink video.mp4 under 10mb")?;
+    assert!(matches!(&sentence.steps[..], [Intent::Compress { target: CompressTarget::Size(_), .. }]));
+    assert!(!sentence.is_ambiguous(), "{}", sentence.describe());
+
+    // Guesses and unknown words are reported so the caller can confirm
+    let sentence = Sentence::parse("make video.mp4 louder and sparkly")?;
+    assert!(sentence.is_ambiguous());
+    assert_eq!(sentence.ignored, vec!["sparkly"]);
+    assert!(matches!(&sentence.steps[0], Intent::AdjustVolume { adjustment: VolumeAdjustment::Decibels(db), .. } if *db == 6.0));
+
+    assert!(Sentence::parse("make a gif without sound").is_err());
+    assert!(Sentence::parse("make video.mp4 sparkly").is_err());
+    assert!(Sentence::parse("video.mp4 from 1:00").is_err());
+    assert!(Sentence::parse("video.mp4 without sound, audio only").is_err());
+    assert!(Sentence::parse("make video.mp4 a gif as out.webm").is_err());
+
+    // The basic verbs: "trim" takes the range, "90 degrees" is one argument
+    let sentence = Sentence::parse("trim video.mp4 from 1:00 to 2:00")?;
+    assert_eq!(sentence.summaries, vec!["trim from 1:00 to 2:00"]);
+    assert!(!sentence.is_ambiguous(), "{}", sentence.describe());
+    let sentence = Sentence::parse("rotate video.mp4 90 degrees")?;
+    assert!(matches!(&sentence.steps[..], [Intent::Rotate { degrees, .. }] if degrees.0 == 90));
+    assert!(!sentence.is_ambiguous(), "{}", sentence.describe());
+    let sentence = Sentence::parse("rotate video.mp4 by 180")?;
+    assert!(matches!(&sentence.steps[..], [Intent::Rotate { degrees, .. }] if degrees.0 == 180));
+    assert!(Sentence::parse("trim video.mp4").is_err());
+
+    // "cut" trims to a range, but cutting the first part keeps the rest
+    let sentence = Sentence::parse("cut video.mp4 from 0:10 to 0:30")?;
+    assert_eq!(sentence.summaries, vec!["trim from 10 to 30"]);
+    assert!(!sentence.is_ambiguous(), "{}", sentence.describe());
+    let err = Sentence::parse("cut the first 5 seconds of video.mp4").unwrap_err();
+    assert!(err.to_string().contains("from 5 to"), "{err}");
+    assert!(Sentence::parse("remove the first 5 seconds of video.mp4").is_err());
+    let sentence = Sentence::parse("the first 5 seconds of video.mp4")?;
+    assert_eq!(sentence.summaries, vec!["trim from 0 to 5"]);
+    Ok(())
+}
+
+#[test]
+fn test_sentence_fallback_keeps_subcommand_suggestions() -> Result<()> {
+    // A typo of a command gets clap's suggestion instead of being read as a sentence
+    let err = Cli::try_parse_from(["ffhuman", "trimm", "video.mp4", "from", "1:00", "to", "2:00"]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::InvalidSubcommand);
+    assert!(err.to_string().contains("'trim'"), "{}", err);
+
+    for args in [
+        vec!["ffhuman", "make video.mp4 a gif"],
+        vec!["ffhuman", "make", "video.mp4", "a", "gif"],
+        vec!["ffhuman", "video.mp4", "without", "sound"],
+        vec!["ffhuman", "shrink", "video.mp4", "under", "10mb"],
+    ] {
+        let intent = Cli::try_parse_from(&args)?.into_intent()?;
+        assert!(matches!(intent, Intent::Sentence { .. }), "{:?}", args);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_history_journal_and_undo() -> Result<()> {
+    use ffhuman::ffmpeg::step::Step;
+    use ffhuman::util::history::*;
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    let history = History::open(dir.path().join("ffhuman").join(HISTORY_FILE));
+    assert!(history.entries()?.is_empty());
+    assert_eq!(history.next_id()?, 1);
+
+    let input = dir.path().join("in.mp4");
+    let kept = dir.path().join("kept.mp4");
+    let removed = dir.path().join("removed.mp4");
+    std::fs::write(&input, b"input")?;
+    std::fs::write(&kept, b"output")?;
+    std::fs::write(&removed, b"output")?;
+
+    let step = Step::new("ffmpeg", vec!["-i".into(), "in.mp4".into(), "-an".into(), "out.mp4".into()]);
+    assert_eq!(step_inputs(&step), vec![PathBuf::from("in.mp4")]);
+
+    let entry = HistoryEntry {
+        id: history.next_id()?,
+        started_at: 0,
+        cwd: dir.path().to_path_buf(),
+        args: vec!["mute".into(), "in.mp4".into()],
+        operation: "mute".into(),
+        inputs: vec![FileRecord::of(&input, false)],
+        outputs: vec![FileRecord::of(&kept, false), FileRecord::of(&removed, false)],
+        steps: vec![step],
+        duration_secs: 0.5,
+        status: RunStatus::Ok,
+        error: None,
+    };
+    history.append(&entry)?;
+    // A torn line doesn't hide the rest of the journal
+    std::fs::OpenOptions::new().append(true).open(history.path())
+        .and_then(|mut f| std::io::Write::write_all(&mut f, b"{\"id\": 7, \"cut\n"))?;
+    assert_eq!(history.get(1)?, entry);
+    assert_eq!(history.next_id()?, 2);
+    assert!(history.get(2).is_err());
+
+    // Only files that still match the recorded hash are removed
+    std::fs::write(&kept, b"edited since")?;
+    assert!(remove_if_unchanged(&entry.outputs[1], true)?.starts_with("would remove"));
+    assert!(removed.exists());
+    assert!(remove_if_unchanged(&entry.outputs[0], false)?.starts_with("kept"));
+    assert!(remove_if_unchanged(&entry.outputs[1], false)?.starts_with("removed"));
+    assert!(kept.exists() && !removed.exists());
+
+    // Files the run overwrote are never removed
+    let replaced = FileRecord::of(&input, true);
+    assert!(remove_if_unchanged(&replaced, false)?.starts_with("kept"));
+    assert!(input.exists());
+    Ok(())
+}
+
+#[test]
+fn test_preflight_reports_all_problems() -> Result<()> {
+    use ffhuman::model::condition::{MediaFacts, Probed};
+    use ffhuman::model::preflight::{Preflight, Severity};
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    let file = |name: &str| -> Result<PathBuf> {
+        let path = dir.path().join(name);
+        std::fs::write(&path, b"media")?;
+        Ok(path)
+    };
+    let (hd, small, silent) = (file("hd.mp4")?, file("small.mp4")?, file("silent.mp4")?);
+    let facts = |path: &PathBuf, size: (f64, f64), audio: &str| MediaFacts::known(path, Probed {
+        width: Some(size.0),
+        height: Some(size.1),
+        fps: Some(30.0),
+        duration: Some(90.0),
+        video_codec: Some("h264".into()),
+        audio_codec: Some(audio.into()),
+    });
+    let check = |intent: Intent| Preflight::with_facts(vec![
+        facts(&hd, (1920.0, 1080.0), "aac"),
+        facts(&small, (1280.0, 720.0), "aac"),
+        facts(&silent, (1280.0, 720.0), "none"),
+    ]).check(&AppConfig::new(None, None, false, true, false), &intent);
+
+    assert!(check(Intent::Crop { input: small.clone(), width: 1280, height: 720 }).is_empty());
+    let problems = check(Intent::Crop { input: small.clone(), width: 1920, height: 1080 });
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].message.contains("larger than the 1280x720 frame"), "{}", problems[0]);
+
+    let problems = check(Intent::Merge { a: hd.clone(), b: small.clone() });
+    assert!(problems[0].suggestion.as_deref().unwrap_or("").contains("to 1920x1080"), "{}", problems[0]);
+
+    let problems = check(Intent::AddAudio { audio: silent.clone(), video: small.clone() });
+    assert!(problems[0].message.contains("no audio stream"), "{}", problems[0]);
+
+    // Every problem is reported, with a suggestion for near-miss names
+    let problems = check(Intent::Watermark {
+        input: dir.path().join("hd.mp5"),
+        logo: dir.path().join("logo.png"),
+        position: WatermarkPosition::parse("top-right")?,
+        opacity: Opacity::default(),
+        size: None,
+    });
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0].suggestion.as_deref(), Some(format!("did you mean {}?", hd.display()).as_str()));
+    assert!(problems[1].message.starts_with("Input not found") && problems[1].suggestion.is_none());
+
+    // Ranges: past the end is an error, ending late only a warning
+    let range = |start: &str, end: &str| -> Result<_> {
+        Ok(check(Intent::Trim { input: hd.clone(), start: Time::parse(start)?, end: Time::parse(end)? }))
+    };
+    assert!(range("0:10", "1:00")?.is_empty());
+    assert_eq!(range("2:00", "3:00")?[0].severity, Severity::Error);
+    assert_eq!(range("1:00", "3:00")?[0].severity, Severity::Warning);
+    assert_eq!(range("1:00", "0:30")?[0].severity, Severity::Error);
+    Ok(())
+}
+
+#[test]
+fn test_image_sequence() -> Result<()> {
+    use ffhuman::model::sequence::{frame_pattern, FrameFormat, ImageSequence};
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    for n in [1, 2, 5, 6, 9, 10] {
+        std::fs::write(dir.path().join(format!("IMG_{n}.png")), b"frame")?;
+    }
+    std::fs::write(dir.path().join("notes.txt"), b"not a frame")?;
+
+    // Natural order, with the gaps reported
+    let sequence = ImageSequence::find(&[dir.path().to_path_buf()])?;
+    let names: Vec<_> = sequence.frames.iter()
+        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, ["IMG_1.png", "IMG_2.png", "IMG_5.png", "IMG_6.png", "IMG_9.png", "IMG_10.png"]);
+    assert_eq!(sequence.gaps(), [(3, 4), (7, 8)]);
+    assert_eq!(sequence.missing(), 4);
+    assert_eq!(sequence.describe_gaps(), "3-4, 7-8");
+    assert_eq!(sequence.name(), "IMG");
+    assert!(sequence.image2_input().is_none());
+
+    // Zero-padded frames without gaps are read with a pattern
+    let padded = TempDir::new()?;
+    for n in 7..=9 {
+        std::fs::write(padded.path().join(format!("frame_{n:04}.png")), b"frame")?;
+    }
+    let sequence = ImageSequence::find(&[padded.path().join("frame_%04d.png")])?;
+    assert_eq!(sequence.image2_input(), Some((padded.path().join("frame_%04d.png"), 7)));
+    let globbed = ImageSequence::find(&[padded.path().join("*.png")])?;
+    assert_eq!(globbed.frames, sequence.frames);
+    assert!(ImageSequence::find(&[padded.path().join("frame_0007.png")]).is_err());
+
+    assert_eq!(frame_pattern(None, "clip", FrameFormat::Png)?, "clip_%06d.png");
+    assert_eq!(frame_pattern(Some("shot_%04d"), "clip", FrameFormat::Exr)?, "shot_%04d.exr");
+    assert_eq!(frame_pattern(Some("shot_%04d.tif"), "clip", FrameFormat::parse("tiff")?)?, "shot_%04d.tif");
+    assert!(frame_pattern(Some("out/%04d"), "clip", FrameFormat::Png).is_err());
+    assert!(frame_pattern(Some("%d_%d"), "clip", FrameFormat::Png).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sequence_with_gaps_leaves_no_frame_list() -> Result<()> {
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    for n in [1, 2, 5] {
+        std::fs::write(dir.path().join(format!("IMG_{n}.png")), b"frame")?;
+    }
+    let app = App::new(AppConfig::new(None, Some(dir.path().to_path_buf()), false, true, false));
+    app.execute(Intent::SequenceToVideo { images: vec![dir.path().to_path_buf()], fps: 24, codec: None, quality: None })?;
+    // The list goes to a temporary file, not next to the output
+    let names: Vec<_> = std::fs::read_dir(dir.path())?
+        .map(|e| e.map(|e| e.file_name().to_string_lossy().to_string()))
+        .collect::<std::io::Result<_>>()?;
+    assert!(names.iter().all(|n| n.ends_with(".png")), "{:?}", names);
+    Ok(())
+}
+
+#[test]
+fn test_video_to_sequence() -> Result<()> {
+    use ffhuman::model::sequence::FrameFormat;
+
+    let app = create_test_app_with_output("test_video_to_sequence")?;
+    let intent = Intent::VideoToSequence {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: FrameFormat::parse("exr")?,
+        pattern: Some("shot_%04d".to_string()),
+        start: Some(1001),
+    };
+    app.execute(intent)?;
+    Ok(())
+}
+
+#[test]
+fn test_subtitle_formats() -> Result<()> {
+    use ffhuman::model::subtitle::{Language, SubtitleContainer, SubtitleFormat, Subtitles};
+
+    let srt = "\u{feff}1\r\n00:00:01,500 --> 00:00:03,000\r\n<i>Hello</i> & welcome\r\nSecond line\r\n\r\n2\r\n00:01:02,005 --> 00:01:04,000\r\nBye\r\n";
+    let subtitles = Subtitles::parse(srt, SubtitleFormat::Srt)?;
+    assert_eq!(subtitles.cues.len(), 2);
+    assert_eq!((subtitles.cues[0].start, subtitles.cues[0].end), (1500, 3000));
+    assert_eq!(subtitles.cues[0].text, "<i>Hello</i> & welcome\nSecond line");
+    assert_eq!(subtitles.end(), 64000);
+
+    let vtt = subtitles.render(SubtitleFormat::Vtt);
+    assert!(vtt.starts_with("WEBVTT\n\n00:00:01.500 --> 00:00:03.000\n<i>Hello</i> &amp; welcome\nSecond line\n"), "{vtt}");
+    assert_eq!(Subtitles::parse(&vtt, SubtitleFormat::Vtt)?, subtitles);
+
+    let ass = subtitles.render(SubtitleFormat::Ass);
+    assert!(ass.contains("Dialogue: 0,0:00:01.50,0:00:03.00,Default,,0,0,0,,{\\i1}Hello{\\i0} & welcome\\NSecond line"), "{ass}");
+    let back = Subtitles::parse(&ass, SubtitleFormat::Ass)?;
+    assert_eq!(back.cues[1].start, 62010, "ASS keeps centiseconds");
+    assert_eq!(back.render(SubtitleFormat::Srt).lines().nth(2), Some("<i>Hello</i> & welcome"));
+
+    // WebVTT notes, settings and voices; ASS with its own field order and styling
+    let vtt = "WEBVTT - captions\n\nNOTE checked\n\nintro\n00:05.000 --> 00:06.000 align:start\n<v Ann>Hi &lt;3</v>\n";
+    assert_eq!(Subtitles::parse(vtt, SubtitleFormat::Vtt)?.cues[0].text, "Hi <3");
+    let ass = "[Events]\nFormat: Start, End, Text\nDialogue: 0:00:02.00,0:00:03.00,{\\an8\\b1}Top{\\b0}, left\nDialogue: 0:00:01.00,0:00:02.00,First\n";
+    let cues = Subtitles::parse(ass, SubtitleFormat::Ass)?.cues;
+    assert_eq!((cues[0].text.as_str(), cues[1].text.as_str()), ("First", "<b>Top</b>, left"));
+
+    assert!(Subtitles::parse("1\n00:00:01,000 --> soon\nHi\n", SubtitleFormat::Srt).is_err());
+    assert!(Subtitles::parse("1\n00:00:01,000 --> 00:00:02,000\nHi\n", SubtitleFormat::Vtt).is_err());
+    assert!(SubtitleFormat::from_path(&PathBuf::from("movie.mp4")).is_err());
+
+    // Languages by code or name, tagged per container
+    let german = Language::parse("de")?;
+    assert_eq!(Language::parse("German")?, german);
+    assert_eq!((german.code_for(SubtitleContainer::Mp4), german.code_for(SubtitleContainer::Mkv)), ("deu", "ger"));
+    assert_eq!(Language::parse("pt-br")?.tag, "pt-BR");
+    assert_eq!(Language::parse("tlh")?.terminologic, "tlh");
+    assert!(Language::parse("xx").is_err());
+    assert_eq!(Language::from_file_name(&PathBuf::from("movie.fr.srt")).map(|l| l.name), Some("French".to_string()));
+    assert_eq!(Language::from_file_name(&PathBuf::from("movie.srt")), None);
+    assert_eq!(SubtitleContainer::Mp4.codec_for(SubtitleFormat::Ass), "mov_text");
+    assert_eq!(SubtitleContainer::Mkv.codec_for(SubtitleFormat::Ass), "ass");
+    Ok(())
+}
+
+#[test]
+fn test_soft_subtitles() -> Result<()> {
+    use ffhuman::model::subtitle::{Language, SubtitleContainer, SubtitleFormat};
+    use tempfile::TempDir;
+
+    let app = create_test_app_with_output("test_soft_subtitles")?;
+    app.execute(Intent::AddSubtitles {
+        input: PathBuf::from(VIDEO_ASSET),
+        subtitles: vec![PathBuf::from("src/assets/example.srt")],
+        languages: vec![Language::parse("en")?],
+        to: Some(SubtitleContainer::parse("mkv")?),
+    })?;
+    app.execute(Intent::ExtractSubtitles {
+        input: PathBuf::from(VIDEO_ASSET),
+        format: Some(SubtitleFormat::Vtt),
+        track: Some(1),
+    })?;
+
+    // Conversion runs in Rust and writes next to the input, keeping its name
+    let dir = TempDir::new()?;
+    let srt = dir.path().join("movie.en.srt");
+    std::fs::copy("src/assets/example.srt", &srt)?;
+    let app = App::new(AppConfig::new(None, None, false, false, false));
+    app.execute(Intent::ConvertSubtitles { input: srt, format: SubtitleFormat::Vtt })?;
+    let vtt = std::fs::read_to_string(dir.path().join("movie.en.vtt"))?;
+    assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:02.500\n"), "{vtt}");
+    Ok(())
+}
+
+#[test]
+fn test_subtitle_timing() -> Result<()> {
+    use ffhuman::model::subtitle::{CutList, Offset, Stretch, SubtitleFormat, Subtitles, Timestamp};
+
+    let srt = "1\n00:00:01,000 --> 00:00:03,000\nOne\n\n2\n00:00:10,000 --> 00:00:12,000\nTwo\n\n3\n00:01:00,000 --> 00:01:04,000\nThree\n";
+    let subtitles = Subtitles::parse(srt, SubtitleFormat::Srt)?;
+    let times = |s: &Subtitles| s.cues.iter().map(|c| (c.start, c.end)).collect::<Vec<_>>();
+
+    let mut shifted = subtitles.clone();
+    shifted.shift(Offset::parse("-2s")?.0);
+    assert_eq!(times(&shifted), [(0, 1000), (8000, 10000), (58000, 62000)]);
+    shifted.shift(Offset::parse("-9.5s")?.0);
+    assert_eq!(times(&shifted), [(0, 500), (48500, 52500)], "cues before 0:00 are clipped or dropped");
+
+    // 25 fps subtitles on 23.976 fps video run slow by 25/23.976
+    let mut stretched = subtitles.clone();
+    stretched.stretch(&Stretch::parse("25=23.976fps")?);
+    assert_eq!(stretched.cues[2].start, 62563);
+    let mut anchored = subtitles.clone();
+    anchored.stretch(&Stretch::parse("0:10=0:11,1:00=1:06")?);
+    assert_eq!(times(&anchored), [(1100, 3300), (11000, 13200), (66000, 70400)]);
+
+    // Cut ranges play back to back; split parts each start at 0
+    let keep = CutList::parse("0:02-0:11, 0:59-1:01.5")?;
+    assert_eq!(times(&subtitles.cut(&keep.ranges())), [(0, 1000), (8000, 9000), (10000, 11500)]);
+    let parts = subtitles.split(&[11_000]);
+    assert_eq!(times(&parts[0]), [(1000, 3000), (10000, 11000)]);
+    assert_eq!(times(&parts[1]), [(0, 1000), (49000, 53000)]);
+    let merged = Subtitles::merge(vec![(parts[0].clone(), 0), (parts[1].clone(), 11_000)]);
+    assert_eq!(merged.cues.len(), 4);
+    assert_eq!(merged.cues[3].start, 60000);
+
+    assert_eq!(Timestamp::parse("1:02:03.5")?, Timestamp(3_723_500));
+    assert_eq!(Timestamp::parse("62.25s")?.to_string(), "1:02.25");
+    assert_eq!(Offset::parse("-500ms")?, Offset(-500));
+    assert_eq!(Offset::parse("+0:01.5")?.to_string(), "+1.5s");
+    assert_eq!(CutList::parse("0:10-0:30,1:00-1:20")?.to_string(), "0:10-0:30,1:00-1:20");
+    assert!(CutList::parse("0:30-0:10").is_err());
+    assert!(Stretch::parse("1:00=1:02").is_err());
+
+    let cli = Cli::try_parse_from(["ffhuman", "subtitle", "shift", "movie.srt", "by", "-2.5s"])?;
+    assert!(matches!(cli.into_intent()?, Intent::ShiftSubtitles { offset: Offset(-2500), .. }));
+    let cli = Cli::try_parse_from(["ffhuman", "subtitle", "stretch", "movie.srt", "--fps", "25=23.976"])?;
+    assert!(matches!(cli.into_intent()?, Intent::StretchSubtitles { stretch: Stretch::Fps(..), .. }));
+    Ok(())
+}
+
+#[test]
+fn test_chapters() -> Result<()> {
+    use ffhuman::model::chapter::{file_name_part, Chapters};
+
+    // YouTube-style lines, with dashes and comments
+    let mut chapters = Chapters::parse("# Episode 12\n00:00 Intro\n02:15 - First topic\n\n1:04:30 Q&A; wrap=up\n")?;
+    let starts: Vec<_> = chapters.chapters.iter().map(|c| (c.start, c.title.as_str())).collect();
+    assert_eq!(starts, [(0, "Intro"), (135_000, "First topic"), (3_870_000, "Q&A; wrap=up")]);
+    assert!(Chapters::parse("02:00 Later\n01:00 Earlier\n").is_err());
+    assert!(Chapters::parse("00:00\n").is_err());
+
+    // YAML lines or maps give the same chapters
+    let yaml = Chapters::parse_yaml("- 00:00 Intro\n- start: \"2:15\"\n  title: First topic\n- start: 3870\n  title: Q&A; wrap=up\n")?;
+    assert_eq!(yaml, chapters);
+
+    chapters.close(Some(4_000_000));
+    let metadata = chapters.to_ffmetadata();
+    assert!(metadata.starts_with(";FFMETADATA1\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=135000\ntitle=Intro\n"), "{metadata}");
+    assert!(metadata.ends_with("START=3870000\nEND=4000000\ntitle=Q&A\\; wrap\\=up\n"), "{metadata}");
+    assert_eq!(chapters.to_list(), "00:00 Intro\n02:15 First topic\n1:04:30 Q&A; wrap=up\n");
+    let json: serde_json::Value = serde_json::from_str(&chapters.to_json())?;
+    assert_eq!(json["chapters"][1]["start"], 135.0);
+    assert_eq!(json["chapters"][2]["end"], 4000.0);
+    assert_eq!(json["chapters"][2]["timestamp"], "1:04:30");
+
+    // What ffprobe reports, untitled chapters numbered
+    let probed = Chapters::from_ffprobe(r#"{"chapters": [
+        {"id": 0, "start_time": "0.000000", "end_time": "65.500000", "tags": {"title": "Intro"}},
+        {"id": 1, "start_time": "65.500000", "end_time": "120.000000"}
+    ]}"#)?;
+    assert_eq!(probed.chapters[1].title, "Chapter 2");
+    assert_eq!((probed.chapters[0].end, probed.chapters[1].start), (Some(65_500), 65_500));
+
+    // Scene changes closer than the minimum length are skipped, also near the end
+    let scenes = Chapters::from_scenes(&[3.0, 12.5, 20.0, 40.0, 55.0], 10_000, Some(60_000));
+    let starts: Vec<_> = scenes.chapters.iter().map(|c| c.start).collect();
+    assert_eq!(starts, [0, 12_500, 40_000]);
+    assert_eq!(scenes.chapters[2].end, Some(60_000));
+
+    assert_eq!(file_name_part(1, "Q&A: what's next?"), "01_Q_A_what_s_next");
+    assert_eq!(file_name_part(12, "???"), "12");
+
+    // split ... by chapters, without quoting the mode
+    assert_eq!(SplitMode::parse("by chapters")?, SplitMode::ByChapters);
+    let cli = Cli::try_parse_from(["ffhuman", "split", VIDEO_ASSET, "by", "chapters"])?;
+    assert!(matches!(cli.into_intent()?, Intent::Split { mode: SplitMode::ByChapters, .. }));
+    let cli = Cli::try_parse_from(["ffhuman", "split", VIDEO_ASSET, "every", "30s"])?;
+    assert!(matches!(cli.into_intent()?, Intent::Split { mode: SplitMode::Every(_), .. }));
+    Ok(())
+}
+
+#[test]
+fn test_add_chapters() -> Result<()> {
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    let list = dir.path().join("chapters.txt");
+    std::fs::write(&list, "00:00 Intro\n00:10 Demo\n")?;
+    let app = create_test_app_with_output("test_add_chapters")?;
+    app.execute(Intent::AddChapters { input: PathBuf::from(VIDEO_ASSET), chapters: list.clone() })?;
+
+    // Scenes from a detect-scenes file; nothing is written in a dry run
+    let scenes = dir.path().join("video_scenes.txt");
+    std::fs::write(&scenes, "4.000\n15.250\n")?;
+    let cli = Cli::try_parse_from([
+        "ffhuman", "chapters", "from-scenes", VIDEO_ASSET, "--scenes", scenes.to_str().unwrap(), "--min-length", "5s",
+    ])?;
+    app.execute(cli.into_intent()?)?;
+
+    std::fs::write(&list, "00:10 Demo\n00:00 Intro\n")?;
+    assert!(app.execute(Intent::AddChapters { input: PathBuf::from(VIDEO_ASSET), chapters: list }).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_trim_follows_subtitles() -> Result<()> {
+    use ffhuman::commands::subtitle::trim_sidecars;
+    use tempfile::TempDir;
+
+    let dir = TempDir::new()?;
+    let video = dir.path().join("talk.mp4");
+    std::fs::write(&video, b"video")?;
+    std::fs::copy("src/assets/example.srt", dir.path().join("talk.en.srt"))?;
+    std::fs::write(dir.path().join("talk_notes.srt"), b"not a sidecar")?;
+
+    let config = AppConfig::new(None, None, false, false, false);
+    trim_sidecars(&config, &video, &dir.path().join("talk_trim.mp4"), 5000, 15000)?;
+    let trimmed = std::fs::read_to_string(dir.path().join("talk_trim.en.srt"))?;
+    assert!(trimmed.starts_with("1\n00:00:00,000 --> 00:00:01,000\nThis is a demonstration"), "{trimmed}");
+    assert!(!dir.path().join("talk_trim_notes.srt").exists());
+    assert_eq!(config.outputs.since(0), [dir.path().join("talk_trim.en.srt")]);
+    Ok(())
+}